@@ -1,10 +1,14 @@
 pub mod next_commit {
+    use database::JobKind;
+
     #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
     pub struct Commit {
+        /// The commit sha, or the toolchain name for release jobs.
         pub sha: String,
         pub include: Option<String>,
         pub exclude: Option<String>,
         pub runs: Option<i32>,
+        pub kind: Option<JobKind>,
     }
 
     #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -12,3 +16,50 @@ pub mod next_commit {
         pub commit: Option<Commit>,
     }
 }
+
+pub mod queue {
+    use database::JobKind;
+
+    /// An administrative request for the benchmark job queue. Every request
+    /// is answered with the jobs in the queue afterwards.
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    pub enum Request {
+        List,
+        /// Changes the priority of a job; lower values are claimed first.
+        Prioritize {
+            artifact: String,
+            priority: i32,
+        },
+        /// Takes a job out of the queue for good.
+        Cancel {
+            artifact: String,
+        },
+        /// Puts a job back in the queue, unclaimed.
+        Retry {
+            artifact: String,
+        },
+        /// Queues up to `limit` of the merges into master since `since` (a
+        /// date) which have no results, oldest first.
+        Backfill {
+            since: String,
+            limit: usize,
+        },
+    }
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    pub struct Job {
+        pub artifact: String,
+        pub kind: JobKind,
+        pub priority: i32,
+        pub include: Option<String>,
+        pub exclude: Option<String>,
+        pub runs: Option<i32>,
+        pub claimed_by: Option<String>,
+    }
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    pub struct Response {
+        /// In the order they will be claimed.
+        pub jobs: Vec<Job>,
+    }
+}
@@ -0,0 +1,180 @@
+//! Comparisons of two artifacts in a results database, for use from the
+//! command line.
+
+use anyhow::Context;
+use database::{ArtifactId, Cache, Crate, DbLabel, Index, Lookup, Profile};
+use std::cmp::Ordering;
+use tokio::runtime::Runtime;
+
+pub struct Change {
+    pub krate: Crate,
+    pub profile: Profile,
+    pub cache: Cache,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl Change {
+    /// The relative change, in percent.
+    pub fn pct(&self) -> f64 {
+        (self.after - self.before) / self.before * 100.0
+    }
+}
+
+/// A benchmark, profile and cache state measured for only one of the
+/// artifacts, e.g. because a patch was added to the benchmark in between.
+pub struct Unmatched {
+    pub krate: Crate,
+    pub profile: Profile,
+    pub cache: Cache,
+    /// Whether it's `before` (rather than `after`) that has it.
+    pub in_before: bool,
+}
+
+pub struct Comparison {
+    /// Sorted by decreasing magnitude of change.
+    pub changes: Vec<Change>,
+    pub unmatched: Vec<Unmatched>,
+}
+
+/// Compares `stat` between the artifacts named `before` and `after` (commit
+/// shas or the ids passed to `bench_local`). Measurements are matched by
+/// benchmark, profile and cache state; those of only one artifact are
+/// reported as unmatched.
+pub fn compare(
+    rt: &mut Runtime,
+    pool: &database::Pool,
+    before: &str,
+    after: &str,
+    stat: &str,
+) -> anyhow::Result<Comparison> {
+    let mut conn = rt.block_on(pool.connection());
+    let index = rt.block_on(Index::load(&mut *conn));
+
+    let mut lookup = |name: &str| -> anyhow::Result<_> {
+        let artifact: ArtifactId = rt
+            .block_on(conn.artifact_by_name(name))
+            .with_context(|| format!("no results for '{}'", name))?;
+        artifact
+            .lookup(&index)
+            .with_context(|| format!("'{}' is not indexed", name))
+    };
+    let before_aid = lookup(before)?;
+    let after_aid = lookup(after)?;
+
+    let mut paths = index
+        .all_pstat_series()
+        .filter(|path| path.3.as_str() == stat)
+        .cloned()
+        .collect::<Vec<_>>();
+    // By name, so that the output doesn't depend on hash order: the unmatched
+    // measurements are listed in this order, and so are changes of the same
+    // magnitude, since the sort by magnitude is stable.
+    paths.sort_by_key(|&(krate, profile, cache, _)| (krate, profile, cache.to_string()));
+    let series = paths
+        .iter()
+        .map(|&(krate, profile, cache, stat)| {
+            DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let values = rt.block_on(conn.get_pstats(&series, &[Some(before_aid), Some(after_aid)]));
+
+    let mut changes = Vec::new();
+    let mut unmatched = Vec::new();
+    for ((krate, profile, cache, _), values) in paths.into_iter().zip(values) {
+        match (values[0], values[1]) {
+            (Some(before), Some(after)) => changes.push(Change {
+                krate,
+                profile,
+                cache,
+                before,
+                after,
+            }),
+            (before, after) if before.is_some() != after.is_some() => unmatched.push(Unmatched {
+                krate,
+                profile,
+                cache,
+                in_before: before.is_some(),
+            }),
+            _ => {}
+        }
+    }
+    changes.sort_by(|a, b| {
+        b.pct()
+            .abs()
+            .partial_cmp(&a.pct().abs())
+            .unwrap_or(Ordering::Equal)
+    });
+    Ok(Comparison { changes, unmatched })
+}
+
+/// Formats a value so that small values, e.g. times in seconds, keep some
+/// precision while large ones, e.g. instruction counts, aren't cluttered.
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 || value.abs() >= 1000.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.4}", value)
+    }
+}
+
+/// Prints the changes whose magnitude is at least `threshold` percent as a
+/// table, followed by a count of regressions and improvements and by the
+/// measurements which couldn't be compared.
+pub fn print_changes(comparison: &Comparison, threshold: f64) {
+    print_table(&comparison.changes, threshold);
+    if comparison.unmatched.is_empty() {
+        return;
+    }
+    println!();
+    println!("measured for only one artifact, so not compared:");
+    for u in &comparison.unmatched {
+        println!(
+            "  {}-{} {} (only {})",
+            u.krate,
+            u.profile,
+            u.cache,
+            if u.in_before { "before" } else { "after" }
+        );
+    }
+}
+
+fn print_table(changes: &[Change], threshold: f64) {
+    let shown = changes
+        .iter()
+        .filter(|c| c.pct().abs() >= threshold)
+        .collect::<Vec<_>>();
+    if shown.is_empty() {
+        println!("no changes of {}% or more", threshold);
+        return;
+    }
+    println!(
+        "{:<40} {:>16} {:>16} {:>9}",
+        "benchmark", "before", "after", "change"
+    );
+    for c in shown {
+        let name = format!("{}-{} {}", c.krate, c.profile, c.cache);
+        println!(
+            "{:<40} {:>16} {:>16} {:>+8.2}%",
+            name,
+            format_value(c.before),
+            format_value(c.after),
+            c.pct()
+        );
+    }
+    let regressions = shown.iter().filter(|c| c.pct() > 0.0).count();
+    println!(
+        "{} regressions and {} improvements of {}% or more ({} measurements compared)",
+        regressions,
+        shown.len() - regressions,
+        threshold,
+        changes.len()
+    );
+}
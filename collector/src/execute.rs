@@ -2,15 +2,16 @@
 
 use crate::{BuildKind, Compiler, RunKind};
 use anyhow::{anyhow, bail, Context};
-use collector::command_output;
+use collector::manifest::{BenchmarkConfig, Category};
+use collector::{command_output, command_output_with_timeout};
 use database::{PatchName, QueryLabel};
 use futures::stream::FuturesUnordered;
 use futures::stream::StreamExt;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt;
-use std::fs::{self, File};
+use std::fs;
 use std::hash;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -65,46 +66,73 @@ fn touch_all(path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn default_runs() -> usize {
-    3
+/// The total size of the files under `path`, not following symlinks.
+fn dir_size(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path).with_context(|| format!("failed to list {:?}", path))? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += meta.len();
+        }
+    }
+    Ok(size)
 }
 
-/// This is the internal representation of an individual benchmark's
-/// perf-config.json file.
-#[derive(Debug, Clone, serde::Deserialize)]
-struct BenchmarkConfig {
-    cargo_opts: Option<String>,
-    cargo_rustc_opts: Option<String>,
-    cargo_toml: Option<String>,
-    #[serde(default)]
-    disabled: bool,
-    #[serde(default = "default_runs")]
-    runs: usize,
-    #[serde(default)]
-    supports_stable: bool,
-
-    /// The file that should be touched to ensure cargo re-checks the leaf crate
-    /// we're interested in. Likely, something similar to `src/lib.rs`. The
-    /// default if this is not present is to touch all .rs files in the
-    /// directory that `Cargo.toml` is in.
-    #[serde(default)]
-    touch_file: Option<String>,
-}
+/// Records how much disk the build of a benchmark in `cwd` takes: the size of
+/// its `target` directories as `size:target-dir`, and the size of the final
+/// artifacts cargo puts at the top of `target/debug` or `target/release` (i.e.
+/// the rlibs and binaries, but not for `Check` builds, which produce none) as
+/// `size:artifacts`.
+fn record_disk_usage(cwd: &Path, build_kind: BuildKind, stats: &mut Stats) -> anyhow::Result<()> {
+    let profile_dir = match build_kind {
+        BuildKind::Opt => "release",
+        BuildKind::Check | BuildKind::Debug | BuildKind::Doc | BuildKind::DocJson => "debug",
+    };
 
-impl Default for BenchmarkConfig {
-    fn default() -> BenchmarkConfig {
-        BenchmarkConfig {
-            cargo_opts: None,
-            cargo_rustc_opts: None,
-            cargo_toml: None,
-            disabled: false,
-            runs: default_runs(),
-            supports_stable: false,
-            touch_file: None,
+    let mut target_size = 0;
+    let mut artifacts_size = 0;
+    let mut dirs = vec![cwd.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("failed to list {:?}", dir))? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if entry.file_name() != "target" {
+                dirs.push(entry.path());
+                continue;
+            }
+            target_size += dir_size(&entry.path())?;
+            let profile_dir = entry.path().join(profile_dir);
+            if build_kind == BuildKind::Check || !profile_dir.is_dir() {
+                continue;
+            }
+            for artifact in fs::read_dir(&profile_dir)? {
+                let artifact = artifact?;
+                let name = artifact.file_name();
+                let name = name.to_string_lossy();
+                let meta = artifact.metadata()?;
+                if meta.is_file() && !name.starts_with('.') && !name.ends_with(".d") {
+                    artifacts_size += meta.len();
+                }
+            }
         }
     }
+
+    stats.insert("size:target-dir".to_string(), target_size as f64);
+    if artifacts_size > 0 {
+        stats.insert("size:artifacts".to_string(), artifacts_size as f64);
+    }
+    Ok(())
 }
 
+/// The statistic whose run-to-run spread decides whether a benchmark needs
+/// extra runs.
+const SPREAD_STATISTIC: &str = "instructions:u";
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash)]
 pub struct BenchmarkName(pub String);
 
@@ -119,6 +147,8 @@ pub struct Benchmark {
     pub path: PathBuf,
     patches: Vec<Patch>,
     config: BenchmarkConfig,
+    /// The image the benchmark is built in, if any. See `use_container`.
+    container: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -128,6 +158,7 @@ pub enum Profiler {
     SelfProfile,
     TimePasses,
     PerfRecord,
+    PerfFlamegraph,
     OProfile,
     Cachegrind,
     Callgrind,
@@ -147,6 +178,7 @@ impl Profiler {
             "self-profile" => Ok(Profiler::SelfProfile),
             "time-passes" => Ok(Profiler::TimePasses),
             "perf-record" => Ok(Profiler::PerfRecord),
+            "perf-flamegraph" => Ok(Profiler::PerfFlamegraph),
             "oprofile" => Ok(Profiler::OProfile),
             "cachegrind" => Ok(Profiler::Cachegrind),
             "callgrind" => Ok(Profiler::Callgrind),
@@ -165,6 +197,7 @@ impl Profiler {
             Profiler::SelfProfile => "self-profile",
             Profiler::TimePasses => "time-passes",
             Profiler::PerfRecord => "perf-record",
+            Profiler::PerfFlamegraph => "perf-flamegraph",
             Profiler::OProfile => "oprofile",
             Profiler::Cachegrind => "cachegrind",
             Profiler::Callgrind => "callgrind",
@@ -184,13 +217,14 @@ impl Profiler {
             | Profiler::SelfProfile
             | Profiler::TimePasses
             | Profiler::PerfRecord
+            | Profiler::PerfFlamegraph
             | Profiler::OProfile
             | Profiler::Cachegrind
             | Profiler::Callgrind
             | Profiler::DHAT
             | Profiler::Massif
             | Profiler::Eprintln => {
-                if build_kind == BuildKind::Doc {
+                if build_kind.is_doc() {
                     Some("rustdoc")
                 } else {
                     Some("rustc")
@@ -198,7 +232,7 @@ impl Profiler {
             }
             Profiler::LlvmLines => match build_kind {
                 BuildKind::Debug | BuildKind::Opt => Some("llvm-lines"),
-                BuildKind::Check | BuildKind::Doc => None,
+                BuildKind::Check | BuildKind::Doc | BuildKind::DocJson => None,
             },
         }
     }
@@ -210,6 +244,7 @@ impl Profiler {
             | Profiler::SelfProfile
             | Profiler::TimePasses
             | Profiler::PerfRecord
+            | Profiler::PerfFlamegraph
             | Profiler::OProfile
             | Profiler::Cachegrind
             | Profiler::Callgrind
@@ -233,6 +268,8 @@ struct CargoProcess<'a> {
     rustc_args: Vec<String>,
     touch_file: Option<String>,
     jobserver: Option<jobserver::Client>,
+    container: Option<&'a str>,
+    timeout: Duration,
 }
 
 impl<'a> CargoProcess<'a> {
@@ -253,7 +290,10 @@ impl<'a> CargoProcess<'a> {
     }
 
     fn base_command(&self, cwd: &Path, subcommand: &str) -> Command {
-        let mut cmd = Command::new(Path::new(self.compiler.cargo));
+        let mut cmd = match self.container {
+            Some(image) => container_command(image, cwd, &self.compiler),
+            None => Command::new(Path::new(self.compiler.cargo)),
+        };
         cmd
             // Not all cargo invocations (e.g. `cargo clean`) need all of these
             // env vars set, but it doesn't hurt to have them.
@@ -324,9 +364,10 @@ impl<'a> CargoProcess<'a> {
                     Some(sub) => sub,
                 }
             } else {
-                match self.build_kind {
-                    BuildKind::Doc => "rustdoc",
-                    _ => "rustc",
+                if self.build_kind.is_doc() {
+                    "rustdoc"
+                } else {
+                    "rustc"
                 }
             };
 
@@ -337,7 +378,7 @@ impl<'a> CargoProcess<'a> {
                     cmd.arg("--profile").arg("check");
                 }
                 BuildKind::Debug => {}
-                BuildKind::Doc => {}
+                BuildKind::Doc | BuildKind::DocJson => {}
                 BuildKind::Opt => {
                     cmd.arg("--release");
                 }
@@ -348,6 +389,11 @@ impl<'a> CargoProcess<'a> {
                 cmd.arg("-Ztimings");
             }
             cmd.arg("--");
+            if self.build_kind == BuildKind::DocJson {
+                cmd.arg("-Zunstable-options")
+                    .arg("--output-format")
+                    .arg("json");
+            }
             // --wrap-rustc-with is not a valid rustc flag. But rustc-fake
             // recognizes it, strips it (and its argument) out, and uses it as an
             // indicator that the rustc invocation should be profiled. This works
@@ -403,13 +449,30 @@ impl<'a> CargoProcess<'a> {
                 cmd.arg(incr_arg);
             }
 
+            // The jobserver's file descriptors aren't passed into containers.
             if let Some(client) = &self.jobserver {
-                client.configure(&mut cmd);
+                if self.container.is_none() {
+                    client.configure(&mut cmd);
+                }
             }
 
             log::debug!("{:?}", cmd);
 
-            let output = command_output(&mut cmd)?;
+            let output = match command_output_with_timeout(&mut cmd, Some(self.timeout)) {
+                Ok(output) => output,
+                Err(e) => {
+                    // Only the `docker` client was killed, not the container.
+                    if self.container.is_some() && e.to_string().starts_with("timeout") {
+                        let name = container_name(self.cwd);
+                        let mut kill = Command::new("docker");
+                        kill.arg("kill").arg(&name);
+                        if let Err(e) = command_output(&mut kill) {
+                            log::warn!("failed to kill container {}: {:?}", name, e);
+                        }
+                    }
+                    return Err(e);
+                }
+            };
             if let Some((ref mut processor, run_kind, run_kind_str, patch)) = self.processor_etc {
                 let data = ProcessOutputData {
                     name: self.processor_name.clone(),
@@ -431,6 +494,83 @@ impl<'a> CargoProcess<'a> {
     }
 }
 
+/// The environment variables set on cargo, forwarded into the container when
+/// running in one. Unset variables aren't forwarded.
+const CONTAINER_ENV: &[&str] = &[
+    "RUSTC",
+    "RUSTC_REAL",
+    "RUSTDOC",
+    "RUSTDOC_REAL",
+    "CARGO_INCREMENTAL",
+    "EXPECT_ONLY_WRAPPED_RUSTC",
+];
+
+/// A command which runs cargo in `image`, in `cwd`. The toolchain, the fake
+/// rustc and `cwd` are mounted at the same paths as on the host, so that all
+/// the paths passed to cargo remain valid.
+fn container_command(image: &str, cwd: &Path, compiler: &Compiler<'_>) -> Command {
+    let mut cmd = Command::new("docker");
+    cmd.arg("run")
+        .arg("--rm")
+        .arg(format!("--name={}", container_name(cwd)))
+        .arg("--network=none")
+        // perf needs `perf_event_open`, which the default profile forbids.
+        .arg("--security-opt=seccomp=unconfined")
+        .arg(format!("--user={}:{}", unsafe { libc::getuid() }, unsafe {
+            libc::getgid()
+        }))
+        .arg("--workdir")
+        .arg(cwd);
+    // The container's processes aren't descendants of the collector, so they
+    // don't inherit any pinning from `--pin-cpus`.
+    match crate::tuning::current_cpus() {
+        Ok(cpus) => {
+            cmd.arg(format!("--cpuset-cpus={}", cpus));
+        }
+        Err(e) => log::warn!("not pinning container: {:?}", e),
+    }
+
+    let mut read_only = vec![FAKE_RUSTC.parent().unwrap().to_path_buf()];
+    for tool in std::iter::once(compiler.rustc)
+        .chain(std::iter::once(compiler.cargo))
+        .chain(compiler.rustdoc)
+    {
+        // Mount the whole toolchain, i.e. the parent of its `bin` directory.
+        if let Some(root) = tool.parent().and_then(|bin| bin.parent()) {
+            if !read_only.iter().any(|p| root.starts_with(p)) {
+                read_only.push(root.to_path_buf());
+            }
+        }
+    }
+    for path in &read_only {
+        let mut volume = path.as_os_str().to_owned();
+        volume.push(":");
+        volume.push(path);
+        volume.push(":ro");
+        cmd.arg("--volume").arg(volume);
+    }
+    let mut volume = cwd.as_os_str().to_owned();
+    volume.push(":");
+    volume.push(cwd);
+    cmd.arg("--volume").arg(volume);
+
+    for var in CONTAINER_ENV {
+        cmd.arg("--env").arg(var);
+    }
+    cmd.arg(image).arg(compiler.cargo);
+    cmd
+}
+
+/// The name of the container cargo runs in, in `cwd`. Killing the `docker`
+/// client leaves the container running, so it is killed by this name instead.
+/// Only one cargo runs in a directory at a time.
+fn container_name(cwd: &Path) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    cwd.hash(&mut hasher);
+    format!("rustc-perf-{}-{:x}", process::id(), hasher.finish())
+}
+
 lazy_static::lazy_static! {
     static ref FAKE_RUSTC: PathBuf = {
         let mut fake_rustc = env::current_exe().unwrap();
@@ -497,6 +637,17 @@ pub trait Processor {
         false
     }
 
+    /// Called before each iteration of a benchmark.
+    fn start_iteration(&mut self) {}
+
+    /// The largest relative spread, i.e. `(max - min) / min`, of the
+    /// measurements taken since `start_first_collection`, used to decide
+    /// whether to add extra runs. Processors which don't measure anything
+    /// never ask for extra runs.
+    fn spread(&self) -> f64 {
+        0.0
+    }
+
     fn measure_rustc(&mut self, _: Compiler<'_>) -> anyhow::Result<()> {
         Ok(())
     }
@@ -511,7 +662,11 @@ pub struct MeasureProcessor<'a> {
     upload: Option<Upload>,
     is_first_collection: bool,
     self_profile: bool,
+    drop_caches: bool,
     tries: u8,
+    /// The values of `SPREAD_STATISTIC` for each build kind and run kind in
+    /// the current collection.
+    observations: HashMap<(BuildKind, String), Vec<f64>>,
 }
 
 impl<'a> MeasureProcessor<'a> {
@@ -522,6 +677,7 @@ impl<'a> MeasureProcessor<'a> {
         artifact: &'a database::ArtifactId,
         cid: database::ArtifactIdNumber,
         self_profile: bool,
+        drop_caches: bool,
     ) -> Self {
         // Check we have `perf` available.
         let has_perf = Command::new("perf").output().is_ok();
@@ -537,7 +693,9 @@ impl<'a> MeasureProcessor<'a> {
             is_first_collection: true,
             // Command::new("summarize").status().is_ok()
             self_profile,
+            drop_caches,
             tries: 0,
+            observations: HashMap::new(),
         }
     }
 
@@ -564,6 +722,7 @@ impl<'a> MeasureProcessor<'a> {
             BuildKind::Check => database::Profile::Check,
             BuildKind::Debug => database::Profile::Debug,
             BuildKind::Doc => database::Profile::Doc,
+            BuildKind::DocJson => database::Profile::DocJson,
             BuildKind::Opt => database::Profile::Opt,
         };
 
@@ -598,6 +757,16 @@ impl<'a> MeasureProcessor<'a> {
 
         let mut buf = FuturesUnordered::new();
         for (stat, value) in stats.0.iter() {
+            if let Err(e) = database::validate::statistic(stat, value) {
+                database::event!(
+                    log::Level::Error, "not recording statistic";
+                    benchmark = self.krate.0.as_str(),
+                    profile = profile.to_string(),
+                    cache = cache.to_string(),
+                    problem = e,
+                );
+                continue;
+            }
             buf.push(self.conn.record_statistic(
                 collection,
                 self.cid,
@@ -734,6 +903,7 @@ impl<'a> Processor for MeasureProcessor<'a> {
 
     fn start_first_collection(&mut self) {
         self.is_first_collection = true;
+        self.observations.clear();
     }
 
     fn finished_first_collection(&mut self, build: BuildKind) -> bool {
@@ -743,13 +913,46 @@ impl<'a> Processor for MeasureProcessor<'a> {
         self.profiler(build) != original
     }
 
+    fn start_iteration(&mut self) {
+        if self.drop_caches {
+            if let Err(e) = crate::tuning::drop_caches() {
+                log::warn!("failed to drop caches: {:?}", e);
+            }
+        }
+    }
+
+    fn spread(&self) -> f64 {
+        self.observations
+            .values()
+            .filter(|values| values.len() > 1)
+            .map(|values| {
+                let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                (max - min) / min
+            })
+            .fold(0.0, f64::max)
+    }
+
     fn process_output(
         &mut self,
         data: &ProcessOutputData<'_>,
         output: process::Output,
     ) -> anyhow::Result<Retry> {
         match process_perf_stat_output(output) {
-            Ok(res) => {
+            Ok(mut res) => {
+                if let Err(e) = record_disk_usage(data.cwd, data.build_kind, &mut res.0) {
+                    log::warn!("failed to measure disk usage: {:?}", e);
+                }
+                // Self-profiling perturbs the measurements, so those runs
+                // don't count towards the spread.
+                if self.profiler(data.build_kind) == Profiler::PerfStat {
+                    if let Some(value) = res.0.get(SPREAD_STATISTIC) {
+                        self.observations
+                            .entry((data.build_kind, data.run_kind_str.to_string()))
+                            .or_default()
+                            .push(value);
+                    }
+                }
                 match data.run_kind {
                     RunKind::Full => {
                         self.insert_stats(database::Cache::Empty, data.build_kind, res);
@@ -855,8 +1058,9 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 let zsp_dir = filepath(self.output_dir, &out_file("Zsp"));
                 let zsp_files_prefix = filepath(&zsp_dir, "Zsp");
                 let summarize_file = filepath(self.output_dir, &out_file("summarize"));
-                let flamegraph_file = filepath(self.output_dir, &out_file("flamegraph"));
-                let crox_file = filepath(self.output_dir, &out_file("crox"));
+                let flamegraph_file =
+                    filepath(self.output_dir, &format!("{}.svg", out_file("flamegraph")));
+                let crox_file = filepath(self.output_dir, &format!("{}.json", out_file("crox")));
 
                 // Move the directory.
                 if zsp_dir.exists() {
@@ -924,6 +1128,30 @@ impl<'a> Processor for ProfileProcessor<'a> {
                 fs::copy(&tmp_perf_file, &perf_file)?;
             }
 
+            // As for perf-record, but the data file is then post-processed
+            // with `perf script` and inferno's `inferno-collapse-perf` and
+            // `inferno-flamegraph` to render a flamegraph SVG in the output
+            // dir.
+            Profiler::PerfFlamegraph => {
+                let tmp_perf_file = filepath(data.cwd.as_ref(), "perf");
+                let perf_file = filepath(self.output_dir, &out_file("perf"));
+                let svg_file = filepath(
+                    self.output_dir,
+                    &format!("{}.svg", out_file("perf-flamegraph")),
+                );
+
+                fs::copy(&tmp_perf_file, &perf_file)?;
+
+                let mut perf_script_cmd = Command::new("perf");
+                perf_script_cmd.arg("script").arg("--input").arg(&perf_file);
+                let script = perf_script_cmd.output().context("perf script")?.stdout;
+                let collapsed = pipe_through(Command::new("inferno-collapse-perf"), &script)
+                    .context("inferno-collapse-perf")?;
+                let svg = pipe_through(Command::new("inferno-flamegraph"), &collapsed)
+                    .context("inferno-flamegraph")?;
+                fs::write(svg_file, svg)?;
+            }
+
             // OProfile produces (via rustc-fake) a data directory called
             // `oprofile_data`. We copy it from the temp dir to the output dir,
             // giving it a new name in the process, and then post-process it
@@ -1014,12 +1242,18 @@ impl<'a> Processor for ProfileProcessor<'a> {
 
             // Massif produces (via rustc-fake) a data file called `msout`. We
             // copy it from the temp dir to the output dir, giving it a new
-            // name in the process.
+            // name in the process, and then post-process it to produce another
+            // data file in the output dir.
             Profiler::Massif => {
                 let tmp_msout_file = filepath(data.cwd.as_ref(), "msout");
                 let msout_file = filepath(self.output_dir, &out_file("msout"));
+                let msann_file = filepath(self.output_dir, &out_file("msann"));
 
                 fs::copy(&tmp_msout_file, &msout_file)?;
+
+                let mut ms_print_cmd = Command::new("ms_print");
+                ms_print_cmd.arg(&msout_file);
+                fs::write(msann_file, &ms_print_cmd.output()?.stdout)?;
             }
 
             // `eprintln!` statements are redirected (via rustc-fake) to a file
@@ -1044,6 +1278,80 @@ impl<'a> Processor for ProfileProcessor<'a> {
     }
 }
 
+/// Runs `cmd` with `input` as its stdin, returning its stdout.
+fn pipe_through(mut cmd: Command, input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+    // Write from another thread so that a child which fills its stdout pipe
+    // before consuming all of its input can't deadlock us.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        bail!("{:?} exited with {}", cmd, output.status);
+    }
+    Ok(output.stdout)
+}
+
+/// Diffs the Cachegrind profiles of two compilers that were written to
+/// `output_dir` by `ProfileProcessor`.
+///
+/// For each `cgout` file of the `before` compiler that has a counterpart from
+/// the `after` compiler, `cg_diff` produces a `cgdiff` file, which is then
+/// post-processed by `cg_annotate` into a human-readable `cgann-diff` file
+/// listing the change in instruction counts per function. Returns the number
+/// of diffs produced.
+pub fn diff_cachegrind(output_dir: &Path, before: &str, after: &str) -> anyhow::Result<usize> {
+    let before_prefix = format!("cgout-{}-", before);
+
+    let mut diffs = 0;
+    for entry in fs::read_dir(output_dir)? {
+        let entry = entry?;
+        let filename = entry.file_name();
+        let filename_str = match filename.to_str() {
+            Some(f) => f,
+            None => continue,
+        };
+        if !filename_str.starts_with(&before_prefix) {
+            continue;
+        }
+        // $BENCHMARK-$BUILDKIND-$RUNKIND
+        let suffix = &filename_str[before_prefix.len()..];
+
+        let after_file = output_dir.join(format!("cgout-{}-{}", after, suffix));
+        if !after_file.exists() {
+            log::warn!("no {} profile for {}, not diffing", after, suffix);
+            continue;
+        }
+        let cgdiff_file = output_dir.join(format!("cgdiff-{}-{}-{}", before, after, suffix));
+        let cgann_file = output_dir.join(format!("cgann-diff-{}-{}-{}", before, after, suffix));
+
+        let mut cg_diff_cmd = Command::new("cg_diff");
+        cg_diff_cmd.arg(entry.path()).arg(&after_file);
+        fs::write(
+            &cgdiff_file,
+            &command_output(&mut cg_diff_cmd).context("cg_diff")?.stdout,
+        )?;
+
+        // The sources of the two compilers may not match (or exist), so don't
+        // annotate them.
+        let mut cg_annotate_cmd = Command::new("cg_annotate");
+        cg_annotate_cmd.arg("--auto=no").arg(&cgdiff_file);
+        fs::write(
+            &cgann_file,
+            &command_output(&mut cg_annotate_cmd)
+                .context("cg_annotate")?
+                .stdout,
+        )?;
+
+        diffs += 1;
+    }
+    Ok(diffs)
+}
+
 impl Benchmark {
     pub fn new(name: String, path: PathBuf) -> anyhow::Result<Self> {
         if name == "rustc" {
@@ -1051,34 +1359,36 @@ impl Benchmark {
                 name: BenchmarkName(name),
                 path,
                 patches: vec![],
-                config: BenchmarkConfig::default(),
+                config: BenchmarkConfig::new("rustc", Category::Primary),
+                container: None,
             });
         }
 
-        let mut patches = vec![];
-        for entry in fs::read_dir(&path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                if ext == "patch" {
-                    patches.push(path.clone());
+        let config = BenchmarkConfig::load(&path)?;
+
+        let patches = if let Some(patches) = &config.patches {
+            patches
+                .iter()
+                .enumerate()
+                .map(|(index, patch)| Patch {
+                    index,
+                    name: patch.name.as_str().into(),
+                    path: PathBuf::from(&patch.file),
+                })
+                .collect()
+        } else {
+            let mut patches = vec![];
+            for entry in fs::read_dir(&path)? {
+                let entry = entry?;
+                let path = entry.path();
+                if let Some(ext) = path.extension() {
+                    if ext == "patch" {
+                        patches.push(path.clone());
+                    }
                 }
             }
-        }
-
-        patches.sort();
-
-        let patches = patches.into_iter().map(|p| Patch::new(p)).collect();
-
-        let config_path = path.join("perf-config.json");
-        let config: BenchmarkConfig = if config_path.exists() {
-            serde_json::from_reader(
-                File::open(&config_path)
-                    .with_context(|| format!("failed to open {:?}", config_path))?,
-            )
-            .with_context(|| format!("failed to parse {:?}", config_path))?
-        } else {
-            BenchmarkConfig::default()
+            patches.sort();
+            patches.into_iter().map(|p| Patch::new(p)).collect()
         };
 
         Ok(Benchmark {
@@ -1086,6 +1396,7 @@ impl Benchmark {
             path,
             patches,
             config,
+            container: None,
         })
     }
 
@@ -1093,6 +1404,12 @@ impl Benchmark {
         self.config.supports_stable
     }
 
+    /// Builds the benchmark in the container image named in its manifest, if
+    /// there is one, rather than directly on the host.
+    pub fn use_container(&mut self) {
+        self.container = self.config.container.clone();
+    }
+
     fn make_temp_dir(&self, base: &Path) -> anyhow::Result<TempDir> {
         // Appending `.` means we copy just the contents of `base` into
         // `tmp_dir`, rather than `base` itself.
@@ -1149,6 +1466,8 @@ impl Benchmark {
                 .collect(),
             touch_file: self.config.touch_file.clone(),
             jobserver: None,
+            container: self.container.as_deref(),
+            timeout: self.config.timeout(&format!("{:?}", build_kind)),
         }
     }
 
@@ -1167,6 +1486,12 @@ impl Benchmark {
             return processor.measure_rustc(compiler).context("measure rustc");
         }
 
+        let build_kinds = build_kinds
+            .iter()
+            .copied()
+            .filter(|kind| self.config.supports_profile(&format!("{:?}", kind)))
+            .collect::<Vec<_>>();
+
         if self.config.disabled || build_kinds.is_empty() {
             eprintln!("Skipping {}: disabled", self.name);
             bail!("disabled benchmark");
@@ -1221,7 +1546,8 @@ impl Benchmark {
             // We want at least two runs for all benchmarks (since we run
             // self-profile separately).
             processor.start_first_collection();
-            for i in 0..cmp::max(iterations, 2) {
+            let max_iterations = cmp::max(iterations, 2) + self.config.max_extra_runs;
+            for i in 0..max_iterations {
                 if i == 1 {
                     let different = processor.finished_first_collection(build_kind);
                     if iterations == 1 && !different {
@@ -1230,7 +1556,22 @@ impl Benchmark {
                         break;
                     }
                 }
+                if i >= cmp::max(iterations, 2) {
+                    // All the requested runs are done; only keep going if the
+                    // results are too noisy to be trusted.
+                    let spread = processor.spread();
+                    if spread <= self.config.spread_threshold {
+                        break;
+                    }
+                    eprintln!(
+                        "{}: spread of {:.2}% exceeds {:.2}%, running again",
+                        self.name,
+                        spread * 100.0,
+                        self.config.spread_threshold * 100.0
+                    );
+                }
                 log::debug!("Benchmark iteration {}/{}", i + 1, iterations);
+                processor.start_iteration();
                 // Don't delete the directory on error.
                 let timing_dir = ManuallyDrop::new(self.make_temp_dir(prep_dir.path())?);
                 let cwd = timing_dir.path();
@@ -1243,7 +1584,7 @@ impl Benchmark {
                 }
 
                 // Rustdoc does not support incremental compilation
-                if build_kind != BuildKind::Doc {
+                if !build_kind.is_doc() {
                     // An incremental build from scratch (slowest incremental case).
                     // This is required for any subsequent incremental builds.
                     if run_kinds.contains(&RunKind::IncrFull)
@@ -1413,7 +1754,9 @@ fn process_perf_stat_output(
 
 #[derive(Clone)]
 pub struct Stats {
-    stats: HashMap<String, f64>,
+    // Ordered, so that statistics are always recorded and printed in the same
+    // order.
+    stats: BTreeMap<String, f64>,
 }
 
 impl Default for Stats {
@@ -1425,7 +1768,7 @@ impl Default for Stats {
 impl Stats {
     pub fn new() -> Stats {
         Stats {
-            stats: HashMap::new(),
+            stats: BTreeMap::new(),
         }
     }
 
@@ -1437,6 +1780,10 @@ impl Stats {
         self.stats.is_empty()
     }
 
+    pub fn get(&self, stat: &str) -> Option<f64> {
+        self.stats.get(stat).cloned()
+    }
+
     pub fn insert(&mut self, stat: String, value: f64) {
         self.stats.insert(stat, value);
     }
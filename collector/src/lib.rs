@@ -1,11 +1,18 @@
 use chrono::NaiveDate;
-pub use database::{Commit, PatchName, QueryLabel};
+pub use database::{Commit, Machine, PatchName, QueryLabel};
 use serde::Deserialize;
 use std::cmp::PartialOrd;
+use std::collections::HashMap;
 use std::fmt;
+use std::fs;
 use std::process::{self, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
 
 pub mod api;
+pub mod manifest;
 mod read2;
 pub mod self_profile;
 
@@ -20,6 +27,9 @@ pub enum Bound {
     // sha, unverified
     Commit(String),
     Date(NaiveDate),
+    /// A number of commits, counted from the other end of the range; e.g. a
+    /// start of `Count(50)` with no end is the last 50 commits.
+    Count(usize),
     None,
 }
 
@@ -29,6 +39,8 @@ impl Bound {
         match self {
             Bound::Commit(sha) => commit.sha == **sha,
             Bound::Date(date) => commit.date.0.naive_utc().date() >= *date,
+            // Depends on the other end, so every commit matches on its own.
+            Bound::Count(_) => true,
             Bound::None => last_month <= commit.date.0.naive_utc().date(),
         }
     }
@@ -37,6 +49,7 @@ impl Bound {
         match self {
             Bound::Commit(sha) => commit.sha == **sha,
             Bound::Date(date) => commit.date.0.date().naive_utc() <= *date,
+            Bound::Count(_) => true,
             Bound::None => true,
         }
     }
@@ -50,6 +63,7 @@ impl serde::Serialize for Bound {
         let s = match *self {
             Bound::Commit(ref s) => s.clone(),
             Bound::Date(ref date) => date.format("%Y-%m-%d").to_string(),
+            Bound::Count(n) => n.to_string(),
             Bound::None => String::new(),
         };
         serializer.serialize_str(&s)
@@ -74,15 +88,19 @@ impl<'de> Deserialize<'de> for Bound {
                     return Ok(Bound::None);
                 }
 
+                // A sha is 40 hex digits, too long to be mistaken for a count.
                 let bound = value
                     .parse::<NaiveDate>()
                     .map(|d| Bound::Date(d))
+                    .or_else(|_| value.parse::<usize>().map(Bound::Count))
                     .unwrap_or(Bound::Commit(value.to_string()));
                 Ok(bound)
             }
 
             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("either a YYYY-mm-dd date or a collection ID (usually commit hash)")
+                f.write_str(
+                    "a YYYY-mm-dd date, a number of commits or a collection ID (usually commit hash)",
+                )
             }
         }
 
@@ -115,7 +133,49 @@ pub fn version_supports_incremental(version_str: &str) -> bool {
     }
 }
 
-/// Rounds serialized and deserialized floats to 2 decimal places.
+/// Identifies the machine we're running on.
+///
+/// The name is taken from `RUSTC_PERF_COLLECTOR` if set, and otherwise is the
+/// hostname. The fingerprint is derived from the CPU model, the number of
+/// online CPUs (not just those this process is pinned to), and the amount of
+/// memory to the nearest GiB, which stays the same across reboots.
+pub fn current_machine() -> anyhow::Result<Machine> {
+    let name = match std::env::var("RUSTC_PERF_COLLECTOR") {
+        Ok(name) => name,
+        Err(_) => {
+            let output = command_output(&mut Command::new("hostname"))?;
+            String::from_utf8(output.stdout)?.trim().to_string()
+        }
+    };
+
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let cpu = cpuinfo
+        .lines()
+        .find(|l| l.starts_with("model name"))
+        .and_then(|l| l.splitn(2, ':').nth(1))
+        .map(|m| m.trim())
+        .unwrap_or("unknown cpu");
+    let cpus = cpuinfo
+        .lines()
+        .filter(|l| l.starts_with("processor"))
+        .count();
+    let meminfo = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let memory = meminfo
+        .lines()
+        .find(|l| l.starts_with("MemTotal:"))
+        .and_then(|l| l["MemTotal:".len()..].trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| format!("{} GiB", (kb + (1 << 19)) >> 20))
+        .unwrap_or_else(|| "unknown memory".to_string());
+
+    Ok(Machine {
+        name,
+        fingerprint: format!("{} x{}, {}", cpu, cpus, memory),
+    })
+}
+
+/// Rounds to two decimal places. NaN and infinities, which JSON can't
+/// represent, are written as `null` and read back as NaN.
 pub mod round_float {
     use serde::{Deserialize, Deserializer, Serializer};
 
@@ -123,14 +183,18 @@ pub mod round_float {
     where
         S: Serializer,
     {
-        serializer.serialize_f64((*n * 100.0).round() / 100.0)
+        if n.is_finite() {
+            serializer.serialize_f64((*n * 100.0).round() / 100.0)
+        } else {
+            serializer.serialize_none()
+        }
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let n = f64::deserialize(deserializer)?;
+        let n = Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN);
         Ok((n * 100.0).round() / 100.0)
     }
 }
@@ -145,9 +209,34 @@ pub fn run_command(cmd: &mut Command) -> anyhow::Result<()> {
 }
 
 pub fn command_output(cmd: &mut Command) -> anyhow::Result<process::Output> {
+    command_output_with_timeout(cmd, None)
+}
+
+/// Like `command_output`, but if the command runs for longer than `timeout`,
+/// it is killed along with everything it spawned, and an error starting with
+/// "timeout" is returned.
+pub fn command_output_with_timeout(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+) -> anyhow::Result<process::Output> {
     log::trace!("running: {:?}", cmd);
     let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
 
+    // The watchdog kills the command unless `done` is dropped, i.e. we return,
+    // before the timeout.
+    let (done, watch) = mpsc::channel::<()>();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let watchdog = timeout.map(|timeout| {
+        let pid = child.id();
+        let timed_out = timed_out.clone();
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = watch.recv_timeout(timeout) {
+                timed_out.store(true, Ordering::SeqCst);
+                kill_process_tree(pid);
+            }
+        })
+    });
+
     let mut stdout = Vec::new();
     let mut stderr = Vec::new();
     let mut stdout_writer = std::io::LineWriter::new(std::io::stdout());
@@ -174,6 +263,18 @@ pub fn command_output(cmd: &mut Command) -> anyhow::Result<process::Output> {
     )?;
 
     let status = child.wait()?;
+    drop(done);
+    if let Some(watchdog) = watchdog {
+        watchdog.join().unwrap();
+    }
+    if timed_out.load(Ordering::SeqCst) {
+        return Err(anyhow::anyhow!(
+            "timeout: killed after {}s\n\nstderr={}\n\n stdout={}",
+            timeout.unwrap().as_secs(),
+            String::from_utf8_lossy(&stderr),
+            String::from_utf8_lossy(&stdout)
+        ));
+    }
     if !status.success() {
         return Err(anyhow::anyhow!(
             "expected success, got {}\n\nstderr={}\n\n stdout={}",
@@ -190,3 +291,41 @@ pub fn command_output(cmd: &mut Command) -> anyhow::Result<process::Output> {
     };
     Ok(output)
 }
+
+/// Kills the process `pid` and all of its descendants, as found in `/proc`.
+fn kill_process_tree(pid: u32) {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    if let Ok(entries) = fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let child = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                Some(child) => child,
+                None => continue,
+            };
+            // The parent's pid is the second field after the command name,
+            // which is in parentheses and may contain spaces.
+            let ppid = fs::read_to_string(entry.path().join("stat"))
+                .ok()
+                .and_then(|stat| {
+                    let fields = stat.rsplit(')').next()?.to_string();
+                    fields.split_whitespace().nth(1)?.parse().ok()
+                });
+            if let Some(ppid) = ppid {
+                children.entry(ppid).or_default().push(child);
+            }
+        }
+    }
+
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(children) = children.get(&tree[i]) {
+            tree.extend(children);
+        }
+        i += 1;
+    }
+    for pid in tree {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
@@ -4,7 +4,7 @@
 extern crate clap;
 
 use anyhow::{bail, Context};
-use database::{ArtifactId, Commit};
+use database::{ArtifactId, ArtifactIdNumber, Commit, JobKind};
 use log::debug;
 use std::collections::HashSet;
 use std::fs;
@@ -12,14 +12,22 @@ use std::io::{stderr, Write};
 use std::path::{Path, PathBuf};
 use std::process;
 use std::process::Command;
-use std::{str, time::Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::{
+    str,
+    time::{Duration, Instant},
+};
 use tokio::runtime::Runtime;
 
+mod compare;
 mod execute;
+mod report;
 mod sysroot;
+mod tuning;
 
 use execute::{Benchmark, Profiler};
 use sysroot::Sysroot;
+use tuning::Tuning;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Compiler<'a> {
@@ -47,6 +55,8 @@ pub enum BuildKind {
     Check,
     Debug,
     Doc,
+    /// rustdoc's JSON output, which is only available on nightly.
+    DocJson,
     Opt,
 }
 
@@ -56,10 +66,16 @@ impl BuildKind {
             BuildKind::Check,
             BuildKind::Debug,
             BuildKind::Doc,
+            BuildKind::DocJson,
             BuildKind::Opt,
         ]
     }
 
+    /// Whether the build runs rustdoc rather than rustc.
+    pub fn is_doc(self) -> bool {
+        self == BuildKind::Doc || self == BuildKind::DocJson
+    }
+
     fn default() -> Vec<Self> {
         // Don't run rustdoc by default.
         vec![BuildKind::Check, BuildKind::Debug, BuildKind::Opt]
@@ -98,6 +114,7 @@ const STRINGS_AND_BUILD_KINDS: &[(&str, BuildKind)] = &[
     ("Check", BuildKind::Check),
     ("Debug", BuildKind::Debug),
     ("Doc", BuildKind::Doc),
+    ("DocJson", BuildKind::DocJson),
     ("Opt", BuildKind::Opt),
 ];
 
@@ -183,6 +200,51 @@ impl BenchmarkErrors {
     }
 }
 
+/// Measures `benchmark`, recording its results, or the error if it failed, in
+/// `conn`. Returns whether it succeeded.
+fn bench_benchmark(
+    rt: &mut Runtime,
+    conn: &mut dyn database::Connection,
+    cid: &ArtifactId,
+    interned_cid: ArtifactIdNumber,
+    benchmark: &Benchmark,
+    build_kinds: &[BuildKind],
+    run_kinds: &[RunKind],
+    compiler: Compiler<'_>,
+    iterations: Option<usize>,
+    self_profile: bool,
+    drop_caches: bool,
+) -> bool {
+    rt.block_on(
+        conn.record_benchmark(benchmark.name.0.as_str(), Some(benchmark.supports_stable())),
+    );
+
+    let mut processor = execute::MeasureProcessor::new(
+        rt,
+        conn,
+        &benchmark.name,
+        &cid,
+        interned_cid,
+        self_profile,
+        drop_caches,
+    );
+    let result = benchmark.measure(&mut processor, build_kinds, run_kinds, compiler, iterations);
+    let succeeded = result.is_ok();
+    if let Err(s) = result {
+        eprintln!(
+            "collector error: Failed to benchmark '{}', recorded: {}",
+            benchmark.name, s
+        );
+        rt.block_on(conn.record_error(
+            interned_cid,
+            benchmark.name.0.as_str(),
+            &format!("{:?}", s),
+        ));
+    };
+    rt.block_on(conn.collector_end_step(interned_cid, &benchmark.name.to_string()));
+    succeeded
+}
+
 fn bench(
     rt: &mut Runtime,
     pool: database::Pool,
@@ -193,6 +255,7 @@ fn bench(
     benchmarks: &[Benchmark],
     iterations: Option<usize>,
     self_profile: bool,
+    tuning: &Tuning,
 ) -> BenchmarkErrors {
     let mut conn = rt.block_on(pool.connection());
     let mut errors = BenchmarkErrors::new();
@@ -218,59 +281,119 @@ fn bench(
         let mut tx = rt.block_on(conn.transaction());
         let interned_cid = rt.block_on(tx.conn().artifact_id(&cid));
         rt.block_on(tx.conn().collector_start(interned_cid, &steps));
+        match collector::current_machine() {
+            Ok(machine) => {
+                rt.block_on(tx.conn().record_machine(interned_cid, &machine));
+                rt.block_on(tx.conn().record_tuning(
+                    interned_cid,
+                    &machine.name,
+                    &tuning.to_string(),
+                ));
+            }
+            Err(e) => log::warn!("failed to identify this machine: {:?}", e),
+        }
 
         rt.block_on(tx.commit()).unwrap();
         interned_cid
     };
 
     let start = Instant::now();
-    let mut skipped = false;
-    for (nth_benchmark, benchmark) in benchmarks.iter().enumerate() {
+    let skipped = AtomicBool::new(false);
+    let benchmarked = AtomicBool::new(false);
+    let failed = AtomicUsize::new(0);
+    // Measures the `nth_benchmark`, unless an earlier run already did.
+    let bench_step = |rt: &mut Runtime,
+                      conn: &mut dyn database::Connection,
+                      nth_benchmark: usize,
+                      transaction: bool| {
+        let benchmark = &benchmarks[nth_benchmark];
         let is_fresh =
             rt.block_on(conn.collector_start_step(interned_cid, &benchmark.name.to_string()));
         if !is_fresh {
-            skipped = true;
+            skipped.store(true, Ordering::SeqCst);
             eprintln!("skipping {} -- already benchmarked", benchmark.name);
-            continue;
+            return;
         }
-        let mut tx = rt.block_on(conn.transaction());
-        rt.block_on(
-            tx.conn()
-                .record_benchmark(benchmark.name.0.as_str(), Some(benchmark.supports_stable())),
-        );
+        benchmarked.store(true, Ordering::SeqCst);
         eprintln!(
             "{}",
             n_benchmarks_remaining(benchmarks.len() - nth_benchmark)
         );
-
-        let mut processor = execute::MeasureProcessor::new(
+        // Record each benchmark's results atomically where possible. When
+        // benchmarking in parallel with a SQLite database, a transaction
+        // would lock the other jobs out of the database for its duration.
+        if transaction {
+            let mut tx = rt.block_on(conn.transaction());
+            if !bench_benchmark(
+                rt,
+                tx.conn(),
+                cid,
+                interned_cid,
+                benchmark,
+                build_kinds,
+                run_kinds,
+                compiler,
+                iterations,
+                self_profile,
+                tuning.drop_caches,
+            ) {
+                failed.fetch_add(1, Ordering::SeqCst);
+            }
+            rt.block_on(tx.commit()).expect("committed");
+        } else if !bench_benchmark(
             rt,
-            tx.conn(),
-            &benchmark.name,
-            &cid,
+            conn,
+            cid,
             interned_cid,
+            benchmark,
+            build_kinds,
+            run_kinds,
+            compiler,
+            iterations,
             self_profile,
-        );
-        let result =
-            benchmark.measure(&mut processor, build_kinds, run_kinds, compiler, iterations);
-        if let Err(s) = result {
-            eprintln!(
-                "collector error: Failed to benchmark '{}', recorded: {}",
-                benchmark.name, s
-            );
-            errors.incr();
-            rt.block_on(tx.conn().record_error(
-                interned_cid,
-                benchmark.name.0.as_str(),
-                &format!("{:?}", s),
-            ));
-        };
-        rt.block_on(
-            tx.conn()
-                .collector_end_step(interned_cid, &benchmark.name.to_string()),
-        );
-        rt.block_on(tx.commit()).expect("committed");
+            tuning.drop_caches,
+        ) {
+            failed.fetch_add(1, Ordering::SeqCst);
+        }
+    };
+
+    if tuning.jobs > 1 {
+        let slots = tuning
+            .cpu_slots()
+            .expect("CPUs divided when parsing arguments");
+        let next = AtomicUsize::new(0);
+        let transaction = !matches!(pool, database::Pool::Sqlite(_));
+        crossbeam_utils::thread::scope(|s| {
+            for slot in &slots {
+                let (pool, next, bench_step) = (&pool, &next, &bench_step);
+                s.spawn(move |_| {
+                    // The builds inherit the thread's affinity.
+                    tuning::pin_to_cpus(slot).expect("pinned job");
+                    let mut rt = tokio::runtime::Builder::new()
+                        .max_threads(1)
+                        .enable_io()
+                        .basic_scheduler()
+                        .build()
+                        .expect("built runtime");
+                    let mut conn = rt.block_on(pool.connection());
+                    loop {
+                        let nth_benchmark = next.fetch_add(1, Ordering::SeqCst);
+                        if nth_benchmark >= benchmarks.len() {
+                            break;
+                        }
+                        bench_step(&mut rt, &mut *conn, nth_benchmark, transaction);
+                    }
+                });
+            }
+        })
+        .unwrap();
+    } else {
+        for nth_benchmark in 0..benchmarks.len() {
+            bench_step(&mut *rt, &mut *conn, nth_benchmark, true);
+        }
     }
+    let (skipped, benchmarked) = (skipped.into_inner(), benchmarked.into_inner());
+    errors.0 = failed.into_inner();
     let end = start.elapsed();
 
     eprintln!(
@@ -278,10 +401,20 @@ fn bench(
         end, errors.0
     );
 
-    if skipped {
-        log::info!("skipping duration record -- skipped parts of run");
-    } else {
+    if !skipped {
         rt.block_on(conn.record_duration(interned_cid, end));
+    } else if benchmarked {
+        // We resumed a run which an earlier collector didn't finish, so the
+        // elapsed time only covers part of it. Add up the steps instead.
+        let total = rt
+            .block_on(conn.in_progress_steps(cid))
+            .iter()
+            .filter(|step| step.is_done)
+            .map(|step| step.duration)
+            .sum::<Duration>();
+        rt.block_on(conn.record_duration(interned_cid, total));
+    } else {
+        log::info!("skipping duration record -- nothing left to benchmark");
     }
 
     rt.block_on(async move {
@@ -291,6 +424,76 @@ fn bench(
     errors
 }
 
+/// Installs a published toolchain (e.g. stable, beta, 1.26.0) with rustup and
+/// benchmarks it.
+fn bench_published(
+    rt: &mut Runtime,
+    pool: database::Pool,
+    benchmark_dir: &Path,
+    toolchain: &str,
+    tuning: &Tuning,
+) -> anyhow::Result<BenchmarkErrors> {
+    let status = Command::new("rustup")
+        .args(&["install", "--profile=minimal", &toolchain])
+        .status()
+        .context("rustup install")?;
+    if !status.success() {
+        anyhow::bail!("failed to install toolchain for {}", toolchain);
+    }
+
+    let run_kinds = if collector::version_supports_incremental(toolchain) {
+        RunKind::all()
+    } else {
+        RunKind::all_non_incr()
+    };
+    // Published toolchains are never nightlies, so can't output JSON docs.
+    let mut build_kinds = BuildKind::all();
+    build_kinds.retain(|bk| *bk != BuildKind::DocJson);
+    if !collector::version_supports_doc(toolchain) {
+        build_kinds.retain(|bk| *bk != BuildKind::Doc);
+    }
+
+    let which = |tool| {
+        String::from_utf8(
+            Command::new("rustup")
+                .arg("which")
+                .arg("--toolchain")
+                .arg(&toolchain)
+                .arg(tool)
+                .output()
+                .context(format!("rustup which {}", tool))?
+                .stdout,
+        )
+        .context("utf8")
+    };
+    let rustc = which("rustc")?;
+    let rustdoc = which("rustdoc")?;
+    let cargo = which("cargo")?;
+
+    // Exclude benchmarks that don't work with a stable compiler.
+    let mut benchmarks = get_benchmarks(benchmark_dir, None, None)?;
+    benchmarks.retain(|b| b.supports_stable());
+
+    Ok(bench(
+        rt,
+        pool,
+        &ArtifactId::Artifact(toolchain.to_string()),
+        &build_kinds,
+        &run_kinds,
+        Compiler {
+            rustc: Path::new(rustc.trim()),
+            rustdoc: Some(Path::new(rustdoc.trim())),
+            cargo: Path::new(cargo.trim()),
+            is_nightly: false,
+            triple: "x86_64-unknown-linux-gnu",
+        },
+        &benchmarks,
+        Some(3),
+        /* self_profile */ false,
+        tuning,
+    ))
+}
+
 fn get_benchmarks(
     benchmark_dir: &Path,
     include: Option<&str>,
@@ -309,14 +512,8 @@ fn get_benchmarks(
             Err(e) => bail!("non-utf8 benchmark name: {:?}", e),
         };
 
-        if path.ends_with(".git")
-            || path.ends_with("scripts")
-            || !entry.file_type()?.is_dir()
-            || path.ends_with("native-tls-0.1.5")
-            || path.ends_with("native-tls-0.2.3")
-            || path.ends_with("rust-mozjs")
-        {
-            debug!("benchmark {} - ignored", name);
+        if !path.join(collector::manifest::MANIFEST_FILE).is_file() {
+            debug!("benchmark {} - no manifest, ignored", name);
             continue;
         }
 
@@ -362,7 +559,8 @@ fn get_benchmarks(
 /// Get a toolchain from the input.
 /// - `rustc`: check if the given one is acceptable.
 /// - `rustdoc`: if one is given, check if it is acceptable. Otherwise, if
-///   `Doc` builds are requested, look for one next to the given `rustc`.
+///   `Doc` or `DocJson` builds are requested, look for one next to the given
+///   `rustc`.
 /// - `cargo`: if one is given, check if it is acceptable. Otherwise, look
 ///   for the nightly Cargo via `rustup`.
 fn get_local_toolchain(
@@ -398,14 +596,14 @@ fn get_local_toolchain(
             Some(PathBuf::from(rustdoc).canonicalize().with_context(|| {
                 format!("failed to canonicalize rustdoc executable '{}'", rustdoc)
             })?)
-        } else if build_kinds.contains(&BuildKind::Doc) {
+        } else if build_kinds.iter().any(|bk| bk.is_doc()) {
             // We need a `rustdoc`. Look for one next to `rustc`.
             if let Ok(rustdoc) = rustc.with_file_name("rustdoc").canonicalize() {
                 debug!("found rustdoc: {:?}", &rustdoc);
                 Some(rustdoc)
             } else {
                 anyhow::bail!(
-                    "'Doc' build specified but '--rustdoc' not specified and no 'rustdoc' found \
+                    "'Doc' or 'DocJson' build specified but '--rustdoc' not specified and no 'rustdoc' found \
                     next to 'rustc'"
                 );
             }
@@ -448,7 +646,7 @@ fn main() {
 }
 
 fn main_result() -> anyhow::Result<i32> {
-    env_logger::init();
+    database::logging::init();
 
     let matches = clap_app!(rustc_perf_collector =>
         (version: "0.1")
@@ -468,15 +666,31 @@ fn main_result() -> anyhow::Result<i32> {
             // Options
             (@arg BUILDS:  --builds  +takes_value
              "One or more (comma-separated) of: 'Check', 'Debug',\n\
-             'Doc', 'Opt', 'All'")
+             'Doc', 'DocJson', 'Opt', 'All'")
             (@arg CARGO:   --cargo   +takes_value "The path to the local Cargo to use")
+            (@arg CONTAINERS: --containers
+             "Build benchmarks in the container images pinned\n\
+             in their manifests (needs docker)")
             (@arg DB:      --db      +takes_value "Database output file")
+            (@arg DROP_CACHES: --("drop-caches") "Drop the page cache before each run (needs root)")
             (@arg EXCLUDE: --exclude     +takes_value
              "Exclude all benchmarks matching anything in\n\
              this comma-separated list of patterns")
             (@arg INCLUDE: --include     +takes_value
              "Include only benchmarks matching something in\n\
              this comma-separated list of patterns")
+            (@arg ITERATIONS: --iterations +takes_value
+             "How many times to run each benchmark; the default\n\
+             is 1, or 'config' for each benchmark's configured count")
+            (@arg JOBS: --jobs +takes_value
+             "How many benchmarks to run at once, each on its\n\
+             own share of the CPUs; the default is 1")
+            (@arg NO_ASLR: --("no-aslr") "Disable ASLR system-wide (needs root)")
+            (@arg NO_TURBO: --("no-turbo") "Disable turbo boost (needs root)")
+            (@arg PERFORMANCE_GOVERNOR: --("performance-governor")
+             "Use the performance CPU frequency governor (needs root)")
+            (@arg PIN_CPUS: --("pin-cpus") +takes_value
+             "Pin benchmarking to these CPUs, e.g. '2-3'")
             (@arg RUNS:    --runs    +takes_value
              "One or more (comma-separated) of: 'Full',\n\
              'IncrFull', 'IncrUnchanged', 'IncrPatched', 'All'")
@@ -484,6 +698,46 @@ fn main_result() -> anyhow::Result<i32> {
             (@arg SELF_PROFILE: --("self-profile") "Collect self-profile data")
         )
 
+        (@subcommand compare_local =>
+            (about: "Compares the results of two bench_local runs or commits")
+            (alias: "diff")
+
+            // Mandatory arguments
+            (@arg ID_BEFORE: +required +takes_value
+             "Identifier or commit sha of the baseline results")
+            (@arg ID_AFTER:  +required +takes_value
+             "Identifier or commit sha of the results to compare")
+
+            // Options
+            (@arg DB:        --db        +takes_value "Database file holding both results")
+            (@arg STAT:      --stat      +takes_value
+             "The statistic to compare; the default is 'instructions:u'")
+            (@arg THRESHOLD: --threshold +takes_value
+             "Only show changes of at least this many percent; the\n\
+             default is 0.1")
+        )
+
+        (@subcommand report =>
+            (about: "Writes a comparison of two artifacts as a self-contained HTML page")
+
+            // Mandatory arguments
+            (@arg ID_BEFORE: +required +takes_value
+             "Identifier, commit sha or release of the baseline results")
+            (@arg ID_AFTER:  +required +takes_value
+             "Identifier, commit sha or release of the results to compare")
+
+            // Options
+            (@arg DB:        --db        +takes_value "Database file holding both results")
+            (@arg OUT:       --out       +takes_value
+             "The file to write the page to; the default is\n\
+             'report.html'")
+            (@arg STAT:      --stat      +takes_value
+             "The statistic to compare; the default is 'instructions:u'")
+            (@arg THRESHOLD: --threshold +takes_value
+             "Only show changes of at least this many percent; the\n\
+             default is 0.1")
+        )
+
         (@subcommand bench_next =>
             (about: "Benchmarks the next commit for perf.rust-lang.org")
 
@@ -491,7 +745,20 @@ fn main_result() -> anyhow::Result<i32> {
             (@arg SITE_URL: +required +takes_value "Site URL")
 
             // Options
+            (@arg CONTAINERS: --containers
+             "Build benchmarks in the container images pinned\n\
+             in their manifests (needs docker)")
             (@arg DB:           --db  +takes_value "Database output file")
+            (@arg DROP_CACHES: --("drop-caches") "Drop the page cache before each run (needs root)")
+            (@arg JOBS: --jobs +takes_value
+             "How many benchmarks to run at once, each on its\n\
+             own share of the CPUs; the default is 1")
+            (@arg NO_ASLR: --("no-aslr") "Disable ASLR system-wide (needs root)")
+            (@arg NO_TURBO: --("no-turbo") "Disable turbo boost (needs root)")
+            (@arg PERFORMANCE_GOVERNOR: --("performance-governor")
+             "Use the performance CPU frequency governor (needs root)")
+            (@arg PIN_CPUS: --("pin-cpus") +takes_value
+             "Pin benchmarking to these CPUs, e.g. '2-3'")
             (@arg SELF_PROFILE: --("self-profile") "Collect self-profile data")
         )
 
@@ -503,6 +770,50 @@ fn main_result() -> anyhow::Result<i32> {
 
             // Options
             (@arg DB: --db +takes_value "Database output file")
+            (@arg DROP_CACHES: --("drop-caches") "Drop the page cache before each run (needs root)")
+            (@arg NO_ASLR: --("no-aslr") "Disable ASLR system-wide (needs root)")
+            (@arg NO_TURBO: --("no-turbo") "Disable turbo boost (needs root)")
+            (@arg PERFORMANCE_GOVERNOR: --("performance-governor")
+             "Use the performance CPU frequency governor (needs root)")
+            (@arg PIN_CPUS: --("pin-cpus") +takes_value
+             "Pin benchmarking to these CPUs, e.g. '2-3'")
+        )
+
+        (@subcommand bench_commit =>
+            (about: "Downloads the CI build of a commit and benchmarks it")
+
+            // Mandatory arguments
+            (@arg COMMIT: +required +takes_value "The sha (or nightly-YYYY-MM-DD) of the master or try commit to benchmark")
+
+            // Options
+            (@arg BUILDS:  --builds  +takes_value
+             "One or more (comma-separated) of: 'Check', 'Debug',\n\
+             'Doc', 'DocJson', 'Opt', 'All'")
+            (@arg CONTAINERS: --containers
+             "Build benchmarks in the container images pinned\n\
+             in their manifests (needs docker)")
+            (@arg DB:      --db      +takes_value "Database output file")
+            (@arg DROP_CACHES: --("drop-caches") "Drop the page cache before each run (needs root)")
+            (@arg EXCLUDE: --exclude     +takes_value
+             "Exclude all benchmarks matching anything in\n\
+             this comma-separated list of patterns")
+            (@arg INCLUDE: --include     +takes_value
+             "Include only benchmarks matching something in\n\
+             this comma-separated list of patterns")
+            (@arg JOBS: --jobs +takes_value
+             "How many benchmarks to run at once, each on its\n\
+             own share of the CPUs; the default is 1")
+            (@arg KEEP_SYSROOT: --("keep-sysroot") "Don't delete the downloaded toolchain afterwards")
+            (@arg NO_ASLR: --("no-aslr") "Disable ASLR system-wide (needs root)")
+            (@arg NO_TURBO: --("no-turbo") "Disable turbo boost (needs root)")
+            (@arg PERFORMANCE_GOVERNOR: --("performance-governor")
+             "Use the performance CPU frequency governor (needs root)")
+            (@arg PIN_CPUS: --("pin-cpus") +takes_value
+             "Pin benchmarking to these CPUs, e.g. '2-3'")
+            (@arg RUNS:    --runs    +takes_value
+             "One or more (comma-separated) of: 'Full',\n\
+             'IncrFull', 'IncrUnchanged', 'IncrPatched', 'All'")
+            (@arg SELF_PROFILE: --("self-profile") "Collect self-profile data")
         )
 
         (@subcommand profile_local =>
@@ -511,15 +822,17 @@ fn main_result() -> anyhow::Result<i32> {
             // Mandatory arguments
             (@arg PROFILER: +required +takes_value
              "One of: 'self-profile', 'time-passes', 'perf-record',\n\
-             'oprofile', 'cachegrind', 'callgrind', 'dhat', 'massif',\n\
-             'eprintln', 'llvm-lines'")
+             'perf-flamegraph', 'oprofile', 'cachegrind', 'callgrind',\n\
+             'dhat', 'massif', 'eprintln', 'llvm-lines'")
             (@arg RUSTC:    +required +takes_value "The path to the local rustc to benchmark")
             (@arg ID:       +required +takes_value "Identifier to associate benchmark results with")
 
              // Options
+            (@arg BENCHMARK: --benchmark +takes_value
+             "Profile only the benchmark with exactly this name")
             (@arg BUILDS: --builds       +takes_value
              "One or more (comma-separated) of: 'Check', \n\
-             'Debug', 'Doc', 'Opt', 'All'")
+             'Debug', 'Doc', 'DocJson', 'Opt', 'All'")
             (@arg CARGO:   --cargo       +takes_value "The path to the local Cargo to use")
             (@arg EXCLUDE: --exclude     +takes_value
              "Exclude all benchmarks matching anything in\n\
@@ -534,6 +847,58 @@ fn main_result() -> anyhow::Result<i32> {
             (@arg RUSTDOC: --rustdoc +takes_value "The path to the local rustdoc to benchmark")
         )
 
+        (@subcommand diff_local =>
+            (about: "Profiles two local rustcs with Cachegrind and diffs the results")
+
+            // Mandatory arguments
+            (@arg RUSTC_BEFORE: +required +takes_value "The path to the baseline rustc")
+            (@arg RUSTC_AFTER:  +required +takes_value "The path to the rustc to compare")
+            (@arg ID_BEFORE:    +required +takes_value "Identifier for the baseline rustc")
+            (@arg ID_AFTER:     +required +takes_value "Identifier for the rustc to compare")
+
+            // Options
+            (@arg BUILDS: --builds       +takes_value
+             "One or more (comma-separated) of: 'Check', \n\
+             'Debug', 'Doc', 'DocJson', 'Opt', 'All'")
+            (@arg CARGO:   --cargo       +takes_value "The path to the local Cargo to use")
+            (@arg EXCLUDE: --exclude     +takes_value
+             "Exclude all benchmarks matching anything in\n\
+             this comma-separated list of patterns")
+            (@arg INCLUDE: --include     +takes_value
+             "Include only benchmarks matching something in\n\
+             this comma-separated list of patterns")
+            (@arg OUT_DIR: --("out-dir") +takes_value "Output directory")
+            (@arg RUNS:    --runs        +takes_value
+             "One or more (comma-separated) of: 'Full',\n\
+             'IncrFull', 'IncrUnchanged', 'IncrPatched', 'All'")
+        )
+
+        (@subcommand profile_commit =>
+            (about: "Profiles a master commit, archiving the output under its sha")
+
+            // Mandatory arguments
+            (@arg PROFILER: +required +takes_value
+             "One of: 'self-profile', 'time-passes', 'perf-record',\n\
+             'perf-flamegraph', 'oprofile', 'cachegrind', 'callgrind',\n\
+             'dhat', 'massif', 'eprintln', 'llvm-lines'")
+            (@arg COMMIT: +required +takes_value "The sha (or nightly-YYYY-MM-DD) of the commit to profile")
+
+            // Options
+            (@arg BUILDS: --builds       +takes_value
+             "One or more (comma-separated) of: 'Check', \n\
+             'Debug', 'Doc', 'DocJson', 'Opt', 'All'")
+            (@arg EXCLUDE: --exclude     +takes_value
+             "Exclude all benchmarks matching anything in\n\
+             this comma-separated list of patterns")
+            (@arg INCLUDE: --include     +takes_value
+             "Include only benchmarks matching something in\n\
+             this comma-separated list of patterns")
+            (@arg OUT_DIR: --("out-dir") +takes_value "Output directory")
+            (@arg RUNS:    --runs        +takes_value
+             "One or more (comma-separated) of: 'Full',\n\
+             'IncrFull', 'IncrUnchanged', 'IncrPatched', 'All'")
+        )
+
         (@subcommand install_next =>
             (about: "Installs the next commit for perf.rust-lang.org")
 
@@ -541,6 +906,103 @@ fn main_result() -> anyhow::Result<i32> {
 
             // Options: (none)
         )
+
+        (@subcommand backfill =>
+            (about: "Queues master commits which have no results, oldest first")
+
+            // Mandatory arguments
+            (@arg RUST_REPO: +required +takes_value "The path to a rust-lang/rust checkout")
+            (@arg FROM:      +required +takes_value "Queue commits from this date (YYYY-MM-DD)")
+            (@arg TO:        +required +takes_value "Queue commits up to this date (YYYY-MM-DD)")
+
+            // Options
+            (@arg BRANCH: --branch +takes_value
+             "The branch of the checkout to take commits from; the\n\
+             default is 'master'")
+            (@arg DB:     --db     +takes_value "Database holding the job queue")
+            (@arg LIMIT:  --limit  +takes_value
+             "Queue at most this many commits; the default is 100")
+        )
+
+        (@subcommand queue =>
+            (about: "Administers the benchmark job queue of perf.rust-lang.org")
+
+            (@subcommand list =>
+                (about: "Lists the queued jobs, in the order they will be claimed")
+
+                // Mandatory arguments
+                (@arg SITE_URL: +required +takes_value "Site URL")
+            )
+            (@subcommand prioritize =>
+                (about: "Changes the priority of a job; lower values are claimed first")
+
+                // Mandatory arguments
+                (@arg SITE_URL: +required +takes_value "Site URL")
+                (@arg ARTIFACT: +required +takes_value "The sha or tag of the job")
+                (@arg PRIORITY: +required +takes_value
+                 "The new priority; 0 is that of try builds")
+            )
+            (@subcommand cancel =>
+                (about: "Takes a job out of the queue")
+
+                // Mandatory arguments
+                (@arg SITE_URL: +required +takes_value "Site URL")
+                (@arg ARTIFACT: +required +takes_value "The sha or tag of the job")
+            )
+            (@subcommand retry =>
+                (about: "Puts a claimed or finished job back in the queue")
+
+                // Mandatory arguments
+                (@arg SITE_URL: +required +takes_value "Site URL")
+                (@arg ARTIFACT: +required +takes_value "The sha or tag of the job")
+            )
+            (@subcommand backfill =>
+                (about: "Queues the master commits the site has no results for, oldest first")
+
+                // Mandatory arguments
+                (@arg SITE_URL: +required +takes_value "Site URL")
+                (@arg SINCE:    +required +takes_value "Queue commits from this date (YYYY-MM-DD)")
+
+                // Options
+                (@arg LIMIT: --limit +takes_value
+                 "Queue at most this many commits; the default is 100")
+            )
+        )
+
+        (@subcommand toolchain =>
+            (about: "Manages the toolchains kept in the cache directory")
+
+            (@subcommand list =>
+                (about: "Lists the toolchains, newest first")
+            )
+            (@subcommand install =>
+                (about: "Downloads the CI build of a master or try commit")
+
+                // Mandatory arguments
+                (@arg COMMIT: +required +takes_value "The sha (or nightly-YYYY-MM-DD) of the commit")
+            )
+            (@subcommand link =>
+                (about: "Adds a local toolchain under a name")
+
+                // Mandatory arguments
+                (@arg NAME: +required +takes_value "The name to give the toolchain")
+                (@arg PATH: +required +takes_value
+                 "The toolchain's directory, i.e. the one containing bin/rustc")
+            )
+            (@subcommand remove =>
+                (about: "Removes a toolchain, or unlinks a local one")
+
+                // Mandatory arguments
+                (@arg NAME: +required +takes_value "The name of the toolchain")
+            )
+            (@subcommand gc =>
+                (about: "Removes all but the most recent downloaded toolchains")
+
+                // Options
+                (@arg KEEP: --keep +takes_value
+                 "How many downloaded toolchains to keep; the default is 3")
+            )
+        )
     )
     .get_matches();
 
@@ -557,6 +1019,7 @@ fn main_result() -> anyhow::Result<i32> {
 
     let default_db = "results.db";
     let default_out_dir = std::ffi::OsStr::new("results");
+    let default_artifacts_dir = std::ffi::OsStr::new("artifacts");
 
     let ret = match matches.subcommand() {
         ("bench_local", Some(sub_m)) => {
@@ -567,18 +1030,32 @@ fn main_result() -> anyhow::Result<i32> {
             // Options
             let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
             let cargo = sub_m.value_of("CARGO");
+            let containers = sub_m.is_present("CONTAINERS");
             let db = sub_m.value_of("DB").unwrap_or(default_db);
             let exclude = sub_m.value_of("EXCLUDE");
             let include = sub_m.value_of("INCLUDE");
+            let iterations = match sub_m.value_of("ITERATIONS") {
+                None => Some(1),
+                Some("config") => None,
+                Some(n) => Some(
+                    n.parse::<usize>()
+                        .with_context(|| format!("invalid iteration count '{}'", n))?,
+                ),
+            };
             let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
             let rustdoc = sub_m.value_of("RUSTDOC");
             let self_profile = sub_m.is_present("SELF_PROFILE");
+            let tuning = Tuning::from_args(sub_m)?;
 
             let pool = database::Pool::open(db);
+            let _tuning_guard = tuning.apply()?;
 
             let (rustc, rustdoc, cargo) = get_local_toolchain(&build_kinds, rustc, rustdoc, cargo)?;
 
-            let benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+            let mut benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+            if containers {
+                benchmarks.iter_mut().for_each(Benchmark::use_container);
+            }
 
             let res = bench(
                 &mut rt,
@@ -594,25 +1071,76 @@ fn main_result() -> anyhow::Result<i32> {
                     is_nightly: true,
                 },
                 &benchmarks,
-                Some(1),
+                iterations,
                 self_profile,
+                &tuning,
             );
             res.fail_if_nonzero()?;
             Ok(0)
         }
 
+        ("compare_local", Some(sub_m)) => {
+            // Mandatory arguments
+            let id_before = sub_m.value_of("ID_BEFORE").unwrap();
+            let id_after = sub_m.value_of("ID_AFTER").unwrap();
+
+            // Options
+            let db = sub_m.value_of("DB").unwrap_or(default_db);
+            let stat = sub_m.value_of("STAT").unwrap_or("instructions:u");
+            let threshold = match sub_m.value_of("THRESHOLD") {
+                Some(t) => t
+                    .parse::<f64>()
+                    .with_context(|| format!("invalid threshold '{}'", t))?,
+                None => 0.1,
+            };
+
+            let pool = database::Pool::open(db);
+            let comparison = compare::compare(&mut rt, &pool, id_before, id_after, stat)?;
+            compare::print_changes(&comparison, threshold);
+            Ok(0)
+        }
+
+        ("report", Some(sub_m)) => {
+            // Mandatory arguments
+            let id_before = sub_m.value_of("ID_BEFORE").unwrap();
+            let id_after = sub_m.value_of("ID_AFTER").unwrap();
+
+            // Options
+            let db = sub_m.value_of("DB").unwrap_or(default_db);
+            let out = sub_m.value_of("OUT").unwrap_or("report.html");
+            let stat = sub_m.value_of("STAT").unwrap_or("instructions:u");
+            let threshold = match sub_m.value_of("THRESHOLD") {
+                Some(t) => t
+                    .parse::<f64>()
+                    .with_context(|| format!("invalid threshold '{}'", t))?,
+                None => 0.1,
+            };
+
+            let pool = database::Pool::open(db);
+            let comparison = compare::compare(&mut rt, &pool, id_before, id_after, stat)?;
+            let history = report::history(&mut rt, &pool, id_before, id_after, stat)?;
+            let html = report::render(&comparison, &history, id_before, id_after, stat, threshold);
+            fs::write(out, html).with_context(|| format!("failed to write {}", out))?;
+            println!("wrote {}", out);
+            Ok(0)
+        }
+
         ("bench_next", Some(sub_m)) => {
             // Mandatory arguments
             let site_url = sub_m.value_of("SITE_URL").unwrap();
 
             // Options
+            let containers = sub_m.is_present("CONTAINERS");
             let db = sub_m.value_of("DB").unwrap_or(default_db);
             let self_profile = sub_m.is_present("SELF_PROFILE");
+            let tuning = Tuning::from_args(sub_m)?;
 
             println!("processing commits");
+            let machine = collector::current_machine()?;
             let client = reqwest::blocking::Client::new();
             let response: collector::api::next_commit::Response = client
                 .get(&format!("{}/perf/next_commit", site_url))
+                .query(&[("collector", &machine.name)])
                 .send()?
                 .json()?;
             let next = if let Some(c) = response.commit {
@@ -622,34 +1150,56 @@ fn main_result() -> anyhow::Result<i32> {
                 // no missing commits
                 return Ok(0);
             };
-            let commit = get_commit_or_fake_it(&next.sha)?;
 
             let pool = database::Pool::open(db);
+            let _tuning_guard = tuning.apply()?;
+            // The site claimed the job for us, so it has to be completed, or
+            // put back in the queue if it couldn't be benchmarked.
+            let jobs = database::Pool::open(db);
+            let conn = rt.block_on(jobs.connection());
+
+            let res = (|| -> anyhow::Result<BenchmarkErrors> {
+                if next.kind == Some(JobKind::Release) {
+                    return bench_published(&mut rt, pool, &benchmark_dir, &next.sha, &tuning);
+                }
 
-            let sysroot = Sysroot::install(commit.sha.to_string(), "x86_64-unknown-linux-gnu")
-                .with_context(|| format!("failed to install sysroot for {:?}", commit))?;
+                let commit = get_commit_or_fake_it(&next.sha)?;
 
-            let benchmarks = get_benchmarks(
-                &benchmark_dir,
-                next.include.as_deref(),
-                next.exclude.as_deref(),
-            )?;
+                let sysroot = Sysroot::install(commit.sha.to_string(), "x86_64-unknown-linux-gnu")
+                    .with_context(|| format!("failed to install sysroot for {:?}", commit))?;
 
-            let res = bench(
-                &mut rt,
-                pool,
-                &ArtifactId::Commit(commit),
-                &BuildKind::all(),
-                &RunKind::all(),
-                Compiler::from_sysroot(&sysroot),
-                &benchmarks,
-                next.runs.map(|v| v as usize),
-                self_profile,
-            );
+                let mut benchmarks = get_benchmarks(
+                    &benchmark_dir,
+                    next.include.as_deref(),
+                    next.exclude.as_deref(),
+                )?;
+                if containers {
+                    benchmarks.iter_mut().for_each(Benchmark::use_container);
+                }
 
+                Ok(bench(
+                    &mut rt,
+                    pool,
+                    &ArtifactId::Commit(commit),
+                    &BuildKind::all(),
+                    &RunKind::all(),
+                    Compiler::from_sysroot(&sysroot),
+                    &benchmarks,
+                    next.runs.map(|v| v as usize),
+                    self_profile,
+                    &tuning,
+                ))
+            })();
+
+            match &res {
+                Ok(errors) if errors.0 == 0 => rt.block_on(conn.complete_job(&next.sha)),
+                _ => {
+                    rt.block_on(conn.retry_job(&next.sha));
+                }
+            }
             client.post(&format!("{}/perf/onpush", site_url)).send()?;
 
-            res.fail_if_nonzero()?;
+            res?.fail_if_nonzero()?;
             Ok(0)
         }
 
@@ -659,68 +1209,55 @@ fn main_result() -> anyhow::Result<i32> {
 
             // Options
             let db = sub_m.value_of("DB").unwrap_or(default_db);
-
-            let status = Command::new("rustup")
-                .args(&["install", "--profile=minimal", &toolchain])
-                .status()
-                .context("rustup install")?;
-            if !status.success() {
-                anyhow::bail!("failed to install toolchain for {}", toolchain);
-            }
+            let tuning = Tuning::from_args(sub_m)?;
 
             let pool = database::Pool::open(db);
+            let _tuning_guard = tuning.apply()?;
+            let res = bench_published(&mut rt, pool, &benchmark_dir, toolchain, &tuning)?;
+            res.fail_if_nonzero()?;
+            Ok(0)
+        }
 
-            let run_kinds = if collector::version_supports_incremental(toolchain) {
-                RunKind::all()
-            } else {
-                RunKind::all_non_incr()
-            };
-            let build_kinds = if collector::version_supports_doc(toolchain) {
-                BuildKind::all()
-            } else {
-                let mut all = BuildKind::all();
-                let doc = all.iter().position(|bk| *bk == BuildKind::Doc).unwrap();
-                all.remove(doc);
-                all
-            };
+        ("bench_commit", Some(sub_m)) => {
+            // Mandatory arguments
+            let sha = sub_m.value_of("COMMIT").unwrap();
 
-            let which = |tool| {
-                String::from_utf8(
-                    Command::new("rustup")
-                        .arg("which")
-                        .arg("--toolchain")
-                        .arg(&toolchain)
-                        .arg(tool)
-                        .output()
-                        .context(format!("rustup which {}", tool))?
-                        .stdout,
-                )
-                .context("utf8")
-            };
-            let rustc = which("rustc")?;
-            let rustdoc = which("rustdoc")?;
-            let cargo = which("cargo")?;
+            // Options
+            let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
+            let containers = sub_m.is_present("CONTAINERS");
+            let db = sub_m.value_of("DB").unwrap_or(default_db);
+            let exclude = sub_m.value_of("EXCLUDE");
+            let include = sub_m.value_of("INCLUDE");
+            let keep_sysroot = sub_m.is_present("KEEP_SYSROOT");
+            let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
+            let self_profile = sub_m.is_present("SELF_PROFILE");
+            let tuning = Tuning::from_args(sub_m)?;
 
-            // Exclude benchmarks that don't work with a stable compiler.
-            let mut benchmarks = get_benchmarks(&benchmark_dir, None, None)?;
-            benchmarks.retain(|b| b.supports_stable());
+            let commit = get_commit_or_fake_it(sha)?;
+            let mut sysroot = Sysroot::install(commit.sha.to_string(), "x86_64-unknown-linux-gnu")
+                .with_context(|| format!("failed to install sysroot for {:?}", commit))?;
+            if keep_sysroot {
+                sysroot.preserve();
+            }
+
+            let mut benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+            if containers {
+                benchmarks.iter_mut().for_each(Benchmark::use_container);
+            }
 
+            let pool = database::Pool::open(db);
+            let _tuning_guard = tuning.apply()?;
             let res = bench(
                 &mut rt,
                 pool,
-                &ArtifactId::Artifact(toolchain.to_string()),
+                &ArtifactId::Commit(commit),
                 &build_kinds,
                 &run_kinds,
-                Compiler {
-                    rustc: Path::new(rustc.trim()),
-                    rustdoc: Some(Path::new(rustdoc.trim())),
-                    cargo: Path::new(cargo.trim()),
-                    is_nightly: false,
-                    triple: "x86_64-unknown-linux-gnu",
-                },
+                Compiler::from_sysroot(&sysroot),
                 &benchmarks,
-                Some(3),
-                /* self_profile */ false,
+                None,
+                self_profile,
+                &tuning,
             );
             res.fail_if_nonzero()?;
             Ok(0)
@@ -733,6 +1270,7 @@ fn main_result() -> anyhow::Result<i32> {
             let id = sub_m.value_of("ID").unwrap();
 
             // Options
+            let benchmark = sub_m.value_of("BENCHMARK");
             let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
             let cargo = sub_m.value_of("CARGO");
             let exclude = sub_m.value_of("EXCLUDE");
@@ -751,7 +1289,13 @@ fn main_result() -> anyhow::Result<i32> {
                 is_nightly: true,
             };
 
-            let benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+            let mut benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+            if let Some(name) = benchmark {
+                benchmarks.retain(|b| b.name.0 == name);
+                if benchmarks.is_empty() {
+                    bail!("no benchmark named '{}'", name);
+                }
+            }
 
             eprintln!("Profiling with {:?}", profiler);
 
@@ -769,6 +1313,107 @@ fn main_result() -> anyhow::Result<i32> {
                     );
                 }
             }
+            eprintln!("Output is in {}", out_dir.display());
+            errors.fail_if_nonzero()?;
+            Ok(0)
+        }
+
+        ("diff_local", Some(sub_m)) => {
+            // Mandatory arguments
+            let rustc_before = sub_m.value_of("RUSTC_BEFORE").unwrap();
+            let rustc_after = sub_m.value_of("RUSTC_AFTER").unwrap();
+            let id_before = sub_m.value_of("ID_BEFORE").unwrap();
+            let id_after = sub_m.value_of("ID_AFTER").unwrap();
+
+            // Options
+            let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
+            let cargo = sub_m.value_of("CARGO");
+            let exclude = sub_m.value_of("EXCLUDE");
+            let include = sub_m.value_of("INCLUDE");
+            let out_dir = PathBuf::from(sub_m.value_of_os("OUT_DIR").unwrap_or(default_out_dir));
+            let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
+
+            let benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+
+            let mut errors = BenchmarkErrors::new();
+            for &(rustc, id) in &[(rustc_before, id_before), (rustc_after, id_after)] {
+                let (rustc, rustdoc, cargo) =
+                    get_local_toolchain(&build_kinds, rustc, None, cargo)?;
+                let compiler = Compiler {
+                    rustc: &rustc,
+                    rustdoc: rustdoc.as_deref(),
+                    cargo: &cargo,
+                    triple: "x86_64-unknown-linux-gnu", // XXX: technically not necessarily true
+                    is_nightly: true,
+                };
+
+                eprintln!("Profiling {} with Cachegrind", id);
+                for (i, benchmark) in benchmarks.iter().enumerate() {
+                    eprintln!("{}", n_benchmarks_remaining(benchmarks.len() - i));
+                    let mut processor =
+                        execute::ProfileProcessor::new(Profiler::Cachegrind, &out_dir, id);
+                    let result = benchmark.measure(
+                        &mut processor,
+                        &build_kinds,
+                        &run_kinds,
+                        compiler,
+                        Some(1),
+                    );
+                    if let Err(ref s) = result {
+                        errors.incr();
+                        eprintln!(
+                            "collector error: Failed to profile '{}' for {}, recorded: {:?}",
+                            benchmark.name, id, s
+                        );
+                    }
+                }
+            }
+
+            let diffs = execute::diff_cachegrind(&out_dir, id_before, id_after)?;
+            eprintln!("Wrote {} diffs to {}", diffs, out_dir.display());
+            errors.fail_if_nonzero()?;
+            Ok(0)
+        }
+
+        ("profile_commit", Some(sub_m)) => {
+            // Mandatory arguments
+            let profiler = Profiler::from_name(sub_m.value_of("PROFILER").unwrap())?;
+            let sha = sub_m.value_of("COMMIT").unwrap();
+
+            // Options
+            let build_kinds = build_kinds_from_arg(&sub_m.value_of("BUILDS"))?;
+            let exclude = sub_m.value_of("EXCLUDE");
+            let include = sub_m.value_of("INCLUDE");
+            let out_dir = PathBuf::from(
+                sub_m
+                    .value_of_os("OUT_DIR")
+                    .unwrap_or(default_artifacts_dir),
+            );
+            let run_kinds = run_kinds_from_arg(&sub_m.value_of("RUNS"))?;
+
+            let commit = get_commit_or_fake_it(sha)?;
+            let sysroot = Sysroot::install(commit.sha.to_string(), "x86_64-unknown-linux-gnu")
+                .with_context(|| format!("failed to install sysroot for {:?}", commit))?;
+            let compiler = Compiler::from_sysroot(&sysroot);
+
+            let benchmarks = get_benchmarks(&benchmark_dir, include, exclude)?;
+
+            eprintln!("Profiling {} with {:?}", commit.sha, profiler);
+
+            let mut errors = BenchmarkErrors::new();
+            for (i, benchmark) in benchmarks.iter().enumerate() {
+                eprintln!("{}", n_benchmarks_remaining(benchmarks.len() - i));
+                let mut processor = execute::ProfileProcessor::new(profiler, &out_dir, &commit.sha);
+                let result =
+                    benchmark.measure(&mut processor, &build_kinds, &run_kinds, compiler, Some(1));
+                if let Err(ref s) = result {
+                    errors.incr();
+                    eprintln!(
+                        "collector error: Failed to profile '{}' with {:?}, recorded: {:?}",
+                        benchmark.name, profiler, s
+                    );
+                }
+            }
             errors.fail_if_nonzero()?;
             Ok(0)
         }
@@ -798,6 +1443,198 @@ fn main_result() -> anyhow::Result<i32> {
             Ok(0)
         }
 
+        ("backfill", Some(sub_m)) => {
+            // Mandatory arguments
+            let rust_repo = Path::new(sub_m.value_of_os("RUST_REPO").unwrap());
+            let from = sub_m.value_of("FROM").unwrap();
+            let to = sub_m.value_of("TO").unwrap();
+
+            // Options
+            let branch = sub_m.value_of("BRANCH").unwrap_or("master");
+            let db = sub_m.value_of("DB").unwrap_or(default_db);
+            let limit = match sub_m.value_of("LIMIT") {
+                Some(n) => n
+                    .parse::<usize>()
+                    .with_context(|| format!("invalid limit '{}'", n))?,
+                None => 100,
+            };
+
+            for date in &[from, to] {
+                chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .with_context(|| format!("invalid date '{}'", date))?;
+            }
+
+            // Merges into master are its first-parent history.
+            let output = collector::command_output(
+                Command::new("git")
+                    .arg("-C")
+                    .arg(rust_repo)
+                    .arg("log")
+                    .arg("--first-parent")
+                    .arg("--reverse")
+                    .arg("--format=%H")
+                    .arg(format!("--since={} 00:00:00", from))
+                    .arg(format!("--until={} 23:59:59", to))
+                    .arg(branch),
+            )?;
+            let shas = String::from_utf8(output.stdout).context("git output is not utf8")?;
+
+            let pool = database::Pool::open(db);
+            let conn = rt.block_on(pool.connection());
+            let mut queued = 0;
+            for sha in shas.lines() {
+                if queued == limit {
+                    break;
+                }
+                if rt.block_on(conn.artifact_by_name(sha)).is_some() {
+                    continue;
+                }
+                // Backfill jobs are only claimed when there is nothing else to
+                // do, so they never delay current work.
+                rt.block_on(conn.enqueue_job(sha, JobKind::Backfill, None, None, None));
+                println!("queued {}", sha);
+                queued += 1;
+            }
+            eprintln!("queued {} commits", queued);
+            Ok(0)
+        }
+
+        ("queue", Some(sub_m)) => {
+            use collector::api::queue::Request;
+
+            let (site_url, request) = match sub_m.subcommand() {
+                ("list", Some(sub_m)) => (sub_m.value_of("SITE_URL").unwrap(), Request::List),
+                ("prioritize", Some(sub_m)) => {
+                    let priority = sub_m.value_of("PRIORITY").unwrap();
+                    let priority = priority
+                        .parse::<i32>()
+                        .with_context(|| format!("invalid priority '{}'", priority))?;
+                    (
+                        sub_m.value_of("SITE_URL").unwrap(),
+                        Request::Prioritize {
+                            artifact: sub_m.value_of("ARTIFACT").unwrap().to_string(),
+                            priority,
+                        },
+                    )
+                }
+                ("cancel", Some(sub_m)) => (
+                    sub_m.value_of("SITE_URL").unwrap(),
+                    Request::Cancel {
+                        artifact: sub_m.value_of("ARTIFACT").unwrap().to_string(),
+                    },
+                ),
+                ("retry", Some(sub_m)) => (
+                    sub_m.value_of("SITE_URL").unwrap(),
+                    Request::Retry {
+                        artifact: sub_m.value_of("ARTIFACT").unwrap().to_string(),
+                    },
+                ),
+                ("backfill", Some(sub_m)) => {
+                    let limit = match sub_m.value_of("LIMIT") {
+                        Some(n) => n
+                            .parse::<usize>()
+                            .with_context(|| format!("invalid limit '{}'", n))?,
+                        None => 100,
+                    };
+                    (
+                        sub_m.value_of("SITE_URL").unwrap(),
+                        Request::Backfill {
+                            since: sub_m.value_of("SINCE").unwrap().to_string(),
+                            limit,
+                        },
+                    )
+                }
+                _ => {
+                    let _ = writeln!(stderr(), "{}", sub_m.usage());
+                    return Ok(2);
+                }
+            };
+
+            let response = queue_request(site_url, &request)?;
+            if response.jobs.is_empty() {
+                println!("the queue is empty");
+            }
+            for job in response.jobs {
+                println!(
+                    "{:<42} {:<10} {:>3}  {}",
+                    job.artifact,
+                    format!("{:?}", job.kind),
+                    job.priority,
+                    job.claimed_by
+                        .map_or_else(String::new, |c| format!("claimed by {}", c)),
+                );
+            }
+            Ok(0)
+        }
+
+        ("toolchain", Some(sub_m)) => match sub_m.subcommand() {
+            ("list", Some(_)) => {
+                for toolchain in sysroot::installed()? {
+                    let modified: chrono::DateTime<chrono::Utc> = toolchain.modified.into();
+                    println!(
+                        "{:<42} {:<10} {}",
+                        toolchain.name,
+                        if toolchain.linked {
+                            "linked"
+                        } else {
+                            "downloaded"
+                        },
+                        modified.format("%Y-%m-%d %H:%M"),
+                    );
+                }
+                Ok(0)
+            }
+
+            ("install", Some(sub_m)) => {
+                // Mandatory arguments
+                let sha = sub_m.value_of("COMMIT").unwrap();
+
+                let sha = get_commit_or_fake_it(sha)?.sha;
+                let mut sysroot = Sysroot::install(sha.clone(), "x86_64-unknown-linux-gnu")
+                    .with_context(|| format!("failed to install sysroot for {}", sha))?;
+                sysroot.preserve();
+                println!("{}", sysroot.rustc.display());
+                Ok(0)
+            }
+
+            ("link", Some(sub_m)) => {
+                // Mandatory arguments
+                let name = sub_m.value_of("NAME").unwrap();
+                let path = Path::new(sub_m.value_of_os("PATH").unwrap());
+
+                sysroot::link(name, path)?;
+                Ok(0)
+            }
+
+            ("remove", Some(sub_m)) => {
+                // Mandatory arguments
+                let name = sub_m.value_of("NAME").unwrap();
+
+                sysroot::remove(name)?;
+                Ok(0)
+            }
+
+            ("gc", Some(sub_m)) => {
+                // Options
+                let keep = match sub_m.value_of("KEEP") {
+                    Some(n) => n
+                        .parse::<usize>()
+                        .with_context(|| format!("invalid count '{}'", n))?,
+                    None => 3,
+                };
+
+                for name in sysroot::gc(keep)? {
+                    println!("removed {}", name);
+                }
+                Ok(0)
+            }
+
+            _ => {
+                let _ = writeln!(stderr(), "{}", sub_m.usage());
+                Ok(2)
+            }
+        },
+
         _ => {
             let _ = writeln!(stderr(), "{}", matches.usage());
             Ok(2)
@@ -806,12 +1643,48 @@ fn main_result() -> anyhow::Result<i32> {
     ret
 }
 
+/// Sends a queue administration request to the site. The site's secret is
+/// taken from `RUSTC_PERF_SECRET`.
+fn queue_request(
+    site_url: &str,
+    request: &collector::api::queue::Request,
+) -> anyhow::Result<collector::api::queue::Response> {
+    let secret = std::env::var("RUSTC_PERF_SECRET")
+        .context("RUSTC_PERF_SECRET should hold the site's secret")?;
+    let response = reqwest::blocking::Client::new()
+        .post(&format!("{}/perf/queue", site_url))
+        .bearer_auth(secret)
+        .json(request)
+        .send()?;
+    if !response.status().is_success() {
+        let status = response.status();
+        bail!("{}: {}", status, response.text().unwrap_or_default());
+    }
+    Ok(response.json()?)
+}
+
+/// The master or try commit with the given sha. A nightly alias
+/// (`nightly-YYYY-MM-DD`) names the last master commit merged before that
+/// nightly was built.
 pub fn get_commit_or_fake_it(sha: &str) -> anyhow::Result<Commit> {
     let mut rt = tokio::runtime::Runtime::new().unwrap();
-    Ok(rt
+    let master_commits = rt
         .block_on(rustc_artifacts::master_commits())
         .map_err(|e| anyhow::anyhow!("{:?}", e))
-        .context("getting master commit list")?
+        .context("getting master commit list")?;
+    if let Some(date) = database::nightly_date(sha) {
+        let cutoff = database::nightly_cutoff(date);
+        return master_commits
+            .into_iter()
+            .filter(|c| c.time < cutoff.0)
+            .max_by_key(|c| c.time)
+            .map(|c| Commit {
+                sha: c.sha.as_str().into(),
+                date: c.time.into(),
+            })
+            .with_context(|| format!("no recent master commit found for {}", sha));
+    }
+    Ok(master_commits
         .into_iter()
         .find(|c| c.sha == *sha)
         .map(|c| Commit {
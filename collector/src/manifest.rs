@@ -0,0 +1,240 @@
+//! Benchmark manifests.
+//!
+//! Each benchmark is a directory in `collector/benchmarks` containing a
+//! `perf-config.toml` manifest; directories without one are not benchmarks.
+//! The manifest is read both by the collector, which uses it to decide how to
+//! build the benchmark, and by the site.
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+pub const MANIFEST_FILE: &str = "perf-config.toml";
+
+/// Which part of the benchmark suite a benchmark belongs to. See
+/// `collector/benchmarks/README.md`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// Real programs that are important.
+    Primary,
+    /// Real programs that stress the compiler.
+    Stress,
+    /// Artificial programs that stress one aspect of the compiler.
+    Artificial,
+}
+
+fn default_runs() -> usize {
+    3
+}
+
+fn default_max_extra_runs() -> usize {
+    2
+}
+
+fn default_spread_threshold() -> f64 {
+    0.01
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_timeout() -> u64 {
+    60 * 60
+}
+
+/// The contents of a benchmark's `perf-config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BenchmarkConfig {
+    /// The benchmark's name, which must match its directory's name.
+    pub name: String,
+    pub category: Category,
+    /// How much the benchmark counts for in summaries of the whole suite.
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+
+    /// What the benchmark is, e.g. "A library for parsing Rust code."
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Why it's in the suite.
+    #[serde(default)]
+    pub rationale: Option<String>,
+    /// Where the code comes from, e.g. the upstream repository's URL.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// How noisy its results are known to be, e.g. which statistics vary
+    /// from run to run.
+    #[serde(default)]
+    pub noise: Option<String>,
+
+    /// The build kinds (`Check`, `Debug`, `Doc`, `DocJson`, `Opt`) the
+    /// benchmark can be built with. All of them if not present.
+    #[serde(default)]
+    pub profiles: Option<Vec<String>>,
+    /// The patches applied, in order, for `IncrPatched` runs. If not present,
+    /// all `*.patch` files in the benchmark's directory are applied in sorted
+    /// order, named after their file names.
+    #[serde(default)]
+    pub patches: Option<Vec<PatchConfig>>,
+
+    /// Extra arguments passed to cargo.
+    #[serde(default)]
+    pub cargo_opts: Option<String>,
+    /// Extra arguments passed to rustc, for the leaf crate only.
+    #[serde(default)]
+    pub cargo_rustc_opts: Option<String>,
+    /// The path to the `Cargo.toml` to build, if not the one at the top of
+    /// the benchmark's directory.
+    #[serde(default)]
+    pub cargo_toml: Option<String>,
+    /// The file that should be touched to ensure cargo re-checks the leaf crate
+    /// we're interested in. Likely, something similar to `src/lib.rs`. The
+    /// default if this is not present is to touch all .rs files in the
+    /// directory that `Cargo.toml` is in.
+    #[serde(default)]
+    pub touch_file: Option<String>,
+    /// The container image the benchmark is built in when the collector is
+    /// run with `--containers`, pinned by digest, e.g.
+    /// `docker.io/library/debian@sha256:...`.
+    #[serde(default)]
+    pub container: Option<String>,
+
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub supports_stable: bool,
+
+    #[serde(default = "default_runs")]
+    pub runs: usize,
+    /// How many runs may be added on top of `runs` if the results are
+    /// unstable.
+    #[serde(default = "default_max_extra_runs")]
+    pub max_extra_runs: usize,
+    /// The relative spread, i.e. `(max - min) / min`, of instruction counts
+    /// across runs above which another run is added.
+    #[serde(default = "default_spread_threshold")]
+    pub spread_threshold: f64,
+
+    /// How many seconds a single build may take before it is killed and the
+    /// benchmark recorded as failed.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Overrides of `timeout` for particular build kinds, e.g. `Opt = 7200`.
+    #[serde(default)]
+    pub profile_timeouts: BTreeMap<String, u64>,
+}
+
+/// A source change which is applied to a benchmark for an `IncrPatched` run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatchConfig {
+    /// The name results are recorded under, e.g. `println`.
+    pub name: String,
+    /// The patch file, relative to the benchmark's directory.
+    pub file: String,
+    /// What the patch does, e.g. "adds a `println!` to `main`".
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl BenchmarkConfig {
+    /// The configuration of a benchmark which isn't described by a manifest,
+    /// i.e. the `rustc` pseudo-benchmark.
+    pub fn new(name: &str, category: Category) -> BenchmarkConfig {
+        BenchmarkConfig {
+            name: name.to_string(),
+            category,
+            weight: default_weight(),
+            description: None,
+            rationale: None,
+            source: None,
+            noise: None,
+            profiles: None,
+            patches: None,
+            cargo_opts: None,
+            cargo_rustc_opts: None,
+            cargo_toml: None,
+            touch_file: None,
+            container: None,
+            disabled: false,
+            supports_stable: false,
+            runs: default_runs(),
+            max_extra_runs: default_max_extra_runs(),
+            spread_threshold: default_spread_threshold(),
+            timeout: default_timeout(),
+            profile_timeouts: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the manifest of the benchmark in `dir`.
+    pub fn load(dir: &Path) -> anyhow::Result<BenchmarkConfig> {
+        let path = dir.join(MANIFEST_FILE);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let config: BenchmarkConfig = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let dir_name = dir.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if config.name != dir_name {
+            bail!(
+                "{} names the benchmark '{}', but it is in '{}'",
+                path.display(),
+                config.name,
+                dir_name
+            );
+        }
+        if let Some(image) = &config.container {
+            if !image.contains("@sha256:") {
+                bail!(
+                    "{}: container image '{}' must be pinned by digest",
+                    path.display(),
+                    image
+                );
+            }
+        }
+        if let Some(patches) = &config.patches {
+            for (i, patch) in patches.iter().enumerate() {
+                if patches[..i].iter().any(|p| p.name == patch.name) {
+                    bail!("{}: duplicate patch name '{}'", path.display(), patch.name);
+                }
+                if !dir.join(&patch.file).is_file() {
+                    bail!("{}: patch file '{}' not found", path.display(), patch.file);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// How long a build of the given build kind, as printed by `Debug`, may
+    /// take.
+    pub fn timeout(&self, profile: &str) -> Duration {
+        Duration::from_secs(*self.profile_timeouts.get(profile).unwrap_or(&self.timeout))
+    }
+
+    /// Whether the benchmark can be built with the given build kind, as
+    /// printed by `Debug` (e.g. `Check`).
+    pub fn supports_profile(&self, profile: &str) -> bool {
+        self.profiles
+            .as_ref()
+            .map_or(true, |profiles| profiles.iter().any(|p| p == profile))
+    }
+}
+
+/// Reads the manifests of all benchmarks in `benchmark_dir`, sorted by name.
+pub fn load_all(benchmark_dir: &Path) -> anyhow::Result<Vec<BenchmarkConfig>> {
+    let mut configs = Vec::new();
+    for entry in fs::read_dir(benchmark_dir)
+        .with_context(|| format!("failed to list benchmark dir '{}'", benchmark_dir.display()))?
+    {
+        let path = entry?.path();
+        if path.join(MANIFEST_FILE).is_file() {
+            configs.push(BenchmarkConfig::load(&path)?);
+        }
+    }
+    configs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(configs)
+}
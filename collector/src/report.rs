@@ -0,0 +1,210 @@
+//! A comparison of two artifacts as a self-contained HTML page, with a
+//! sparkline of each benchmark's history in between, e.g. to attach to
+//! release notes or an email.
+
+use crate::compare::{format_value, Comparison};
+use anyhow::Context;
+use database::{ArtifactId, DbLabel, Index, Lookup};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use tokio::runtime::Runtime;
+
+/// The most points a sparkline has; longer histories are sampled.
+const MAX_POINTS: usize = 100;
+
+/// The values of `stat` of each benchmark (named `crate-profile cache`) at
+/// `before`, the master commits dated in between (if both are commits) and
+/// `after`.
+pub fn history(
+    rt: &mut Runtime,
+    pool: &database::Pool,
+    before: &str,
+    after: &str,
+    stat: &str,
+) -> anyhow::Result<BTreeMap<String, Vec<Option<f64>>>> {
+    let mut conn = rt.block_on(pool.connection());
+    let index = rt.block_on(Index::load(&mut *conn));
+    let mut artifact = |name: &str| -> anyhow::Result<ArtifactId> {
+        rt.block_on(conn.artifact_by_name(name))
+            .with_context(|| format!("no results for '{}'", name))
+    };
+    let before = artifact(before)?;
+    let after = artifact(after)?;
+
+    let mut artifacts = vec![before.clone()];
+    if let (ArtifactId::Commit(b), ArtifactId::Commit(a)) = (&before, &after) {
+        let between = index
+            .sorted_commits()
+            .iter()
+            .filter(|c| !c.is_try() && b.date < c.date && c.date < a.date)
+            .collect::<Vec<_>>();
+        let step = (between.len() + MAX_POINTS - 3) / (MAX_POINTS - 2);
+        artifacts.extend(
+            between
+                .into_iter()
+                .step_by(step.max(1))
+                .map(|c| ArtifactId::Commit(c.clone())),
+        );
+    }
+    artifacts.push(after);
+    let aids = artifacts
+        .iter()
+        .map(|a| a.lookup(&index))
+        .collect::<Vec<_>>();
+
+    let paths = index
+        .all_pstat_series()
+        .filter(|path| path.3.as_str() == stat)
+        .cloned()
+        .collect::<Vec<_>>();
+    let series = paths
+        .iter()
+        .map(|&(krate, profile, cache, stat)| {
+            DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let values = rt.block_on(conn.get_pstats(&series, &aids));
+    Ok(paths
+        .into_iter()
+        .zip(values)
+        .map(|((krate, profile, cache, _), values)| {
+            (format!("{}-{} {}", krate, profile, cache), values)
+        })
+        .collect())
+}
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An inline SVG line of the values, scaled to fit, skipping missing ones.
+fn sparkline(values: &[Option<f64>]) -> String {
+    const WIDTH: f64 = 120.0;
+    const HEIGHT: f64 = 20.0;
+    let present = values.iter().flatten().copied().collect::<Vec<_>>();
+    if present.len() < 2 {
+        return String::new();
+    }
+    let min = present.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = present.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let x_step = WIDTH / (values.len() - 1) as f64;
+    let points = values
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| {
+            let y = HEIGHT - 1.0 - (v? - min) / range * (HEIGHT - 2.0);
+            Some(format!("{:.1},{:.1}", i as f64 * x_step, y))
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\
+         <polyline fill=\"none\" stroke=\"#555\" stroke-width=\"1\" points=\"{}\"/></svg>",
+        WIDTH,
+        HEIGHT,
+        WIDTH,
+        HEIGHT,
+        points.join(" ")
+    )
+}
+
+/// Renders the changes whose magnitude is at least `threshold` percent, and
+/// the measurements which couldn't be compared.
+pub fn render(
+    comparison: &Comparison,
+    history: &BTreeMap<String, Vec<Option<f64>>>,
+    before: &str,
+    after: &str,
+    stat: &str,
+    threshold: f64,
+) -> String {
+    let title = format!("{} compared with {}: {}", after, before, stat);
+    let mut html = String::new();
+    write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; margin: 2em; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th, td {{ padding: 2px 8px; text-align: right; }}\n\
+         th:first-child, td:first-child {{ text-align: left; }}\n\
+         .regression {{ color: #b00; }}\n\
+         .improvement {{ color: #070; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        escape(&title)
+    )
+    .unwrap();
+
+    let shown = comparison
+        .changes
+        .iter()
+        .filter(|c| c.pct().abs() >= threshold)
+        .collect::<Vec<_>>();
+    let regressions = shown.iter().filter(|c| c.pct() > 0.0).count();
+    writeln!(
+        html,
+        "<p>{} regressions and {} improvements of {}% or more ({} measurements compared).</p>",
+        regressions,
+        shown.len() - regressions,
+        threshold,
+        comparison.changes.len()
+    )
+    .unwrap();
+
+    if !shown.is_empty() {
+        html.push_str(
+            "<table>\n<tr><th>benchmark</th><th>before</th><th>after</th>\
+             <th>change</th><th>history</th></tr>\n",
+        );
+        for c in shown {
+            let name = format!("{}-{} {}", c.krate, c.profile, c.cache);
+            let class = if c.pct() > 0.0 {
+                "regression"
+            } else {
+                "improvement"
+            };
+            writeln!(
+                html,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{:+.2}%</td><td>{}</td></tr>",
+                class,
+                escape(&name),
+                format_value(c.before),
+                format_value(c.after),
+                c.pct(),
+                history.get(&name).map_or_else(String::new, |v| sparkline(v))
+            )
+            .unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !comparison.unmatched.is_empty() {
+        html.push_str("<h2>Measured for only one artifact, so not compared</h2>\n<ul>\n");
+        for u in &comparison.unmatched {
+            writeln!(
+                html,
+                "<li>{}-{} {} (only {})</li>",
+                escape(u.krate.as_str()),
+                u.profile,
+                escape(&u.cache.to_string()),
+                escape(if u.in_before { before } else { after })
+            )
+            .unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
@@ -150,7 +150,7 @@ fn main() {
                 assert!(cmd.status().expect("failed to spawn").success());
             }
 
-            "perf-record" => {
+            "perf-record" | "perf-flamegraph" => {
                 let mut cmd = Command::new("perf");
                 let has_perf = cmd.output().is_ok();
                 assert!(has_perf);
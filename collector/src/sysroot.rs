@@ -3,10 +3,15 @@ use chrono::{DateTime, Utc};
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{BufReader, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tar::Archive;
 use xz2::bufread::XzDecoder;
 
+/// The directory toolchains are downloaded into, and local toolchains are
+/// linked into.
+const CACHE_DIR: &str = "cache";
+
 #[derive(Debug, Clone)]
 struct Commit {
     sha: String,
@@ -24,7 +29,7 @@ pub struct Sysroot {
 
 impl Sysroot {
     pub fn install(sha: String, triple: &str) -> anyhow::Result<Self> {
-        let unpack_into = "cache";
+        let unpack_into = CACHE_DIR;
 
         fs::create_dir_all(&unpack_into)?;
 
@@ -34,14 +39,23 @@ impl Sysroot {
             triple: triple.to_owned(),
         };
 
-        download.get_and_extract(ModuleVariant::Rustc)?;
-        download.get_and_extract(ModuleVariant::Std)?;
-        download.get_and_extract(ModuleVariant::Cargo)?;
-        download.get_and_extract(ModuleVariant::RustSrc)?;
+        let result = download
+            .get_and_extract(ModuleVariant::Rustc)
+            .and_then(|()| download.get_and_extract(ModuleVariant::Std))
+            .and_then(|()| download.get_and_extract(ModuleVariant::Cargo))
+            .and_then(|()| download.get_and_extract(ModuleVariant::RustSrc))
+            .and_then(|()| download.clone().into_sysroot());
 
-        let sysroot = download.into_sysroot()?;
+        // Don't leave a partial toolchain behind, e.g. if the commit's CI build
+        // failed or hasn't finished uploading yet.
+        let dir = download.directory.join(&download.rust_sha);
+        if result.is_err() && dir.exists() {
+            if let Err(err) = fs::remove_dir_all(&dir) {
+                log::info!("failed to remove {:?}: {:?}", dir, err);
+            }
+        }
 
-        Ok(sysroot)
+        result
     }
 
     pub fn preserve(&mut self) {
@@ -54,16 +68,123 @@ impl Drop for Sysroot {
         if self.preserve {
             return;
         }
-        fs::remove_dir_all(format!("cache/{}", self.sha)).unwrap_or_else(|err| {
+        let dir = Path::new(CACHE_DIR).join(&self.sha);
+        fs::remove_dir_all(&dir).unwrap_or_else(|err| {
             log::info!(
                 "failed to remove {:?}, please do so manually: {:?}",
-                format!("cache/{}", self.sha),
+                dir,
                 err
             );
         });
     }
 }
 
+/// A toolchain in the cache: either one downloaded by `Sysroot::install` and
+/// preserved, in which case it is named after its commit, or a local toolchain
+/// linked in with `link`.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub name: String,
+    pub linked: bool,
+    pub modified: SystemTime,
+}
+
+/// The path of the toolchain `name` in the cache, if `name` can't reach
+/// outside of it.
+fn cached(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains(|c| c == '/' || std::path::is_separator(c))
+    {
+        return Err(anyhow!("invalid toolchain name {:?}", name));
+    }
+    Ok(Path::new(CACHE_DIR).join(name))
+}
+
+/// The toolchains in the cache, most recently modified first.
+pub fn installed() -> anyhow::Result<Vec<Toolchain>> {
+    let mut toolchains = Vec::new();
+    let entries = match fs::read_dir(CACHE_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(toolchains),
+        Err(e) => return Err(e).with_context(|| format!("failed to list {}", CACHE_DIR)),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let linked = entry.file_type()?.is_symlink();
+        let path = entry.path();
+        // Skip downloaded archives.
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| anyhow!("non-utf8 toolchain name: {:?}", name))?;
+        toolchains.push(Toolchain {
+            name,
+            modified: fs::symlink_metadata(&path)?.modified()?,
+            linked,
+        });
+    }
+    toolchains.sort_by(|a, b| b.modified.cmp(&a.modified));
+    Ok(toolchains)
+}
+
+/// Links the local toolchain at `path`, i.e. the directory containing
+/// `bin/rustc`, into the cache as `name`.
+pub fn link(name: &str, path: &Path) -> anyhow::Result<()> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("failed to canonicalize {:?}", path))?;
+    if !path.join("bin").join("rustc").is_file() {
+        return Err(anyhow!("{:?} does not contain bin/rustc", path));
+    }
+    let link = cached(name)?;
+    if fs::symlink_metadata(&link).is_ok() {
+        return Err(anyhow!("a toolchain named {} already exists", name));
+    }
+    fs::create_dir_all(CACHE_DIR)?;
+    std::os::unix::fs::symlink(&path, &link)
+        .with_context(|| format!("failed to link {:?} to {:?}", link, path))
+}
+
+/// Removes the toolchain `name` from the cache. Linked toolchains are only
+/// unlinked.
+pub fn remove(name: &str) -> anyhow::Result<()> {
+    let path = cached(name)?;
+    let meta =
+        fs::symlink_metadata(&path).with_context(|| format!("no toolchain named {}", name))?;
+    if meta.file_type().is_symlink() {
+        fs::remove_file(&path)
+    } else {
+        fs::remove_dir_all(&path)
+    }
+    .with_context(|| format!("failed to remove {:?}", path))
+}
+
+/// Removes all but the `keep` most recently modified downloaded toolchains,
+/// along with any leftover archives. Linked toolchains are kept. Returns the
+/// names of what was removed.
+pub fn gc(keep: usize) -> anyhow::Result<Vec<String>> {
+    let mut removed = Vec::new();
+    for toolchain in installed()?.into_iter().filter(|t| !t.linked).skip(keep) {
+        remove(&toolchain.name)?;
+        removed.push(toolchain.name);
+    }
+    if let Ok(entries) = fs::read_dir(CACHE_DIR) {
+        for entry in entries {
+            let path = entry?.path();
+            if path.to_string_lossy().ends_with(".tar.xz") {
+                fs::remove_file(&path).with_context(|| format!("failed to remove {:?}", path))?;
+                removed.push(path.file_name().unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    Ok(removed)
+}
+
 #[derive(Debug, Clone)]
 struct SysrootDownload {
     directory: PathBuf,
@@ -0,0 +1,270 @@
+//! Settings which reduce the noise in measurements, at the cost of requiring
+//! root for most of them, and the degree of parallelism, which adds to it.
+
+use anyhow::{bail, Context};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+const NO_TURBO: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+const RANDOMIZE_VA_SPACE: &str = "/proc/sys/kernel/randomize_va_space";
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+const ONLINE_CPUS: &str = "/sys/devices/system/cpu/online";
+
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    /// The CPUs the collector and everything it runs are pinned to, e.g. `2-3`.
+    pub cpus: Option<String>,
+    pub no_turbo: bool,
+    pub no_aslr: bool,
+    pub performance_governor: bool,
+    /// Drop the page cache before each benchmark iteration.
+    pub drop_caches: bool,
+    /// How many benchmarks are run at once. Each job gets its own share of the
+    /// CPUs.
+    pub jobs: usize,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            cpus: None,
+            no_turbo: false,
+            no_aslr: false,
+            performance_governor: false,
+            drop_caches: false,
+            jobs: 1,
+        }
+    }
+}
+
+impl Tuning {
+    pub fn from_args(sub_m: &clap::ArgMatches<'_>) -> anyhow::Result<Tuning> {
+        let jobs = match sub_m.value_of("JOBS") {
+            Some(jobs) => match jobs.parse::<usize>() {
+                Ok(jobs) if jobs > 0 => jobs,
+                _ => bail!("invalid job count '{}'", jobs),
+            },
+            None => 1,
+        };
+        let tuning = Tuning {
+            cpus: sub_m.value_of("PIN_CPUS").map(|s| s.to_string()),
+            no_turbo: sub_m.is_present("NO_TURBO"),
+            no_aslr: sub_m.is_present("NO_ASLR"),
+            performance_governor: sub_m.is_present("PERFORMANCE_GOVERNOR"),
+            drop_caches: sub_m.is_present("DROP_CACHES"),
+            jobs,
+        };
+        if jobs > 1 {
+            tuning
+                .cpu_slots()
+                .with_context(|| format!("invalid job count '{}'", jobs))?;
+        }
+        Ok(tuning)
+    }
+
+    /// Divides the CPUs we're allowed to use between the jobs, as contiguous
+    /// ranges of equal size, e.g. `0-3` and `4-7`. Left over CPUs are unused.
+    pub fn cpu_slots(&self) -> anyhow::Result<Vec<String>> {
+        let cpus = match &self.cpus {
+            Some(cpus) => parse_cpus(cpus)?,
+            None => parse_cpus(&current_cpus()?)?,
+        };
+        let per_job = cpus.len() / self.jobs;
+        if per_job == 0 {
+            bail!("{} jobs but only {} CPUs", self.jobs, cpus.len());
+        }
+        Ok(cpus
+            .chunks_exact(per_job)
+            .take(self.jobs)
+            .map(|slot| {
+                slot.iter()
+                    .map(|cpu| cpu.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect())
+    }
+
+    /// Applies the settings. System-wide settings are restored when the
+    /// returned guard is dropped.
+    pub fn apply(&self) -> anyhow::Result<TuningGuard> {
+        let mut guard = TuningGuard {
+            restore: Vec::new(),
+        };
+        // The CPUs the benchmarks run on: the ones pinned to, or any.
+        let governed = match (&self.cpus, self.performance_governor) {
+            (_, false) => Vec::new(),
+            (Some(cpus), true) => parse_cpus(cpus)?,
+            (None, true) => online_cpus()?,
+        };
+        if let Some(cpus) = &self.cpus {
+            pin_to_cpus(cpus)?;
+        }
+        if self.no_turbo {
+            guard.write(NO_TURBO.into(), "1")?;
+        }
+        if self.no_aslr {
+            guard.write(RANDOMIZE_VA_SPACE.into(), "0")?;
+        }
+        for cpu in governed {
+            let path = format!(
+                "/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor",
+                cpu
+            );
+            guard.write(path.into(), "performance")?;
+        }
+        if self.drop_caches {
+            // Check early that we're allowed to, rather than failing halfway
+            // through the benchmarks.
+            drop_caches()?;
+        }
+        Ok(guard)
+    }
+}
+
+/// A description of the settings, recorded alongside the results. Empty if
+/// nothing was changed.
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut settings = Vec::new();
+        if let Some(cpus) = &self.cpus {
+            settings.push(format!("cpus={}", cpus));
+        }
+        if self.no_turbo {
+            settings.push("no-turbo".to_string());
+        }
+        if self.no_aslr {
+            settings.push("no-aslr".to_string());
+        }
+        if self.performance_governor {
+            settings.push("performance-governor".to_string());
+        }
+        if self.drop_caches {
+            settings.push("drop-caches".to_string());
+        }
+        if self.jobs > 1 {
+            settings.push(format!("jobs={}", self.jobs));
+        }
+        write!(f, "{}", settings.join(","))
+    }
+}
+
+pub struct TuningGuard {
+    restore: Vec<(PathBuf, String)>,
+}
+
+impl TuningGuard {
+    fn write(&mut self, path: PathBuf, value: &str) -> anyhow::Result<()> {
+        let previous = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        fs::write(&path, value)
+            .with_context(|| format!("failed to write {} (are you root?)", path.display()))?;
+        self.restore.push((path, previous.trim().to_string()));
+        Ok(())
+    }
+}
+
+impl Drop for TuningGuard {
+    fn drop(&mut self) {
+        for (path, value) in self.restore.drain(..).rev() {
+            if let Err(e) = fs::write(&path, &value) {
+                log::warn!(
+                    "failed to restore {} to {:?}: {:?}",
+                    path.display(),
+                    value,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// All the CPUs which are online, whichever this process may run on.
+fn online_cpus() -> anyhow::Result<Vec<usize>> {
+    let online = fs::read_to_string(ONLINE_CPUS)
+        .with_context(|| format!("failed to read {}", ONLINE_CPUS))?;
+    parse_cpus(online.trim())
+}
+
+/// Parses a list of CPUs written in the same format as `taskset --cpu-list`,
+/// e.g. `0,2-3`.
+fn parse_cpus(cpus: &str) -> anyhow::Result<Vec<usize>> {
+    let mut parsed = Vec::new();
+    for range in cpus.split(',') {
+        let mut bounds = range.splitn(2, '-');
+        let start: usize = bounds
+            .next()
+            .unwrap()
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid CPU list '{}'", cpus))?;
+        let end: usize = match bounds.next() {
+            Some(end) => end
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid CPU list '{}'", cpus))?,
+            None => start,
+        };
+        if start > end {
+            bail!("invalid CPU list '{}': {}-{} is reversed", cpus, start, end);
+        }
+        if end >= libc::CPU_SETSIZE as usize {
+            bail!(
+                "invalid CPU list '{}': CPUs go up to {}",
+                cpus,
+                libc::CPU_SETSIZE - 1
+            );
+        }
+        parsed.extend(start..=end);
+    }
+    Ok(parsed)
+}
+
+/// Pins the calling thread, and so every process it spawns from then on, to
+/// the given CPUs, written in the format `parse_cpus` takes. Called before any
+/// other threads are started, this pins the whole process.
+pub fn pin_to_cpus(cpus: &str) -> anyhow::Result<()> {
+    let parsed = parse_cpus(cpus)?;
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for cpu in parsed {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!(
+                "failed to pin to CPUs {}: {}",
+                cpus,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The CPUs this process may run on, in the format `pin_to_cpus` takes.
+pub fn current_cpus() -> anyhow::Result<String> {
+    let set = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            bail!(
+                "failed to get CPU affinity: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        set
+    };
+    let cpus = (0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .map(|cpu| cpu.to_string())
+        .collect::<Vec<_>>();
+    Ok(cpus.join(","))
+}
+
+pub fn drop_caches() -> anyhow::Result<()> {
+    unsafe {
+        libc::sync();
+    }
+    fs::write(DROP_CACHES, "3")
+        .with_context(|| format!("failed to write {} (are you root?)", DROP_CACHES))
+}
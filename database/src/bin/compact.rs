@@ -0,0 +1,94 @@
+//! Applies the retention policy to a database: self-profile query data for
+//! artifacts older than a cutoff date is deleted, and the space it used is
+//! reclaimed. Summary statistics (pstats) are kept forever.
+//!
+//! Run this while nothing else is using the database, as reclaiming space
+//! locks it.
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use database::{pool::ConnectionManager, Pool};
+use rusqlite::params;
+
+fn sqlite_size(conn: &rusqlite::Connection) -> i64 {
+    let pages: i64 = conn
+        .query_row("pragma page_count", params![], |r| r.get(0))
+        .unwrap();
+    let page_size: i64 = conn
+        .query_row("pragma page_size", params![], |r| r.get(0))
+        .unwrap();
+    pages * page_size
+}
+
+async fn postgres_size(conn: &tokio_postgres::Client) -> i64 {
+    conn.query_one("select pg_database_size(current_database())", &[])
+        .await
+        .unwrap()
+        .get(0)
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let cutoff = std::env::args()
+        .nth(2)
+        .expect("cutoff date (YYYY-MM-DD) as second arg");
+    let cutoff = NaiveDate::parse_from_str(&cutoff, "%Y-%m-%d")
+        .unwrap_or_else(|e| panic!("invalid cutoff date {}: {}", cutoff, e));
+    let cutoff = Utc.from_utc_date(&cutoff).and_hms(0, 0, 0);
+
+    let (before, deleted, after) = match Pool::open(&db) {
+        Pool::Sqlite(mut p) => {
+            let conn = p.raw().open().await.into_inner().unwrap();
+            let before = sqlite_size(&conn);
+            // Try builds have no date, and are kept.
+            let deleted = conn
+                .execute(
+                    "delete from self_profile_query where aid in \
+                    (select id from artifact where date < ?)",
+                    params![cutoff.timestamp()],
+                )
+                .unwrap();
+            conn.execute(
+                "delete from self_profile_query_series where id not in \
+                (select distinct series from self_profile_query)",
+                params![],
+            )
+            .unwrap();
+            conn.execute_batch("vacuum").unwrap();
+            (before, deleted as u64, sqlite_size(&conn))
+        }
+        Pool::Postgres(mut p) => {
+            let conn: tokio_postgres::Client = p.raw().open().await.into();
+            let before = postgres_size(&conn).await;
+            let deleted = conn
+                .execute(
+                    "delete from self_profile_query where aid in \
+                    (select id from artifact where date < $1)",
+                    &[&cutoff],
+                )
+                .await
+                .unwrap();
+            conn.execute(
+                "delete from self_profile_query_series where id not in \
+                (select distinct series from self_profile_query)",
+                &[],
+            )
+            .await
+            .unwrap();
+            conn.batch_execute("vacuum full self_profile_query, self_profile_query_series")
+                .await
+                .unwrap();
+            (before, deleted, postgres_size(&conn).await)
+        }
+    };
+
+    println!(
+        "deleted {} self-profile rows from before {}; size went from {} to {} MiB ({} MiB saved)",
+        deleted,
+        cutoff.date(),
+        before / (1 << 20),
+        after / (1 << 20),
+        (before - after) / (1 << 20)
+    );
+}
@@ -0,0 +1,116 @@
+//! Exports all statistics in a database as one flat CSV table on stdout, for
+//! analysis with other tools (e.g. pandas or DuckDB).
+//!
+//! Columns are `artifact,type,date,crate,profile,cache,statistic,value`, one
+//! row per recorded value. The date is empty for try builds and artifacts
+//! without one. Rows are ordered by date, then artifact.
+//!
+//! For Parquet, partitioned by month and optionally on a schedule, see
+//! `export-parquet`.
+
+use chrono::{DateTime, TimeZone, Utc};
+use database::{pool::ConnectionManager, Pool};
+use futures::StreamExt;
+use rusqlite::params;
+use std::io::Write;
+
+const QUERY: &str = "select artifact.name, artifact.type, artifact.date, \
+    pstat_series.crate, pstat_series.profile, pstat_series.cache, \
+    pstat_series.statistic, pstat.value \
+    from pstat \
+    join pstat_series on pstat_series.id = pstat.series \
+    join artifact on artifact.id = pstat.aid \
+    order by artifact.date, artifact.name";
+
+/// Quotes a field if it contains characters which are special in CSV.
+fn field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn write_row(
+    out: &mut impl Write,
+    artifact: &str,
+    ty: &str,
+    date: Option<DateTime<Utc>>,
+    series: [&str; 4],
+    value: f64,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "{},{},{},{},{},{},{},{}",
+        field(artifact),
+        field(ty),
+        date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+        field(series[0]),
+        field(series[1]),
+        field(series[2]),
+        field(series[3]),
+        value
+    )
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    writeln!(
+        out,
+        "artifact,type,date,crate,profile,cache,statistic,value"
+    )
+    .unwrap();
+
+    let mut rows = 0;
+    match Pool::open(&db) {
+        Pool::Sqlite(mut p) => {
+            let conn = p.raw().open().await.into_inner().unwrap();
+            let mut query = conn.prepare(QUERY).unwrap();
+            let mut results = query.query(params![]).unwrap();
+            while let Some(row) = results.next().unwrap() {
+                let date: Option<i64> = row.get(2).unwrap();
+                write_row(
+                    &mut out,
+                    &row.get::<_, String>(0).unwrap(),
+                    &row.get::<_, String>(1).unwrap(),
+                    date.map(|d| Utc.timestamp(d, 0)),
+                    [
+                        &row.get::<_, String>(3).unwrap(),
+                        &row.get::<_, String>(4).unwrap(),
+                        &row.get::<_, String>(5).unwrap(),
+                        &row.get::<_, String>(6).unwrap(),
+                    ],
+                    row.get(7).unwrap(),
+                )
+                .unwrap();
+                rows += 1;
+            }
+        }
+        Pool::Postgres(mut p) => {
+            let conn: tokio_postgres::Client = p.raw().open().await.into();
+            let results = conn.query_raw(QUERY, vec![]).await.unwrap();
+            futures::pin_mut!(results);
+            while let Some(row) = results.next().await {
+                let row = row.unwrap();
+                write_row(
+                    &mut out,
+                    row.get(0),
+                    row.get(1),
+                    row.get(2),
+                    [row.get(3), row.get(4), row.get(5), row.get(6)],
+                    row.get(7),
+                )
+                .unwrap();
+                rows += 1;
+            }
+        }
+    }
+
+    out.flush().unwrap();
+    eprintln!("exported {} rows", rows);
+}
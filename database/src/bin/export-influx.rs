@@ -0,0 +1,152 @@
+//! Exports all statistics in a database as InfluxDB line protocol on stdout,
+//! e.g. to load with `influx write` (see `database::influx` for the format).
+//!
+//! Usage: `export-influx <db> [--phases]`
+//!
+//! With `--phases`, self-profile queries are exported too, which is far more
+//! data. Try builds and artifacts without a date are left out, since a point
+//! needs a timestamp.
+
+use chrono::{DateTime, TimeZone, Utc};
+use database::{pool::ConnectionManager, Pool, QueryDatum};
+use futures::StreamExt;
+use rusqlite::params;
+use std::io::Write;
+use std::time::Duration;
+
+const STATISTICS: &str = "select artifact.name, artifact.date, \
+    pstat_series.crate, pstat_series.profile, pstat_series.cache, \
+    pstat_series.statistic, pstat.value \
+    from pstat \
+    join pstat_series on pstat_series.id = pstat.series \
+    join artifact on artifact.id = pstat.aid \
+    where artifact.date is not null \
+    order by artifact.date, artifact.name";
+
+const QUERIES: &str = "select artifact.name, artifact.date, \
+    self_profile_query_series.crate, self_profile_query_series.profile, \
+    self_profile_query_series.cache, self_profile_query_series.query, \
+    self_profile_query.self_time, self_profile_query.blocked_time, \
+    self_profile_query.incremental_load_time, self_profile_query.number_of_cache_hits, \
+    self_profile_query.invocation_count \
+    from self_profile_query \
+    join self_profile_query_series on self_profile_query_series.id = self_profile_query.series \
+    join artifact on artifact.id = self_profile_query.aid \
+    where artifact.date is not null \
+    order by artifact.date, artifact.name";
+
+/// The times are stored as nanoseconds.
+fn query_datum(times: [i64; 3], number_of_cache_hits: i32, invocation_count: i32) -> QueryDatum {
+    let nanos = |n: i64| Duration::from_nanos(n as u64);
+    QueryDatum {
+        self_time: nanos(times[0]),
+        blocked_time: nanos(times[1]),
+        incremental_load_time: nanos(times[2]),
+        number_of_cache_hits: number_of_cache_hits as u32,
+        invocation_count: invocation_count as u32,
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let phases = std::env::args().any(|a| a == "--phases");
+
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    let mut lines = 0;
+    match Pool::open(&db) {
+        Pool::Sqlite(mut p) => {
+            let conn = p.raw().open().await.into_inner().unwrap();
+            let mut query = conn.prepare(STATISTICS).unwrap();
+            let mut rows = query.query(params![]).unwrap();
+            while let Some(row) = rows.next().unwrap() {
+                let line = database::influx::statistic(
+                    &row.get::<_, String>(2).unwrap(),
+                    &row.get::<_, String>(3).unwrap(),
+                    &row.get::<_, String>(4).unwrap(),
+                    &row.get::<_, String>(5).unwrap(),
+                    row.get(6).unwrap(),
+                    &row.get::<_, String>(0).unwrap(),
+                    Utc.timestamp(row.get(1).unwrap(), 0),
+                );
+                writeln!(out, "{}", line).unwrap();
+                lines += 1;
+            }
+            if phases {
+                let mut query = conn.prepare(QUERIES).unwrap();
+                let mut rows = query.query(params![]).unwrap();
+                while let Some(row) = rows.next().unwrap() {
+                    let qd = query_datum(
+                        [
+                            row.get(6).unwrap(),
+                            row.get(7).unwrap(),
+                            row.get(8).unwrap(),
+                        ],
+                        row.get(9).unwrap(),
+                        row.get(10).unwrap(),
+                    );
+                    let line = database::influx::query(
+                        &row.get::<_, String>(2).unwrap(),
+                        &row.get::<_, String>(3).unwrap(),
+                        &row.get::<_, String>(4).unwrap(),
+                        &row.get::<_, String>(5).unwrap(),
+                        &qd,
+                        &row.get::<_, String>(0).unwrap(),
+                        Utc.timestamp(row.get(1).unwrap(), 0),
+                    );
+                    writeln!(out, "{}", line).unwrap();
+                    lines += 1;
+                }
+            }
+        }
+        Pool::Postgres(mut p) => {
+            let conn: tokio_postgres::Client = p.raw().open().await.into();
+            let rows = conn.query_raw(STATISTICS, vec![]).await.unwrap();
+            futures::pin_mut!(rows);
+            while let Some(row) = rows.next().await {
+                let row = row.unwrap();
+                let date: DateTime<Utc> = row.get(1);
+                let line = database::influx::statistic(
+                    row.get(2),
+                    row.get(3),
+                    row.get(4),
+                    row.get(5),
+                    row.get(6),
+                    row.get(0),
+                    date,
+                );
+                writeln!(out, "{}", line).unwrap();
+                lines += 1;
+            }
+            if phases {
+                let rows = conn.query_raw(QUERIES, vec![]).await.unwrap();
+                futures::pin_mut!(rows);
+                while let Some(row) = rows.next().await {
+                    let row = row.unwrap();
+                    let date: DateTime<Utc> = row.get(1);
+                    let qd = query_datum(
+                        [row.get(6), row.get(7), row.get(8)],
+                        row.get(9),
+                        row.get(10),
+                    );
+                    let line = database::influx::query(
+                        row.get(2),
+                        row.get(3),
+                        row.get(4),
+                        row.get(5),
+                        &qd,
+                        row.get(0),
+                        date,
+                    );
+                    writeln!(out, "{}", line).unwrap();
+                    lines += 1;
+                }
+            }
+        }
+    }
+
+    out.flush().unwrap();
+    eprintln!("exported {} lines", lines);
+}
@@ -0,0 +1,414 @@
+//! Exports all statistics in a database (and with `--phases`, all self-profile
+//! queries) as Parquet files partitioned by month, for analytical queries
+//! (e.g. BigQuery, DuckDB or Spark) which shouldn't hit the live server.
+//!
+//! Usage: `export-parquet <db> <destination> [--phases] [--every=<hours>]`
+//!
+//! The files are laid out Hive-style, as
+//! `<destination>/statistics/month=2021-01/part-0.parquet` (and `queries/...`),
+//! with `month=none` for try builds and artifacts without a date. Statistics
+//! have the columns of `export-csv`; queries have the artifact, its type and
+//! date, the crate, profile, cache and query, and the times in nanoseconds and
+//! counts of the query.
+//!
+//! A `gs://` or `s3://` destination is written to a local directory first and
+//! then synced to the bucket with `gsutil` or `aws`, which have to be
+//! installed and signed in. With `--every`, the export is repeated that often,
+//! replacing the files each time.
+
+use anyhow::Context as _;
+use chrono::{DateTime, TimeZone, Utc};
+use database::{pool::ConnectionManager, Pool};
+use futures::StreamExt;
+use parquet::column::writer::ColumnWriter;
+use parquet::data_type::ByteArray;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+use parquet::schema::parser::parse_message_type;
+use rusqlite::params;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const STATISTICS: &str = "select artifact.name, artifact.type, artifact.date, \
+    pstat_series.crate, pstat_series.profile, pstat_series.cache, \
+    pstat_series.statistic, pstat.value \
+    from pstat \
+    join pstat_series on pstat_series.id = pstat.series \
+    join artifact on artifact.id = pstat.aid \
+    order by artifact.date, artifact.name";
+
+const STATISTICS_SCHEMA: &str = "message statistics {
+    required binary artifact (UTF8);
+    required binary type (UTF8);
+    optional int64 date (TIMESTAMP_MILLIS);
+    required binary crate (UTF8);
+    required binary profile (UTF8);
+    required binary cache (UTF8);
+    required binary statistic (UTF8);
+    required double value;
+}";
+
+const QUERIES: &str = "select artifact.name, artifact.type, artifact.date, \
+    self_profile_query_series.crate, self_profile_query_series.profile, \
+    self_profile_query_series.cache, self_profile_query_series.query, \
+    self_profile_query.self_time, self_profile_query.blocked_time, \
+    self_profile_query.incremental_load_time, self_profile_query.number_of_cache_hits, \
+    self_profile_query.invocation_count \
+    from self_profile_query \
+    join self_profile_query_series on self_profile_query_series.id = self_profile_query.series \
+    join artifact on artifact.id = self_profile_query.aid \
+    order by artifact.date, artifact.name";
+
+const QUERIES_SCHEMA: &str = "message queries {
+    required binary artifact (UTF8);
+    required binary type (UTF8);
+    optional int64 date (TIMESTAMP_MILLIS);
+    required binary crate (UTF8);
+    required binary profile (UTF8);
+    required binary cache (UTF8);
+    required binary query (UTF8);
+    required int64 self_time;
+    required int64 blocked_time;
+    required int64 incremental_load_time;
+    required int64 number_of_cache_hits;
+    required int64 invocation_count;
+}";
+
+/// The values of one column for the rows of a partition, in schema order.
+enum Column {
+    Text(Vec<ByteArray>),
+    /// Milliseconds since the epoch of the non-null values, and a definition
+    /// level per row (0 for null).
+    Date(Vec<i64>, Vec<i16>),
+    Double(Vec<f64>),
+    Long(Vec<i64>),
+}
+
+enum Value<'a> {
+    Text(&'a str),
+    Date(Option<DateTime<Utc>>),
+    Double(f64),
+    Long(i64),
+}
+
+impl Column {
+    fn push(&mut self, value: Value) {
+        match (self, value) {
+            (Column::Text(values), Value::Text(v)) => values.push(ByteArray::from(v)),
+            (Column::Date(values, levels), Value::Date(v)) => {
+                levels.push(v.is_some() as i16);
+                values.extend(v.map(|d| d.timestamp_millis()));
+            }
+            (Column::Double(values), Value::Double(v)) => values.push(v),
+            (Column::Long(values), Value::Long(v)) => values.push(v),
+            _ => unreachable!("value doesn't match the column"),
+        }
+    }
+
+    fn write(&mut self, writer: &mut ColumnWriter) -> anyhow::Result<()> {
+        match (self, writer) {
+            (Column::Text(values), ColumnWriter::ByteArrayColumnWriter(w)) => {
+                w.write_batch(values, None, None)?;
+                values.clear();
+            }
+            (Column::Date(values, levels), ColumnWriter::Int64ColumnWriter(w)) => {
+                w.write_batch(values, Some(&levels[..]), None)?;
+                values.clear();
+                levels.clear();
+            }
+            (Column::Double(values), ColumnWriter::DoubleColumnWriter(w)) => {
+                w.write_batch(values, None, None)?;
+                values.clear();
+            }
+            (Column::Long(values), ColumnWriter::Int64ColumnWriter(w)) => {
+                w.write_batch(values, None, None)?;
+                values.clear();
+            }
+            _ => anyhow::bail!("column doesn't match the schema"),
+        }
+        Ok(())
+    }
+}
+
+/// One dataset (statistics or queries), written a partition at a time; rows
+/// come ordered by date, so each month's are together.
+struct Table {
+    dir: PathBuf,
+    schema: &'static str,
+    columns: Vec<Column>,
+    partition: Option<String>,
+    rows: usize,
+    total: usize,
+}
+
+impl Table {
+    fn new(dir: PathBuf, schema: &'static str, columns: Vec<Column>) -> Table {
+        Table {
+            dir,
+            schema,
+            columns,
+            partition: None,
+            rows: 0,
+            total: 0,
+        }
+    }
+
+    fn push(&mut self, row: Vec<Value>) -> anyhow::Result<()> {
+        let date = match row[2] {
+            Value::Date(date) => date,
+            _ => unreachable!("the third column is the date"),
+        };
+        let partition =
+            date.map_or_else(|| String::from("none"), |d| d.format("%Y-%m").to_string());
+        if self.partition.as_ref() != Some(&partition) {
+            self.flush()?;
+            self.partition = Some(partition);
+        }
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Writes the current partition's rows, replacing the partition's file.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let partition = match &self.partition {
+            Some(partition) if self.rows > 0 => partition,
+            _ => return Ok(()),
+        };
+        let dir = self.dir.join(format!("month={}", partition));
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {:?}", dir))?;
+        let path = dir.join("part-0.parquet");
+        let tmp = dir.join("part-0.parquet.tmp");
+
+        let schema = Arc::new(parse_message_type(self.schema)?);
+        let props = Arc::new(
+            WriterProperties::builder()
+                .set_compression(parquet::basic::Compression::SNAPPY)
+                .build(),
+        );
+        let file = std::fs::File::create(&tmp).with_context(|| format!("creating {:?}", tmp))?;
+        let mut writer = SerializedFileWriter::new(file, schema, props)?;
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = self.columns.iter_mut();
+        while let Some(mut column_writer) = row_group.next_column()? {
+            columns
+                .next()
+                .context("more columns in the schema than values")?
+                .write(&mut column_writer)?;
+            row_group.close_column(column_writer)?;
+        }
+        writer.close_row_group(row_group)?;
+        writer.close()?;
+        std::fs::rename(&tmp, &path).with_context(|| format!("writing {:?}", path))?;
+
+        self.total += self.rows;
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the last partition, returning the number of rows written.
+    fn finish(mut self) -> anyhow::Result<usize> {
+        self.flush()?;
+        Ok(self.total)
+    }
+}
+
+fn statistics(dir: &Path) -> Table {
+    let text = || Column::Text(Vec::new());
+    Table::new(
+        dir.join("statistics"),
+        STATISTICS_SCHEMA,
+        vec![
+            text(),
+            text(),
+            Column::Date(Vec::new(), Vec::new()),
+            text(),
+            text(),
+            text(),
+            text(),
+            Column::Double(Vec::new()),
+        ],
+    )
+}
+
+fn queries(dir: &Path) -> Table {
+    let text = || Column::Text(Vec::new());
+    let long = || Column::Long(Vec::new());
+    Table::new(
+        dir.join("queries"),
+        QUERIES_SCHEMA,
+        vec![
+            text(),
+            text(),
+            Column::Date(Vec::new(), Vec::new()),
+            text(),
+            text(),
+            text(),
+            text(),
+            long(),
+            long(),
+            long(),
+            long(),
+            long(),
+        ],
+    )
+}
+
+/// Writes every dataset under `dir`.
+async fn export(db: &str, dir: &Path, phases: bool) -> anyhow::Result<()> {
+    let mut stats = statistics(dir);
+    let mut queries = if phases { Some(queries(dir)) } else { None };
+    match Pool::open(db) {
+        Pool::Sqlite(mut p) => {
+            let conn = p.raw().open().await.into_inner().unwrap();
+            let date = |d: Option<i64>| d.map(|d| Utc.timestamp(d, 0));
+            let mut query = conn.prepare(STATISTICS)?;
+            let mut rows = query.query(params![])?;
+            while let Some(row) = rows.next()? {
+                stats.push(vec![
+                    Value::Text(&row.get::<_, String>(0)?),
+                    Value::Text(&row.get::<_, String>(1)?),
+                    Value::Date(date(row.get(2)?)),
+                    Value::Text(&row.get::<_, String>(3)?),
+                    Value::Text(&row.get::<_, String>(4)?),
+                    Value::Text(&row.get::<_, String>(5)?),
+                    Value::Text(&row.get::<_, String>(6)?),
+                    Value::Double(row.get(7)?),
+                ])?;
+            }
+            if let Some(table) = &mut queries {
+                let mut query = conn.prepare(QUERIES)?;
+                let mut rows = query.query(params![])?;
+                while let Some(row) = rows.next()? {
+                    table.push(vec![
+                        Value::Text(&row.get::<_, String>(0)?),
+                        Value::Text(&row.get::<_, String>(1)?),
+                        Value::Date(date(row.get(2)?)),
+                        Value::Text(&row.get::<_, String>(3)?),
+                        Value::Text(&row.get::<_, String>(4)?),
+                        Value::Text(&row.get::<_, String>(5)?),
+                        Value::Text(&row.get::<_, String>(6)?),
+                        Value::Long(row.get(7)?),
+                        Value::Long(row.get(8)?),
+                        Value::Long(row.get(9)?),
+                        Value::Long(row.get(10)?),
+                        Value::Long(row.get(11)?),
+                    ])?;
+                }
+            }
+        }
+        Pool::Postgres(mut p) => {
+            let conn: tokio_postgres::Client = p.raw().open().await.into();
+            let rows = conn.query_raw(STATISTICS, vec![]).await?;
+            futures::pin_mut!(rows);
+            while let Some(row) = rows.next().await {
+                let row = row?;
+                stats.push(vec![
+                    Value::Text(row.get(0)),
+                    Value::Text(row.get(1)),
+                    Value::Date(row.get(2)),
+                    Value::Text(row.get(3)),
+                    Value::Text(row.get(4)),
+                    Value::Text(row.get(5)),
+                    Value::Text(row.get(6)),
+                    Value::Double(row.get(7)),
+                ])?;
+            }
+            if let Some(table) = &mut queries {
+                let rows = conn.query_raw(QUERIES, vec![]).await?;
+                futures::pin_mut!(rows);
+                while let Some(row) = rows.next().await {
+                    let row = row?;
+                    table.push(vec![
+                        Value::Text(row.get(0)),
+                        Value::Text(row.get(1)),
+                        Value::Date(row.get(2)),
+                        Value::Text(row.get(3)),
+                        Value::Text(row.get(4)),
+                        Value::Text(row.get(5)),
+                        Value::Text(row.get(6)),
+                        Value::Long(row.get(7)),
+                        Value::Long(row.get(8)),
+                        Value::Long(row.get(9)),
+                        Value::Long(row.get::<_, i32>(10).into()),
+                        Value::Long(row.get::<_, i32>(11).into()),
+                    ])?;
+                }
+            }
+        }
+    }
+
+    database::event!(log::Level::Info, "exported statistics"; rows = stats.finish()?);
+    if let Some(table) = queries {
+        database::event!(log::Level::Info, "exported queries"; rows = table.finish()?);
+    }
+    Ok(())
+}
+
+/// Syncs `dir` to a bucket with the bucket's command line tool.
+fn upload(dir: &Path, bucket: &str) -> anyhow::Result<()> {
+    let mut command = if bucket.starts_with("gs://") {
+        let mut command = std::process::Command::new("gsutil");
+        command.args(&["-m", "rsync", "-r"]).arg(dir).arg(bucket);
+        command
+    } else {
+        let mut command = std::process::Command::new("aws");
+        command.args(&["s3", "sync"]).arg(dir).arg(bucket);
+        command
+    };
+    let status = command
+        .status()
+        .with_context(|| format!("running {:?}", command))?;
+    if !status.success() {
+        anyhow::bail!("{:?} failed: {}", command, status);
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let destination = std::env::args()
+        .nth(2)
+        .expect("destination directory or bucket as second arg");
+    let phases = std::env::args().any(|a| a == "--phases");
+    let every = match std::env::args().find_map(|a| a.strip_prefix("--every=").map(String::from)) {
+        Some(hours) => {
+            let hours = hours
+                .parse::<u64>()
+                .ok()
+                .filter(|&h| h > 0)
+                .with_context(|| format!("--every should be a number of hours, not {:?}", hours))?;
+            Some(Duration::from_secs(hours * 60 * 60))
+        }
+        None => None,
+    };
+
+    let is_bucket = destination.starts_with("gs://") || destination.starts_with("s3://");
+    let dir = if is_bucket {
+        std::env::temp_dir().join("rustc-perf-parquet")
+    } else {
+        PathBuf::from(&destination)
+    };
+    loop {
+        let result = match export(&db, &dir, phases).await {
+            Ok(()) if is_bucket => upload(&dir, &destination),
+            result => result,
+        };
+        let every = match (result, every) {
+            (Ok(()), Some(every)) => every,
+            (Ok(()), None) => return Ok(()),
+            // On a schedule, carry on and try again next time.
+            (Err(e), Some(every)) => {
+                log::error!("export failed: {:?}", e);
+                every
+            }
+            (Err(e), None) => return Err(e),
+        };
+        tokio::time::delay_for(every).await;
+    }
+}
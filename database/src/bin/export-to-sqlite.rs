@@ -263,7 +263,7 @@ impl Table for SelfProfileQuery {
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    database::logging::init();
     let postgres = std::env::args().nth(1).expect("postgres database");
     let sqlite = std::env::args().nth(2).expect("sqlite database");
     let mut postgres: tokio_postgres::Client = match Pool::open(&postgres) {
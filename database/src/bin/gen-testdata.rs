@@ -0,0 +1,40 @@
+//! Fills a database with synthetic results, for running the site locally
+//! without access to real data; see `database::testdata`.
+//!
+//! Usage: `gen-testdata <db> [commits] [noise] [regressions]`
+//!
+//! `commits` defaults to 50, `noise` (in percent) to 1 and `regressions` to 3.
+//! The injected regressions are listed on stdout.
+
+use database::testdata::{self, Params};
+
+fn arg<T: std::str::FromStr>(n: usize, name: &str, default: T) -> T {
+    match std::env::args().nth(n) {
+        Some(v) => v
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid {}: {}", name, v)),
+        None => default,
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let defaults = Params::default();
+    let params = Params {
+        commits: arg(2, "commit count", defaults.commits),
+        noise: arg(3, "noise", defaults.noise),
+        regressions: arg(4, "regression count", defaults.regressions),
+    };
+
+    for regression in testdata::generate(&db, params).await {
+        println!("{}", regression);
+    }
+
+    eprintln!(
+        "generated {} commits with {} benchmarks",
+        params.commits,
+        testdata::BENCHMARKS.len()
+    );
+}
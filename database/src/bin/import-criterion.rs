@@ -0,0 +1,103 @@
+//! Imports the results of Criterion benchmarks, i.e. a `target/criterion`
+//! directory, as the results of an artifact. This lets projects track their
+//! own benchmarks with the site.
+//!
+//! Each Criterion benchmark is recorded as a benchmark of its own, named after
+//! its full id with `/` replaced by `-`, under the `opt` profile and `full`
+//! cache state. The mean time is recorded as `wall-time` and the median as
+//! `wall-time:median`, both in seconds.
+
+use anyhow::Context as _;
+use database::{ArtifactId, Cache, Pool, Profile};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct BenchmarkId {
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    mean: Estimate,
+    median: Estimate,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    /// In nanoseconds.
+    point_estimate: f64,
+}
+
+/// Finds the directories with the latest results of each benchmark, i.e. the
+/// `new` directories.
+fn find_results(dir: &Path, results: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("failed to list {:?}", dir))? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if path.file_name().map_or(false, |n| n == "new") {
+            if path.join("estimates.json").is_file() {
+                results.push(path);
+            }
+        } else {
+            find_results(&path, results)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {:?}", path))?;
+    serde_json::from_str(&contents).with_context(|| format!("failed to parse {:?}", path))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let artifact = std::env::args()
+        .nth(2)
+        .expect("artifact to record the results under as second arg");
+    let criterion_dir = std::env::args()
+        .nth(3)
+        .unwrap_or_else(|| String::from("target/criterion"));
+
+    let mut results = Vec::new();
+    find_results(Path::new(&criterion_dir), &mut results)?;
+    results.sort();
+
+    let pool = Pool::open(&db);
+    let conn = pool.connection().await;
+    let cid = conn
+        .collection_id(&format!("criterion-{}", chrono::Utc::now().timestamp()))
+        .await;
+    let aid = conn
+        .artifact_id(&ArtifactId::Artifact(artifact.clone()))
+        .await;
+
+    for dir in &results {
+        let id: BenchmarkId = read_json(&dir.join("benchmark.json"))?;
+        let estimates: Estimates = read_json(&dir.join("estimates.json"))?;
+        let name = id.full_id.replace('/', "-");
+
+        conn.record_benchmark(&name, None).await;
+        for (stat, estimate) in &[
+            ("wall-time", &estimates.mean),
+            ("wall-time:median", &estimates.median),
+        ] {
+            let value = estimate.point_estimate / 1e9;
+            if let Err(e) = database::validate::statistic(stat, value) {
+                eprintln!("skipping {}: {}", name, e);
+                continue;
+            }
+            conn.record_statistic(cid, aid, &name, Profile::Opt, Cache::Empty, stat, value)
+                .await;
+        }
+    }
+
+    println!("imported {} benchmarks as {}", results.len(), artifact);
+    Ok(())
+}
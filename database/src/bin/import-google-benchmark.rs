@@ -0,0 +1,164 @@
+//! Imports the results of Google Benchmark benchmarks, i.e. the file written
+//! with `--benchmark_out=<file> --benchmark_out_format=json`, as the results of
+//! an artifact, like `import-criterion` does for Criterion.
+//!
+//! Each benchmark is recorded as a benchmark of its own, named after its run
+//! name with `/` replaced by `-`, under the `opt` profile and `full` cache
+//! state. The mean real time is recorded as `wall-time` and the median as
+//! `wall-time:median`, both in seconds, and the mean CPU time as `cpu-clock`,
+//! in milliseconds as `perf` reports it. With repetitions these are computed
+//! from the individual runs, or taken from the `mean` and `median` aggregates
+//! if only those were reported.
+
+use anyhow::Context as _;
+use database::{ArtifactId, Cache, Pool, Profile};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+struct Output {
+    benchmarks: Vec<Run>,
+}
+
+#[derive(Deserialize)]
+struct Run {
+    name: String,
+    /// The name without any aggregate suffix; older versions don't write it.
+    run_name: Option<String>,
+    /// `iteration` or `aggregate`.
+    #[serde(default)]
+    run_type: Option<String>,
+    aggregate_name: Option<String>,
+    #[serde(default)]
+    error_occurred: bool,
+    #[serde(default)]
+    real_time: f64,
+    #[serde(default)]
+    cpu_time: f64,
+    time_unit: Option<String>,
+}
+
+impl Run {
+    /// Seconds per time unit.
+    fn scale(&self) -> anyhow::Result<f64> {
+        Ok(match self.time_unit.as_deref().unwrap_or("ns") {
+            "ns" => 1e-9,
+            "us" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            unit => anyhow::bail!("{}: unknown time unit {:?}", self.name, unit),
+        })
+    }
+}
+
+/// `(real, cpu)` times in seconds of one benchmark.
+#[derive(Default)]
+struct Times {
+    iterations: Vec<(f64, f64)>,
+    mean: Option<(f64, f64)>,
+    median: Option<(f64, f64)>,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+impl Times {
+    /// `(stat, value)` to record.
+    fn statistics(&self) -> Vec<(&'static str, f64)> {
+        let (mean_time, median_time) = if self.iterations.is_empty() {
+            match (self.mean, self.median) {
+                (Some(mean), median) => (mean, median.map(|m| m.0)),
+                (None, _) => return Vec::new(),
+            }
+        } else {
+            let mut real = self.iterations.iter().map(|t| t.0).collect::<Vec<_>>();
+            let cpu = self.iterations.iter().map(|t| t.1).collect::<Vec<_>>();
+            ((mean(&real), mean(&cpu)), Some(median(&mut real)))
+        };
+        let mut stats = vec![("wall-time", mean_time.0), ("cpu-clock", mean_time.1 * 1e3)];
+        if let Some(median_time) = median_time {
+            stats.push(("wall-time:median", median_time));
+        }
+        stats
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let artifact = std::env::args()
+        .nth(2)
+        .expect("artifact to record the results under as second arg");
+    let path = std::env::args()
+        .nth(3)
+        .expect("Google Benchmark JSON output as third arg");
+
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read {:?}", path))?;
+    let output: Output =
+        serde_json::from_str(&contents).with_context(|| format!("failed to parse {:?}", path))?;
+
+    let mut benchmarks = BTreeMap::<String, Times>::new();
+    for run in &output.benchmarks {
+        if run.error_occurred {
+            eprintln!("skipping {}: it failed", run.name);
+            continue;
+        }
+        let scale = run.scale()?;
+        let times = (run.real_time * scale, run.cpu_time * scale);
+        let name = run.run_name.as_ref().unwrap_or(&run.name).replace('/', "-");
+        let entry = benchmarks.entry(name).or_default();
+        match (run.run_type.as_deref(), run.aggregate_name.as_deref()) {
+            (Some("aggregate"), Some("mean")) => entry.mean = Some(times),
+            (Some("aggregate"), Some("median")) => entry.median = Some(times),
+            (Some("aggregate"), _) => {}
+            _ => entry.iterations.push(times),
+        }
+    }
+
+    let pool = Pool::open(&db);
+    let conn = pool.connection().await;
+    let cid = conn
+        .collection_id(&format!(
+            "google-benchmark-{}",
+            chrono::Utc::now().timestamp()
+        ))
+        .await;
+    let aid = conn
+        .artifact_id(&ArtifactId::Artifact(artifact.clone()))
+        .await;
+
+    let mut imported = 0;
+    for (name, times) in &benchmarks {
+        let stats = times.statistics();
+        if stats.is_empty() {
+            eprintln!("skipping {}: no runs or mean", name);
+            continue;
+        }
+        conn.record_benchmark(name, None).await;
+        imported += 1;
+        for (stat, value) in stats {
+            if let Err(e) = database::validate::statistic(stat, value) {
+                eprintln!("skipping {}: {}", name, e);
+                continue;
+            }
+            conn.record_statistic(cid, aid, name, Profile::Opt, Cache::Empty, stat, value)
+                .await;
+        }
+    }
+
+    println!("imported {} benchmarks as {}", imported, artifact);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Imports the results of `hyperfine --export-json <file>`, e.g. end-to-end
+//! `cargo build` timings taken outside the collector, as the results of an
+//! artifact or, with `--date`, of a commit.
+//!
+//! Usage: `import-hyperfine <db> <artifact or sha> <file> [--date=<date>]`
+//!
+//! Each command is recorded as a benchmark of its own, under the `opt` profile
+//! and `full` cache state, named after the command (or its `--command-name`)
+//! with anything other than letters, digits and `_.:+` replaced by `-`. The
+//! mean time is recorded as `wall-time` and the median as `wall-time:median`,
+//! both in seconds, and the mean user and system time together as
+//! `cpu-clock`, in milliseconds as `perf` reports it. Commands which exited
+//! unsuccessfully are skipped.
+
+use anyhow::Context as _;
+use database::{ArtifactId, Cache, Commit, Date, Pool, Profile};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Output {
+    results: Vec<Command>,
+}
+
+#[derive(Deserialize)]
+struct Command {
+    command: String,
+    /// In seconds, as are the other times.
+    mean: f64,
+    median: Option<f64>,
+    user: Option<f64>,
+    system: Option<f64>,
+    #[serde(default)]
+    exit_codes: Vec<Option<i32>>,
+}
+
+/// The benchmark name for a command, e.g. `cargo-build-release` for `cargo
+/// build --release`.
+fn benchmark_name(command: &str) -> String {
+    let mut name = String::new();
+    for c in command.chars() {
+        if c.is_ascii_alphanumeric() || "_.:+".contains(c) {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    name.trim_end_matches('-').to_string()
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let mut args = std::env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect::<Vec<_>>()
+        .into_iter();
+    let db = args.next().expect("database as first arg");
+    let artifact = args
+        .next()
+        .expect("artifact or commit to record the results under as second arg");
+    let path = args.next().expect("hyperfine JSON export as third arg");
+    let date = match std::env::args().find_map(|a| a.strip_prefix("--date=").map(String::from)) {
+        Some(date) => Some(
+            date.parse::<Date>()
+                .map_err(|e| anyhow::anyhow!("--date: {:?}", e))?,
+        ),
+        None => None,
+    };
+
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read {:?}", path))?;
+    let output: Output =
+        serde_json::from_str(&contents).with_context(|| format!("failed to parse {:?}", path))?;
+
+    let artifact_id = match date {
+        Some(date) => {
+            let commit = Commit {
+                sha: artifact.clone(),
+                date,
+            };
+            database::validate::commit(&commit).map_err(|e| anyhow::anyhow!("{}", e))?;
+            ArtifactId::Commit(commit)
+        }
+        None => ArtifactId::Artifact(artifact.clone()),
+    };
+    let pool = Pool::open(&db);
+    let conn = pool.connection().await;
+    let cid = conn
+        .collection_id(&format!("hyperfine-{}", chrono::Utc::now().timestamp()))
+        .await;
+    let aid = conn.artifact_id(&artifact_id).await;
+
+    let mut imported = 0;
+    for command in &output.results {
+        let name = benchmark_name(&command.command);
+        if name.is_empty() {
+            eprintln!("skipping {:?}: no name", command.command);
+            continue;
+        }
+        if command.exit_codes.iter().any(|&c| c != Some(0)) {
+            eprintln!("skipping {}: it failed", name);
+            continue;
+        }
+        let mut stats = vec![("wall-time", command.mean)];
+        if let Some(median) = command.median {
+            stats.push(("wall-time:median", median));
+        }
+        if let (Some(user), Some(system)) = (command.user, command.system) {
+            stats.push(("cpu-clock", (user + system) * 1e3));
+        }
+
+        conn.record_benchmark(&name, None).await;
+        imported += 1;
+        for (stat, value) in stats {
+            if let Err(e) = database::validate::statistic(stat, value) {
+                eprintln!("skipping {}: {}", name, e);
+                continue;
+            }
+            conn.record_statistic(cid, aid, &name, Profile::Opt, Cache::Empty, stat, value)
+                .await;
+        }
+    }
+
+    println!("imported {} commands as {}", imported, artifact);
+    Ok(())
+}
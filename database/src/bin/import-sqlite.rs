@@ -3,7 +3,7 @@ use std::collections::HashSet;
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    database::logging::init();
 
     let sqlite = std::env::args()
         .nth(1)
@@ -1,175 +1,51 @@
 //! This ingests JSON (old-style) content into a database.
+//!
+//! Usage: `ingest-json <db> <uploaded-dir> [--trace]
+//! [--duplicates=error|keep-newest|merge] [--influx=<write-url>]
+//! [--sink=<url>...] [--sink-format=perf|bmf] <files...>`
+//!
+//! A file whose commit or artifact is already in the database is a duplicate.
+//! By default it's an error, and the file is skipped; `keep-newest` replaces
+//! the existing results with the file's, and `merge` adds the file's results
+//! to them as another collection. Duplicates are recorded, to be shown on the
+//! site's status page.
+//!
+//! Only `.json` and `.json.sz` files are read; anything else, such as an
+//! editor's backup, is skipped. Each file is moved into `uploaded-dir` once
+//! ingested. Files which can't be parsed or fail validation (see
+//! `database::validate`) are reported and left where they are. With `--trace`,
+//! a table of where the time went is printed at the end.
+//!
+//! With `--influx`, each commit's results are also pushed to InfluxDB once
+//! ingested (see `database::influx`), e.g. to
+//! `http://localhost:8086/write?db=rustc_perf`. Artifacts have no date, so
+//! aren't pushed.
+//!
+//! Each `--sink` is a URL which every file's results (commits' and artifacts')
+//! are also POSTed to once ingested (see `database::sink`), e.g. an external
+//! benchmarking service or a second perf instance. `--sink-format=bmf` sends
+//! them in Bencher Metric Format rather than as perf's own JSON. The
+//! `SINK_TOKEN` environment variable, if set, is sent as a bearer token.
+//! Results which a sink fails to accept are kept in `uploaded-dir/sink-retry`
+//! and sent again, before any new ones, on the next run.
 
 use anyhow::Context as _;
 use chrono::{DateTime, Utc};
+use database::json::{QueryData, Results};
 use database::pool::ConnectionManager;
-use database::{Cache, Crate, Profile};
-use database::{Commit, PatchName, Pool, QueryLabel};
+use database::sink::{Batch, ResultSink, Spool};
+use database::Profile;
+use database::{Pool, QueryLabel};
 use futures::stream::{FuturesUnordered, StreamExt};
 use hashbrown::{HashMap, HashSet};
 use rusqlite::params;
 use rusqlite::types::{ToSqlOutput, ValueRef};
-use serde::Deserialize;
 use std::convert::TryFrom;
 use std::io::Read;
 use std::sync::{Arc, Mutex};
-use std::{future::Future, path::Path, pin::Pin, time::Duration};
+use std::{future::Future, path::Path, pin::Pin};
 use tokio_postgres::Statement;
 
-#[derive(Deserialize)]
-pub struct ArtifactData {
-    pub id: String,
-    // String in Result is the output of the command that failed
-    pub benchmarks: HashMap<Crate, Result<Benchmark, String>>,
-}
-
-#[derive(Deserialize)]
-pub struct CommitData {
-    pub commit: Commit,
-    // String in Result is the output of the command that failed
-    pub benchmarks: HashMap<Crate, Result<Benchmark, String>>,
-}
-
-#[derive(Deserialize)]
-pub struct Benchmark {
-    pub runs: Vec<Run>,
-    pub name: Crate,
-}
-
-#[derive(Deserialize)]
-pub struct Run {
-    pub stats: Stats,
-    #[serde(default)]
-    pub self_profile: Option<SelfProfile>,
-    #[serde(default)]
-    pub check: bool,
-    pub release: bool,
-    pub state: BenchmarkState,
-}
-
-#[derive(Deserialize)]
-pub struct Stats {
-    stats: Vec<Option<f64>>,
-}
-
-impl Stats {
-    pub fn new() -> Stats {
-        Stats {
-            stats: vec![None; 10],
-        }
-    }
-
-    pub fn iter(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
-        self.stats.iter().enumerate().filter_map(|(idx, s)| {
-            s.map(|s| {
-                (
-                    match idx {
-                        0 => "cpu-clock:u",
-                        1 => "cycles:u",
-                        2 => "faults",
-                        3 => "faults:u",
-                        4 => "instructions:u",
-                        5 => "max-rss",
-                        6 => "task-clock",
-                        7 => "task-clock:u",
-                        8 => "wall-time",
-                        9 => "cpu-clock",
-                        _ => panic!("unknown id: {}", idx),
-                    },
-                    s,
-                )
-            })
-        })
-    }
-}
-
-#[derive(Deserialize)]
-pub enum BenchmarkState {
-    Clean,
-    IncrementalStart,
-    IncrementalClean,
-    IncrementalPatched(Patch),
-}
-
-#[derive(Deserialize)]
-pub struct Patch {
-    pub name: PatchName,
-}
-
-#[derive(Deserialize)]
-#[serde(from = "InternalSelfProfile")]
-pub struct SelfProfile {
-    pub query_data: Vec<QueryData>,
-}
-
-pub struct QueryData {
-    pub label: QueryLabel,
-    pub self_time: u64,
-    pub number_of_cache_hits: u32,
-    pub invocation_count: u32,
-    pub blocked_time: u64,
-    pub incremental_load_time: u64,
-}
-
-impl QueryData {
-    pub fn self_time(&self) -> Duration {
-        Duration::from_nanos(self.self_time)
-    }
-
-    pub fn blocked_time(&self) -> Duration {
-        Duration::from_nanos(self.blocked_time)
-    }
-
-    pub fn incremental_load_time(&self) -> Duration {
-        Duration::from_nanos(self.incremental_load_time)
-    }
-}
-
-#[derive(Deserialize)]
-struct InternalSelfProfile {
-    label: Vec<QueryLabel>,
-    // nanos
-    self_time: Vec<u64>,
-    number_of_cache_hits: Vec<u32>,
-    invocation_count: Vec<u32>,
-    // nanos
-    blocked_time: Vec<u64>,
-    // nanos
-    incremental_load_time: Vec<u64>,
-}
-
-impl From<InternalSelfProfile> for SelfProfile {
-    fn from(profile: InternalSelfProfile) -> SelfProfile {
-        let InternalSelfProfile {
-            label,
-            self_time,
-            number_of_cache_hits,
-            invocation_count,
-            blocked_time,
-            incremental_load_time,
-        } = profile;
-        let mut query_data = Vec::with_capacity(label.len());
-        let label = label.into_iter();
-        let mut self_time = self_time.into_iter();
-        let mut number_of_cache_hits = number_of_cache_hits.into_iter();
-        let mut invocation_count = invocation_count.into_iter();
-        let mut blocked_time = blocked_time.into_iter();
-        let mut incremental_load_time = incremental_load_time.into_iter();
-        for label in label {
-            query_data.push(QueryData {
-                label,
-                self_time: self_time.next().unwrap(),
-                number_of_cache_hits: number_of_cache_hits.next().unwrap(),
-                invocation_count: invocation_count.next().unwrap(),
-                blocked_time: blocked_time.next().unwrap(),
-                incremental_load_time: incremental_load_time.next().unwrap(),
-            });
-        }
-        assert_eq!(query_data.capacity(), query_data.len());
-        SelfProfile { query_data }
-    }
-}
-
 #[derive(Debug, Hash, PartialEq, Eq)]
 struct PstatSeries {
     krate: Arc<String>,
@@ -186,8 +62,42 @@ struct SpqSeries {
     query: &'static str,
 }
 
+/// What to do with results for an artifact which is already in the database.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum DuplicatePolicy {
+    Error,
+    KeepNewest,
+    Merge,
+}
+
+impl DuplicatePolicy {
+    fn as_str(self) -> &'static str {
+        match self {
+            DuplicatePolicy::Error => "error",
+            DuplicatePolicy::KeepNewest => "keep-newest",
+            DuplicatePolicy::Merge => "merge",
+        }
+    }
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "error" => DuplicatePolicy::Error,
+            "keep-newest" => DuplicatePolicy::KeepNewest,
+            "merge" => DuplicatePolicy::Merge,
+            _ => return Err(format!("{:?} is not a duplicate policy", s)),
+        })
+    }
+}
+
 #[async_trait::async_trait]
 trait Ingesting {
+    async fn has_artifact(&self, name: &str) -> bool;
+    /// Deletes the artifact and all of its results.
+    async fn delete_artifact(&self, name: &str);
+    async fn record_duplicate(&self, name: &str, policy: DuplicatePolicy);
     async fn artifact(&self, name: &str, date: Option<DateTime<Utc>>, ty: &str) -> i16;
     async fn benchmark(&self, name: &str);
     async fn collection(&self) -> i32;
@@ -211,6 +121,28 @@ impl<'a> Sqlite<'a> {
 
 #[async_trait::async_trait]
 impl Ingesting for Sqlite<'_> {
+    async fn has_artifact(&self, name: &str) -> bool {
+        self.conn()
+            .query_row(
+                "select exists(select 1 from artifact where name = ?)",
+                params![name],
+                |r| r.get(0),
+            )
+            .unwrap()
+    }
+    async fn delete_artifact(&self, name: &str) {
+        self.conn()
+            .execute("delete from artifact where name = ?", params![name])
+            .unwrap();
+    }
+    async fn record_duplicate(&self, name: &str, policy: DuplicatePolicy) {
+        self.conn()
+            .execute(
+                "insert or replace into duplicate_upload (artifact, policy) VALUES (?, ?)",
+                params![name, policy.as_str()],
+            )
+            .unwrap();
+    }
     async fn artifact(&self, name: &str, date: Option<DateTime<Utc>>, ty: &str) -> i16 {
         self.conn()
             .execute(
@@ -428,6 +360,32 @@ struct CachedStatements {
 
 #[async_trait::async_trait]
 impl Ingesting for Postgres<'_> {
+    async fn has_artifact(&self, name: &str) -> bool {
+        self.conn
+            .query_one(
+                "select exists(select 1 from artifact where name = $1)",
+                &[&name],
+            )
+            .await
+            .unwrap()
+            .get(0)
+    }
+    async fn delete_artifact(&self, name: &str) {
+        self.conn
+            .execute("delete from artifact where name = $1", &[&name])
+            .await
+            .unwrap();
+    }
+    async fn record_duplicate(&self, name: &str, policy: DuplicatePolicy) {
+        self.conn
+            .execute(
+                "insert into duplicate_upload (artifact, policy) VALUES ($1, $2)
+                ON CONFLICT (artifact) DO UPDATE SET policy = excluded.policy",
+                &[&name, &policy.as_str()],
+            )
+            .await
+            .unwrap();
+    }
     async fn artifact(&self, name: &str, date: Option<DateTime<Utc>>, ty: &str) -> i16 {
         self.conn
             .execute(
@@ -586,10 +544,72 @@ impl Ingesting for Postgres<'_> {
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
-    let db = std::env::args().nth(1).expect("database as first arg");
-    let uploaded = std::env::args().nth(2).expect("uploaded");
+    database::logging::init();
+    let trace = std::env::args().any(|a| a == "--trace");
+    if trace {
+        database::trace::enable();
+    }
+    let mut policy = DuplicatePolicy::Error;
+    let mut influx = None;
+    let mut sink_urls = Vec::new();
+    let mut sink_format = database::sink::Format::Perf;
+    for arg in std::env::args() {
+        if let Some(p) = arg.strip_prefix("--duplicates=") {
+            policy = p.parse().unwrap_or_else(|e| panic!("{}", e));
+        }
+        if let Some(url) = arg.strip_prefix("--influx=") {
+            influx = Some(url.to_owned());
+        }
+        if let Some(url) = arg.strip_prefix("--sink=") {
+            sink_urls.push(url.to_owned());
+        }
+        if let Some(f) = arg.strip_prefix("--sink-format=") {
+            sink_format = f.parse().unwrap_or_else(|e| panic!("{}", e));
+        }
+    }
+    let token = std::env::var("SINK_TOKEN").ok();
+    let client = database::sink::client().unwrap();
+    let sinks = sink_urls
+        .into_iter()
+        .map(|url| -> Box<dyn ResultSink> {
+            Box::new(database::sink::Http {
+                url,
+                token: token.clone(),
+                format: sink_format,
+                client: client.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut args = std::env::args()
+        .filter(|a| {
+            a != "--trace"
+                && !a.starts_with("--duplicates=")
+                && !a.starts_with("--influx=")
+                && !a.starts_with("--sink=")
+                && !a.starts_with("--sink-format=")
+        })
+        .skip(1);
+    let db = args.next().expect("database as first arg");
+    let uploaded = args.next().expect("uploaded");
     let uploaded = Path::new(&uploaded);
+    let spool = Spool {
+        dir: uploaded.join("sink-retry"),
+    };
+    for sink in &sinks {
+        match spool.retry(&**sink).await {
+            Ok(0) => {}
+            Ok(n) => database::event!(
+                log::Level::Info, "resent to sink";
+                sink = sink.name(),
+                batches = n,
+            ),
+            Err(e) => database::event!(
+                log::Level::Error, "failed to resend to sink";
+                sink = sink.name(),
+                problem = format!("{:#}", e),
+            ),
+        }
+    }
     let pool = Pool::open(&db);
     let mut sqlite = None::<rusqlite::Connection>;
     let mut postgres = None::<tokio_postgres::Client>;
@@ -678,7 +698,17 @@ async fn main() {
         None
     };
 
-    let paths = std::env::args().skip(3).collect::<Vec<_>>();
+    let paths = args
+        .filter(
+            |path| match database::validate::file_name(Path::new(path)) {
+                Ok(()) => true,
+                Err(e) => {
+                    database::event!(log::Level::Warn, "skipping file"; problem = e);
+                    false
+                }
+            },
+        )
+        .collect::<Vec<_>>();
     let paths_count = paths.len();
     let mut s_cache = IdCache::default();
     if let Some(sqlite) = &mut sqlite {
@@ -823,7 +853,7 @@ async fn main() {
     }
 
     let mut last = std::time::Instant::now();
-    for (idx, path) in paths.into_iter().enumerate() {
+    for (idx, (path, res)) in parse_ahead(paths).enumerate() {
         if idx % 10 == 0 {
             eprintln!(
                 "{}/{}, per {:?}; estimated time left {:?}",
@@ -835,35 +865,122 @@ async fn main() {
             last = std::time::Instant::now();
         }
         let path = Path::new(&path);
-        let sfut: std::pin::Pin<Box<dyn Future<Output = ()>>> = if let Some(s) = &mut sqlite {
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => {
+                database::event!(
+                    log::Level::Error, "skipping unparseable file";
+                    file = path.display().to_string(),
+                    problem = format!("{:#}", e),
+                );
+                continue;
+            }
+        };
+        let problems = database::json::validate(&res);
+        if !problems.is_empty() {
+            for problem in &problems {
+                database::event!(
+                    log::Level::Error, "invalid results";
+                    file = path.display().to_string(),
+                    problem = problem.as_str(),
+                );
+            }
+            database::event!(
+                log::Level::Error, "skipping invalid file";
+                file = path.display().to_string(),
+                count = problems.len(),
+            );
+            continue;
+        }
+        let lines = if influx.is_some() {
+            influx_lines(&res)
+        } else {
+            Vec::new()
+        };
+        let batch = if sinks.is_empty() {
+            None
+        } else {
+            Some(Batch::new(&res))
+        };
+        let (sres, pres) = if sqlite.is_some() {
+            (Some(res), None)
+        } else {
+            (None, Some(res))
+        };
+        type Ingested<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + 'a>>;
+        let sfut: Ingested = if let Some(s) = &mut sqlite {
             let mut s_cache = &mut s_cache;
             Box::pin(async move {
                 let mut s = Sqlite {
                     conn: Mutex::new(s),
                 };
                 s.conn().execute_batch("BEGIN DEFERRED").unwrap();
-                ingest(&mut s, &mut s_cache, path).await;
+                let res = ingest(&mut s, &mut s_cache, sres.unwrap(), policy).await;
                 s.conn().execute_batch("COMMIT").unwrap();
+                res
             })
         } else {
-            Box::pin(async move {})
+            Box::pin(async move { Ok(()) })
         };
         let cached = cached.clone();
-        let pfut: std::pin::Pin<Box<dyn Future<Output = ()>>> = if let Some(p) = &mut postgres {
+        let pfut: Ingested = if let Some(p) = &mut postgres {
             let mut p_cache = &mut p_cache;
             Box::pin(async move {
                 let mut p = Postgres {
                     conn: p.transaction().await.unwrap(),
                     cached: cached.unwrap(),
                 };
-                ingest(&mut p, &mut p_cache, path).await;
+                let res = ingest(&mut p, &mut p_cache, pres.unwrap(), policy).await;
                 p.conn.commit().await.unwrap();
+                res
             })
         } else {
-            Box::pin(async move {})
+            Box::pin(async move { Ok(()) })
         };
-        futures::join!(sfut, pfut);
+        let _span = database::trace::span("ingest");
+        let (sres, pres) = futures::join!(sfut, pfut);
+        if let Err(e) = sres.and(pres) {
+            database::event!(
+                log::Level::Error, "skipping file";
+                file = path.display().to_string(),
+                problem = e,
+            );
+            continue;
+        }
         std::fs::rename(path, uploaded.join(path.file_name().unwrap())).unwrap();
+        if let Some(url) = &influx {
+            if let Err(e) = database::influx::push(url, &lines).await {
+                database::event!(
+                    log::Level::Error, "failed to push to InfluxDB";
+                    file = path.display().to_string(),
+                    problem = format!("{:#}", e),
+                );
+            }
+        }
+        if let Some(batch) = &batch {
+            let sent = futures::future::join_all(sinks.iter().map(|sink| sink.send(batch))).await;
+            for (sink, sent) in sinks.iter().zip(sent) {
+                if let Err(e) = sent {
+                    database::event!(
+                        log::Level::Error, "failed to send to sink";
+                        file = path.display().to_string(),
+                        sink = sink.name(),
+                        problem = format!("{:#}", e),
+                    );
+                    if let Err(e) = spool.save(&**sink, batch) {
+                        database::event!(
+                            log::Level::Error, "failed to keep batch for retry";
+                            sink = sink.name(),
+                            problem = format!("{:#}", e),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    if trace {
+        eprint!("{}", database::trace::report());
     }
 }
 
@@ -874,10 +991,16 @@ struct IdCache {
     spq_series: HashMap<SpqSeries, i32>,
 }
 
-async fn ingest<T: Ingesting>(conn: &T, caches: &mut IdCache, path: &Path) {
-    let res = deserialize_path(path);
+/// Ingests one file's results, unless they're a duplicate which `policy` says
+/// is an error.
+async fn ingest<T: Ingesting>(
+    conn: &T,
+    caches: &mut IdCache,
+    res: Results,
+    policy: DuplicatePolicy,
+) -> Result<(), String> {
     let (name, date, ty, benchmarks) = match res {
-        Res::Commit(cd) => (
+        Results::Commit(cd) => (
             cd.commit.sha.to_string(),
             if cd.commit.is_try() {
                 None
@@ -887,9 +1010,31 @@ async fn ingest<T: Ingesting>(conn: &T, caches: &mut IdCache, path: &Path) {
             if cd.commit.is_try() { "try" } else { "master" },
             cd.benchmarks,
         ),
-        Res::Artifact(ad) => (ad.id, None, "release", ad.benchmarks),
+        Results::Artifact(ad) => (ad.id, None, "release", ad.benchmarks),
     };
 
+    if conn.has_artifact(&name).await {
+        conn.record_duplicate(&name, policy).await;
+        match policy {
+            DuplicatePolicy::Error => {
+                return Err(format!("{} is already in the database", name));
+            }
+            DuplicatePolicy::KeepNewest => {
+                database::event!(
+                    log::Level::Warn, "replacing duplicate results";
+                    artifact = name.as_str(),
+                );
+                conn.delete_artifact(&name).await;
+            }
+            DuplicatePolicy::Merge => {
+                database::event!(
+                    log::Level::Warn, "merging duplicate results";
+                    artifact = name.as_str(),
+                );
+            }
+        }
+    }
+
     // All JSON files are implicitly assumed to be just one collection
     let (aid, cid) = futures::join!(conn.artifact(&name, date, ty), conn.collection());
 
@@ -917,25 +1062,14 @@ async fn ingest<T: Ingesting>(conn: &T, caches: &mut IdCache, path: &Path) {
         };
 
         for run in &benchmark.runs {
-            let profile = if run.check {
-                Profile::Check
-            } else if run.release {
-                Profile::Opt
-            } else {
-                Profile::Debug
-            };
-            let profile_str = match profile {
+            let profile_str = match run.profile() {
                 Profile::Check => "check",
                 Profile::Debug => "debug",
                 Profile::Doc => "doc",
+                Profile::DocJson => "doc-json",
                 Profile::Opt => "opt",
             };
-            let state = match &run.state {
-                BenchmarkState::Clean => Cache::Empty,
-                BenchmarkState::IncrementalStart => Cache::IncrementalEmpty,
-                BenchmarkState::IncrementalClean => Cache::IncrementalFresh,
-                BenchmarkState::IncrementalPatched(p) => Cache::IncrementalPatch(p.name),
-            };
+            let state = run.cache();
 
             for (sid, stat) in run.stats.iter() {
                 let name = name.clone();
@@ -990,39 +1124,115 @@ async fn ingest<T: Ingesting>(conn: &T, caches: &mut IdCache, path: &Path) {
         buf.push(conn.self_profile_query(sid, aid, cid, &spq_values[idx]));
     }
     while let Some(()) = buf.next().await {}
+    Ok(())
+}
+
+/// A commit's results as InfluxDB lines.
+fn influx_lines(res: &Results) -> Vec<String> {
+    let cd = match res {
+        Results::Commit(cd) => cd,
+        Results::Artifact(_) => return Vec::new(),
+    };
+    let (sha, date) = (cd.commit.sha.as_str(), cd.commit.date.0);
+    let mut lines = Vec::new();
+    for (krate, benchmark) in &cd.benchmarks {
+        let benchmark = match benchmark {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        for run in &benchmark.runs {
+            let profile = run.profile().to_string();
+            let cache = run.cache().to_string();
+            for (stat, value) in run.stats.iter() {
+                lines.push(database::influx::statistic(
+                    krate.as_str(),
+                    &profile,
+                    &cache,
+                    stat,
+                    value,
+                    sha,
+                    date,
+                ));
+            }
+            for qd in run.self_profile.iter().flat_map(|sp| &sp.query_data) {
+                let datum = database::QueryDatum {
+                    self_time: qd.self_time(),
+                    blocked_time: qd.blocked_time(),
+                    incremental_load_time: qd.incremental_load_time(),
+                    number_of_cache_hits: qd.number_of_cache_hits,
+                    invocation_count: qd.invocation_count,
+                };
+                lines.push(database::influx::query(
+                    krate.as_str(),
+                    &profile,
+                    &cache,
+                    qd.label.as_str(),
+                    &datum,
+                    sha,
+                    date,
+                ));
+            }
+        }
+    }
+    lines
 }
 
-enum Res {
-    Artifact(ArtifactData),
-    Commit(CommitData),
+/// How many threads parse files at once.
+const PARSE_THREADS: usize = 4;
+
+/// Parses the files on a few threads, ahead of the database writes, and hands
+/// them back in order. Otherwise most of the time is spent parsing, which only
+/// keeps one core busy.
+fn parse_ahead(paths: Vec<String>) -> impl Iterator<Item = (String, anyhow::Result<Results>)> {
+    let count = paths.len();
+    let receivers = (0..PARSE_THREADS)
+        .map(|thread| {
+            // Each thread takes every `PARSE_THREADS`th file, staying at most a
+            // couple of files ahead.
+            let (tx, rx) = std::sync::mpsc::sync_channel(2);
+            let paths = paths
+                .iter()
+                .skip(thread)
+                .step_by(PARSE_THREADS)
+                .cloned()
+                .collect::<Vec<_>>();
+            std::thread::spawn(move || {
+                for path in paths {
+                    let res = deserialize_path(Path::new(&path));
+                    if tx.send((path, res)).is_err() {
+                        break;
+                    }
+                }
+            });
+            rx
+        })
+        .collect::<Vec<_>>();
+    (0..count).map(move |idx| {
+        receivers[idx % PARSE_THREADS]
+            .recv()
+            .expect("parsing thread panicked")
+    })
 }
 
-fn deserialize_path(path: &Path) -> Res {
-    let mut file = std::fs::File::open(path)
-        .with_context(|| format!("Failed to open {}", path.display()))
-        .unwrap();
+fn deserialize_path(path: &Path) -> anyhow::Result<Results> {
+    let _span = database::trace::span("parse json");
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
     let mut file_contents = Vec::new();
     if path.extension().map_or(false, |e| e == "sz") {
         let mut szip_reader = snap::read::FrameDecoder::new(std::io::BufReader::new(file));
         szip_reader
             .read_to_end(&mut file_contents)
-            .with_context(|| format!("Failed to read {}", path.display()))
-            .unwrap();
+            .with_context(|| format!("Failed to read {}", path.display()))?;
     } else {
         file.read_to_end(&mut file_contents)
-            .with_context(|| format!("Failed to read {}", path.display()))
-            .unwrap();
+            .with_context(|| format!("Failed to read {}", path.display()))?;
     };
 
-    if path
+    let is_artifact = path
         .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .starts_with("artifact-")
-    {
-        Res::Artifact(serde_json::from_slice(&file_contents).unwrap())
-    } else {
-        Res::Commit(serde_json::from_slice(&file_contents).unwrap())
-    }
+        .and_then(|n| n.to_str())
+        .map_or(false, |n| n.starts_with("artifact-"));
+    database::json::parse(&file_contents, is_artifact)
+        .with_context(|| format!("Failed to parse {}", path.display()))
 }
@@ -0,0 +1,183 @@
+//! Merges the results in one database into another, e.g. to consolidate the
+//! data of machines which were benchmarking separately.
+//!
+//! Usage: `merge <into> <from> [policy]`
+//!
+//! Artifacts which only have results in `from` are copied. An artifact with
+//! results in both databases is a conflict, which is resolved by the policy:
+//! - `keep` (the default): keep the results in `into`.
+//! - `replace`: replace the results in `into` with those in `from`.
+//! - `more`: keep whichever results have more values, preferring `into`.
+//! - `ask`: ask on the terminal for each conflict.
+//!
+//! As with `import-sqlite`, only the summary statistics are merged; errors and
+//! self-profile data are not.
+
+use database::{
+    ArtifactId, ArtifactIdNumber, Cache, Connection, Crate, DbLabel, Index, Lookup, Pool,
+    ProcessStatistic, Profile,
+};
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+type Series = (Crate, Profile, Cache, ProcessStatistic);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Policy {
+    Keep,
+    Replace,
+    More,
+    Ask,
+}
+
+impl std::str::FromStr for Policy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "keep" => Policy::Keep,
+            "replace" => Policy::Replace,
+            "more" => Policy::More,
+            "ask" => Policy::Ask,
+            _ => return Err(format!("{} is not a policy", s)),
+        })
+    }
+}
+
+/// All values recorded for an artifact.
+async fn values(conn: &dyn Connection, index: &Index, aid: ArtifactIdNumber) -> Vec<(Series, f64)> {
+    let paths = index.all_pstat_series().copied().collect::<Vec<_>>();
+    let ids = paths
+        .iter()
+        .map(|&(krate, profile, cache, stat)| {
+            DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(index)
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let values = conn.get_pstats(&ids, &[Some(aid)]).await;
+    paths
+        .into_iter()
+        .zip(values)
+        .filter_map(|(path, v)| Some((path, v[0]?)))
+        .collect()
+}
+
+fn ask(name: &str, into: usize, from: usize) -> bool {
+    loop {
+        print!(
+            "{} has {} values in the target and {} in the source; replace? [y/n] ",
+            name, into, from
+        );
+        std::io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer).unwrap() == 0 {
+            // No more input, so keep what's there.
+            return false;
+        }
+        match answer.trim() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => {}
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let into = std::env::args()
+        .nth(1)
+        .expect("database to merge into as first arg");
+    let from = std::env::args()
+        .nth(2)
+        .expect("database to merge from as second arg");
+    let policy: Policy = std::env::args()
+        .nth(3)
+        .map(|p| p.parse().unwrap())
+        .unwrap_or(Policy::Keep);
+
+    let into = Pool::open(&into);
+    let mut into_conn = into.connection().await;
+    let from = Pool::open(&from);
+    let mut from_conn = from.connection().await;
+    let into_idx = into_conn.load_index().await;
+    let from_idx = from_conn.load_index().await;
+
+    let cid_name = format!("merged-{}", chrono::Utc::now().timestamp());
+    println!("Collection ID for merge is {}", cid_name);
+    let cid = into_conn.collection_id(&cid_name).await;
+
+    let artifacts = from_idx
+        .commits()
+        .into_iter()
+        .map(ArtifactId::Commit)
+        .chain(
+            from_idx
+                .artifacts()
+                .map(|a| ArtifactId::Artifact(a.to_owned())),
+        )
+        .collect::<Vec<_>>();
+
+    let mut benchmarks = HashSet::new();
+    let (mut copied, mut replaced, mut kept) = (0, 0, 0);
+    for artifact in artifacts {
+        let name = match &artifact {
+            ArtifactId::Commit(c) => c.sha.clone(),
+            ArtifactId::Artifact(a) => a.clone(),
+        };
+        let from_aid = artifact.lookup(&from_idx).unwrap();
+        let from_values = values(&*from_conn, &from_idx, from_aid).await;
+        if from_values.is_empty() {
+            continue;
+        }
+        let into_count = match artifact.lookup(&into_idx) {
+            Some(aid) => values(&*into_conn, &into_idx, aid).await.len(),
+            None => 0,
+        };
+
+        if into_count > 0 {
+            let replace = match policy {
+                Policy::Keep => false,
+                Policy::Replace => true,
+                Policy::More => from_values.len() > into_count,
+                Policy::Ask => ask(&name, into_count, from_values.len()),
+            };
+            if !replace {
+                kept += 1;
+                continue;
+            }
+            into_conn.purge_artifact(&name).await;
+            replaced += 1;
+        } else {
+            copied += 1;
+        }
+
+        let aid = into_conn.artifact_id(&artifact).await;
+        for ((krate, profile, cache, stat), value) in from_values {
+            if benchmarks.insert(krate) {
+                into_conn.record_benchmark(krate.as_str(), None).await;
+            }
+            into_conn
+                .record_statistic(
+                    cid,
+                    aid,
+                    krate.as_str(),
+                    profile,
+                    cache,
+                    stat.as_str(),
+                    value,
+                )
+                .await;
+        }
+    }
+
+    println!(
+        "copied {} artifacts, replaced {}, kept {} existing",
+        copied, replaced, kept
+    );
+}
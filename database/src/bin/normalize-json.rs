@@ -0,0 +1,108 @@
+//! Rewrites a directory of JSON (old-style) results, as taken by `ingest-json`,
+//! in a canonical form: keys sorted, floats in their shortest round-tripping
+//! representation, pretty-printed with a trailing newline. Compressed (`.sz`)
+//! files stay compressed. This makes diffs of a data repository show only the
+//! actual changes.
+//!
+//! Usage: `normalize-json <dir> [--check] [--convert-legacy]`
+//!
+//! With `--check`, nothing is written; the files which aren't normalized are
+//! listed, and the exit code is 1 if there are any. With `--convert-legacy`,
+//! files in the legacy format (see `database::legacy`) are also rewritten in
+//! the current one, which `ingest-json` would otherwise convert as it reads
+//! them.
+
+use anyhow::Context as _;
+use std::io::{Read, Write};
+use std::path::Path;
+
+fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let file = std::fs::File::open(path)?;
+    let mut contents = Vec::new();
+    if path.extension().map_or(false, |e| e == "sz") {
+        snap::read::FrameDecoder::new(std::io::BufReader::new(file)).read_to_end(&mut contents)?;
+    } else {
+        std::io::BufReader::new(file).read_to_end(&mut contents)?;
+    }
+    Ok(contents)
+}
+
+fn write(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    // Write to a temporary file first, so that an interrupted run never leaves
+    // a truncated file behind.
+    let tmp = path.with_extension("tmp");
+    {
+        let file = std::fs::File::create(&tmp)?;
+        if path.extension().map_or(false, |e| e == "sz") {
+            let mut encoder = snap::write::FrameEncoder::new(file);
+            encoder.write_all(contents)?;
+            encoder.flush()?;
+        } else {
+            let mut file = file;
+            file.write_all(contents)?;
+        }
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// The canonical form of a file. `serde_json::Value` keeps object keys in a
+/// sorted map, and prints floats with `ryu`, so re-serializing is enough.
+fn normalize(contents: &[u8], convert_legacy: bool) -> anyhow::Result<Vec<u8>> {
+    let value: serde_json::Value = if convert_legacy && database::legacy::is_legacy(contents) {
+        database::legacy::convert(contents)?
+    } else {
+        serde_json::from_slice(contents)?
+    };
+    let mut normalized = serde_json::to_vec_pretty(&value)?;
+    normalized.push(b'\n');
+    Ok(normalized)
+}
+
+fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let dir = std::env::args()
+        .nth(1)
+        .expect("data directory as first arg");
+    let check = std::env::args().skip(2).any(|a| a == "--check");
+    let convert_legacy = std::env::args().skip(2).any(|a| a == "--convert-legacy");
+
+    let mut paths = std::fs::read_dir(&dir)
+        .with_context(|| format!("failed to list {}", dir))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|p| match database::validate::file_name(p) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("skipping {}", e);
+            false
+        }
+    });
+    paths.sort();
+
+    let mut changed = 0;
+    for path in &paths {
+        let contents = read(path).with_context(|| format!("failed to read {:?}", path))?;
+        let normalized = normalize(&contents, convert_legacy)
+            .with_context(|| format!("failed to parse {:?}", path))?;
+        if normalized == contents {
+            continue;
+        }
+        changed += 1;
+        if check {
+            println!("{}", path.display());
+        } else {
+            write(path, &normalized).with_context(|| format!("failed to write {:?}", path))?;
+        }
+    }
+
+    if check {
+        eprintln!("{} of {} files are not normalized", changed, paths.len());
+        if changed > 0 {
+            std::process::exit(1);
+        }
+    } else {
+        eprintln!("normalized {} of {} files", changed, paths.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Deletes a benchmark, and all results ever recorded for it, from a database.
+//! This is for benchmarks which were retired or turn out to have been broken.
+//!
+//! Usage: `purge-benchmark <db> <benchmark> [--dry-run]`
+//!
+//! With `--dry-run`, only the number of values which would be deleted is
+//! printed. Otherwise a SQLite database is first backed up next to itself; back
+//! up a Postgres database with `pg_dump` before running this.
+//!
+//! A running site keeps its index of the data in memory, so it must be told to
+//! reload it (`POST /perf/onpush`), or restarted, afterwards.
+
+use database::{pool::ConnectionManager, Pool};
+use rusqlite::params;
+
+/// The tables holding results, with the table of the series they belong to.
+const TABLES: &[(&str, &str)] = &[
+    ("pstat", "pstat_series"),
+    ("self_profile_query", "self_profile_query_series"),
+    ("error", "error_series"),
+];
+
+fn count_query(table: &str, series: &str, placeholder: &str) -> String {
+    format!(
+        "select count(*) from {} join {} on {}.id = {}.series where {}.crate = {}",
+        table, series, series, table, series, placeholder
+    )
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let benchmark = std::env::args()
+        .nth(2)
+        .expect("benchmark to delete as second arg");
+    let dry_run = std::env::args().nth(3).map_or(false, |a| a == "--dry-run");
+
+    match Pool::open(&db) {
+        Pool::Sqlite(mut p) => {
+            let conn = p.raw().open().await.into_inner().unwrap();
+            for (table, series) in TABLES {
+                let count: i64 = conn
+                    .query_row(&count_query(table, series, "?"), params![benchmark], |r| {
+                        r.get(0)
+                    })
+                    .unwrap();
+                println!("{}: {} rows", table, count);
+            }
+            if dry_run {
+                return;
+            }
+            let backup = format!("{}.{}.bak", db, chrono::Utc::now().timestamp());
+            conn.execute("vacuum into ?", params![backup]).unwrap();
+            println!("backed up to {}", backup);
+            // Deleting the benchmark cascades to its series and their values.
+            conn.execute("delete from benchmark where name = ?", params![benchmark])
+                .unwrap();
+        }
+        Pool::Postgres(mut p) => {
+            let conn: tokio_postgres::Client = p.raw().open().await.into();
+            for (table, series) in TABLES {
+                let count: i64 = conn
+                    .query_one(count_query(table, series, "$1").as_str(), &[&benchmark])
+                    .await
+                    .unwrap()
+                    .get(0);
+                println!("{}: {} rows", table, count);
+            }
+            if dry_run {
+                return;
+            }
+            // Deleting the benchmark cascades to its series and their values.
+            conn.execute("delete from benchmark where name = $1", &[&benchmark])
+                .await
+                .unwrap();
+        }
+    }
+
+    println!(
+        "deleted {}; reload or restart the site to update it",
+        benchmark
+    );
+}
@@ -0,0 +1,60 @@
+//! Renames benchmarks, so that the history of a renamed benchmark (e.g.
+//! `regex.0.1.30` to `regex`) isn't split in two on the graphs.
+//!
+//! Usage: `rename-benchmark <db> <mapping>`
+//!
+//! Each non-empty line of the mapping file is `<old name> <new name>`; lines
+//! starting with `#` are ignored. If the new benchmark already has results,
+//! the old benchmark's results are merged into them, keeping the new ones
+//! where both exist. The old name is recorded as an alias, so that requests to
+//! the site which use it still work.
+//!
+//! Raw self-profile data is uploaded under the benchmark's name at the time, so
+//! that of a renamed benchmark's old results can't be downloaded from the site
+//! afterwards.
+
+use database::Pool;
+use std::collections::HashSet;
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let mapping = std::env::args().nth(2).expect("mapping file as second arg");
+    let mapping = std::fs::read_to_string(&mapping)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", mapping, e));
+
+    let pool = Pool::open(&db);
+    let mut conn = pool.connection().await;
+    let index = conn.load_index().await;
+    let benchmarks = index
+        .all_pstat_series()
+        .map(|path| path.0.as_str())
+        .chain(index.all_errors().map(|krate| krate.as_str()))
+        .collect::<HashSet<_>>();
+
+    let mut renames = Vec::new();
+    for (i, line) in mapping.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [from, to] if from != to => renames.push((from, to)),
+            _ => panic!("line {}: expected `<old name> <new name>`", i + 1),
+        }
+    }
+
+    let mut tx = conn.transaction().await;
+    for (from, to) in renames {
+        if !benchmarks.contains(from) {
+            eprintln!("skipping {}: no such benchmark", from);
+            continue;
+        }
+        tx.conn().rename_benchmark(from, to).await;
+        println!("renamed {} to {}", from, to);
+    }
+    tx.commit().await.unwrap();
+
+    println!("reload or restart the site to update it");
+}
@@ -0,0 +1,138 @@
+//! Prints an overview of the master commits in a database: how many there are,
+//! the dates they cover, gaps between them, how many of them each benchmark
+//! has results for, and which benchmarks are the noisiest.
+//!
+//! Usage: `stats <db> [gap-days]`
+//!
+//! Gaps of more than `gap-days` (default 2) days between consecutive commits
+//! are listed. Coverage and noise are measured on one series per benchmark:
+//! `instructions:u` of a full check build where available. Noise is the median
+//! change between consecutive commits, in percent.
+
+use database::{Cache, DbLabel, Lookup, Pool, Profile};
+use std::collections::BTreeMap;
+
+/// The series used to represent a benchmark, by preference.
+fn rank(profile: Profile, cache: Cache, stat: &str) -> (bool, bool, bool) {
+    (
+        stat != "instructions:u",
+        cache != Cache::Empty,
+        profile != Profile::Check,
+    )
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Some(values[values.len() / 2])
+}
+
+#[tokio::main]
+async fn main() {
+    database::logging::init();
+    let db = std::env::args().nth(1).expect("database as first arg");
+    let gap_days = std::env::args()
+        .nth(2)
+        .map(|d| d.parse::<i64>().expect("gap in days as second arg"))
+        .unwrap_or(2);
+
+    let pool = Pool::open(&db);
+    let mut conn = pool.connection().await;
+    let index = conn.load_index().await;
+
+    let commits = index
+        .commits()
+        .into_iter()
+        .filter(|c| !c.is_try())
+        .collect::<Vec<_>>();
+    let (first, last) = match (commits.first(), commits.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            println!("no master commits");
+            return;
+        }
+    };
+    println!(
+        "{} master commits from {} to {}",
+        commits.len(),
+        first.date.0.format("%Y-%m-%d"),
+        last.date.0.format("%Y-%m-%d"),
+    );
+
+    println!();
+    println!("gaps of more than {} days:", gap_days);
+    let mut gaps = 0;
+    for w in commits.windows(2) {
+        let days = (w[1].date.0 - w[0].date.0).num_days();
+        if days > gap_days {
+            println!(
+                "  {} to {} ({} days)",
+                w[0].date.0.format("%Y-%m-%d"),
+                w[1].date.0.format("%Y-%m-%d"),
+                days
+            );
+            gaps += 1;
+        }
+    }
+    if gaps == 0 {
+        println!("  none");
+    }
+
+    let mut series = BTreeMap::new();
+    for &(krate, profile, cache, stat) in index.all_pstat_series() {
+        let entry = series.entry(krate).or_insert((profile, cache, stat));
+        if rank(profile, cache, stat.as_str()) < rank(entry.0, entry.1, entry.2.as_str()) {
+            *entry = (profile, cache, stat);
+        }
+    }
+    let ids = series
+        .iter()
+        .map(|(&krate, &(profile, cache, stat))| {
+            DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let aids = commits
+        .iter()
+        .map(|c| database::ArtifactId::Commit(c.clone()).lookup(&index))
+        .collect::<Vec<_>>();
+    let values = conn.get_pstats(&ids, &aids).await;
+
+    println!();
+    println!("coverage:");
+    let mut noise = Vec::new();
+    for ((krate, &(profile, cache, stat)), values) in series.iter().zip(&values) {
+        let present = values.iter().flatten().count();
+        println!(
+            "  {:<30} {:>6.1}% ({}-{} {})",
+            krate.as_str(),
+            present as f64 / commits.len() as f64 * 100.0,
+            profile,
+            cache,
+            stat.as_str()
+        );
+        let mut changes = values
+            .windows(2)
+            .filter_map(|w| Some(((w[1]? - w[0]?) / w[0]?).abs() * 100.0))
+            .filter(|c| c.is_finite())
+            .collect::<Vec<_>>();
+        if let Some(m) = median(&mut changes) {
+            noise.push((krate.as_str(), m));
+        }
+    }
+
+    println!();
+    println!("noisiest benchmarks:");
+    noise.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    for (krate, m) in noise.iter().take(10) {
+        println!("  {:<30} {:>8.3}%", krate, m);
+    }
+}
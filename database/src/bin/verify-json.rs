@@ -0,0 +1,176 @@
+//! Checks a directory of JSON (old-style) results, as taken by `ingest-json`,
+//! for problems which would make ingesting it fail or produce bad data. Each
+//! file is parsed and validated exactly as `ingest-json` does (see
+//! `database::json`), and the files are then checked against each other.
+//!
+//! Each problem is printed to stdout as a line of JSON; a summary is printed to
+//! stderr. Exits with 1 if there were any problems.
+
+use anyhow::Context as _;
+use database::json::{Benchmark, BenchmarkState, Results};
+use database::{Crate, PatchName};
+use hashbrown::{HashMap, HashSet};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize)]
+struct Problem {
+    file: PathBuf,
+    kind: &'static str,
+    message: String,
+}
+
+fn read(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let file = std::fs::File::open(path)?;
+    let mut contents = Vec::new();
+    if path.extension().map_or(false, |e| e == "sz") {
+        snap::read::FrameDecoder::new(std::io::BufReader::new(file)).read_to_end(&mut contents)?;
+    } else {
+        std::io::BufReader::new(file).read_to_end(&mut contents)?;
+    }
+    Ok(contents)
+}
+
+/// The patches each benchmark was run with.
+fn patches(
+    benchmarks: &HashMap<Crate, Result<Benchmark, String>>,
+) -> HashMap<Crate, BTreeSet<PatchName>> {
+    benchmarks
+        .iter()
+        .filter_map(|(name, b)| Some((name, b.as_ref().ok()?)))
+        .map(|(name, b)| {
+            let patches = b
+                .runs
+                .iter()
+                .filter_map(|run| match &run.state {
+                    BenchmarkState::IncrementalPatched(p) => Some(p.name),
+                    BenchmarkState::Clean
+                    | BenchmarkState::IncrementalStart
+                    | BenchmarkState::IncrementalClean => None,
+                })
+                .collect();
+            (*name, patches)
+        })
+        .collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    database::logging::init();
+    let dir = std::env::args()
+        .nth(1)
+        .expect("data directory as first arg");
+
+    let mut paths = std::fs::read_dir(&dir)
+        .with_context(|| format!("failed to list {}", dir))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|p| match database::validate::file_name(p) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("skipping {}", e);
+            false
+        }
+    });
+    paths.sort();
+
+    let mut problems = Vec::new();
+    let mut problem = |file: &Path, kind, message| {
+        problems.push(Problem {
+            file: file.to_owned(),
+            kind,
+            message,
+        })
+    };
+
+    let mut shas = HashMap::new();
+    let mut artifact_ids = HashMap::new();
+    // (date, file, patches per benchmark) for master commits.
+    let mut history = Vec::new();
+
+    for path in &paths {
+        let contents = match read(path) {
+            Ok(c) => c,
+            Err(e) => {
+                problem(path, "unreadable", format!("{:?}", e));
+                continue;
+            }
+        };
+        let is_artifact = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map_or(false, |n| n.starts_with("artifact-"));
+
+        let res = match database::json::parse(&contents, is_artifact) {
+            Ok(res) => res,
+            Err(e) => {
+                problem(path, "parse", e.to_string());
+                continue;
+            }
+        };
+        for e in database::json::validate(&res) {
+            problem(path, "invalid", e);
+        }
+
+        match &res {
+            Results::Artifact(data) => {
+                if let Some(previous) = artifact_ids.insert(data.id.clone(), path.clone()) {
+                    problem(
+                        path,
+                        "duplicate-artifact",
+                        format!("{} is also in {}", data.id, previous.display()),
+                    );
+                }
+            }
+            Results::Commit(data) => {
+                let commit = &data.commit;
+                if let Some(previous) = shas.insert(commit.sha.clone(), path.clone()) {
+                    problem(
+                        path,
+                        "duplicate-commit",
+                        format!("{} is also in {}", commit.sha, previous.display()),
+                    );
+                }
+                if !commit.is_try() {
+                    history.push((commit.date, path.clone(), patches(&data.benchmarks)));
+                }
+            }
+        }
+    }
+
+    // The benchmarks' patches change over time, but a commit whose patches
+    // differ from those of both of its neighbours, which agree with each other,
+    // is an outlier.
+    history.sort_by(|a, b| a.0.cmp(&b.0));
+    for window in history.windows(3) {
+        let (before, (_, path, current), after) = (&window[0].2, &window[1], &window[2].2);
+        for (name, patches) in current {
+            if let (Some(b), Some(a)) = (before.get(name), after.get(name)) {
+                if b == a && patches != b {
+                    let expected = b.iter().collect::<HashSet<_>>();
+                    let actual = patches.iter().collect::<HashSet<_>>();
+                    problem(
+                        path,
+                        "inconsistent-patches",
+                        format!(
+                            "{} is missing {:?} and has extra {:?} compared to adjacent commits",
+                            name,
+                            expected.difference(&actual).collect::<Vec<_>>(),
+                            actual.difference(&expected).collect::<Vec<_>>(),
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    for p in &problems {
+        println!("{}", serde_json::to_string(p)?);
+    }
+    eprintln!("checked {} files: {} problems", paths.len(), problems.len());
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! Results as InfluxDB line protocol, for `export-influx` and for `ingest-json`
+//! to push as it goes.
+//!
+//! Statistics are `rustc_perf` points and self-profile queries (compiler
+//! phases) are `rustc_perf_query` points, both tagged by crate, profile and
+//! cache, with incremental patches split out into a `patch` tag. The artifact
+//! is a field rather than a tag, to keep the number of series down, and the
+//! timestamp is its date.
+
+use crate::QueryDatum;
+use chrono::{DateTime, Utc};
+
+/// Escapes a tag key or value: commas, equals signs and spaces.
+fn tag(s: &str) -> String {
+    s.replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Quotes a string field value.
+fn string_field(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The tags shared by both measurements. Caches are stored as e.g.
+/// `incr-patched: println`, which becomes `cache=incr-patched,patch=println`.
+fn series_tags(krate: &str, profile: &str, cache: &str) -> String {
+    let (cache, patch) = match cache.find(": ") {
+        Some(idx) => (&cache[..idx], Some(&cache[idx + 2..])),
+        None => (cache, None),
+    };
+    let mut tags = format!(
+        "crate={},profile={},cache={}",
+        tag(krate),
+        tag(profile),
+        tag(cache)
+    );
+    if let Some(patch) = patch {
+        tags.push_str(&format!(",patch={}", tag(patch)));
+    }
+    tags
+}
+
+/// One statistic of a benchmark at an artifact.
+pub fn statistic(
+    krate: &str,
+    profile: &str,
+    cache: &str,
+    stat: &str,
+    value: f64,
+    artifact: &str,
+    date: DateTime<Utc>,
+) -> String {
+    format!(
+        "rustc_perf,{},statistic={} value={},artifact={} {}",
+        series_tags(krate, profile, cache),
+        tag(stat),
+        value,
+        string_field(artifact),
+        date.timestamp_nanos()
+    )
+}
+
+/// One self-profile query of a benchmark at an artifact, with its times in
+/// seconds.
+pub fn query(
+    krate: &str,
+    profile: &str,
+    cache: &str,
+    query: &str,
+    qd: &QueryDatum,
+    artifact: &str,
+    date: DateTime<Utc>,
+) -> String {
+    format!(
+        "rustc_perf_query,{},phase={} self_time={},blocked_time={},incremental_load_time={},\
+         number_of_cache_hits={}i,invocation_count={}i,artifact={} {}",
+        series_tags(krate, profile, cache),
+        tag(query),
+        qd.self_time.as_secs_f64(),
+        qd.blocked_time.as_secs_f64(),
+        qd.incremental_load_time.as_secs_f64(),
+        qd.number_of_cache_hits,
+        qd.invocation_count,
+        string_field(artifact),
+        date.timestamp_nanos()
+    )
+}
+
+/// Writes lines to an InfluxDB write endpoint, e.g.
+/// `http://localhost:8086/write?db=rustc_perf`.
+pub async fn push(url: &str, lines: &[String]) -> anyhow::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let response = reqwest::Client::new()
+        .post(url)
+        .body(lines.join("\n"))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "{} responded {}: {}",
+            url,
+            response.status(),
+            response.text().await.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
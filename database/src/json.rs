@@ -0,0 +1,286 @@
+//! The JSON results files which the collector used to write, one per commit or
+//! artifact, as read by `ingest-json`.
+//!
+//! These files come from outside, so parsing one must never panic: anything
+//! malformed is a parse error, and anything well-formed but wrong is reported
+//! by `validate`.
+
+use crate::{Cache, Commit, Crate, PatchName, Profile, QueryLabel};
+use hashbrown::HashMap;
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+pub enum Results {
+    Artifact(ArtifactData),
+    Commit(CommitData),
+}
+
+/// Parses a file's (decompressed) contents. Artifact files are named
+/// `artifact-*`; the rest are commits, in the current format or the legacy one
+/// (see `crate::legacy`).
+pub fn parse(contents: &[u8], is_artifact: bool) -> serde_json::Result<Results> {
+    if is_artifact {
+        serde_json::from_slice(contents).map(Results::Artifact)
+    } else {
+        match serde_json::from_slice(contents) {
+            Ok(cd) => Ok(Results::Commit(cd)),
+            Err(_) if crate::legacy::is_legacy(contents) => {
+                let converted = crate::legacy::convert(contents)?;
+                serde_json::from_value(converted).map(Results::Commit)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Everything wrong with a file's results.
+pub fn validate(res: &Results) -> Vec<String> {
+    let mut problems = Vec::new();
+    let benchmarks = match res {
+        Results::Commit(cd) => {
+            problems.extend(crate::validate::commit(&cd.commit).err());
+            &cd.benchmarks
+        }
+        Results::Artifact(ad) => &ad.benchmarks,
+    };
+    for (name, benchmark) in benchmarks {
+        let benchmark = match benchmark {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        if benchmark.runs.is_empty() {
+            problems.push(format!("{} has no runs", name));
+        }
+        for run in &benchmark.runs {
+            for (stat, value) in run.stats.iter() {
+                if let Err(e) = crate::validate::statistic(stat, value) {
+                    problems.push(format!("{}: {}", name, e));
+                }
+            }
+            if let Some(sp) = &run.self_profile {
+                let labels = sp.query_data.iter().map(|qd| qd.label.as_str());
+                if let Err(e) = crate::validate::queries(labels) {
+                    problems.push(format!("{}: {}", name, e));
+                }
+                // The times are stored as (signed) bigints.
+                let too_long = |nanos: u64| nanos > i64::MAX as u64;
+                for qd in &sp.query_data {
+                    if too_long(qd.self_time)
+                        || too_long(qd.blocked_time)
+                        || too_long(qd.incremental_load_time)
+                    {
+                        problems.push(format!("{}: {} takes too long", name, qd.label));
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+#[derive(Deserialize)]
+pub struct ArtifactData {
+    pub id: String,
+    // String in Result is the output of the command that failed
+    pub benchmarks: HashMap<Crate, Result<Benchmark, String>>,
+}
+
+#[derive(Deserialize)]
+pub struct CommitData {
+    pub commit: Commit,
+    // String in Result is the output of the command that failed
+    pub benchmarks: HashMap<Crate, Result<Benchmark, String>>,
+}
+
+#[derive(Deserialize)]
+pub struct Benchmark {
+    pub runs: Vec<Run>,
+    pub name: Crate,
+}
+
+#[derive(Deserialize)]
+pub struct Run {
+    pub stats: Stats,
+    #[serde(default)]
+    pub self_profile: Option<SelfProfile>,
+    #[serde(default)]
+    pub check: bool,
+    pub release: bool,
+    pub state: BenchmarkState,
+}
+
+impl Run {
+    pub fn profile(&self) -> Profile {
+        if self.check {
+            Profile::Check
+        } else if self.release {
+            Profile::Opt
+        } else {
+            Profile::Debug
+        }
+    }
+
+    pub fn cache(&self) -> Cache {
+        match &self.state {
+            BenchmarkState::Clean => Cache::Empty,
+            BenchmarkState::IncrementalStart => Cache::IncrementalEmpty,
+            BenchmarkState::IncrementalClean => Cache::IncrementalFresh,
+            BenchmarkState::IncrementalPatched(p) => Cache::IncrementalPatch(p.name),
+        }
+    }
+}
+
+/// The statistics, by their index in the file.
+const STAT_NAMES: &[&str] = &[
+    "cpu-clock:u",
+    "cycles:u",
+    "faults",
+    "faults:u",
+    "instructions:u",
+    "max-rss",
+    "task-clock",
+    "task-clock:u",
+    "wall-time",
+    "cpu-clock",
+];
+
+#[derive(Deserialize)]
+#[serde(try_from = "RawStats")]
+pub struct Stats {
+    stats: Vec<Option<f64>>,
+}
+
+/// `Stats` as written, before its length is checked.
+#[derive(Deserialize)]
+struct RawStats {
+    stats: Vec<Option<f64>>,
+}
+
+impl TryFrom<RawStats> for Stats {
+    type Error = String;
+
+    fn try_from(RawStats { stats }: RawStats) -> Result<Stats, String> {
+        if stats.len() > STAT_NAMES.len() {
+            return Err(format!(
+                "{} statistics, but only {} are known",
+                stats.len(),
+                STAT_NAMES.len()
+            ));
+        }
+        Ok(Stats { stats })
+    }
+}
+
+impl Stats {
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+        STAT_NAMES
+            .iter()
+            .zip(&self.stats)
+            .filter_map(|(name, s)| s.map(|s| (*name, s)))
+    }
+}
+
+#[derive(Deserialize)]
+pub enum BenchmarkState {
+    Clean,
+    IncrementalStart,
+    IncrementalClean,
+    IncrementalPatched(Patch),
+}
+
+#[derive(Deserialize)]
+pub struct Patch {
+    pub name: PatchName,
+}
+
+#[derive(Deserialize)]
+#[serde(try_from = "InternalSelfProfile")]
+pub struct SelfProfile {
+    pub query_data: Vec<QueryData>,
+}
+
+pub struct QueryData {
+    pub label: QueryLabel,
+    pub self_time: u64,
+    pub number_of_cache_hits: u32,
+    pub invocation_count: u32,
+    pub blocked_time: u64,
+    pub incremental_load_time: u64,
+}
+
+impl QueryData {
+    pub fn self_time(&self) -> Duration {
+        Duration::from_nanos(self.self_time)
+    }
+
+    pub fn blocked_time(&self) -> Duration {
+        Duration::from_nanos(self.blocked_time)
+    }
+
+    pub fn incremental_load_time(&self) -> Duration {
+        Duration::from_nanos(self.incremental_load_time)
+    }
+}
+
+#[derive(Deserialize)]
+struct InternalSelfProfile {
+    label: Vec<QueryLabel>,
+    // nanos
+    self_time: Vec<u64>,
+    number_of_cache_hits: Vec<u32>,
+    invocation_count: Vec<u32>,
+    // nanos
+    blocked_time: Vec<u64>,
+    // nanos
+    incremental_load_time: Vec<u64>,
+}
+
+impl TryFrom<InternalSelfProfile> for SelfProfile {
+    type Error = String;
+
+    fn try_from(profile: InternalSelfProfile) -> Result<SelfProfile, String> {
+        let InternalSelfProfile {
+            label,
+            self_time,
+            number_of_cache_hits,
+            invocation_count,
+            blocked_time,
+            incremental_load_time,
+        } = profile;
+        let lengths = [
+            self_time.len(),
+            number_of_cache_hits.len(),
+            invocation_count.len(),
+            blocked_time.len(),
+            incremental_load_time.len(),
+        ];
+        if lengths.iter().any(|&len| len != label.len()) {
+            return Err(format!(
+                "{} query labels, but {:?} values",
+                label.len(),
+                lengths
+            ));
+        }
+        let mut self_time = self_time.into_iter();
+        let mut number_of_cache_hits = number_of_cache_hits.into_iter();
+        let mut invocation_count = invocation_count.into_iter();
+        let mut blocked_time = blocked_time.into_iter();
+        let mut incremental_load_time = incremental_load_time.into_iter();
+        // The lengths were checked above, so every `next` has a value.
+        let query_data = label
+            .into_iter()
+            .filter_map(|label| {
+                Some(QueryData {
+                    label,
+                    self_time: self_time.next()?,
+                    number_of_cache_hits: number_of_cache_hits.next()?,
+                    invocation_count: invocation_count.next()?,
+                    blocked_time: blocked_time.next()?,
+                    incremental_load_time: incremental_load_time.next()?,
+                })
+            })
+            .collect();
+        Ok(SelfProfile { query_data })
+    }
+}
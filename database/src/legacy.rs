@@ -0,0 +1,124 @@
+//! The results format of the scripts which benchmarked rustc before the
+//! collector did, converted to the current (`json`) format so that graphs
+//! extend back over that history. `json::parse` converts legacy files as it
+//! reads them, and `normalize-json --convert-legacy` rewrites them in the
+//! current format.
+//!
+//! A legacy file is one commit's results: the time (in seconds) and peak
+//! memory (in megabytes) of each crate the compiler built, and the time of
+//! each of its passes.
+//!
+//! ```text
+//! {"header": {"commit": "<sha>", "date": "2016-03-02 07:17:41 +0000", ...},
+//!  "times": [{"crate": "syntax", "total": 12.3, "mem": 812,
+//!             "times": [{"name": "parsing", "time": 0.4, ...}, ...]}, ...]}
+//! ```
+//!
+//! Each crate becomes a benchmark with one clean, optimized run: its total as
+//! `wall-time`, its memory as `max-rss` (in kilobytes, like the collector's),
+//! and its passes as self-profile queries, with a pass which appears more than
+//! once summed.
+
+use crate::Date;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+struct File {
+    header: Header,
+    times: Vec<CrateTimes>,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    commit: String,
+    date: String,
+}
+
+#[derive(Deserialize)]
+struct CrateTimes {
+    #[serde(rename = "crate")]
+    krate: String,
+    total: f64,
+    #[serde(default)]
+    mem: Option<f64>,
+    #[serde(default)]
+    times: Vec<Pass>,
+}
+
+#[derive(Deserialize)]
+struct Pass {
+    name: String,
+    time: f64,
+}
+
+/// Whether a file's contents look like a legacy file: an object with a
+/// `header` and `times`, neither of which the current format has.
+pub fn is_legacy(contents: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Keys {
+        header: serde::de::IgnoredAny,
+        times: serde::de::IgnoredAny,
+    }
+    serde_json::from_slice::<Keys>(contents).is_ok()
+}
+
+/// Legacy dates have a numeric timezone after a space, which `Date` doesn't
+/// parse.
+fn date(s: &str) -> Result<Date, String> {
+    if let Ok(date) = s.parse::<Date>() {
+        return Ok(date);
+    }
+    DateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S %z")
+        .map(|d| Date(d.with_timezone(&Utc)))
+        .map_err(|e| format!("{:?} is not a date: {}", s, e))
+}
+
+/// Converts a legacy file to a commit file in the current format.
+pub fn convert(contents: &[u8]) -> serde_json::Result<Value> {
+    let file: File = serde_json::from_slice(contents)?;
+    let date = date(&file.header.date).map_err(serde::de::Error::custom)?;
+    let mut benchmarks = serde_json::Map::new();
+    for krate in file.times {
+        // Indexed like `json::STAT_NAMES`.
+        let mut stats = vec![Value::Null; 10];
+        stats[8] = json!(krate.total);
+        if let Some(mem) = krate.mem {
+            stats[5] = json!(mem * 1024.0);
+        }
+        let mut passes = BTreeMap::<String, f64>::new();
+        for pass in krate.times {
+            *passes.entry(pass.name).or_default() += pass.time;
+        }
+        let nanos = passes
+            .values()
+            .map(|secs| (secs * 1e9) as u64)
+            .collect::<Vec<_>>();
+        let zeros = vec![0; passes.len()];
+        let self_profile = json!({
+            "label": passes.keys().collect::<Vec<_>>(),
+            "self_time": nanos,
+            "number_of_cache_hits": zeros,
+            "invocation_count": vec![1; passes.len()],
+            "blocked_time": zeros,
+            "incremental_load_time": zeros,
+        });
+        let run = json!({
+            "stats": { "stats": stats },
+            "self_profile": self_profile,
+            "check": false,
+            "release": true,
+            "state": "Clean",
+        });
+        benchmarks.insert(
+            krate.krate.clone(),
+            json!({ "Ok": { "runs": [run], "name": krate.krate } }),
+        );
+    }
+    Ok(json!({
+        "commit": { "sha": file.header.commit, "date": date },
+        "benchmarks": benchmarks,
+    }))
+}
@@ -1,20 +1,54 @@
-use chrono::offset::TimeZone;
-use chrono::{DateTime, Datelike, NaiveDate, Utc};
-use hashbrown::HashMap;
+use chrono::offset::{LocalResult, TimeZone};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc, Weekday};
+use hashbrown::{HashMap, HashSet};
 use intern::intern;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
-use std::hash;
 use std::ops::{Add, Sub};
 use std::time::Duration;
 
+pub mod influx;
+pub mod json;
+pub mod legacy;
+pub mod logging;
 pub mod pool;
+pub mod sink;
+pub mod testdata;
+pub mod trace;
+pub mod validate;
 
 pub use pool::{Connection, Pool};
 
 intern!(pub struct ProcessStatistic);
 intern!(pub struct Crate);
 
+/// The pull request which a commit merged (or, for a try commit, was built
+/// for).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitPr {
+    pub sha: String,
+    pub pr: u32,
+    pub title: String,
+    /// The GitHub login of the pull request's author.
+    pub author: String,
+}
+
+/// What has been done about the changes a master commit made, as recorded
+/// through the Zulip bot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Triage {
+    pub sha: String,
+    /// Who acknowledged the changes, if anyone has.
+    pub acknowledged_by: Option<String>,
+    /// Who is looking into the changes.
+    pub assignee: Option<String>,
+    /// The issue tracking the changes, e.g. its URL.
+    pub issue: Option<String>,
+    /// Whether the changes have been posted to Zulip.
+    pub posted: bool,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct QueuedCommit {
     pub pr: u32,
@@ -25,6 +59,86 @@ pub struct QueuedCommit {
     pub runs: Option<i32>,
 }
 
+/// The kind of work a benchmark job represents.
+///
+/// Variants are declared in priority order: jobs of an earlier kind are always
+/// claimed before jobs of a later kind, so that e.g. try builds never wait
+/// behind a long backfill.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum JobKind {
+    Try,
+    Master,
+    Release,
+    Backfill,
+}
+
+impl JobKind {
+    /// Lower values are claimed first.
+    pub fn priority(&self) -> i32 {
+        match self {
+            JobKind::Try => 0,
+            JobKind::Master => 1,
+            JobKind::Release => 2,
+            JobKind::Backfill => 3,
+        }
+    }
+}
+
+impl std::str::FromStr for JobKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "try" => JobKind::Try,
+            "master" => JobKind::Master,
+            "release" => JobKind::Release,
+            "backfill" => JobKind::Backfill,
+            _ => return Err(format!("{} is not a job kind", s)),
+        })
+    }
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                JobKind::Try => "try",
+                JobKind::Master => "master",
+                JobKind::Release => "release",
+                JobKind::Backfill => "backfill",
+            }
+        )
+    }
+}
+
+/// An entry in the benchmark job queue.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedJob {
+    /// A commit sha, or a toolchain name for release jobs.
+    pub artifact: String,
+    pub kind: JobKind,
+    /// Lower values are claimed first; initially the kind's priority.
+    pub priority: i32,
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    pub runs: Option<i32>,
+    /// The name of the collector which claimed this job, if any.
+    pub claimed_by: Option<String>,
+}
+
+/// A machine on which benchmarks are collected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Machine {
+    /// Chosen by whoever runs the collector; defaults to the hostname.
+    pub name: String,
+    /// Describes the hardware, so that results from a machine which was
+    /// upgraded in place aren't compared with its earlier results.
+    pub fingerprint: String,
+}
+
 #[derive(Debug, Hash, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Date(pub DateTime<Utc>);
 
@@ -35,17 +149,47 @@ pub struct DateParseError {
     pub error: chrono::ParseError,
 }
 
+/// The formats of dates and times without a timezone, which are taken to be in
+/// UTC.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+
+/// The fewest digits in a number of seconds since the epoch, i.e. any time
+/// since September 2001, so that a number can't be mistaken for a compact date
+/// like `20200101`.
+const MIN_SECONDS_DIGITS: usize = 10;
+
 impl std::str::FromStr for Date {
     type Err = DateParseError;
+    /// Parses an RFC 3339 date (as dates are written), a date and time without
+    /// a timezone, a date alone, or a number of seconds since the epoch (of at
+    /// least `MIN_SECONDS_DIGITS` digits).
     fn from_str(s: &str) -> Result<Date, DateParseError> {
-        match DateTime::parse_from_rfc3339(s) {
-            Ok(value) => Ok(Date(value.with_timezone(&Utc))),
-            Err(error) => Err(DateParseError {
-                input: s.to_string(),
-                format: format!("RFC 3339"),
-                error,
-            }),
+        let s = s.trim();
+        let error = match DateTime::parse_from_rfc3339(s) {
+            Ok(value) => return Ok(Date(value.with_timezone(&Utc))),
+            Err(error) => error,
+        };
+        for format in DATE_FORMATS {
+            if let Ok(dt) = Utc.datetime_from_str(s, format) {
+                return Ok(Date(dt));
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Date(Utc.from_utc_datetime(&date.and_hms(0, 0, 0))));
         }
+        if s.len() >= MIN_SECONDS_DIGITS && s.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(seconds) = s.parse::<i64>() {
+                if let LocalResult::Single(dt) = Utc.timestamp_opt(seconds, 0) {
+                    return Ok(Date(dt));
+                }
+            }
+        }
+        Err(DateParseError {
+            input: s.to_string(),
+            format: "RFC 3339, YYYY-MM-DD[ HH:MM:SS] or seconds since the epoch (10+ digits)"
+                .to_string(),
+            error,
+        })
     }
 }
 
@@ -79,6 +223,19 @@ impl Date {
     }
 }
 
+/// The date of a nightly alias, `nightly-YYYY-MM-DD`.
+pub fn nightly_date(name: &str) -> Option<NaiveDate> {
+    let date = name.strip_prefix("nightly-")?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// When the nightly of a date was built. Nightlies are built from master as
+/// it is at midnight UTC, so a nightly's commit is the last one merged before
+/// then.
+pub fn nightly_cutoff(date: NaiveDate) -> Date {
+    Date(Utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
 impl fmt::Display for Date {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(&self.0.to_rfc3339())
@@ -139,16 +296,39 @@ impl<'de> Deserialize<'de> for Date {
                 })
             }
 
+            fn visit_i64<E>(self, value: i64) -> ::std::result::Result<Date, E>
+            where
+                E: serde::de::Error,
+            {
+                match Utc.timestamp_opt(value, 0) {
+                    LocalResult::Single(dt) => Ok(Date(dt)),
+                    _ => Err(serde::de::Error::invalid_value(
+                        serde::de::Unexpected::Signed(value),
+                        &self,
+                    )),
+                }
+            }
+
+            fn visit_u64<E>(self, value: u64) -> ::std::result::Result<Date, E>
+            where
+                E: serde::de::Error,
+            {
+                let seconds = i64::try_from(value).map_err(|_| {
+                    serde::de::Error::invalid_value(serde::de::Unexpected::Unsigned(value), &self)
+                })?;
+                self.visit_i64(seconds)
+            }
+
             fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                f.write_str("an RFC 3339 date")
+                f.write_str("a date, or a number of seconds since the epoch")
             }
         }
 
-        deserializer.deserialize_str(DateVisitor)
+        deserializer.deserialize_any(DateVisitor)
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct Commit {
     pub sha: String,
     pub date: Date,
@@ -161,31 +341,18 @@ impl Commit {
     }
 }
 
-impl hash::Hash for Commit {
-    fn hash<H: hash::Hasher>(&self, hasher: &mut H) {
-        self.sha.hash(hasher);
-    }
-}
-
-impl PartialEq for Commit {
-    fn eq(&self, other: &Self) -> bool {
-        self.sha == other.sha
-    }
-}
-
-impl Eq for Commit {}
-
 impl PartialOrd for Commit {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(&other))
     }
 }
 
+// Commits are ordered by date, and commits with the same date by sha so that
+// their order is stable. Two commits are equal only if both their shas and
+// their dates are, as for the derived `PartialEq`.
 impl Ord for Commit {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.date
-            .cmp(&other.date)
-            .then_with(|| self.sha.cmp(&other.sha))
+        (&self.date, &self.sha).cmp(&(&other.date, &other.sha))
     }
 }
 
@@ -196,6 +363,8 @@ pub enum Profile {
     Check,
     Debug,
     Doc,
+    /// rustdoc's JSON output, i.e. `--output-format json`.
+    DocJson,
     Opt,
 }
 
@@ -206,6 +375,7 @@ impl std::str::FromStr for Profile {
             "check" => Profile::Check,
             "debug" => Profile::Debug,
             "doc" => Profile::Doc,
+            "doc-json" => Profile::DocJson,
             "opt" => Profile::Opt,
             _ => return Err(format!("{} is not a profile", s)),
         })
@@ -222,6 +392,7 @@ impl fmt::Display for Profile {
                 Profile::Opt => "opt",
                 Profile::Debug => "debug",
                 Profile::Doc => "doc",
+                Profile::DocJson => "doc-json",
             }
         )
     }
@@ -442,6 +613,15 @@ pub struct Index {
     errors: Indexed<Crate>,
     pstats: Indexed<(Crate, Profile, Cache, ProcessStatistic)>,
     queries: Indexed<(Crate, Profile, Cache, QueryLabel)>,
+
+    /// The former names of renamed benchmarks, mapped to their current names.
+    aliases: HashMap<String, String>,
+
+    /// The keys of `commits`, sorted, so that they can be handed out without
+    /// copying them every time.
+    sorted_commits: Vec<Commit>,
+    /// The position of each commit in `sorted_commits`, by sha.
+    positions: HashMap<String, usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -492,6 +672,13 @@ where
     {
         self.map.get(value).copied()
     }
+
+    /// Roughly the bytes of heap held by the map itself, not counting any held
+    /// by its keys.
+    fn heap_size(&self) -> usize {
+        // Each slot also has a control byte.
+        self.map.capacity() * (std::mem::size_of::<(T, u32)>() + 1)
+    }
 }
 
 mod index_serde {
@@ -607,9 +794,36 @@ impl Lookup for ArtifactId {
 
 impl Index {
     pub async fn load(conn: &mut dyn pool::Connection) -> Index {
+        let _span = trace::span("load index");
         conn.load_index().await
     }
 
+    /// Roughly the bytes of heap held. Interned names are shared, and not
+    /// counted.
+    pub fn heap_size(&self) -> usize {
+        let shas = self
+            .sorted_commits
+            .iter()
+            .map(|c| c.sha.capacity())
+            .sum::<usize>();
+        self.commits.heap_size()
+            + self.sorted_commits.capacity() * std::mem::size_of::<Commit>()
+            // Each sha is held in `commits`, `sorted_commits` and `positions`.
+            + 3 * shas
+            + self.artifacts.heap_size()
+            + self.artifacts.map.keys().map(|a| a.len()).sum::<usize>()
+            + self.errors.heap_size()
+            + self.pstats.heap_size()
+            + self.queries.heap_size()
+            + self.positions.capacity() * (std::mem::size_of::<(String, usize)>() + 1)
+            + self.aliases.capacity() * (std::mem::size_of::<(String, String)>() + 1)
+            + self
+                .aliases
+                .iter()
+                .map(|(k, v)| k.capacity() + v.capacity())
+                .sum::<usize>()
+    }
+
     pub fn lookup(&self, path: &DbLabel, cid: &ArtifactId) -> Option<(u32, ArtifactIdNumber)> {
         let cid = cid.lookup(self)?;
         let series = path.lookup(self)?;
@@ -626,14 +840,90 @@ impl Index {
         T::get(db, series, cid).await
     }
 
+    /// The current name of a benchmark which may have been renamed.
+    pub fn resolve_benchmark<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, |n| n.as_str())
+    }
+
     pub fn artifacts(&self) -> impl Iterator<Item = &'_ str> + '_ {
         self.artifacts.map.keys().map(|s| &**s)
     }
 
-    pub fn commits(&self) -> Vec<Commit> {
+    pub(crate) fn sort_commits(&mut self) {
         let mut commits = self.commits.map.keys().cloned().collect::<Vec<_>>();
         commits.sort();
-        commits
+        self.sorted_commits = commits;
+        self.record_positions();
+    }
+
+    /// Reorders the commits whose position in the order they were merged is
+    /// known (from `merge_position`) into that order. The other commits keep
+    /// their places, by date.
+    ///
+    /// Commits merged close together can have dates which are out of order,
+    /// or equal, so their dates alone don't always order them correctly. The
+    /// dates stay very nearly sorted.
+    pub fn order_by_merges(&mut self, merge_position: impl Fn(&str) -> Option<usize>) {
+        let slots = self
+            .sorted_commits
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, c)| Some((slot, merge_position(&c.sha)?)))
+            .collect::<Vec<_>>();
+        let mut merged = slots
+            .iter()
+            .map(|&(slot, position)| (position, self.sorted_commits[slot].clone()))
+            .collect::<Vec<_>>();
+        merged.sort_by_key(|&(position, _)| position);
+        for (&(slot, _), (_, commit)) in slots.iter().zip(merged) {
+            self.sorted_commits[slot] = commit;
+        }
+        self.record_positions();
+    }
+
+    fn record_positions(&mut self) {
+        self.positions = self
+            .sorted_commits
+            .iter()
+            .enumerate()
+            .map(|(position, c)| (c.sha.clone(), position))
+            .collect();
+    }
+
+    pub fn commits(&self) -> Vec<Commit> {
+        self.sorted_commits.clone()
+    }
+
+    /// Like `commits`, but borrowed.
+    pub fn sorted_commits(&self) -> &[Commit] {
+        &self.sorted_commits
+    }
+
+    /// The position of a commit in `sorted_commits`.
+    pub fn commit_position(&self, sha: &str) -> Option<usize> {
+        self.positions.get(sha).copied()
+    }
+
+    /// Like `commit_position`, but also accepts a nightly alias
+    /// (`nightly-YYYY-MM-DD`), which stands for the last master commit with
+    /// results merged before the nightly was built.
+    pub fn resolve_commit(&self, name: &str) -> Option<usize> {
+        let date = match nightly_date(name) {
+            Some(date) => date,
+            None => return self.commit_position(name),
+        };
+        let cutoff = nightly_cutoff(date);
+        let end = self
+            .sorted_commits
+            .binary_search_by(|c| {
+                if c.date < cutoff {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            })
+            .unwrap_err();
+        self.sorted_commits[..end].iter().rposition(|c| !c.is_try())
     }
 
     // FIXME: in theory this won't scale indefinitely as there's potentially
@@ -694,6 +984,387 @@ impl Index {
     }
 }
 
+/// Values stored in columns: the artifacts and values of all series are in
+/// two flat arrays, sorted by series and then artifact, and each series is a
+/// range of them.
+#[derive(Debug, Clone)]
+struct Columns<V> {
+    series: HashMap<u32, std::ops::Range<usize>>,
+    aids: Vec<ArtifactIdNumber>,
+    values: Vec<V>,
+}
+
+impl<V> Default for Columns<V> {
+    fn default() -> Self {
+        Columns {
+            series: HashMap::new(),
+            aids: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<V: Copy> Columns<V> {
+    /// Appends a value. Values must be pushed sorted by series and then by
+    /// artifact.
+    fn push(&mut self, sid: u32, aid: ArtifactIdNumber, value: V) {
+        let idx = self.values.len();
+        let range = self.series.entry(sid).or_insert(idx..idx);
+        debug_assert_eq!(range.end, idx, "series {} pushed out of order", sid);
+        range.end = idx + 1;
+        self.aids.push(aid);
+        self.values.push(value);
+    }
+
+    fn get(&self, sid: u32, aid: ArtifactIdNumber) -> Option<V> {
+        let range = self.series.get(&sid)?;
+        let idx = self.aids[range.clone()].binary_search(&aid).ok()?;
+        Some(self.values[range.start + idx])
+    }
+
+    fn heap_size(&self) -> usize {
+        self.series.capacity() * (std::mem::size_of::<(u32, std::ops::Range<usize>)>() + 1)
+            + self.aids.capacity() * std::mem::size_of::<ArtifactIdNumber>()
+            + self.values.capacity() * std::mem::size_of::<V>()
+    }
+}
+
+/// All pstat values, held in memory so that a series can be read without going
+/// to the database. Reading a series at a range of artifacts costs a binary
+/// search per artifact rather than a query per value.
+///
+/// Optionally, the values of old commits are kept as `f32`s, which is precise
+/// enough for graphs and takes two thirds of the memory.
+#[derive(Debug, Clone, Default)]
+pub struct PstatCache {
+    exact: Columns<f64>,
+    reduced: Columns<f32>,
+}
+
+impl PstatCache {
+    /// Loads all values, keeping those of master commits before
+    /// `reduce_before` (if given) at reduced precision. Try commits, whose
+    /// placeholder date sorts before any master commit's, are kept in full.
+    pub async fn load(
+        conn: &dyn pool::Connection,
+        index: &Index,
+        reduce_before: Option<Date>,
+    ) -> PstatCache {
+        let _span = trace::span("load pstats");
+        let reduced = match reduce_before {
+            Some(date) => index
+                .sorted_commits()
+                .iter()
+                .take_while(|c| c.date < date)
+                .filter(|c| !c.is_try())
+                .filter_map(|c| index.commits.get(c).map(ArtifactIdNumber))
+                .collect::<HashSet<_>>(),
+            None => HashSet::new(),
+        };
+        let mut cache = PstatCache::default();
+        // The values come sorted by series, then artifact. Non-finite values,
+        // recorded before they were rejected, are left out so that they read
+        // as missing rather than poisoning every average they're part of.
+        for (sid, aid, value) in conn.all_pstats().await {
+            if !value.is_finite() {
+                log::warn!(
+                    "skipping {} for series {} at artifact {}",
+                    value,
+                    sid,
+                    aid.0
+                );
+                continue;
+            }
+            if reduced.contains(&aid) {
+                cache.reduced.push(sid, aid, value as f32);
+            } else {
+                cache.exact.push(sid, aid, value);
+            }
+        }
+        cache
+    }
+
+    /// The number of values held.
+    pub fn len(&self) -> usize {
+        self.exact.values.len() + self.reduced.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Roughly the bytes of heap held.
+    pub fn heap_size(&self) -> usize {
+        self.exact.heap_size() + self.reduced.heap_size()
+    }
+
+    /// The value of a series at an artifact.
+    pub fn value(&self, series: u32, aid: ArtifactIdNumber) -> Option<f64> {
+        self.exact
+            .get(series, aid)
+            .or_else(|| self.reduced.get(series, aid).map(f64::from))
+    }
+
+    /// The values of a series at each of `cids`, like `Connection::get_pstats`.
+    pub fn get(&self, series: u32, cids: &[Option<ArtifactIdNumber>]) -> Vec<Option<f64>> {
+        cids.iter().map(|cid| self.value(series, (*cid)?)).collect()
+    }
+
+    /// The ids of all series with values, sorted.
+    fn series_ids(&self) -> Vec<u32> {
+        let mut sids = self
+            .exact
+            .series
+            .keys()
+            .chain(self.reduced.series.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        sids.sort_unstable();
+        sids.dedup();
+        sids
+    }
+}
+
+/// The relative change of every series from each master commit to the master
+/// commit before it (`0.01` is 1% more), worked out once when the values are
+/// loaded. Anything comparing commits to their parents reads these instead of
+/// recomputing them.
+///
+/// There is no delta where either commit lacks a value, or where the earlier
+/// value is zero.
+#[derive(Debug, Clone, Default)]
+pub struct Deltas {
+    deltas: Columns<f32>,
+}
+
+impl Deltas {
+    pub fn new(index: &Index, pstats: &PstatCache) -> Deltas {
+        let _span = trace::span("deltas");
+        let aids = index
+            .sorted_commits()
+            .iter()
+            .filter(|c| !c.is_try())
+            .filter_map(|c| index.commits.get(c).map(ArtifactIdNumber))
+            .collect::<Vec<_>>();
+
+        let mut deltas = Columns::default();
+        for sid in pstats.series_ids() {
+            let mut points = aids
+                .windows(2)
+                .filter_map(|w| {
+                    let before = pstats.value(sid, w[0])?;
+                    let after = pstats.value(sid, w[1])?;
+                    if before == 0.0 {
+                        return None;
+                    }
+                    Some((w[1], ((after - before) / before) as f32))
+                })
+                .collect::<Vec<_>>();
+            points.sort_by_key(|&(aid, _)| aid);
+            for (aid, delta) in points {
+                deltas.push(sid, aid, delta);
+            }
+        }
+        Deltas { deltas }
+    }
+
+    /// The number of deltas held.
+    pub fn len(&self) -> usize {
+        self.deltas.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Roughly the bytes of heap held.
+    pub fn heap_size(&self) -> usize {
+        self.deltas.heap_size()
+    }
+
+    /// The change of a series at a master commit.
+    pub fn get(&self, series: u32, aid: ArtifactIdNumber) -> Option<f32> {
+        self.deltas.get(series, aid)
+    }
+
+    /// The change of every series with a delta at a master commit.
+    pub fn at(&self, aid: ArtifactIdNumber) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.deltas
+            .series
+            .keys()
+            .filter_map(move |&sid| Some((sid, self.deltas.get(sid, aid)?)))
+    }
+}
+
+/// How finely a series is sampled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// Every commit.
+    Commit,
+    /// The median of each day's commits.
+    Day,
+    /// The median of each week's commits (or each window's, with rolling
+    /// periods).
+    Week,
+}
+
+/// How commits are grouped into weeks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Periods {
+    /// Calendar weeks, starting on `Calendar::week_start`.
+    Aligned,
+    /// Windows of this many days, counted back from the day of the newest
+    /// commit, so that the last one is always complete.
+    Rolling { days: u32 },
+}
+
+/// Where days and weeks start, for downsampling. By default, weeks are
+/// calendar weeks starting on Monday, in UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Calendar {
+    pub week_start: Weekday,
+    /// The (fixed) offset of the timezone days start in.
+    pub offset: FixedOffset,
+    pub periods: Periods,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        Calendar {
+            week_start: Weekday::Mon,
+            offset: FixedOffset::east(0),
+            periods: Periods::Aligned,
+        }
+    }
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Resolution::Commit
+    }
+}
+
+impl Resolution {
+    /// The first day of the period containing `date`, or `None` at commit
+    /// resolution. Rolling periods end on `newest`.
+    fn period(&self, date: Date, calendar: &Calendar, newest: NaiveDate) -> Option<NaiveDate> {
+        let day = calendar.day(date);
+        match (self, calendar.periods) {
+            (Resolution::Commit, _) => None,
+            (Resolution::Day, _) => Some(day),
+            (Resolution::Week, Periods::Aligned) => {
+                let since_start = (day.weekday().num_days_from_monday() + 7
+                    - calendar.week_start.num_days_from_monday())
+                    % 7;
+                Some(day - chrono::Duration::days(since_start as i64))
+            }
+            (Resolution::Week, Periods::Rolling { days }) => {
+                let days = i64::from(days.max(1));
+                let back = (newest - day).num_days().div_euclid(days);
+                Some(newest - chrono::Duration::days(back * days + days - 1))
+            }
+        }
+    }
+}
+
+impl Calendar {
+    /// The day `date` falls on.
+    fn day(&self, date: Date) -> NaiveDate {
+        date.0.with_timezone(&self.offset).naive_local().date()
+    }
+}
+
+/// The pstat values at a coarser resolution than per commit. Each period's
+/// values are recorded under the last master commit of the period, which
+/// stands for the whole period.
+#[derive(Debug, Clone, Default)]
+pub struct Downsampled {
+    /// The commits standing for each period, sorted.
+    pub commits: Vec<Commit>,
+    pub pstats: PstatCache,
+}
+
+impl Downsampled {
+    pub fn new(
+        index: &Index,
+        pstats: &PstatCache,
+        resolution: Resolution,
+        calendar: &Calendar,
+    ) -> Downsampled {
+        let _span = trace::span("downsample");
+        let newest = index
+            .sorted_commits()
+            .iter()
+            .rev()
+            .find(|c| !c.is_try())
+            .map_or_else(|| Utc::now().naive_utc().date(), |c| calendar.day(c.date));
+        // Each period's last commit, and all of its commits.
+        let mut periods: Vec<(Commit, ArtifactIdNumber, Vec<ArtifactIdNumber>)> = Vec::new();
+        for commit in index.sorted_commits().iter().filter(|c| !c.is_try()) {
+            let aid = match index.commits.get(commit) {
+                Some(aid) => ArtifactIdNumber(aid),
+                None => continue,
+            };
+            let period = resolution.period(commit.date, calendar, newest);
+            match periods.last_mut() {
+                Some((last, last_aid, aids))
+                    if period.is_some()
+                        && resolution.period(last.date, calendar, newest) == period =>
+                {
+                    *last = commit.clone();
+                    *last_aid = aid;
+                    aids.push(aid);
+                }
+                _ => periods.push((commit.clone(), aid, vec![aid])),
+            }
+        }
+
+        let mut downsampled = PstatCache::default();
+        for sid in pstats.series_ids() {
+            let mut points = periods
+                .iter()
+                .filter_map(|(_, representative, aids)| {
+                    let mut period = aids
+                        .iter()
+                        .filter_map(|&aid| pstats.value(sid, aid))
+                        .collect::<Vec<_>>();
+                    median(&mut period).map(|m| (*representative, m))
+                })
+                .collect::<Vec<_>>();
+            points.sort_by_key(|&(aid, _)| aid);
+            for (aid, value) in points {
+                downsampled.exact.push(sid, aid, value);
+            }
+        }
+
+        Downsampled {
+            commits: periods.into_iter().map(|(commit, _, _)| commit).collect(),
+            pstats: downsampled,
+        }
+    }
+
+    /// Roughly the bytes of heap held.
+    pub fn heap_size(&self) -> usize {
+        self.commits.capacity() * std::mem::size_of::<Commit>()
+            + self.commits.iter().map(|c| c.sha.capacity()).sum::<usize>()
+            + self.pstats.heap_size()
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let mid = values.len() / 2;
+    Some(if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
 #[derive(Debug)]
 pub struct Step {
     pub name: String,
@@ -711,3 +1382,77 @@ impl fmt::Display for CollectionId {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(sha: &str, date: &str) -> Commit {
+        Commit {
+            sha: sha.to_string(),
+            date: date.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        let date: Date = "2020-06-30T12:34:56+00:00".parse().unwrap();
+        assert_eq!(date.to_string().parse::<Date>().unwrap(), date);
+        let json = serde_json::to_string(&date).unwrap();
+        assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), date);
+
+        let seconds = date.0.timestamp().to_string();
+        assert_eq!(seconds.parse::<Date>().unwrap(), date);
+        assert_eq!("2020-06-30 12:34:56".parse::<Date>().unwrap(), date,);
+        assert_eq!(
+            "2020-06-30".parse::<Date>().unwrap().to_string(),
+            "2020-06-30T00:00:00+00:00",
+        );
+    }
+
+    #[test]
+    fn compact_dates_are_not_seconds() {
+        assert!("20200101".parse::<Date>().is_err());
+        assert!("123".parse::<Date>().is_err());
+        assert!("-1577836800".parse::<Date>().is_err());
+        assert_eq!(
+            "1577836800".parse::<Date>().unwrap().to_string(),
+            "2020-01-01T00:00:00+00:00",
+        );
+    }
+
+    #[test]
+    fn commits_are_ordered_by_date_then_sha() {
+        let a = commit("b", "2020-01-01");
+        let b = commit("a", "2020-01-02");
+        let c = commit("c", "2020-01-02");
+        assert!(a < b);
+        assert!(b < c);
+        assert!(a < c);
+        assert_eq!(b.cmp(&b.clone()), Ordering::Equal);
+
+        let mut commits = vec![c.clone(), a.clone(), b.clone()];
+        commits.sort();
+        assert_eq!(commits, vec![a, b, c]);
+    }
+
+    #[test]
+    fn commit_order_agrees_with_equality() {
+        let commits = [
+            commit("a", "2020-01-01"),
+            commit("b", "2020-01-02"),
+            commit("a", "2020-01-03"),
+            commit("b", "2020-01-02"),
+        ];
+        for a in &commits {
+            for b in &commits {
+                assert_eq!(a == b, a.cmp(b) == Ordering::Equal, "{:?} {:?}", a, b);
+            }
+        }
+        // The same sha with another date sorts by date like any other commit,
+        // so the order stays transitive.
+        assert!(commits[0] < commits[1]);
+        assert!(commits[1] < commits[2]);
+        assert!(commits[0] < commits[2]);
+    }
+}
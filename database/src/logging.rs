@@ -0,0 +1,83 @@
+//! Logging setup shared by the binaries, with optional structured output.
+//!
+//! Messages can carry fields (the file, sha, benchmark, count, ...) through
+//! the `event!` macro. By default lines look like `env_logger`'s, with the
+//! fields appended as `key=value`; with `LOG_FORMAT=json`, each line is a JSON
+//! object with `time`, `level`, `target` and `message` keys and one key per
+//! field, for log pipelines to aggregate on. `RUST_LOG` filters as usual.
+
+use std::cell::RefCell;
+use std::io::Write;
+
+pub use serde_json::Value;
+
+thread_local! {
+    /// The fields of the message being logged on this thread, if any.
+    static FIELDS: RefCell<Vec<(&'static str, Value)>> = RefCell::new(Vec::new());
+}
+
+/// Logs a message with fields, e.g.
+///
+/// ```ignore
+/// database::event!(log::Level::Warn, "skipping results"; sha = sha, count = 3);
+/// ```
+///
+/// Field values are anything which converts into a JSON `Value` (strings and
+/// numbers).
+#[macro_export]
+macro_rules! event {
+    ($level:expr, $($arg:expr),+; $($key:ident = $value:expr),+ $(,)?) => {
+        $crate::logging::with_fields(
+            vec![$((stringify!($key), $crate::logging::Value::from($value))),+],
+            || ::log::log!($level, $($arg),+),
+        )
+    };
+}
+
+#[doc(hidden)]
+pub fn with_fields(fields: Vec<(&'static str, Value)>, log: impl FnOnce()) {
+    // The logger formats on the logging thread, before `log!` returns.
+    FIELDS.with(|f| *f.borrow_mut() = fields);
+    log();
+    FIELDS.with(|f| f.borrow_mut().clear());
+}
+
+/// Sets up logging, in place of `env_logger::init`.
+pub fn init() {
+    let json = std::env::var("LOG_FORMAT").map_or(false, |f| f == "json");
+    env_logger::Builder::from_default_env()
+        .format(move |buf, record| {
+            let time = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            FIELDS.with(|fields| {
+                let fields = fields.borrow();
+                if json {
+                    let mut line = serde_json::Map::new();
+                    line.insert("time".into(), time.into());
+                    line.insert("level".into(), record.level().as_str().into());
+                    line.insert("target".into(), record.target().into());
+                    line.insert("message".into(), record.args().to_string().into());
+                    for (key, value) in fields.iter() {
+                        line.insert((*key).into(), value.clone());
+                    }
+                    writeln!(buf, "{}", Value::Object(line))
+                } else {
+                    write!(
+                        buf,
+                        "[{} {:<5} {}] {}",
+                        time,
+                        record.level(),
+                        record.target(),
+                        record.args()
+                    )?;
+                    for (key, value) in fields.iter() {
+                        match value {
+                            Value::String(s) => write!(buf, " {}={:?}", key, s)?,
+                            value => write!(buf, " {}={}", key, value)?,
+                        }
+                    }
+                    writeln!(buf)
+                }
+            })
+        })
+        .init();
+}
@@ -1,5 +1,6 @@
 use crate::{ArtifactId, ArtifactIdNumber};
 use crate::{Cache, CollectionId, Index, Profile, QueryDatum, QueuedCommit, Step};
+use crate::{CommitPr, JobKind, Machine, QueuedJob, Triage};
 use chrono::{DateTime, Utc};
 use hashbrown::HashMap;
 use std::sync::{Arc, Mutex};
@@ -24,6 +25,15 @@ pub trait Connection: Send + Sync {
 
     async fn collection_id(&self, version: &str) -> CollectionId;
     async fn artifact_id(&self, artifact: &ArtifactId) -> ArtifactIdNumber;
+    /// Deletes an artifact along with everything recorded for it. Does nothing
+    /// if there is no artifact with this name.
+    async fn purge_artifact(&self, artifact: &str);
+    /// Renames a benchmark, merging its results into those of `to` if that
+    /// benchmark already exists (keeping the results of `to` where both have
+    /// one), and records `from` as an alias of `to`.
+    async fn rename_benchmark(&self, from: &str, to: &str);
+    /// Maps the former names of renamed benchmarks to their current names.
+    async fn benchmark_aliases(&self) -> HashMap<String, String>;
     /// None means that the caller doesn't know; it should be left alone if
     /// known or set to false if unknown.
     async fn record_benchmark(&self, krate: &str, supports_stable: Option<bool>);
@@ -77,6 +87,10 @@ pub trait Connection: Send + Sync {
         series: &[u32],
         cid: &[Option<ArtifactIdNumber>],
     ) -> Vec<Vec<Option<f64>>>;
+    /// Every pstat value, as `(series, artifact, value)`, sorted by series and
+    /// then artifact. Like `get_pstats`, this takes the minimum of repeated
+    /// values.
+    async fn all_pstats(&self) -> Vec<(u32, ArtifactIdNumber, f64)>;
     async fn get_self_profile(
         &self,
         cid: ArtifactIdNumber,
@@ -90,6 +104,8 @@ pub trait Connection: Send + Sync {
         cid: ArtifactIdNumber,
     ) -> Option<QueryDatum>;
     async fn get_error(&self, cid: ArtifactIdNumber) -> HashMap<String, Option<String>>;
+    /// The errors of one crate, at every artifact it failed at.
+    async fn get_errors_of(&self, krate: &str) -> HashMap<ArtifactIdNumber, String>;
 
     async fn queue_pr(
         &self,
@@ -103,6 +119,50 @@ pub trait Connection: Send + Sync {
     async fn queued_commits(&self) -> Vec<QueuedCommit>;
     async fn mark_complete(&self, sha: &str) -> Option<QueuedCommit>;
 
+    // Benchmark job queue
+
+    /// Adds a job to the queue. If the artifact is already queued, or its job
+    /// was cancelled, this does nothing; an artifact whose job completed is
+    /// queued again.
+    async fn enqueue_job(
+        &self,
+        artifact: &str,
+        kind: JobKind,
+        include: Option<&str>,
+        exclude: Option<&str>,
+        runs: Option<i32>,
+    );
+    /// Claims the next job to benchmark on the given collector.
+    ///
+    /// A job which this collector claimed but never completed is returned
+    /// again before any unclaimed job, so that a restarted collector resumes
+    /// its work. Otherwise jobs are handed out by priority and then in queue
+    /// order. Jobs claimed by other collectors are never returned.
+    async fn claim_job(&self, collector: &str) -> Option<QueuedJob>;
+    async fn complete_job(&self, artifact: &str);
+    /// All incomplete jobs, in the order they will be claimed.
+    async fn queued_jobs(&self) -> Vec<QueuedJob>;
+    /// Changes the priority of an incomplete job. Returns false if there is
+    /// no such job.
+    async fn set_job_priority(&self, artifact: &str, priority: i32) -> bool;
+    /// Takes an incomplete job out of the queue without benchmarking it. It
+    /// won't be queued again unless retried. Returns false if there is no
+    /// such job.
+    async fn cancel_job(&self, artifact: &str) -> bool;
+    /// Puts the artifact's latest job back in the queue, unclaimed, whether or
+    /// not it was completed or cancelled. Returns false if there is no such
+    /// job.
+    async fn retry_job(&self, artifact: &str) -> bool;
+
+    /// Records that `machine` collected (some of) the data for this artifact.
+    async fn record_machine(&self, aid: ArtifactIdNumber, machine: &Machine);
+    /// Records the noise-reduction settings (CPU pinning, turbo, etc.) the
+    /// collector ran with, replacing any previously recorded.
+    async fn record_tuning(&self, aid: ArtifactIdNumber, collector: &str, tuning: &str);
+    /// The machines which collected data for this artifact. Empty for
+    /// artifacts collected before machines were recorded.
+    async fn machines_of(&self, aid: ArtifactIdNumber) -> Vec<Machine>;
+
     // Collector status API
 
     async fn collector_start(&self, aid: ArtifactIdNumber, steps: &[String]);
@@ -128,6 +188,20 @@ pub trait Connection: Send + Sync {
     /// (Currently only works for try commits)
     async fn pr_of(&self, sha: &str) -> Option<u32>;
 
+    /// The cached pull request of a commit, if it has been looked up before.
+    async fn commit_pr(&self, sha: &str) -> Option<CommitPr>;
+    /// Caches the pull request of a commit.
+    async fn record_commit_pr(&self, pr: &CommitPr);
+
+    /// The triage state of a master commit, if any has been recorded.
+    async fn triage(&self, sha: &str) -> Option<Triage>;
+    /// Records the triage state of a master commit, replacing any before.
+    async fn record_triage(&self, triage: &Triage);
+
+    /// The artifacts which results were uploaded for more than once, and how
+    /// the last duplicate was dealt with.
+    async fn duplicate_uploads(&self) -> Vec<(String, String)>;
+
     /// Returns the collection ids corresponding to the query. Usually just one.
     ///
     /// Currently only supported by postgres (sqlite does not store self-profile
@@ -255,3 +329,140 @@ impl Pool {
         }
     }
 }
+
+/// The statements renaming a benchmark, shared by both backends. Each is
+/// returned with the number of parameters it takes: parameter 1 is the old
+/// name, and parameter 2 (if used) the new one. `param` formats the
+/// placeholder of a parameter.
+pub(crate) fn rename_benchmark_statements(
+    param: fn(usize) -> String,
+    raw_self_profile: bool,
+) -> Vec<(String, usize)> {
+    let (old, new) = (param(1), param(2));
+    let mut statements = vec![(
+        format!(
+            "insert into benchmark (name, stabilized) \
+            select {new}, stabilized from benchmark where name = {old} \
+            and not exists (select 1 from benchmark where name = {new})",
+            old = old,
+            new = new
+        ),
+        2,
+    )];
+
+    // (series table, value table, columns identifying a series besides the
+    // crate, columns identifying a value besides the series)
+    let tables: &[(&str, &str, &[&str], &[&str])] = &[
+        (
+            "pstat_series",
+            "pstat",
+            &["profile", "cache", "statistic"],
+            &["aid", "cid"],
+        ),
+        (
+            "self_profile_query_series",
+            "self_profile_query",
+            &["profile", "cache", "query"],
+            &["aid", "cid"],
+        ),
+        ("error_series", "error", &[], &["aid"]),
+    ];
+    for (series, values, series_key, value_key) in tables {
+        let same_series = series_key
+            .iter()
+            .map(|k| format!(" and n.{k} = o.{k}", k = k))
+            .collect::<String>();
+        // The series of `new` matching the series of a value.
+        let new_series = format!(
+            "(select n.id from {s} o join {s} n on n.crate = {new}{same} \
+            where o.id = {v}.series)",
+            s = series,
+            v = values,
+            new = new,
+            same = same_series
+        );
+        let same_value = value_key
+            .iter()
+            .map(|k| format!(" and d.{k} = {v}.{k}", k = k, v = values))
+            .collect::<String>();
+        // Move the values of series which `new` has too, unless `new` has a
+        // value of its own.
+        statements.push((
+            format!(
+                "update {v} set series = {n} \
+                where series in (select id from {s} where crate = {old}) \
+                and {n} is not null \
+                and not exists (select 1 from {v} d where d.series = {n}{same})",
+                v = values,
+                s = series,
+                n = new_series,
+                old = old,
+                same = same_value
+            ),
+            2,
+        ));
+        // Series which `new` doesn't have can simply be renamed.
+        let same_series = series_key
+            .iter()
+            .map(|k| format!(" and n.{k} = {s}.{k}", k = k, s = series))
+            .collect::<String>();
+        statements.push((
+            format!(
+                "update {s} set crate = {new} where crate = {old} \
+                and not exists (select 1 from {s} n where n.crate = {new}{same})",
+                s = series,
+                new = new,
+                old = old,
+                same = same_series
+            ),
+            2,
+        ));
+    }
+    if raw_self_profile {
+        statements.push((
+            format!(
+                "update raw_self_profile set crate = {new} where crate = {old} \
+                and not exists (select 1 from raw_self_profile n where n.crate = {new} \
+                and n.aid = raw_self_profile.aid and n.cid = raw_self_profile.cid \
+                and n.profile = raw_self_profile.profile and n.cache = raw_self_profile.cache)",
+                new = new,
+                old = old
+            ),
+            2,
+        ));
+    }
+
+    statements.extend(vec![
+        (
+            format!(
+                "delete from benchmark_alias where alias = {old} or alias = {new}",
+                old = old,
+                new = new
+            ),
+            2,
+        ),
+        (
+            format!(
+                "update benchmark_alias set name = {new} where name = {old}",
+                old = old,
+                new = new
+            ),
+            2,
+        ),
+        (
+            format!(
+                "insert into benchmark_alias (alias, name) values ({old}, {new})",
+                old = old,
+                new = new
+            ),
+            2,
+        ),
+        // Whatever is left of the old benchmark, i.e. values which `new`
+        // already had, is deleted along with it.
+        (
+            format!("delete from benchmark where name = {old}", old = old),
+            1,
+        ),
+    ]);
+    statements
+}
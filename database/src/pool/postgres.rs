@@ -1,7 +1,7 @@
 use crate::pool::{Connection, ConnectionManager, ManagedConnection, Transaction};
 use crate::{
-    ArtifactId, ArtifactIdNumber, Cache, CollectionId, Commit, Crate, Date, Index, Profile,
-    QueuedCommit,
+    ArtifactId, ArtifactIdNumber, Cache, CollectionId, Commit, CommitPr, Crate, Date, Index,
+    JobKind, Machine, Profile, QueuedCommit, QueuedJob, Triage,
 };
 use anyhow::Context as _;
 use chrono::{DateTime, TimeZone, Utc};
@@ -202,6 +202,72 @@ static MIGRATIONS: &[&str] = &[
     alter table rustc_compilation alter column aid set data type integer;
     alter table self_profile_query alter column aid set data type integer;
     "#,
+    r#"
+    create table benchmark_job(
+        id integer primary key generated always as identity,
+        artifact text not null unique,
+        kind text not null,
+        priority integer not null,
+        include text,
+        exclude text,
+        runs integer,
+        requested timestamptz not null,
+        claimed timestamptz,
+        complete boolean not null default false
+    );
+    "#,
+    r#"
+    alter table benchmark_job add column claimed_by text;
+    create table artifact_machine(
+        aid integer not null references artifact(id) on delete cascade on update cascade,
+        collector text not null,
+        fingerprint text not null,
+        UNIQUE(aid, collector, fingerprint)
+    );
+    "#,
+    r#"
+    create table artifact_tuning(
+        aid integer not null references artifact(id) on delete cascade on update cascade,
+        collector text not null,
+        tuning text not null,
+        UNIQUE(aid, collector)
+    );
+    "#,
+    r#"
+    create table benchmark_alias(
+        alias text primary key not null,
+        name text not null references benchmark(name) on delete cascade on update cascade
+    );
+    "#,
+    r#"
+    create table commit_pr(
+        sha text primary key not null,
+        pr integer not null,
+        title text not null,
+        author text not null
+    );
+    "#,
+    r#"
+    create table duplicate_upload(
+        artifact text primary key not null,
+        -- How the duplicate was dealt with: error, keep-newest or merge.
+        policy text not null
+    );
+    "#,
+    r#"
+    create table triage(
+        sha text primary key not null,
+        acknowledged_by text,
+        assignee text,
+        issue text,
+        posted boolean not null default false
+    );
+    "#,
+    r#"
+    alter table benchmark_job drop constraint benchmark_job_artifact_key;
+    alter table benchmark_job add column cancelled boolean not null default false;
+    create unique index benchmark_job_queued_artifact on benchmark_job(artifact) where not complete;
+    "#,
 ];
 
 #[async_trait::async_trait]
@@ -473,7 +539,7 @@ where
     }
 
     async fn load_index(&mut self) -> Index {
-        Index {
+        let mut index = Index {
             commits: self
                 .conn()
                 .query(
@@ -544,6 +610,7 @@ where
                                 "opt" => Profile::Opt,
                                 "debug" => Profile::Debug,
                                 "doc" => Profile::Doc,
+                                "doc-json" => Profile::DocJson,
                                 o => unreachable!("{}: not a profile", o),
                             },
                             row.get::<_, String>(3).as_str().parse().unwrap(),
@@ -571,6 +638,7 @@ where
                                 "opt" => Profile::Opt,
                                 "debug" => Profile::Debug,
                                 "doc" => Profile::Doc,
+                                "doc-json" => Profile::DocJson,
                                 o => unreachable!("{}: not a profile", o),
                             },
                             row.get::<_, String>(3).as_str().parse().unwrap(),
@@ -579,7 +647,11 @@ where
                     )
                 })
                 .collect(),
-        }
+            aliases: self.benchmark_aliases().await,
+            ..Index::default()
+        };
+        index.sort_commits();
+        index
     }
     async fn get_pstats(
         &self,
@@ -600,6 +672,25 @@ where
             .map(|row| row.get::<_, Vec<Option<f64>>>(0))
             .collect()
     }
+    async fn all_pstats(&self) -> Vec<(u32, crate::ArtifactIdNumber, f64)> {
+        self.conn()
+            .query(
+                "select series, aid, min(value) from pstat
+                    group by series, aid order by series, aid",
+                &[],
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get::<_, i32>(0) as u32,
+                    crate::ArtifactIdNumber(row.get::<_, i32>(1) as u32),
+                    row.get(2),
+                )
+            })
+            .collect()
+    }
     async fn get_self_profile_query(
         &self,
         series: u32,
@@ -668,6 +759,24 @@ where
             .map(|row| (row.get(0), row.get(1)))
             .collect()
     }
+    async fn get_errors_of(&self, krate: &str) -> HashMap<crate::ArtifactIdNumber, String> {
+        let rows = self
+            .conn()
+            .query(
+                "select aid, error from error
+                    join error_series on error.series = error_series.id
+                    where crate = $1",
+                &[&krate],
+            )
+            .await
+            .unwrap();
+        rows.into_iter()
+            .map(|row| {
+                let aid: i32 = row.get(0);
+                (crate::ArtifactIdNumber(aid as u32), row.get(1))
+            })
+            .collect()
+    }
     async fn queue_pr(
         &self,
         pr: u32,
@@ -736,6 +845,161 @@ where
             runs: row.get(5),
         })
     }
+    async fn enqueue_job(
+        &self,
+        artifact: &str,
+        kind: JobKind,
+        include: Option<&str>,
+        exclude: Option<&str>,
+        runs: Option<i32>,
+    ) {
+        self.conn()
+            .execute(
+                "insert into benchmark_job (artifact, kind, priority, include, exclude, runs, requested)
+                select $1::text, $2::text, $3::integer, $4::text, $5::text, $6::integer, CURRENT_TIMESTAMP
+                where not exists (select 1 from benchmark_job where artifact = $1 and cancelled)
+                ON CONFLICT DO NOTHING",
+                &[
+                    &artifact,
+                    &kind.to_string(),
+                    &kind.priority(),
+                    &include,
+                    &exclude,
+                    &runs,
+                ],
+            )
+            .await
+            .unwrap();
+    }
+    async fn claim_job(&self, collector: &str) -> Option<QueuedJob> {
+        let row = self
+            .conn()
+            .query_opt(
+                "update benchmark_job set
+                    claimed = coalesce(claimed, statement_timestamp()),
+                    claimed_by = $1
+                where id = (
+                    select id from benchmark_job
+                    where complete = false and (claimed is null or claimed_by = $1)
+                    order by claimed is null, priority, id
+                    limit 1
+                    for update skip locked
+                )
+                returning artifact, kind, priority, include, exclude, runs, claimed_by",
+                &[&collector],
+            )
+            .await
+            .unwrap()?;
+        Some(QueuedJob {
+            artifact: row.get(0),
+            kind: row.get::<_, String>(1).parse().unwrap(),
+            priority: row.get(2),
+            include: row.get(3),
+            exclude: row.get(4),
+            runs: row.get(5),
+            claimed_by: row.get(6),
+        })
+    }
+    async fn complete_job(&self, artifact: &str) {
+        self.conn()
+            .execute(
+                "update benchmark_job set complete = true where artifact = $1 and complete = false",
+                &[&artifact],
+            )
+            .await
+            .unwrap();
+    }
+    async fn queued_jobs(&self) -> Vec<QueuedJob> {
+        let rows = self
+            .conn()
+            .query(
+                "select artifact, kind, priority, include, exclude, runs, claimed_by from benchmark_job
+                where complete = false
+                order by claimed is null, priority, id",
+                &[],
+            )
+            .await
+            .unwrap();
+        rows.into_iter()
+            .map(|row| QueuedJob {
+                artifact: row.get(0),
+                kind: row.get::<_, String>(1).parse().unwrap(),
+                priority: row.get(2),
+                include: row.get(3),
+                exclude: row.get(4),
+                runs: row.get(5),
+                claimed_by: row.get(6),
+            })
+            .collect()
+    }
+    async fn set_job_priority(&self, artifact: &str, priority: i32) -> bool {
+        self.conn()
+            .execute(
+                "update benchmark_job set priority = $1 where artifact = $2 and complete = false",
+                &[&priority, &artifact],
+            )
+            .await
+            .unwrap()
+            > 0
+    }
+    async fn cancel_job(&self, artifact: &str) -> bool {
+        self.conn()
+            .execute(
+                "update benchmark_job set complete = true, cancelled = true
+                where artifact = $1 and complete = false",
+                &[&artifact],
+            )
+            .await
+            .unwrap()
+            > 0
+    }
+    async fn retry_job(&self, artifact: &str) -> bool {
+        self.conn()
+            .execute(
+                "update benchmark_job set claimed = null, claimed_by = null, complete = false, cancelled = false
+                where id = (select max(id) from benchmark_job where artifact = $1)",
+                &[&artifact],
+            )
+            .await
+            .unwrap()
+            > 0
+    }
+    async fn record_machine(&self, aid: ArtifactIdNumber, machine: &Machine) {
+        self.conn()
+            .execute(
+                "insert into artifact_machine (aid, collector, fingerprint) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING",
+                &[&(aid.0 as i32), &machine.name, &machine.fingerprint],
+            )
+            .await
+            .unwrap();
+    }
+    async fn record_tuning(&self, aid: ArtifactIdNumber, collector: &str, tuning: &str) {
+        self.conn()
+            .execute(
+                "insert into artifact_tuning (aid, collector, tuning) VALUES ($1, $2, $3)
+                ON CONFLICT (aid, collector) DO UPDATE SET tuning = excluded.tuning",
+                &[&(aid.0 as i32), &collector, &tuning],
+            )
+            .await
+            .unwrap();
+    }
+    async fn machines_of(&self, aid: ArtifactIdNumber) -> Vec<Machine> {
+        self.conn()
+            .query(
+                "select collector, fingerprint from artifact_machine where aid = $1
+                order by collector, fingerprint",
+                &[&(aid.0 as i32)],
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| Machine {
+                name: row.get(0),
+                fingerprint: row.get(1),
+            })
+            .collect()
+    }
     async fn collection_id(&self, version: &str) -> CollectionId {
         CollectionId(
             self.conn()
@@ -857,6 +1121,30 @@ where
         ArtifactIdNumber(aid)
     }
 
+    async fn purge_artifact(&self, artifact: &str) {
+        self.conn()
+            .execute("delete from artifact where name = $1", &[&artifact])
+            .await
+            .unwrap();
+    }
+
+    async fn rename_benchmark(&self, from: &str, to: &str) {
+        let args: [&(dyn tokio_postgres::types::ToSql + Sync); 2] = [&from, &to];
+        for (sql, n) in crate::pool::rename_benchmark_statements(|i| format!("${}", i), true) {
+            self.conn().execute(sql.as_str(), &args[..n]).await.unwrap();
+        }
+    }
+
+    async fn benchmark_aliases(&self) -> HashMap<String, String> {
+        self.conn()
+            .query("select alias, name from benchmark_alias", &[])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect()
+    }
+
     async fn record_self_profile_query(
         &self,
         collection: CollectionId,
@@ -1127,6 +1415,78 @@ where
             .unwrap()
             .map(|r| r.get::<_, i32>(0) as u32)
     }
+    async fn commit_pr(&self, sha: &str) -> Option<CommitPr> {
+        self.conn()
+            .query_opt(
+                "select sha, pr, title, author from commit_pr where sha = $1",
+                &[&sha],
+            )
+            .await
+            .unwrap()
+            .map(|r| CommitPr {
+                sha: r.get(0),
+                pr: r.get::<_, i32>(1) as u32,
+                title: r.get(2),
+                author: r.get(3),
+            })
+    }
+    async fn record_commit_pr(&self, pr: &CommitPr) {
+        self.conn()
+            .execute(
+                "insert into commit_pr (sha, pr, title, author) values ($1, $2, $3, $4)
+                on conflict (sha) do update set pr = excluded.pr, title = excluded.title,
+                    author = excluded.author",
+                &[&pr.sha, &(pr.pr as i32), &pr.title, &pr.author],
+            )
+            .await
+            .unwrap();
+    }
+    async fn triage(&self, sha: &str) -> Option<Triage> {
+        self.conn()
+            .query_opt(
+                "select sha, acknowledged_by, assignee, issue, posted from triage where sha = $1",
+                &[&sha],
+            )
+            .await
+            .unwrap()
+            .map(|r| Triage {
+                sha: r.get(0),
+                acknowledged_by: r.get(1),
+                assignee: r.get(2),
+                issue: r.get(3),
+                posted: r.get(4),
+            })
+    }
+    async fn record_triage(&self, triage: &Triage) {
+        self.conn()
+            .execute(
+                "insert into triage (sha, acknowledged_by, assignee, issue, posted)
+                values ($1, $2, $3, $4, $5)
+                on conflict (sha) do update set acknowledged_by = excluded.acknowledged_by,
+                    assignee = excluded.assignee, issue = excluded.issue, posted = excluded.posted",
+                &[
+                    &triage.sha,
+                    &triage.acknowledged_by,
+                    &triage.assignee,
+                    &triage.issue,
+                    &triage.posted,
+                ],
+            )
+            .await
+            .unwrap();
+    }
+    async fn duplicate_uploads(&self) -> Vec<(String, String)> {
+        self.conn()
+            .query(
+                "select artifact, policy from duplicate_upload order by artifact",
+                &[],
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.get(0), r.get(1)))
+            .collect()
+    }
     async fn record_raw_self_profile(
         &self,
         collection: CollectionId,
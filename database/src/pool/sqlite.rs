@@ -1,6 +1,6 @@
 use crate::pool::{Connection, ConnectionManager, ManagedConnection, Transaction};
-use crate::{ArtifactId, CollectionId, Commit, Crate, Date, Profile};
-use crate::{ArtifactIdNumber, Index, QueryDatum, QueuedCommit};
+use crate::{ArtifactId, CollectionId, Commit, CommitPr, Crate, Date, Profile, Triage};
+use crate::{ArtifactIdNumber, Index, JobKind, Machine, QueryDatum, QueuedCommit, QueuedJob};
 use chrono::{DateTime, TimeZone, Utc};
 use hashbrown::HashMap;
 use rusqlite::params;
@@ -162,6 +162,90 @@ static MIGRATIONS: &[&str] = &[
         PRIMARY KEY(aid, cid, crate)
     );
     "#,
+    r#"
+    create table benchmark_job(
+        id integer primary key not null,
+        artifact text not null unique,
+        kind text not null,
+        priority integer not null,
+        include text,
+        exclude text,
+        runs integer,
+        requested integer not null,
+        claimed integer,
+        complete boolean not null default 0
+    );
+    "#,
+    r#"
+    alter table benchmark_job add column claimed_by text;
+    create table artifact_machine(
+        aid integer not null references artifact(id) on delete cascade on update cascade,
+        collector text not null,
+        fingerprint text not null,
+        UNIQUE(aid, collector, fingerprint)
+    );
+    "#,
+    r#"
+    create table artifact_tuning(
+        aid integer not null references artifact(id) on delete cascade on update cascade,
+        collector text not null,
+        tuning text not null,
+        UNIQUE(aid, collector)
+    );
+    "#,
+    r#"
+    create table benchmark_alias(
+        alias text primary key not null,
+        name text not null references benchmark(name) on delete cascade on update cascade
+    );
+    "#,
+    r#"
+    create table commit_pr(
+        sha text primary key not null,
+        pr integer not null,
+        title text not null,
+        author text not null
+    );
+    "#,
+    r#"
+    create table duplicate_upload(
+        artifact text primary key not null,
+        -- How the duplicate was dealt with: error, keep-newest or merge.
+        policy text not null
+    );
+    "#,
+    r#"
+    create table triage(
+        sha text primary key not null,
+        acknowledged_by text,
+        assignee text,
+        issue text,
+        posted boolean not null default false
+    );
+    "#,
+    r#"
+    create table benchmark_job_new(
+        id integer primary key not null,
+        artifact text not null,
+        kind text not null,
+        priority integer not null,
+        include text,
+        exclude text,
+        runs integer,
+        requested integer not null,
+        claimed integer,
+        complete boolean not null default 0,
+        claimed_by text,
+        cancelled boolean not null default 0
+    );
+    insert into benchmark_job_new
+        (id, artifact, kind, priority, include, exclude, runs, requested, claimed, complete, claimed_by)
+        select id, artifact, kind, priority, include, exclude, runs, requested, claimed, complete, claimed_by
+        from benchmark_job;
+    drop table benchmark_job;
+    alter table benchmark_job_new rename to benchmark_job;
+    create unique index benchmark_job_queued_artifact on benchmark_job(artifact) where complete = 0;
+    "#,
 ];
 
 #[async_trait::async_trait]
@@ -172,6 +256,8 @@ impl ConnectionManager for Sqlite {
         conn.pragma_update(None, "cache_size", &-128000).unwrap();
         conn.pragma_update(None, "journal_mode", &"WAL").unwrap();
         conn.pragma_update(None, "foreign_keys", &"ON").unwrap();
+        // Several collector jobs may write to the same database at once.
+        conn.busy_timeout(Duration::from_secs(60)).unwrap();
 
         self.1.call_once(|| {
             let version: i32 = conn
@@ -298,6 +384,7 @@ impl Connection for SqliteConnection {
                             "opt" => Profile::Opt,
                             "debug" => Profile::Debug,
                             "doc" => Profile::Doc,
+                            "doc-json" => Profile::DocJson,
                             o => unreachable!("{}: not a profile", o),
                         },
                         row.get::<_, String>(3)?.as_str().parse().unwrap(),
@@ -321,7 +408,7 @@ impl Connection for SqliteConnection {
             .unwrap()
             .map(|r| r.unwrap())
             .collect();
-        Index {
+        let mut index = Index {
             commits,
             artifacts,
             errors,
@@ -339,6 +426,7 @@ impl Connection for SqliteConnection {
                                 "opt" => Profile::Opt,
                                 "debug" => Profile::Debug,
                                 "doc" => Profile::Doc,
+                                "doc-json" => Profile::DocJson,
                                 o => unreachable!("{}: not a profile", o),
                             },
                             row.get::<_, String>(3)?.as_str().parse().unwrap(),
@@ -350,7 +438,11 @@ impl Connection for SqliteConnection {
                 .map(|r| r.unwrap())
                 .collect(),
             queries,
-        }
+            aliases: self.benchmark_aliases().await,
+            ..Index::default()
+        };
+        index.sort_commits();
+        index
     }
 
     async fn get_pstats(
@@ -385,6 +477,20 @@ impl Connection for SqliteConnection {
             })
             .collect()
     }
+    async fn all_pstats(&self) -> Vec<(u32, ArtifactIdNumber, f64)> {
+        self.raw_ref()
+            .prepare_cached(
+                "select series, aid, min(value) from pstat
+                    group by series, aid order by series, aid",
+            )
+            .unwrap()
+            .query_map(params![], |row| {
+                Ok((row.get(0)?, ArtifactIdNumber(row.get(1)?), row.get(2)?))
+            })
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
     async fn get_self_profile_query(
         &self,
         series: u32,
@@ -461,6 +567,24 @@ impl Connection for SqliteConnection {
             .collect::<Result<_, _>>()
             .unwrap()
     }
+    async fn get_errors_of(&self, krate: &str) -> HashMap<crate::ArtifactIdNumber, String> {
+        self.raw_ref()
+            .prepare_cached(
+                "select aid, error from error
+                    join error_series on error.series = error_series.id
+                    where crate = ?",
+            )
+            .unwrap()
+            .query_map(params![krate], |row| {
+                Ok((
+                    crate::ArtifactIdNumber(row.get::<_, i32>(0)? as u32),
+                    row.get(1)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
     async fn queue_pr(
         &self,
         pr: u32,
@@ -541,6 +665,166 @@ impl Connection for SqliteConnection {
             .optional()
             .unwrap()
     }
+    async fn enqueue_job(
+        &self,
+        artifact: &str,
+        kind: JobKind,
+        include: Option<&str>,
+        exclude: Option<&str>,
+        runs: Option<i32>,
+    ) {
+        self.raw_ref()
+            .prepare_cached(
+                "insert or ignore into benchmark_job (artifact, kind, priority, include, exclude, runs, requested)
+                select ?1, ?2, ?3, ?4, ?5, ?6, strftime('%s','now')
+                where not exists (select 1 from benchmark_job where artifact = ?1 and cancelled)",
+            )
+            .unwrap()
+            .execute(params![
+                artifact,
+                kind.to_string(),
+                kind.priority(),
+                include,
+                exclude,
+                &runs
+            ])
+            .unwrap();
+    }
+    async fn claim_job(&self, collector: &str) -> Option<QueuedJob> {
+        let mut conn = self.raw_ref();
+        // Take the write lock up front, so that another collector sharing the
+        // database can't claim the same job between the select and the update.
+        let tx = conn
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .unwrap();
+        let (id, job) = tx
+            .query_row(
+                "select id, artifact, kind, priority, include, exclude, runs from benchmark_job
+                where complete = 0 and (claimed is null or claimed_by = ?)
+                order by claimed is null, priority, id
+                limit 1",
+                params![collector],
+                |row| {
+                    Ok((
+                        row.get::<_, i32>(0)?,
+                        QueuedJob {
+                            artifact: row.get(1)?,
+                            kind: row.get::<_, String>(2)?.parse().unwrap(),
+                            priority: row.get(3)?,
+                            include: row.get(4)?,
+                            exclude: row.get(5)?,
+                            runs: row.get(6)?,
+                            claimed_by: Some(collector.to_owned()),
+                        },
+                    ))
+                },
+            )
+            .optional()
+            .unwrap()?;
+        tx.execute(
+            "update benchmark_job set
+                claimed = coalesce(claimed, strftime('%s','now')),
+                claimed_by = ?
+            where id = ?",
+            params![collector, id],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        Some(job)
+    }
+    async fn complete_job(&self, artifact: &str) {
+        self.raw_ref()
+            .execute(
+                "update benchmark_job set complete = 1 where artifact = ? and complete = 0",
+                params![artifact],
+            )
+            .unwrap();
+    }
+    async fn queued_jobs(&self) -> Vec<QueuedJob> {
+        self.raw_ref()
+            .prepare_cached(
+                "select artifact, kind, priority, include, exclude, runs, claimed_by from benchmark_job
+                where complete = 0
+                order by claimed is null, priority, id",
+            )
+            .unwrap()
+            .query_map(params![], |row| {
+                Ok(QueuedJob {
+                    artifact: row.get(0)?,
+                    kind: row.get::<_, String>(1)?.parse().unwrap(),
+                    priority: row.get(2)?,
+                    include: row.get(3)?,
+                    exclude: row.get(4)?,
+                    runs: row.get(5)?,
+                    claimed_by: row.get(6)?,
+                })
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+    async fn set_job_priority(&self, artifact: &str, priority: i32) -> bool {
+        self.raw_ref()
+            .execute(
+                "update benchmark_job set priority = ? where artifact = ? and complete = 0",
+                params![priority, artifact],
+            )
+            .unwrap()
+            > 0
+    }
+    async fn cancel_job(&self, artifact: &str) -> bool {
+        self.raw_ref()
+            .execute(
+                "update benchmark_job set complete = 1, cancelled = 1
+                where artifact = ? and complete = 0",
+                params![artifact],
+            )
+            .unwrap()
+            > 0
+    }
+    async fn retry_job(&self, artifact: &str) -> bool {
+        self.raw_ref()
+            .execute(
+                "update benchmark_job set claimed = null, claimed_by = null, complete = 0, cancelled = 0
+                where id = (select max(id) from benchmark_job where artifact = ?)",
+                params![artifact],
+            )
+            .unwrap()
+            > 0
+    }
+    async fn record_machine(&self, aid: ArtifactIdNumber, machine: &Machine) {
+        self.raw_ref()
+            .execute(
+                "insert or ignore into artifact_machine (aid, collector, fingerprint) VALUES (?, ?, ?)",
+                params![&aid.0, &machine.name, &machine.fingerprint],
+            )
+            .unwrap();
+    }
+    async fn record_tuning(&self, aid: ArtifactIdNumber, collector: &str, tuning: &str) {
+        self.raw_ref()
+            .execute(
+                "insert or replace into artifact_tuning (aid, collector, tuning) VALUES (?, ?, ?)",
+                params![&aid.0, &collector, &tuning],
+            )
+            .unwrap();
+    }
+    async fn machines_of(&self, aid: ArtifactIdNumber) -> Vec<Machine> {
+        self.raw_ref()
+            .prepare_cached(
+                "select collector, fingerprint from artifact_machine where aid = ?
+                order by collector, fingerprint",
+            )
+            .unwrap()
+            .query_map(params![&aid.0], |row| {
+                Ok(Machine {
+                    name: row.get(0)?,
+                    fingerprint: row.get(1)?,
+                })
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
     async fn collection_id(&self, version: &str) -> CollectionId {
         let raw = self.raw_ref();
         raw.execute(
@@ -641,6 +925,30 @@ impl Connection for SqliteConnection {
         )
     }
 
+    async fn purge_artifact(&self, artifact: &str) {
+        self.raw_ref()
+            .execute("delete from artifact where name = ?", params![artifact])
+            .unwrap();
+    }
+
+    async fn rename_benchmark(&self, from: &str, to: &str) {
+        let conn = self.raw_ref();
+        let args: [&dyn rusqlite::ToSql; 2] = [&from, &to];
+        for (sql, n) in crate::pool::rename_benchmark_statements(|i| format!("?{}", i), false) {
+            conn.execute(&sql, &args[..n]).unwrap();
+        }
+    }
+
+    async fn benchmark_aliases(&self) -> HashMap<String, String> {
+        self.raw_ref()
+            .prepare_cached("select alias, name from benchmark_alias")
+            .unwrap()
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
     async fn record_self_profile_query(
         &self,
         collection: CollectionId,
@@ -887,6 +1195,73 @@ impl Connection for SqliteConnection {
             .optional()
             .unwrap()
     }
+    async fn commit_pr(&self, sha: &str) -> Option<CommitPr> {
+        self.raw_ref()
+            .query_row(
+                "select sha, pr, title, author from commit_pr where sha = ?",
+                params![sha],
+                |row| {
+                    Ok(CommitPr {
+                        sha: row.get(0)?,
+                        pr: row.get(1)?,
+                        title: row.get(2)?,
+                        author: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .unwrap()
+    }
+    async fn record_commit_pr(&self, pr: &CommitPr) {
+        self.raw_ref()
+            .execute(
+                "insert or replace into commit_pr (sha, pr, title, author) values (?, ?, ?, ?)",
+                params![pr.sha, pr.pr, pr.title, pr.author],
+            )
+            .unwrap();
+    }
+    async fn triage(&self, sha: &str) -> Option<Triage> {
+        self.raw_ref()
+            .query_row(
+                "select sha, acknowledged_by, assignee, issue, posted from triage where sha = ?",
+                params![sha],
+                |row| {
+                    Ok(Triage {
+                        sha: row.get(0)?,
+                        acknowledged_by: row.get(1)?,
+                        assignee: row.get(2)?,
+                        issue: row.get(3)?,
+                        posted: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+            .unwrap()
+    }
+    async fn record_triage(&self, triage: &Triage) {
+        self.raw_ref()
+            .execute(
+                "insert or replace into triage (sha, acknowledged_by, assignee, issue, posted)
+                values (?, ?, ?, ?, ?)",
+                params![
+                    triage.sha,
+                    triage.acknowledged_by,
+                    triage.assignee,
+                    triage.issue,
+                    triage.posted
+                ],
+            )
+            .unwrap();
+    }
+    async fn duplicate_uploads(&self) -> Vec<(String, String)> {
+        self.raw_ref()
+            .prepare_cached("select artifact, policy from duplicate_upload order by artifact")
+            .unwrap()
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
     async fn record_raw_self_profile(
         &self,
         _collection: CollectionId,
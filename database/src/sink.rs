@@ -0,0 +1,223 @@
+//! Places other than the database to mirror results to as they're ingested,
+//! e.g. an external benchmarking service or a second perf instance, for
+//! redundancy. `ingest-json` sends each file's results to every sink at once
+//! when the file is ingested. A sink which fails (or doesn't answer within
+//! `TIMEOUT`) is logged and doesn't stop ingestion; the batch is kept in a
+//! `Spool` and sent again on the next run.
+//!
+//! Results are flattened into a `Batch` up front, since ingesting consumes
+//! them. Self-profile queries aren't sent.
+
+use crate::json::Results;
+use crate::Date;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a sink has to accept a batch.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One statistic of one benchmark.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Point {
+    #[serde(rename = "crate")]
+    pub krate: String,
+    pub profile: String,
+    pub cache: String,
+    pub statistic: String,
+    pub value: f64,
+}
+
+/// A file's results: a commit's (with its date) or an artifact's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Batch {
+    pub artifact: String,
+    pub date: Option<Date>,
+    pub points: Vec<Point>,
+}
+
+impl Batch {
+    pub fn new(res: &Results) -> Batch {
+        let (artifact, date, benchmarks) = match res {
+            Results::Commit(cd) => (cd.commit.sha.clone(), Some(cd.commit.date), &cd.benchmarks),
+            Results::Artifact(ad) => (ad.id.clone(), None, &ad.benchmarks),
+        };
+        let mut points = Vec::new();
+        for (krate, benchmark) in benchmarks {
+            let benchmark = match benchmark {
+                Ok(b) => b,
+                Err(_) => continue,
+            };
+            for run in &benchmark.runs {
+                let profile = run.profile().to_string();
+                let cache = run.cache().to_string();
+                for (stat, value) in run.stats.iter() {
+                    points.push(Point {
+                        krate: krate.as_str().to_string(),
+                        profile: profile.clone(),
+                        cache: cache.clone(),
+                        statistic: stat.to_string(),
+                        value,
+                    });
+                }
+            }
+        }
+        points.sort_by(|a, b| {
+            (&a.krate, &a.profile, &a.cache, &a.statistic).cmp(&(
+                &b.krate,
+                &b.profile,
+                &b.cache,
+                &b.statistic,
+            ))
+        });
+        Batch {
+            artifact,
+            date,
+            points,
+        }
+    }
+
+    /// The batch in Bencher Metric Format: an object keyed by benchmark
+    /// (`crate-profile-cache`), each an object keyed by statistic (the
+    /// "measure"), each `{"value": _}`.
+    pub fn to_bmf(&self) -> serde_json::Value {
+        let mut benchmarks = BTreeMap::new();
+        for p in &self.points {
+            benchmarks
+                .entry(format!("{}-{}-{}", p.krate, p.profile, p.cache))
+                .or_insert_with(serde_json::Map::new)
+                .insert(p.statistic.clone(), serde_json::json!({ "value": p.value }));
+        }
+        serde_json::to_value(benchmarks).unwrap()
+    }
+}
+
+#[async_trait]
+pub trait ResultSink: Send + Sync {
+    /// For logging.
+    fn name(&self) -> String;
+
+    async fn send(&self, batch: &Batch) -> anyhow::Result<()>;
+}
+
+/// How `Http` encodes a batch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// The `Batch` itself, as JSON.
+    Perf,
+    /// Bencher Metric Format (see `Batch::to_bmf`).
+    Bmf,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "perf" => Format::Perf,
+            "bmf" => Format::Bmf,
+            _ => return Err(format!("{:?} is not a sink format", s)),
+        })
+    }
+}
+
+/// POSTs each batch as JSON to a URL, with the token (if any) as a bearer
+/// token.
+pub struct Http {
+    pub url: String,
+    pub token: Option<String>,
+    pub format: Format,
+    /// Shared by all the sinks; see `client`.
+    pub client: reqwest::Client,
+}
+
+/// A client for `Http` sinks, which gives up after `TIMEOUT`.
+pub fn client() -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().timeout(TIMEOUT).build()?)
+}
+
+#[async_trait]
+impl ResultSink for Http {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn send(&self, batch: &Batch) -> anyhow::Result<()> {
+        let body = match self.format {
+            Format::Perf => serde_json::to_value(batch)?,
+            Format::Bmf => batch.to_bmf(),
+        };
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "{} responded {}: {}",
+                self.url,
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The batches which a sink failed to accept, kept as files in a directory
+/// per sink, so that they can be sent again later.
+pub struct Spool {
+    pub dir: PathBuf,
+}
+
+impl Spool {
+    fn sink_dir(&self, sink: &dyn ResultSink) -> PathBuf {
+        let name = sink
+            .name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        self.dir.join(name)
+    }
+
+    /// Keeps `batch` to be sent to `sink` later.
+    pub fn save(&self, sink: &dyn ResultSink, batch: &Batch) -> anyhow::Result<()> {
+        let dir = self.sink_dir(sink);
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {:?}", dir))?;
+        let path = dir.join(format!("{}.json", batch.artifact.replace('/', "_")));
+        std::fs::write(&path, serde_json::to_vec(batch)?)
+            .with_context(|| format!("writing {:?}", path))
+    }
+
+    /// Sends the batches kept for `sink` again, oldest first, forgetting those
+    /// it accepts. Stops at the first failure, which is returned.
+    pub async fn retry(&self, sink: &dyn ResultSink) -> anyhow::Result<usize> {
+        let dir = self.sink_dir(sink);
+        let mut paths = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .map(|e| {
+                    let e = e?;
+                    Ok((e.metadata()?.modified()?, e.path()))
+                })
+                .collect::<std::io::Result<Vec<_>>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e).with_context(|| format!("listing {:?}", dir)),
+        };
+        paths.sort();
+        let mut sent = 0;
+        for (_, path) in paths {
+            let batch: Batch = serde_json::from_slice(&std::fs::read(&path)?)
+                .with_context(|| format!("reading {:?}", path))?;
+            sink.send(&batch).await?;
+            std::fs::remove_file(&path)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
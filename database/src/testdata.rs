@@ -0,0 +1,160 @@
+//! Synthetic results, for running the site locally without access to real
+//! data, and as the fixture of the site's tests.
+//!
+//! `generate` records `commits` daily master commits, ending on 2020-06-30,
+//! with results for a handful of benchmarks in every profile and cache state.
+//! Each value varies randomly by up to `noise` percent. `regressions` step
+//! changes of 5-25% are injected into random benchmarks at random commits. The
+//! output only depends on the parameters, so the same parameters always
+//! produce the same data.
+
+use crate::{ArtifactId, Cache, Commit, Date, PatchName, Pool, Profile};
+use chrono::{Duration, TimeZone, Utc};
+
+pub const BENCHMARKS: &[&str] = &[
+    "helloworld",
+    "regex",
+    "syn",
+    "serde",
+    "style-servo",
+    "deep-vector",
+];
+
+const PROFILES: &[Profile] = &[Profile::Check, Profile::Debug, Profile::Opt];
+
+/// Each statistic, with the typical magnitude of its values.
+const STATS: &[(&str, f64)] = &[
+    ("instructions:u", 5e9),
+    ("cycles:u", 3e9),
+    ("wall-time", 2.0),
+    ("max-rss", 3e5),
+];
+
+/// A small deterministic xorshift generator; the data needn't be random in any
+/// strong sense, only reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A float in [0, 1).
+    fn float(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Params {
+    pub commits: usize,
+    /// In percent.
+    pub noise: f64,
+    pub regressions: usize,
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            commits: 50,
+            noise: 1.0,
+            regressions: 3,
+        }
+    }
+}
+
+/// The date of the last generated commit.
+pub fn last_date() -> Date {
+    Date(Utc.ymd(2020, 6, 30).and_hms(12, 0, 0))
+}
+
+/// Fills the database `db` with synthetic results. Returns a description of
+/// each injected regression.
+pub async fn generate(db: &str, params: Params) -> Vec<String> {
+    let Params {
+        commits,
+        noise,
+        regressions,
+    } = params;
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let caches = vec![
+        Cache::Empty,
+        Cache::IncrementalEmpty,
+        Cache::IncrementalFresh,
+        Cache::IncrementalPatch(PatchName::from("println")),
+    ];
+
+    // The baseline of each benchmark, relative to the typical magnitudes.
+    let mut scale = BENCHMARKS
+        .iter()
+        .map(|_| 0.1 + rng.float() * 2.0)
+        .collect::<Vec<_>>();
+    // (commit index, benchmark index, factor)
+    let mut steps = (0..regressions)
+        .map(|_| {
+            (
+                1 + rng.next() as usize % commits.max(2).saturating_sub(1),
+                rng.next() as usize % BENCHMARKS.len(),
+                1.05 + rng.float() * 0.2,
+            )
+        })
+        .collect::<Vec<_>>();
+    steps.sort_by_key(|s| s.0);
+
+    let pool = Pool::open(db);
+    let conn = pool.connection().await;
+    for krate in BENCHMARKS {
+        conn.record_benchmark(krate, Some(true)).await;
+    }
+
+    let end = last_date().0;
+    let mut injected = Vec::new();
+    for i in 0..commits {
+        let commit = Commit {
+            sha: format!("{:040x}", 0xc0ffee_u64 * (i as u64 + 1)),
+            date: Date(end - Duration::days((commits - 1 - i) as i64)),
+        };
+        for &(at, b, factor) in &steps {
+            if at == i {
+                scale[b] *= factor;
+                injected.push(format!(
+                    "{} regressed by {:.1}% at {}",
+                    BENCHMARKS[b],
+                    (factor - 1.0) * 100.0,
+                    commit.sha
+                ));
+            }
+        }
+
+        let cid = conn.collection_id(&format!("gen-testdata-{}", i)).await;
+        let aid = conn.artifact_id(&ArtifactId::Commit(commit)).await;
+        for (b, krate) in BENCHMARKS.iter().enumerate() {
+            for (p, &profile) in PROFILES.iter().enumerate() {
+                for (c, &cache) in caches.iter().enumerate() {
+                    for &(stat, magnitude) in STATS {
+                        // Optimized and clean builds take longer.
+                        let base = magnitude
+                            * scale[b]
+                            * (1.0 + p as f64 * 0.5)
+                            * if c == 0 || c == 1 { 1.0 } else { 0.2 };
+                        let jitter = 1.0 + (rng.float() * 2.0 - 1.0) * noise / 100.0;
+                        let value = base * jitter;
+                        let value = if stat == "wall-time" {
+                            value
+                        } else {
+                            value.round()
+                        };
+                        conn.record_statistic(cid, aid, krate, profile, cache, stat, value)
+                            .await;
+                    }
+                }
+            }
+        }
+        conn.record_duration(aid, std::time::Duration::from_secs(3600))
+            .await;
+    }
+    injected
+}
@@ -0,0 +1,95 @@
+//! Timing spans, for finding out where the time goes.
+//!
+//! A `Span` measures from its creation until it is dropped. Spans cost nothing
+//! until tracing is enabled (the binaries do so with `--trace`); after that,
+//! each span's duration is logged at trace level and added to the totals for
+//! its name, which `report` formats as a table.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static::lazy_static! {
+    static ref TOTALS: Mutex<HashMap<Cow<'static, str>, Totals>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Totals {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+#[must_use = "a span measures until it is dropped"]
+pub struct Span {
+    name: Cow<'static, str>,
+    start: Option<Instant>,
+}
+
+/// Starts a span. Names should come from a small, fixed set, since the totals
+/// of every name are kept.
+pub fn span(name: impl Into<Cow<'static, str>>) -> Span {
+    if !is_enabled() {
+        return Span {
+            name: Cow::Borrowed(""),
+            start: None,
+        };
+    }
+    Span {
+        name: name.into(),
+        start: Some(Instant::now()),
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        let elapsed = start.elapsed();
+        log::trace!("{}: {:?}", self.name, elapsed);
+        let name = std::mem::replace(&mut self.name, Cow::Borrowed(""));
+        let mut totals = TOTALS.lock().unwrap();
+        let totals = totals.entry(name).or_default();
+        totals.count += 1;
+        totals.total += elapsed;
+        totals.max = totals.max.max(elapsed);
+    }
+}
+
+/// The totals of each span so far, most total time first.
+pub fn report() -> String {
+    let totals = TOTALS.lock().unwrap();
+    let mut rows = totals.iter().collect::<Vec<_>>();
+    rows.sort_by_key(|(_, t)| std::cmp::Reverse(t.total));
+
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let mut report = format!(
+        "{:<40} {:>8} {:>12} {:>10} {:>10}\n",
+        "span", "count", "total ms", "mean ms", "max ms"
+    );
+    for (name, t) in rows {
+        report.push_str(&format!(
+            "{:<40} {:>8} {:>12.1} {:>10.2} {:>10.2}\n",
+            name,
+            t.count,
+            ms(t.total),
+            ms(t.total / t.count),
+            ms(t.max)
+        ));
+    }
+    report
+}
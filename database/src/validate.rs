@@ -0,0 +1,89 @@
+//! Checks of results before they're recorded, so that bad data is rejected
+//! rather than skewing graphs and summaries.
+//!
+//! Each check returns a description of the problem, if any.
+
+use crate::Commit;
+use chrono::{Duration, NaiveDate, Utc};
+use hashbrown::HashSet;
+use std::path::Path;
+
+/// Master commits older than Rust 1.0 were never benchmarked, so such a date
+/// is a mistake.
+fn rust_1_0() -> NaiveDate {
+    NaiveDate::from_ymd(2015, 5, 15)
+}
+
+/// The name of a results file: UTF-8, not hidden (nor an editor's backup) and
+/// ending in `.json` or `.json.sz`. Anything else in a results directory isn't
+/// worth trying to parse.
+pub fn file_name(path: &Path) -> Result<(), String> {
+    let name = match path.file_name().map(|n| n.to_str()) {
+        Some(Some(name)) => name,
+        Some(None) => return Err(format!("{:?} is not UTF-8", path)),
+        None => return Err(format!("{:?} has no file name", path)),
+    };
+    if name.starts_with('.') || name.ends_with('~') {
+        return Err(format!("{:?} is hidden or a backup", path));
+    }
+    if !(name.ends_with(".json") || name.ends_with(".json.sz")) {
+        return Err(format!("{:?} is not a .json or .json.sz file", path));
+    }
+    Ok(())
+}
+
+/// A full, lowercase sha.
+pub fn sha(sha: &str) -> Result<(), String> {
+    let is_hex = |b: u8| matches!(b, b'0'..=b'9' | b'a'..=b'f');
+    if sha.len() == 40 && sha.bytes().all(is_hex) {
+        Ok(())
+    } else {
+        Err(format!("{:?} is not a full sha", sha))
+    }
+}
+
+/// A date which isn't in the future (allowing for clock skew) and, for master
+/// commits, isn't before Rust 1.0.
+pub fn date(commit: &Commit) -> Result<(), String> {
+    if commit.date.0 > Utc::now() + Duration::days(1) {
+        return Err(format!(
+            "{} is dated {}, in the future",
+            commit.sha, commit.date
+        ));
+    }
+    if !commit.is_try() && commit.date.0.naive_utc().date() < rust_1_0() {
+        return Err(format!(
+            "{} is dated {}, before Rust 1.0",
+            commit.sha, commit.date
+        ));
+    }
+    Ok(())
+}
+
+pub fn commit(commit: &Commit) -> Result<(), String> {
+    sha(&commit.sha)?;
+    date(commit)
+}
+
+/// A finite, non-negative statistic.
+pub fn statistic(stat: &str, value: f64) -> Result<(), String> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(format!("{} is {}", stat, value))
+    }
+}
+
+/// The self-profile queries of one run, each of which should appear once.
+pub fn queries<'a>(labels: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    let duplicates = labels
+        .into_iter()
+        .filter(|label| !seen.insert(*label))
+        .collect::<Vec<_>>();
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("queries appear more than once: {:?}", duplicates))
+    }
+}
@@ -0,0 +1,13 @@
+//! Feeds arbitrary bytes to the results file parser, and whatever parses to
+//! the validator.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for &is_artifact in &[false, true] {
+        if let Ok(res) = database::json::parse(data, is_artifact) {
+            database::json::validate(&res);
+        }
+    }
+});
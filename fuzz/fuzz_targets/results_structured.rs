@@ -0,0 +1,73 @@
+//! Feeds well-formed but arbitrary commit results to the parser and validator.
+//! Arbitrary bytes rarely get past the JSON syntax, so this mirrors the shape
+//! of a results file, letting the fuzzer mutate the values (lengths, numbers,
+//! states) rather than the punctuation.
+
+#![no_main]
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct CommitData {
+    commit: Commit,
+    benchmarks: BTreeMap<String, Result<Benchmark, String>>,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct Commit {
+    sha: String,
+    /// Seconds since the epoch, which the parser also accepts.
+    date: i64,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct Benchmark {
+    runs: Vec<Run>,
+    name: String,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct Run {
+    stats: Stats,
+    self_profile: Option<SelfProfile>,
+    check: bool,
+    release: bool,
+    state: BenchmarkState,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct Stats {
+    stats: Vec<Option<f64>>,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+enum BenchmarkState {
+    Clean,
+    IncrementalStart,
+    IncrementalClean,
+    IncrementalPatched(Patch),
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct Patch {
+    name: String,
+}
+
+#[derive(Arbitrary, Debug, Serialize)]
+struct SelfProfile {
+    label: Vec<String>,
+    self_time: Vec<u64>,
+    number_of_cache_hits: Vec<u32>,
+    invocation_count: Vec<u32>,
+    blocked_time: Vec<u64>,
+    incremental_load_time: Vec<u64>,
+}
+
+fuzz_target!(|data: CommitData| {
+    let json = serde_json::to_vec(&data).expect("results serialize");
+    if let Ok(res) = database::json::parse(&json, false) {
+        database::json::validate(&res);
+    }
+});
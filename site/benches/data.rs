@@ -0,0 +1,98 @@
+//! Benchmarks of loading the data and answering queries from it, so that
+//! changes which slow the site down are noticed.
+//!
+//! These run against the database named by `BENCH_DB`, which should be large
+//! enough to be representative; generate one with e.g.
+//!
+//! ```text
+//! cargo run --bin gen-testdata --release bench.db 2000
+//! BENCH_DB=bench.db cargo bench -p site
+//! ```
+
+use collector::Bound;
+use criterion::{criterion_group, criterion_main, Criterion};
+use site::api::graph;
+use site::load::{InputData, LoadMode};
+
+fn database() -> String {
+    std::env::var("BENCH_DB").expect("BENCH_DB should name the database to benchmark against")
+}
+
+fn load(c: &mut Criterion) {
+    let db = database();
+    let mut rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    group.bench_function("from_fs", |b| {
+        b.iter(|| {
+            rt.block_on(InputData::from_fs(&db, LoadMode::Lenient))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn queries(c: &mut Criterion) {
+    let db = database();
+    let mut rt = tokio::runtime::Runtime::new().unwrap();
+    let data = rt
+        .block_on(InputData::from_fs(&db, LoadMode::Lenient))
+        .unwrap();
+    // Explicit ends, as open ones are relative to today, which is well past
+    // the end of generated data.
+    let (first, recent, last) = {
+        let index = data.index.load();
+        let commits = index.sorted_commits();
+        let sha =
+            |c: Option<&database::Commit>| c.expect("the database has no commits").sha.clone();
+        (
+            sha(commits.first()),
+            sha(commits.iter().rev().nth(99).or_else(|| commits.first())),
+            sha(commits.last()),
+        )
+    };
+    let all = || Bound::Commit(first.clone())..=Bound::Commit(last.clone());
+
+    c.bench_function("data_range/all", |b| b.iter(|| data.data_range(all())));
+    c.bench_function("data_range/recent", |b| {
+        b.iter(|| data.data_range(Bound::Commit(recent.clone())..=Bound::Commit(last.clone())))
+    });
+
+    let mut group = c.benchmark_group("graph");
+    group.sample_size(10);
+    // The landing page's graphs, over all commits, including the summaries.
+    let landing_page = graph::Request {
+        start: Bound::Commit(first.clone()),
+        end: Bound::Commit(last.clone()),
+        stat: String::from("instructions:u"),
+        absolute: true,
+        resolution: Default::default(),
+    };
+    group.bench_function("landing_page", |b| {
+        b.iter(|| {
+            data.graph_cache.clear();
+            rt.block_on(site::server::handle_graph(landing_page.clone(), &data))
+                .unwrap()
+        })
+    });
+    for (name, start) in vec![("recent", &recent), ("all", &first)] {
+        let request = graph::Request {
+            start: Bound::Commit(start.clone()),
+            end: Bound::Commit(last.clone()),
+            stat: String::from("wall-time"),
+            absolute: false,
+            resolution: Default::default(),
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                data.graph_cache.clear();
+                rt.block_on(site::server::handle_graph(request.clone(), &data))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, load, queries);
+criterion_main!(benches);
@@ -15,7 +15,7 @@
 
 use database::{Crate, Date};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::result::Result as StdResult;
 
@@ -46,17 +46,29 @@ pub struct DateData {
     pub date: Option<Date>,
     pub pr: Option<u32>,
     pub commit: String,
-    pub data: HashMap<String, Vec<(String, f64)>>,
+    #[serde(serialize_with = "crate::util::round::value")]
+    pub data: BTreeMap<String, Vec<(String, f64)>>,
     // crate -> nanoseconds
-    pub bootstrap: HashMap<String, u64>,
+    pub bootstrap: BTreeMap<String, u64>,
 }
 
 pub type ServerResult<T> = StdResult<T, String>;
 
 pub mod info {
+    use collector::manifest::Category;
     use database::Date;
     use serde::Serialize;
 
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Benchmark {
+        pub name: String,
+        pub category: Category,
+        pub weight: f64,
+        /// The names of the patches applied for `IncrPatched` runs, if
+        /// declared in the manifest.
+        pub patches: Vec<String>,
+    }
+
     #[derive(Debug, Clone, PartialEq, Serialize)]
     pub struct Response {
         /// Sorted list of statistic names known
@@ -64,6 +76,82 @@ pub mod info {
 
         /// Chronologically last loaded run date.
         pub as_of: Option<Date>,
+
+        /// The benchmarks described by the manifests in
+        /// `collector/benchmarks`, sorted by name.
+        pub benchmarks: Vec<Benchmark>,
+    }
+}
+
+/// What the benchmarks' manifests say about them.
+pub mod benchmarks {
+    use collector::manifest::Category;
+    use serde::Serialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Benchmark {
+        pub name: String,
+        pub category: Category,
+        pub weight: f64,
+        pub description: Option<String>,
+        pub rationale: Option<String>,
+        pub source: Option<String>,
+        pub noise: Option<String>,
+        pub supports_stable: bool,
+        pub patches: Vec<Patch>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Patch {
+        pub name: String,
+        pub description: Option<String>,
+    }
+
+    /// Sorted by name.
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Response(pub Vec<Benchmark>);
+}
+
+pub mod benchmark_detail {
+    use collector::Bound;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+        /// The crate, e.g. `syn`.
+        pub benchmark: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize)]
+    pub struct Response {
+        /// From the benchmark's manifest, if it's still in the suite.
+        pub benchmark: Option<super::benchmarks::Benchmark>,
+        // (UTC timestamp in seconds, sha)
+        pub commits: Vec<(i64, String)>,
+        /// By statistic, profile and cache.
+        pub stats: BTreeMap<String, BTreeMap<String, BTreeMap<String, Measurement>>>,
+        /// Oldest first.
+        pub failures: Vec<Failure>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize)]
+    pub struct Measurement {
+        /// For each commit; `None` where it wasn't measured.
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub values: Vec<Option<f64>>,
+        /// The standard deviation of the change from one master commit to the
+        /// next, in percent, up to the end of the range.
+        #[serde(serialize_with = "crate::util::round::percent")]
+        pub noise: Option<f64>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize)]
+    pub struct Failure {
+        pub commit: String,
+        pub error: String,
     }
 }
 
@@ -71,9 +159,13 @@ pub mod dashboard {
     use serde::{Deserialize, Serialize};
     #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct Cases {
+        #[serde(serialize_with = "crate::util::round::value")]
         pub clean_averages: Vec<f64>,
+        #[serde(serialize_with = "crate::util::round::value")]
         pub base_incr_averages: Vec<f64>,
+        #[serde(serialize_with = "crate::util::round::value")]
         pub clean_incr_averages: Vec<f64>,
+        #[serde(serialize_with = "crate::util::round::value")]
         pub println_incr_averages: Vec<f64>,
     }
 
@@ -113,7 +205,7 @@ pub mod data {
 pub mod graph {
     use collector::Bound;
     use serde::{Deserialize, Serialize};
-    use std::collections::{HashMap, HashSet};
+    use std::collections::{BTreeMap, BTreeSet};
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     pub struct Request {
@@ -121,22 +213,54 @@ pub mod graph {
         pub end: Bound,
         pub stat: String,
         pub absolute: bool,
+        /// At a coarser resolution than per commit, each point is the median
+        /// of a period, shown at the period's last commit.
+        #[serde(default)]
+        pub resolution: database::Resolution,
     }
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     pub struct GraphData {
         pub commit: u16,
-        pub absolute: f32,
-        pub percent: f32,
-        pub y: f32,
+        /// Left out, like `percent`, when `?values=` asks for `y` alone.
+        #[serde(
+            serialize_with = "crate::util::round::value",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub absolute: Option<f32>,
+        #[serde(
+            serialize_with = "crate::util::round::percent",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub percent: Option<f32>,
+        /// `absolute` or `percent`, as requested.
+        pub y: Y,
         pub x: u64,
         pub is_interpolated: bool,
     }
 
+    /// A point's value, rounded as what it is: the value itself, or the
+    /// change in percent from the first point.
+    #[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Y {
+        Absolute(#[serde(serialize_with = "crate::util::round::value")] f32),
+        Percent(#[serde(serialize_with = "crate::util::round::percent")] f32),
+    }
+
+    impl Y {
+        pub fn get(self) -> f32 {
+            match self {
+                Y::Absolute(y) | Y::Percent(y) => y,
+            }
+        }
+    }
+
     #[derive(Debug, PartialEq, Clone, Serialize)]
     pub struct Response {
-        pub benchmarks: HashMap<String, HashMap<String, Vec<(String, Vec<GraphData>)>>>,
-        pub max: HashMap<String, f32>,
+        pub benchmarks: BTreeMap<String, BTreeMap<String, Vec<(String, Vec<GraphData>)>>>,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub max: BTreeMap<String, f32>,
         pub colors: Vec<String>,
         pub commits: Vec<String>,
     }
@@ -144,23 +268,23 @@ pub mod graph {
     #[derive(Debug, PartialEq, Clone, Serialize)]
     pub struct Series {
         // y-values
-        pub points: Vec<f32>,
+        pub points: Vec<Y>,
         // The index of interpolated coordinates
-        pub is_interpolated: HashSet<u16>,
+        pub is_interpolated: BTreeSet<u16>,
     }
 
     #[derive(Debug, PartialEq, Clone, Serialize)]
     pub struct NewResponse {
         // (UTC timestamp in seconds, sha)
         pub commits: Vec<(i64, String)>,
-        pub benchmarks: HashMap<String, HashMap<database::Profile, HashMap<String, Series>>>,
+        pub benchmarks: BTreeMap<String, BTreeMap<database::Profile, BTreeMap<String, Series>>>,
     }
 }
 
 pub mod bootstrap {
     use collector::Bound;
-    use hashbrown::HashMap;
     use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     pub struct Request {
@@ -173,12 +297,13 @@ pub mod bootstrap {
         // (UTC timestamp, sha)
         pub commits: Vec<(i64, String)>,
         // Optional nanoseconds
-        pub by_crate: HashMap<String, Vec<Option<u64>>>,
+        pub by_crate: BTreeMap<String, Vec<Option<u64>>>,
     }
 }
 
 pub mod days {
     use crate::api::DateData;
+    use collector::manifest::Category;
     use collector::Bound;
     use serde::{Deserialize, Serialize};
 
@@ -204,6 +329,109 @@ pub mod days {
         /// If `a` and `b` are adjacent artifacts (i.e., `a` is the parent of
         /// `b`).
         pub is_contiguous: bool,
+
+        /// Annotated Cachegrind diffs from `a` to `b`, one per benchmark,
+        /// which can be fetched from `/perf/artifacts/<name>`.
+        pub cachegrind_diffs: Vec<String>,
+
+        /// Chrome `trace_event` profiles of `b`, one per benchmark, which can
+        /// be fetched from `/perf/artifacts/<name>`.
+        pub chrome_traces: Vec<String>,
+
+        /// The measurements (`crate-profile cache`) which `b` has and `a`
+        /// doesn't, so can't be compared; sorted.
+        pub missing_in_a: Vec<String>,
+        /// The measurements which `a` has and `b` doesn't; sorted.
+        pub missing_in_b: Vec<String>,
+
+        /// The measurements which both have, with what the comparison page
+        /// sorts and filters by; sorted by benchmark and then cache.
+        pub rows: Vec<Row>,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Row {
+        /// `crate-profile`, as in `DateData::data`.
+        pub benchmark: String,
+        pub cache: String,
+        /// From the benchmark's manifest, if it has one.
+        pub category: Option<Category>,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub a: f64,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub b: f64,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub absolute_change: f64,
+        #[serde(serialize_with = "crate::util::round::percent")]
+        pub percent_change: f64,
+        /// The standard deviation of the measurement's percent change from
+        /// one master commit to the next, over the latest of them up to `a`;
+        /// `None` without enough history.
+        #[serde(serialize_with = "crate::util::round::percent")]
+        pub noise: Option<f64>,
+        /// Whether the change is well outside the noise; `None` without
+        /// enough history to tell.
+        pub significant: Option<bool>,
+    }
+}
+
+pub mod artifacts {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The names of the profiler output files archived for the commit,
+        /// which can be fetched from `/perf/artifacts/<name>`.
+        pub artifacts: Vec<String>,
+    }
+}
+
+pub mod commit_info {
+    use database::CommitPr;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub sha: String,
+        /// The pull request the commit merged, if known.
+        pub pr: Option<CommitPr>,
+    }
+}
+
+pub mod search {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// Sorted, and at most as many as the limit.
+        pub results: Vec<String>,
+        /// Whether more matched than the limit.
+        pub truncated: bool,
+    }
+}
+
+pub mod nearest {
+    use database::Commit;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The benchmarked commit closest to the date, or the one whose sha
+        /// starts with the prefix.
+        pub commit: Commit,
+        /// Whether the date or sha given was the commit's own.
+        pub exact: bool,
+    }
+}
+
+pub mod unbenchmarked {
+    use database::Commit;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        /// The merges into master which have no results, oldest first.
+        pub commits: Vec<Commit>,
     }
 }
 
@@ -212,6 +440,7 @@ pub mod status {
     use database::ArtifactId;
     use database::Commit;
     use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
 
     #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
     pub struct BenchmarkStatus {
@@ -244,6 +473,15 @@ pub mod status {
         pub current: Option<CurrentState>,
         // None if no recent end, otherwise seconds since epoch
         pub most_recent_end: Option<i64>,
+        /// Approximate bytes of memory used by each of the major structures
+        /// holding the data, and by the process as a whole.
+        pub memory: BTreeMap<String, usize>,
+        /// Problems with the config or benchmark manifests which were skipped
+        /// over when loading, each with where it was found.
+        pub load_problems: Vec<String>,
+        /// Artifacts whose results were uploaded more than once, and how the
+        /// last duplicate was dealt with (`error`, `keep-newest` or `merge`).
+        pub duplicates: Vec<(String, String)>,
     }
 }
 
@@ -286,6 +524,11 @@ pub mod self_profile {
     pub struct Response {
         pub base_profile: Option<SelfProfile>,
         pub profile: SelfProfile,
+        /// With a base profile, the queries which only `profile` has, and so
+        /// have nothing to be compared with.
+        pub added_queries: Vec<QueryLabel>,
+        /// With a base profile, the queries which only it has.
+        pub removed_queries: Vec<QueryLabel>,
     }
 
     #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -298,6 +541,7 @@ pub mod self_profile {
     pub struct QueryData {
         pub label: QueryLabel,
         pub self_time: Duration,
+        #[serde(serialize_with = "crate::util::round::percent")]
         pub percent_total_time: f32,
         pub number_of_cache_misses: u32,
         pub number_of_cache_hits: u32,
@@ -307,6 +551,179 @@ pub mod self_profile {
     }
 }
 
+/// How the self-profile queries of one benchmark changed between two commits.
+pub mod self_profile_diff {
+    use database::QueryLabel;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub base_commit: String,
+        pub commit: String,
+        /// `crate-profile`, as for self-profiles.
+        pub benchmark: String,
+        pub run_name: String,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Response {
+        /// The change in the sum of all queries' self time, in seconds.
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub total_self_time_delta: f64,
+        /// Largest contribution to the total change (either way) first.
+        pub queries: Vec<QueryDelta>,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct QueryDelta {
+        pub label: QueryLabel,
+        /// In seconds; zero where a query is only in the other profile.
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub base_self_time: f64,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub self_time: f64,
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub self_time_delta: f64,
+        /// The share of the total change in self time, in percent; negative
+        /// for a query which moved against it.
+        #[serde(serialize_with = "crate::util::round::percent")]
+        pub contribution: f64,
+        pub invocation_count_delta: i64,
+        pub number_of_cache_hits_delta: i64,
+    }
+}
+
+/// Benchmark totals at each stable release and the latest beta and nightly,
+/// for one statistic; see `releases`.
+pub mod releases {
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct Response {
+        /// Oldest first: the releases, then the latest beta and nightly.
+        pub artifacts: Vec<String>,
+        /// By profile, the total at each artifact.
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub totals: BTreeMap<String, Vec<f64>>,
+        /// By profile, how many measurements the totals are over.
+        pub benchmarks: BTreeMap<String, usize>,
+    }
+}
+
+/// The self time of a benchmark's compiler phases over a range of commits,
+/// aligned for stacked area charts.
+pub mod phases {
+    use collector::Bound;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        pub start: Bound,
+        pub end: Bound,
+        /// `crate-profile`, as for self-profiles.
+        pub benchmark: String,
+        pub run_name: String,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize)]
+    pub struct Response {
+        /// Oldest first; every phase has a point for each.
+        pub commits: Vec<String>,
+        /// Whether a commit's points were filled in from the nearest commit
+        /// with a self-profile, as it has none of its own.
+        pub is_interpolated: Vec<bool>,
+        /// Bottom of the stack first.
+        pub phases: Vec<Phase>,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Serialize)]
+    pub struct Phase {
+        pub name: String,
+        /// In seconds, for each commit.
+        #[serde(serialize_with = "crate::util::round::value")]
+        pub self_time: Vec<f32>,
+    }
+}
+
+/// The Grafana (Simple)JSON datasource contract, served under `/perf/grafana/`.
+pub mod grafana {
+    use database::Date;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Range {
+        pub from: Date,
+        pub to: Date,
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    pub struct SearchRequest {
+        /// Part of a series name, as typed into the query editor.
+        #[serde(default)]
+        pub target: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Target {
+        /// A series name, `crate/profile/cache/statistic`.
+        pub target: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct QueryRequest {
+        pub range: Range,
+        pub targets: Vec<Target>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct TimeSeries {
+        pub target: String,
+        /// `[value, milliseconds since the epoch]`, oldest first.
+        pub datapoints: Vec<(f64, i64)>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AnnotationsRequest {
+        pub range: Range,
+        /// Grafana's description of the annotation, which it expects back.
+        pub annotation: serde_json::Value,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Annotation {
+        pub annotation: serde_json::Value,
+        /// Milliseconds since the epoch.
+        pub time: i64,
+        pub title: String,
+        pub text: String,
+        pub tags: Vec<String>,
+    }
+}
+
+/// A Zulip outgoing webhook's request and reply, for `/perf/zulip`.
+pub mod zulip {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Message {
+        pub sender_full_name: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Request {
+        /// The text of the message, starting with the bot's mention.
+        pub data: String,
+        pub token: String,
+        pub message: Message,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Response {
+        pub content: String,
+    }
+}
+
 pub mod github {
     use serde::{Deserialize, Serialize};
 
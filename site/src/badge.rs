@@ -0,0 +1,152 @@
+//! Status badges for dashboards and READMEs to embed, served at
+//! `/perf/badge/<metric>.svg` in the style of shields.io:
+//!
+//! - `geomean-30d`: the geometric mean change of a statistic
+//!   (`instructions:u` unless `?stat=` says otherwise) over every benchmark,
+//!   from the master commit 30 days before the latest to the latest;
+//! - `last-commit-age`: how long ago the latest benchmarked master commit
+//!   was merged.
+
+use crate::load::InputData;
+use chrono::{Duration, Utc};
+use database::{ArtifactId, DbLabel, Lookup};
+
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+
+/// Changes smaller than this (in percent) are shown as neutral.
+const NOISE: f64 = 1.0;
+
+/// The label, value and colour of a metric, if it's known and there's data
+/// for it.
+pub fn metric(data: &InputData, name: &str, stat: &str) -> Option<(String, String, &'static str)> {
+    match name {
+        "geomean-30d" => {
+            let change = geomean_change(data, stat, Duration::days(30));
+            let (value, color) = match change {
+                Some(change) if change > NOISE => (format!("{:+.2}%", change), RED),
+                Some(change) if change < -NOISE => (format!("{:+.2}%", change), GREEN),
+                Some(change) => (format!("{:+.2}%", change), GREY),
+                None => (String::from("no data"), GREY),
+            };
+            Some((format!("{} 30d", stat), value, color))
+        }
+        "last-commit-age" => {
+            let index = data.index.load();
+            let latest = index.sorted_commits().iter().rev().find(|c| !c.is_try());
+            let (value, color) = match latest {
+                Some(commit) => {
+                    let age = Utc::now() - commit.date.0;
+                    let color = if age < Duration::days(1) {
+                        GREEN
+                    } else if age < Duration::days(3) {
+                        YELLOW
+                    } else {
+                        RED
+                    };
+                    (format_age(age), color)
+                }
+                None => (String::from("none"), GREY),
+            };
+            Some((String::from("last benchmarked"), value, color))
+        }
+        _ => None,
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    if age < Duration::hours(1) {
+        format!("{}m ago", age.num_minutes().max(0))
+    } else if age < Duration::days(2) {
+        format!("{}h ago", age.num_hours())
+    } else {
+        format!("{}d ago", age.num_days())
+    }
+}
+
+/// The geometric mean of the relative change (in percent) of every benchmark
+/// measured at both the latest master commit and the last one at least
+/// `period` before it.
+fn geomean_change(data: &InputData, stat: &str, period: Duration) -> Option<f64> {
+    let index = data.index.load();
+    let commits = index
+        .sorted_commits()
+        .iter()
+        .filter(|c| !c.is_try())
+        .collect::<Vec<_>>();
+    let latest = *commits.last()?;
+    let earlier = commits
+        .iter()
+        .rev()
+        .find(|c| c.date.0 <= latest.date.0 - period)?;
+    let aids = [
+        ArtifactId::Commit((*earlier).clone()).lookup(&index),
+        ArtifactId::Commit(latest.clone()).lookup(&index),
+    ];
+
+    let pstats = data.pstats.load();
+    let (mut sum, mut count) = (0.0, 0);
+    for &(krate, profile, cache, s) in index.all_pstat_series() {
+        if s.as_str() != stat {
+            continue;
+        }
+        let sid = match (DbLabel::ProcessStat {
+            krate,
+            profile,
+            cache,
+            stat: s,
+        })
+        .lookup(&index)
+        {
+            Some(sid) => sid,
+            None => continue,
+        };
+        if let [Some(before), Some(after)] = pstats.get(sid, &aids)[..] {
+            if before > 0.0 && after > 0.0 {
+                sum += (after / before).ln();
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    Some(((sum / count as f64).exp() - 1.0) * 100.0)
+}
+
+/// Escapes text for SVG, in content and in quoted attributes alike.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A flat two-part badge. Text widths are estimated, which is close enough
+/// for the short labels and values used here.
+pub fn render(label: &str, value: &str, color: &str) -> String {
+    let width = |text: &str| text.chars().count() as u32 * 7 + 10;
+    let (lw, vw) = (width(label), width(value));
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total}\" height=\"20\" \
+         role=\"img\" aria-label=\"{label}: {value}\">\
+         <title>{label}: {value}</title>\
+         <rect width=\"{lw}\" height=\"20\" fill=\"#555\"/>\
+         <rect x=\"{lw}\" width=\"{vw}\" height=\"20\" fill=\"{color}\"/>\
+         <g fill=\"#fff\" text-anchor=\"middle\" \
+         font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" font-size=\"11\">\
+         <text x=\"{lx}\" y=\"14\">{label}</text>\
+         <text x=\"{vx}\" y=\"14\">{value}</text></g></svg>",
+        total = lw + vw,
+        lw = lw,
+        vw = vw,
+        lx = lw / 2,
+        vx = lw + vw / 2,
+        label = escape(label),
+        value = escape(value),
+        color = color,
+    )
+}
@@ -0,0 +1,106 @@
+//! A size-bounded cache of the responses to expensive queries.
+//!
+//! Responses are keyed by their request, serialized, so two requests for the
+//! same thing share an entry. The cache holds up to a number of bytes of
+//! entries, serialized; when it's full, the least recently used entries are
+//! dropped. All entries are dropped when new data arrives.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The key of a request, or `None` if it can't be serialized (in which case it
+/// isn't cached).
+pub fn key<R: Serialize>(request: &R) -> Option<String> {
+    serde_json::to_string(request).ok()
+}
+
+pub struct LruCache<V> {
+    /// The most the entries may add up to, in bytes, serialized.
+    max_bytes: usize,
+    inner: Mutex<Inner<V>>,
+}
+
+struct Inner<V> {
+    /// Incremented on every use; each entry records when it was last used.
+    tick: u64,
+    /// Each entry's last use, serialized size (see `serialized_size`) and
+    /// value.
+    entries: HashMap<String, (u64, usize, V)>,
+    /// The sum of the entries' sizes.
+    size: usize,
+}
+
+impl<V: Clone + Serialize> LruCache<V> {
+    pub fn new(max_bytes: usize) -> Self {
+        LruCache {
+            max_bytes,
+            inner: Mutex::new(Inner {
+                tick: 0,
+                entries: HashMap::new(),
+                size: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(key)?;
+        entry.0 = tick;
+        Some(entry.2.clone())
+    }
+
+    pub fn insert(&self, key: String, value: V) {
+        let size = key.len() + serde_json::to_vec(&value).map_or(0, |v| v.len());
+        // An entry which doesn't fit even on its own isn't cached.
+        if size > self.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        inner.tick += 1;
+        let tick = inner.tick;
+        if let Some((_, old_size, _)) = inner.entries.remove(&key) {
+            inner.size -= old_size;
+        }
+        while inner.size + size > self.max_bytes {
+            // A linear scan is fine, as inserting follows a cache miss, which
+            // takes far longer.
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, (used, _, _))| *used)
+                .map(|(k, _)| k.clone());
+            let oldest = match oldest {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if let Some((_, old_size, _)) = inner.entries.remove(&oldest) {
+                inner.size -= old_size;
+            }
+        }
+        inner.size += size;
+        inner.entries.insert(key, (tick, size, value));
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.entries.clear();
+        inner.size = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total size of the entries, serialized, which is roughly what they
+    /// take in memory. Each entry is measured once, when it's inserted.
+    pub fn serialized_size(&self) -> usize {
+        self.inner.lock().size
+    }
+}
@@ -0,0 +1,91 @@
+//! Everything known about one benchmark over a range of commits, for a page of
+//! its own: its manifest, every statistic in every profile and cache (times,
+//! memory, counters and sizes alike), how noisy each of them is, and the
+//! commits at which it failed.
+//!
+//! The values are read straight from the `PstatCache` rather than queried, as
+//! every statistic is wanted; the noise is as in comparisons (see
+//! `significance`).
+
+use crate::api::benchmark_detail::{Failure, Measurement, Request, Response};
+use crate::load::InputData;
+use database::{ArtifactId, DbLabel, Lookup};
+use std::collections::BTreeMap;
+
+pub async fn detail(request: Request, data: &InputData) -> Result<Response, String> {
+    let _span = database::trace::span("benchmark detail");
+    let commits = data.data_range(request.start.clone()..=request.end.clone());
+    let (krate, aids, stats) = {
+        let index = data.index.load();
+        let krate = index.resolve_benchmark(&request.benchmark).to_string();
+        let aids = commits
+            .iter()
+            .map(|c| ArtifactId::Commit(c.clone()).lookup(&index))
+            .collect::<Vec<_>>();
+        let history = crate::significance::history(&index, commits.last());
+        let pstats = data.pstats.load();
+        let deltas = data.deltas.load();
+
+        let mut stats = BTreeMap::new();
+        for &(k, profile, cache, stat) in index.all_pstat_series() {
+            if k.as_str() != krate {
+                continue;
+            }
+            let label = DbLabel::ProcessStat {
+                krate: k,
+                profile,
+                cache,
+                stat,
+            };
+            let sid = match label.lookup(&index) {
+                Some(sid) => sid,
+                None => continue,
+            };
+            let values = aids
+                .iter()
+                .map(|aid| aid.and_then(|aid| pstats.value(sid, aid)))
+                .collect::<Vec<_>>();
+            if values.iter().all(|v| v.is_none()) {
+                continue;
+            }
+            let noise = crate::significance::noise(&deltas, sid, &history).map(|n| n * 100.0);
+            stats
+                .entry(stat.to_string())
+                .or_insert_with(BTreeMap::new)
+                .entry(profile.to_string())
+                .or_insert_with(BTreeMap::new)
+                .insert(cache.to_string(), Measurement { values, noise });
+        }
+        (krate, aids, stats)
+    };
+    let benchmark = data
+        .benchmarks
+        .iter()
+        .find(|b| b.name == krate)
+        .map(crate::server::benchmark_info);
+    if benchmark.is_none() && stats.is_empty() {
+        return Err(format!("no benchmark named {:?}", request.benchmark));
+    }
+
+    let mut errors = data.conn().await.get_errors_of(&krate).await;
+    let failures = commits
+        .iter()
+        .zip(aids)
+        .filter_map(|(commit, aid)| {
+            Some(Failure {
+                commit: commit.sha.clone(),
+                error: errors.remove(&aid?)?,
+            })
+        })
+        .collect();
+
+    Ok(Response {
+        benchmark,
+        commits: commits
+            .iter()
+            .map(|c| (c.date.0.timestamp(), c.sha.clone()))
+            .collect(),
+        stats,
+        failures,
+    })
+}
@@ -0,0 +1,133 @@
+//! The loaded results as JSON Lines, streamed from `/perf/export.jsonl` one
+//! artifact at a time, so that neither side has to hold a whole extraction in
+//! memory: the next artifact's lines aren't produced until the client has
+//! taken the last ones.
+//!
+//! Each line is one value, with the fields of `export-csv`'s columns:
+//! `artifact`, `type` (`master`, `try` or `release`), `date` (null for
+//! releases), `crate`, `profile`, `cache`, `statistic` and `value`. Values are
+//! the ones the site holds, so older ones may have reduced precision (see
+//! `REDUCED_PRECISION_AFTER_DAYS`); use the database tools for exact values.
+
+use crate::load::InputData;
+use collector::Bound;
+use database::{ArtifactId, DbLabel, Lookup};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Which values to export.
+pub struct Filter {
+    /// The range of commits, as for graphs; everything if `None`, including
+    /// releases.
+    pub range: Option<(Bound, Bound)>,
+    pub stat: Option<String>,
+    pub krate: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    artifact: &'a str,
+    #[serde(rename = "type")]
+    ty: &'a str,
+    date: Option<String>,
+    #[serde(rename = "crate")]
+    krate: &'a str,
+    profile: &'a str,
+    cache: &'a str,
+    statistic: &'a str,
+    value: f64,
+}
+
+/// Sends the filtered values to `sender`, stopping early if the client goes
+/// away.
+pub async fn stream(data: Arc<InputData>, filter: Filter, mut sender: hyper::body::Sender) {
+    let index = data.index.load_full();
+    let pstats = data.pstats.load_full();
+
+    let mut series = index
+        .all_pstat_series()
+        .filter(|s| {
+            filter
+                .stat
+                .as_ref()
+                .map_or(true, |stat| s.3.as_str() == stat)
+        })
+        .filter(|s| filter.krate.as_ref().map_or(true, |k| s.0.as_str() == k))
+        .filter_map(|&(krate, profile, cache, stat)| {
+            let sid = DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)?;
+            let names = (
+                krate.to_string(),
+                profile.to_string(),
+                cache.to_string(),
+                stat.to_string(),
+            );
+            Some((names, sid))
+        })
+        .collect::<Vec<_>>();
+    series.sort();
+
+    let commits = match &filter.range {
+        Some((start, end)) => data.data_range(start.clone()..=end.clone()),
+        None => index.sorted_commits().to_vec(),
+    };
+    let mut artifacts = commits
+        .into_iter()
+        .map(ArtifactId::Commit)
+        .collect::<Vec<_>>();
+    if filter.range.is_none() {
+        let mut releases = index.artifacts().map(String::from).collect::<Vec<_>>();
+        releases.sort();
+        artifacts.extend(releases.into_iter().map(ArtifactId::Artifact));
+    }
+
+    let mut lines = 0;
+    for artifact in artifacts {
+        let aid = match artifact.lookup(&index) {
+            Some(aid) => aid,
+            None => continue,
+        };
+        let (name, ty, date) = match &artifact {
+            ArtifactId::Commit(c) => (
+                c.sha.as_str(),
+                if c.is_try() { "try" } else { "master" },
+                Some(c.date.0.to_rfc3339()),
+            ),
+            ArtifactId::Artifact(name) => (name.as_str(), "release", None),
+        };
+        let mut chunk = Vec::new();
+        for ((krate, profile, cache, stat), sid) in &series {
+            let value = match pstats.value(*sid, aid) {
+                Some(value) => value,
+                None => continue,
+            };
+            let line = Line {
+                artifact: name,
+                ty,
+                date: date.clone(),
+                krate,
+                profile,
+                cache,
+                statistic: stat,
+                value,
+            };
+            serde_json::to_writer(&mut chunk, &line).unwrap();
+            chunk.push(b'\n');
+            lines += 1;
+        }
+        if chunk.is_empty() {
+            continue;
+        }
+        // Waits for the client to take the previous chunk.
+        if let Err(e) = sender.send_data(bytes::Bytes::from(chunk)).await {
+            log::warn!("export stopped after {} lines: {:?}", lines, e);
+            return;
+        }
+    }
+    log::info!("exported {} lines", lines);
+}
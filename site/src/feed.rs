@@ -0,0 +1,166 @@
+//! An Atom feed of the significant changes between consecutive benchmarked
+//! master commits, served at `/perf/feed.xml`, so anyone can follow
+//! regressions and improvements in a feed reader.
+//!
+//! A change is significant if a benchmark's statistic moved by at least the
+//! threshold from the master commit before. Each entry is a commit with such
+//! changes, linking to its comparison with that commit.
+
+use crate::load::InputData;
+use database::{ArtifactId, Commit, DbLabel, Lookup};
+use std::fmt::Write;
+
+/// Where the site is served, for the links in the feed.
+pub const SITE: &str = "https://perf.rust-lang.org";
+
+/// How many of the latest master commits the feed covers.
+const COMMITS: usize = 50;
+
+/// A master commit which changed some benchmarks significantly.
+pub struct Changes {
+    pub parent: Commit,
+    pub commit: Commit,
+    /// `(crate-profile cache, relative change)`, largest change first.
+    pub changes: Vec<(String, f64)>,
+}
+
+impl Changes {
+    pub fn regressions(&self) -> usize {
+        self.changes
+            .iter()
+            .filter(|(_, delta)| *delta > 0.0)
+            .count()
+    }
+
+    pub fn comparison_url(&self, stat: &str) -> String {
+        format!(
+            "{}/compare.html?start={}&end={}&stat={}",
+            SITE, self.parent.sha, self.commit.sha, stat
+        )
+    }
+}
+
+/// The master commits among the latest which changed a statistic of any
+/// benchmark by at least `threshold` (`0.01` is 1%), newest first.
+pub fn significant_changes(data: &InputData, stat: &str, threshold: f64) -> Vec<Changes> {
+    let commits = data
+        .index
+        .load()
+        .sorted_commits()
+        .iter()
+        .filter(|c| !c.is_try())
+        .cloned()
+        .collect::<Vec<_>>();
+    let commits = &commits[commits.len().saturating_sub(COMMITS + 1)..];
+    changes_in(data, commits, stat, threshold, None)
+}
+
+/// Like `significant_changes`, but among the given master commits (oldest
+/// first, each compared with the one before) and, with `krate`, only in that
+/// crate's benchmarks.
+pub fn changes_in(
+    data: &InputData,
+    commits: &[Commit],
+    stat: &str,
+    threshold: f64,
+    krate: Option<&str>,
+) -> Vec<Changes> {
+    let index = data.index.load();
+    let deltas = data.deltas.load();
+    let mut series = index
+        .all_pstat_series()
+        .filter(|s| s.3.as_str() == stat)
+        .filter(|s| krate.map_or(true, |k| s.0.as_str() == k))
+        .filter_map(|&(krate, profile, cache, stat)| {
+            let sid = DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)?;
+            Some((format!("{}-{} {}", krate.as_str(), profile, cache), sid))
+        })
+        .collect::<Vec<_>>();
+    series.sort();
+
+    let mut result = Vec::new();
+    for pair in commits.windows(2).rev() {
+        let aid = match ArtifactId::Commit(pair[1].clone()).lookup(&index) {
+            Some(aid) => aid,
+            None => continue,
+        };
+        let mut changes = series
+            .iter()
+            .filter_map(|(name, sid)| Some((name.clone(), deltas.get(*sid, aid)? as f64)))
+            .filter(|(_, delta)| delta.abs() >= threshold)
+            .collect::<Vec<_>>();
+        if changes.is_empty() {
+            continue;
+        }
+        changes.sort_by(|a, b| b.1.abs().partial_cmp(&a.1.abs()).unwrap());
+        result.push(Changes {
+            parent: pair[0].clone(),
+            commit: pair[1].clone(),
+            changes,
+        });
+    }
+    result
+}
+
+/// Escapes text for XML content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the feed for one statistic, with `threshold` as a fraction (`0.01`
+/// is 1%).
+pub fn render(data: &InputData, stat: &str, threshold: f64) -> String {
+    let mut entries = String::new();
+    let mut updated = None;
+    for changes in significant_changes(data, stat, threshold) {
+        let regressions = changes.regressions();
+        let improvements = changes.changes.len() - regressions;
+        let date = changes.commit.date.0.to_rfc3339();
+        updated.get_or_insert_with(|| date.clone());
+        let url = changes.comparison_url(stat);
+        let mut summary = String::new();
+        for (name, delta) in &changes.changes {
+            writeln!(summary, "{:+.2}% {}", delta * 100.0, name).unwrap();
+        }
+        let sha = &changes.commit.sha;
+        write!(
+            entries,
+            "<entry><title>{}: {} regressions, {} improvements in {}</title>\
+             <id>{}</id><link href=\"{}\"/><updated>{}</updated>\
+             <summary>{}</summary></entry>\n",
+            &sha[..std::cmp::min(10, sha.len())],
+            regressions,
+            improvements,
+            escape(stat),
+            escape(&url),
+            escape(&url),
+            date,
+            escape(&summary)
+        )
+        .unwrap();
+    }
+
+    let self_url = format!("{}/perf/feed.xml?stat={}", SITE, stat);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>rustc performance changes in {}</title>\n\
+         <id>{}</id>\n<link rel=\"self\" href=\"{}\"/>\n\
+         <updated>{}</updated>\n<author><name>rustc-perf</name></author>\n\
+         {}</feed>\n",
+        escape(stat),
+        escape(&self_url),
+        escape(&self_url),
+        updated.unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        entries
+    )
+}
@@ -56,7 +56,7 @@ pub async fn handle_github(
             .contains(&request.comment.user.id)
     {
         post_comment(
-            &data.config,
+            &data.config(),
             request.issue.number,
             "Insufficient permissions to issue commands to rust-timer.",
         )
@@ -74,7 +74,7 @@ pub async fn handle_github(
                 .await;
         }
         post_comment(
-            &data.config,
+            &data.config(),
             request.issue.number,
             "Awaiting bors try build completion.
 
@@ -137,7 +137,7 @@ pub async fn handle_github(
                     .await
                     .map_err(|e| e.to_string())?;
             post_comment(
-                &data.config,
+                &data.config(),
                 request.issue.number,
                 &format!("Master base SHA: {}", branch.master_base_sha),
             )
@@ -198,7 +198,7 @@ r? @ghost",
         // off: we'd need to store the state in the database and handle the try
         // build starting and generally that's a lot of work for not too much gain.
         post_comment(
-            &data.config,
+            &data.config(),
             pr.number,
             &format!(
                 "@bors try @rust-timer queue
@@ -333,7 +333,7 @@ pub async fn create_ref(
     sha: &str,
 ) -> anyhow::Result<()> {
     let timer_token = data
-        .config
+        .config()
         .keys
         .github
         .clone()
@@ -382,7 +382,7 @@ pub async fn create_pr(
     description: &str,
 ) -> anyhow::Result<CreatePrResponse> {
     let timer_token = data
-        .config
+        .config()
         .keys
         .github
         .clone()
@@ -429,7 +429,7 @@ pub async fn create_commit(
     parents: &[&str],
 ) -> anyhow::Result<String> {
     let timer_token = data
-        .config
+        .config()
         .keys
         .github
         .clone()
@@ -465,7 +465,7 @@ pub async fn get_commit(
     sha: &str,
 ) -> anyhow::Result<github::Commit> {
     let timer_token = data
-        .config
+        .config()
         .keys
         .github
         .clone()
@@ -532,7 +532,7 @@ async fn enqueue_sha(
             commit_response.parents[0].sha,
             try_commit.comparison_url(),
         );
-        post_comment(&data.config, request.issue.number, msg).await;
+        post_comment(&data.config(), request.issue.number, msg).await;
     }
     Ok(github::Response)
 }
@@ -594,14 +594,14 @@ where
 pub async fn post_finished(data: &InputData) {
     // If the github token is not configured, do not run this -- we don't want
     // to mark things as complete without posting the comment.
-    if data.config.keys.github.is_none() {
+    if data.config().keys.github.is_none() {
         return;
     }
     let conn = data.conn().await;
     let index = data.index.load();
     let mut commits = index
-        .commits()
-        .into_iter()
+        .sorted_commits()
+        .iter()
         .map(|c| c.sha.to_string())
         .collect::<HashSet<_>>();
     let queued = conn.queued_commits().await;
@@ -632,7 +632,7 @@ pub async fn post_finished(data: &InputData) {
                 commit.parent_sha, commit.sha
             );
             post_comment(
-                &data.config,
+                &data.config(),
                 commit.pr,
                 format!(
                     "Finished benchmarking try commit ({}): [comparison url]({}).
@@ -0,0 +1,108 @@
+//! The Grafana (Simple)JSON datasource contract over the loaded results, so a
+//! Grafana JSON datasource can be pointed at `/perf/grafana` directly.
+//!
+//! Series are named `crate/profile/cache/statistic` and have a point per
+//! benchmarked master commit, at the commit's date. Every such commit in the
+//! requested range is also an annotation, with its sha as the text.
+
+use crate::api::grafana::{Annotation, AnnotationsRequest, QueryRequest, Range, TimeSeries};
+use crate::load::InputData;
+use database::{ArtifactId, Commit, DbLabel, Index, Lookup};
+
+fn series_name(krate: &str, profile: &str, cache: &str, stat: &str) -> String {
+    format!("{}/{}/{}/{}", krate, profile, cache, stat)
+}
+
+/// The master commits in the range, oldest first.
+fn commits(index: &Index, range: &Range) -> Vec<Commit> {
+    index
+        .sorted_commits()
+        .iter()
+        .filter(|c| !c.is_try() && range.from <= c.date && c.date <= range.to)
+        .cloned()
+        .collect()
+}
+
+/// The names of the series containing `target`, sorted.
+pub fn search(data: &InputData, target: &str) -> Vec<String> {
+    let index = data.index.load();
+    let mut names = index
+        .all_pstat_series()
+        .map(|(krate, profile, cache, stat)| {
+            series_name(
+                krate.as_str(),
+                &profile.to_string(),
+                &cache.to_string(),
+                stat.as_str(),
+            )
+        })
+        .filter(|name| name.contains(target))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// The points of each target in the range. Unknown targets have no points,
+/// rather than failing the whole panel.
+pub fn query(data: &InputData, request: &QueryRequest) -> Vec<TimeSeries> {
+    let index = data.index.load();
+    let pstats = data.pstats.load();
+    let commits = commits(&index, &request.range);
+    let aids = commits
+        .iter()
+        .map(|c| ArtifactId::Commit(c.clone()).lookup(&index))
+        .collect::<Vec<_>>();
+    request
+        .targets
+        .iter()
+        .map(|target| {
+            let series = index
+                .all_pstat_series()
+                .find(|(krate, profile, cache, stat)| {
+                    series_name(
+                        krate.as_str(),
+                        &profile.to_string(),
+                        &cache.to_string(),
+                        stat.as_str(),
+                    ) == target.target
+                });
+            let sid = series.and_then(|&(krate, profile, cache, stat)| {
+                DbLabel::ProcessStat {
+                    krate,
+                    profile,
+                    cache,
+                    stat,
+                }
+                .lookup(&index)
+            });
+            let datapoints = match sid {
+                Some(sid) => pstats
+                    .get(sid, &aids)
+                    .into_iter()
+                    .zip(&commits)
+                    .filter_map(|(value, c)| Some((value?, c.date.0.timestamp_millis())))
+                    .collect(),
+                None => Vec::new(),
+            };
+            TimeSeries {
+                target: target.target.clone(),
+                datapoints,
+            }
+        })
+        .collect()
+}
+
+/// The master commits in the range, tagged `master`.
+pub fn annotations(data: &InputData, request: &AnnotationsRequest) -> Vec<Annotation> {
+    let index = data.index.load();
+    commits(&index, &request.range)
+        .into_iter()
+        .map(|c| Annotation {
+            annotation: request.annotation.clone(),
+            time: c.date.0.timestamp_millis(),
+            title: c.sha.clone(),
+            text: c.sha,
+            tags: vec![String::from("master")],
+        })
+        .collect()
+}
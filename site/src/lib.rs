@@ -22,10 +22,24 @@ pub mod github;
 
 pub mod api;
 mod average;
+mod badge;
+pub mod cache;
 pub mod db;
+mod detail;
+mod export;
+mod feed;
+mod grafana;
 mod interpolate;
 pub mod load;
+mod phases;
+pub mod pr_cache;
+mod prometheus;
+mod releases;
+mod search;
 mod selector;
 mod self_profile;
 pub mod server;
+mod significance;
+mod timeline;
 pub mod util;
+pub mod zulip;
@@ -8,20 +8,24 @@
 // except according to those terms.
 
 use arc_swap::ArcSwap;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::ops::RangeInclusive;
+use std::ops::RangeBounds;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use anyhow::Context;
-use chrono::{Duration, Utc};
+use chrono::{Duration, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::cache::LruCache;
 use crate::db;
 use crate::util;
+use crate::util::round::Precision;
+use crate::zulip::Zulip;
 use collector::Bound;
-use database::Date;
+use database::{Calendar, Date, Deltas, Downsampled, Periods, Resolution};
 
 use crate::api::github;
 use collector;
@@ -73,18 +77,490 @@ pub struct Keys {
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub keys: Keys,
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+    #[serde(default)]
+    pub precision: PrecisionConfig,
+    #[serde(default)]
+    pub zulip: ZulipConfig,
+}
+
+/// Where the days and weeks of downsampled graphs start.
+#[derive(Debug, Default, Deserialize)]
+pub struct CalendarConfig {
+    /// The day weeks start on, e.g. `"mon"` (the default) or `"tuesday"`.
+    pub week_start: Option<String>,
+    /// The offset from UTC, in hours, of the timezone days start in; UTC by
+    /// default.
+    pub utc_offset: Option<i32>,
+    /// If set, "weeks" are windows of this many days ending on the newest
+    /// commit, rather than calendar weeks.
+    pub rolling_days: Option<u32>,
+}
+
+/// How metrics in responses are rounded; see `util::round`.
+#[derive(Debug, Default, Deserialize)]
+pub struct PrecisionConfig {
+    /// Decimal places of percentages; 2 by default.
+    pub percent_decimals: Option<u32>,
+    /// Significant digits of other metrics; 6 by default.
+    pub significant_digits: Option<u32>,
+}
+
+/// The Zulip bot which posts regressions and takes triage commands; see
+/// `zulip`. Nothing is posted unless the site, bot and stream are all set.
+#[derive(Debug, Default, Deserialize)]
+pub struct ZulipConfig {
+    /// The organization's URL, e.g. `https://rust-lang.zulipchat.com`.
+    pub site: Option<String>,
+    pub bot_email: Option<String>,
+    pub api_key: Option<String>,
+    pub stream: Option<String>,
+    /// The topic regressions are posted under; `perf regressions` by default.
+    pub topic: Option<String>,
+    /// The token of the bot's outgoing webhook, which commands must carry.
+    /// Commands are refused if it isn't set.
+    pub webhook_token: Option<String>,
+    /// How much (in percent) a benchmark has to regress by for a commit to
+    /// be posted; 1 by default.
+    pub threshold: Option<f64>,
+}
+
+/// How `InputData::from_fs` deals with problems in its inputs other than the
+/// database: the config, the environment and the benchmark manifests.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail on the first problem, with an error saying what and where it is;
+    /// e.g. for CI.
+    Strict,
+    /// Log problems and carry on without whatever was wrong, so that the
+    /// site keeps serving. The problems are shown on the status page.
+    Lenient,
+}
+
+impl LoadMode {
+    /// `result`, or in lenient mode `default` if it's an error, which is
+    /// added to `problems`.
+    fn handle<T>(
+        self,
+        result: anyhow::Result<T>,
+        default: T,
+        problems: &mut Vec<String>,
+    ) -> anyhow::Result<T> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) if self == LoadMode::Lenient => {
+                log::warn!("{:?}", e);
+                problems.push(format!("{:#}", e));
+                Ok(default)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Parses an optional environment variable.
+fn env_var<T>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("invalid {} {:?}: {}", name, value, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+impl Config {
+    /// Reads `site-config.toml`, or if there isn't one, the environment.
+    pub fn load(mode: LoadMode, problems: &mut Vec<String>) -> anyhow::Result<Config> {
+        if let Ok(s) = fs::read_to_string("site-config.toml") {
+            let config: Config = toml::from_str(&s).context("parsing site-config.toml")?;
+            config
+                .calendar_with(mode, problems)
+                .context("in the [calendar] section of site-config.toml")?;
+            config
+                .precision_with(mode, problems)
+                .context("in the [precision] section of site-config.toml")?;
+            config
+                .zulip_with(mode, problems)
+                .context("in the [zulip] section of site-config.toml")?;
+            return Ok(config);
+        }
+        let config = Config {
+            keys: Keys {
+                github: std::env::var("GITHUB_API_TOKEN").ok(),
+                secret: std::env::var("GITHUB_WEBHOOK_SECRET").ok(),
+            },
+            calendar: CalendarConfig {
+                week_start: std::env::var("WEEK_START").ok(),
+                utc_offset: mode.handle(env_var("UTC_OFFSET"), None, problems)?,
+                rolling_days: mode.handle(env_var("ROLLING_DAYS"), None, problems)?,
+            },
+            precision: PrecisionConfig {
+                percent_decimals: mode.handle(env_var("PERCENT_DECIMALS"), None, problems)?,
+                significant_digits: mode.handle(env_var("SIGNIFICANT_DIGITS"), None, problems)?,
+            },
+            zulip: ZulipConfig {
+                site: std::env::var("ZULIP_SITE").ok(),
+                bot_email: std::env::var("ZULIP_BOT_EMAIL").ok(),
+                api_key: std::env::var("ZULIP_API_KEY").ok(),
+                stream: std::env::var("ZULIP_STREAM").ok(),
+                topic: std::env::var("ZULIP_TOPIC").ok(),
+                webhook_token: std::env::var("ZULIP_WEBHOOK_TOKEN").ok(),
+                threshold: mode.handle(env_var("ZULIP_THRESHOLD"), None, problems)?,
+            },
+        };
+        config
+            .calendar_with(mode, problems)
+            .context("in the calendar environment variables")?;
+        config
+            .precision_with(mode, problems)
+            .context("in the precision environment variables")?;
+        config
+            .zulip_with(mode, problems)
+            .context("in the Zulip environment variables")?;
+        Ok(config)
+    }
+
+    pub fn calendar(&self) -> Calendar {
+        self.calendar_with(LoadMode::Lenient, &mut Vec::new())
+            .unwrap_or_default()
+    }
+
+    /// The calendar, leaving out (or in strict mode, failing on) any invalid
+    /// settings.
+    fn calendar_with(
+        &self,
+        mode: LoadMode,
+        problems: &mut Vec<String>,
+    ) -> anyhow::Result<Calendar> {
+        let mut calendar = Calendar::default();
+        if let Some(day) = &self.calendar.week_start {
+            let week_start = day
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid week start {:?}", day));
+            calendar.week_start = mode.handle(week_start, calendar.week_start, problems)?;
+        }
+        if let Some(hours) = self.calendar.utc_offset {
+            let offset = FixedOffset::east_opt(hours * 60 * 60)
+                .ok_or_else(|| anyhow::anyhow!("invalid UTC offset {:?}", hours));
+            calendar.offset = mode.handle(offset, calendar.offset, problems)?;
+        }
+        if let Some(days) = self.calendar.rolling_days {
+            let periods = if days == 0 {
+                Err(anyhow::anyhow!("rolling windows should be at least a day"))
+            } else {
+                Ok(Periods::Rolling { days })
+            };
+            calendar.periods = mode.handle(periods, calendar.periods, problems)?;
+        }
+        Ok(calendar)
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.precision_with(LoadMode::Lenient, &mut Vec::new())
+            .unwrap_or_default()
+    }
+
+    /// The precision, leaving out (or in strict mode, failing on) any invalid
+    /// settings.
+    fn precision_with(
+        &self,
+        mode: LoadMode,
+        problems: &mut Vec<String>,
+    ) -> anyhow::Result<Precision> {
+        let mut precision = Precision::default();
+        if let Some(decimals) = self.precision.percent_decimals {
+            let decimals = if decimals <= 10 {
+                Ok(decimals)
+            } else {
+                Err(anyhow::anyhow!("{} decimal places is too many", decimals))
+            };
+            precision.percent_decimals =
+                mode.handle(decimals, precision.percent_decimals, problems)?;
+        }
+        if let Some(digits) = self.precision.significant_digits {
+            // An f64 has at most 17 significant (decimal) digits.
+            let digits = if (1..=17).contains(&digits) {
+                Ok(digits)
+            } else {
+                Err(anyhow::anyhow!(
+                    "significant digits should be from 1 to 17, not {}",
+                    digits
+                ))
+            };
+            precision.significant_digits =
+                mode.handle(digits, precision.significant_digits, problems)?;
+        }
+        Ok(precision)
+    }
+
+    pub fn zulip(&self) -> Option<Zulip> {
+        self.zulip_with(LoadMode::Lenient, &mut Vec::new())
+            .unwrap_or_default()
+    }
+
+    /// Where and when to post regressions, if posting is set up, leaving out
+    /// (or in strict mode, failing on) any invalid settings.
+    fn zulip_with(
+        &self,
+        mode: LoadMode,
+        problems: &mut Vec<String>,
+    ) -> anyhow::Result<Option<Zulip>> {
+        let config = &self.zulip;
+        let mut zulip = match (
+            &config.site,
+            &config.bot_email,
+            &config.api_key,
+            &config.stream,
+        ) {
+            (Some(site), Some(bot_email), Some(api_key), Some(stream)) => Zulip {
+                site: site.trim_end_matches('/').to_string(),
+                bot_email: bot_email.clone(),
+                api_key: api_key.clone(),
+                stream: stream.clone(),
+                topic: config
+                    .topic
+                    .clone()
+                    .unwrap_or_else(|| "perf regressions".into()),
+                threshold: 0.01,
+            },
+            (None, None, None, None) => return Ok(None),
+            _ => {
+                let incomplete = Err(anyhow::anyhow!(
+                    "posting needs the site, bot_email, api_key and stream"
+                ));
+                return mode.handle(incomplete, None, problems);
+            }
+        };
+        if !zulip.site.starts_with("https://") && !zulip.site.starts_with("http://") {
+            let site = Err(anyhow::anyhow!("invalid site {:?}", zulip.site));
+            return mode.handle(site, None, problems);
+        }
+        if let Some(percent) = config.threshold {
+            let threshold = if percent.is_finite() && percent >= 0.0 {
+                Ok(percent / 100.0)
+            } else {
+                Err(anyhow::anyhow!("invalid threshold {}", percent))
+            };
+            zulip.threshold = mode.handle(threshold, zulip.threshold, problems)?;
+        }
+        Ok(Some(zulip))
+    }
+}
+
+/// How many bytes of graph responses to keep; each can be a few megabytes.
+const GRAPH_CACHE_BYTES: usize = 128 << 20;
+/// How many bytes of comparison responses to keep.
+const COMPARE_CACHE_BYTES: usize = 32 << 20;
+/// How many bytes of rendered metrics to keep.
+const METRICS_CACHE_BYTES: usize = 4 << 20;
+
+/// The date before which values are kept in memory at reduced precision, if
+/// `REDUCED_PRECISION_AFTER_DAYS` is set.
+pub fn reduced_precision_cutoff() -> Option<Date> {
+    reduced_precision_cutoff_with(LoadMode::Lenient, &mut Vec::new()).unwrap_or_default()
+}
+
+fn reduced_precision_cutoff_with(
+    mode: LoadMode,
+    problems: &mut Vec<String>,
+) -> anyhow::Result<Option<Date>> {
+    let days = mode.handle(
+        env_var::<i64>("REDUCED_PRECISION_AFTER_DAYS"),
+        None,
+        problems,
+    )?;
+    Ok(days.map(|days| Date(Utc::now() - Duration::days(days))))
+}
+
+/// The position of each master commit in the order bors merged them, from a
+/// checkout of rust-lang/rust if `RUST_CHECKOUT` names one, and otherwise from
+/// the list of recent master commits. Commits which aren't found are ordered
+/// by date.
+pub async fn merge_order() -> HashMap<String, usize> {
+    if let Some(checkout) = std::env::var_os("RUST_CHECKOUT") {
+        // Reading the history runs git.
+        let history = {
+            let checkout = checkout.clone();
+            tokio::task::spawn_blocking(move || master_history(Path::new(&checkout), None))
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("reading history panicked: {}", e)))
+        };
+        match history {
+            Ok(history) => {
+                return history
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, commit)| (commit.sha, position))
+                    .collect()
+            }
+            Err(e) => log::warn!("failed to read history from {:?}: {:?}", checkout, e),
+        }
+    }
+
+    let commits = match rustc_artifacts::master_commits().await {
+        Ok(commits) => commits,
+        Err(e) => {
+            log::warn!("failed to fetch master commits: {}", e);
+            return HashMap::new();
+        }
+    };
+    // Follow the parents back from the newest commit, which has no child.
+    let parents = commits
+        .iter()
+        .map(|c| (c.sha.as_str(), c.parent_sha.as_str()))
+        .collect::<HashMap<_, _>>();
+    let has_child = commits
+        .iter()
+        .map(|c| c.parent_sha.as_str())
+        .collect::<HashSet<_>>();
+    let mut chain = Vec::new();
+    let mut next = commits
+        .iter()
+        .map(|c| c.sha.as_str())
+        .find(|sha| !has_child.contains(sha));
+    while let Some(sha) = next {
+        chain.push(sha);
+        next = parents.get(sha).copied();
+    }
+    chain
+        .into_iter()
+        .rev()
+        .enumerate()
+        .map(|(position, sha)| (sha.to_string(), position))
+        .collect()
+}
+
+/// The merges into master, oldest first: the first-parent history of the
+/// checkout's `HEAD`, which should be an up-to-date master branch.
+fn master_history(checkout: &Path, since: Option<Date>) -> anyhow::Result<Vec<Commit>> {
+    let mut git = std::process::Command::new("git");
+    git.arg("-C")
+        .arg(checkout)
+        .args(&["log", "--first-parent", "--reverse", "--format=%H %cI"]);
+    if let Some(since) = since {
+        git.arg(format!("--since={}", since));
+    }
+    let output = git.arg("HEAD").output().context("failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git log failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| {
+            let mut parts = line.splitn(2, ' ');
+            let sha = parts.next().unwrap_or_default().to_string();
+            let date = parts
+                .next()
+                .unwrap_or_default()
+                .parse::<Date>()
+                .map_err(|e| anyhow::anyhow!("{:?}", e))
+                .with_context(|| format!("unexpected git output {:?}", line))?;
+            Ok(Commit { sha, date })
+        })
+        .collect()
+}
+
+/// Loads the index, with the commits in the order they were merged.
+pub async fn load_index(conn: &mut dyn database::pool::Connection) -> db::Index {
+    let mut index = db::Index::load(conn).await;
+    let order = merge_order().await;
+    index.order_by_merges(|sha| order.get(sha).copied());
+    index
+}
+
+/// Downsamples the values to each of the resolutions coarser than per commit.
+pub fn downsample(
+    index: &crate::db::Index,
+    pstats: &database::PstatCache,
+    calendar: &Calendar,
+) -> HashMap<Resolution, Downsampled> {
+    [Resolution::Day, Resolution::Week]
+        .iter()
+        .map(|&r| (r, Downsampled::new(index, pstats, r, calendar)))
+        .collect()
 }
 
 pub struct InputData {
-    pub config: Config,
+    /// Replaced by `reload_config`, e.g. on SIGHUP.
+    pub config: ArcSwap<Config>,
 
+    /// Set with `set_landing_page`.
     pub landing_page: ArcSwap<Option<Arc<crate::api::graph::Response>>>,
+    /// The size of `landing_page`, serialized, measured when it's set.
+    landing_page_size: AtomicUsize,
+    /// Recent graph and comparison responses, dropped when new data arrives.
+    pub graph_cache: LruCache<Arc<crate::api::graph::Response>>,
+    pub compare_cache: LruCache<crate::api::days::Response>,
+    /// `/perf/metrics`, with and without phases; see `prometheus::render`.
+    pub metrics_cache: LruCache<Arc<String>>,
 
     pub index: ArcSwap<crate::db::Index>,
+    /// Kept up to date with `index`, so that graphs can be drawn without
+    /// querying the database.
+    pub pstats: ArcSwap<database::PstatCache>,
+    /// `pstats` by day and by week, for graphs of long ranges.
+    pub downsampled: ArcSwap<HashMap<Resolution, Downsampled>>,
+    /// The change of each series at each master commit from the one before.
+    pub deltas: ArcSwap<Deltas>,
+    /// Benchmark totals at each release, for every statistic.
+    pub releases: ArcSwap<crate::releases::Releases>,
     pub pool: Pool,
+
+    /// The manifests of the benchmarks in `collector/benchmarks`, if found.
+    pub benchmarks: Vec<collector::manifest::BenchmarkConfig>,
+    /// What was wrong with the inputs other than the config, when loaded
+    /// leniently.
+    pub load_problems: Vec<String>,
+    /// What was wrong with the config, as last (re)loaded.
+    pub config_problems: ArcSwap<Vec<String>>,
+    /// Held while the data or the config is reloaded, so that one doesn't
+    /// undo the other (e.g. downsample with a calendar just replaced).
+    pub reload_lock: tokio::sync::Mutex<()>,
 }
 
 impl InputData {
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Reads the config again, without reloading the data. Only the
+    /// downsampled series depend on it, through the calendar; they (and the
+    /// graphs drawn from them) are recomputed if it changed. Problems replace
+    /// `config_problems`, and if the config can't be read at all the current
+    /// one stays.
+    pub async fn reload_config(&self) -> anyhow::Result<()> {
+        let _reloading = self.reload_lock.lock().await;
+        let _span = database::trace::span("reload config");
+        let mut problems = Vec::new();
+        let config =
+            Config::load(LoadMode::Lenient, &mut problems).context("reloading the config")?;
+        util::round::set_precision(config.precision());
+        let calendar = config.calendar();
+        let recalendared = calendar != self.config().calendar();
+        self.config.store(Arc::new(config));
+        self.config_problems.store(Arc::new(problems));
+        if recalendared {
+            let index = self.index.load_full();
+            let pstats = self.pstats.load_full();
+            let downsampled =
+                tokio::task::spawn_blocking(move || downsample(&index, &pstats, &calendar))
+                    .await
+                    .context("downsampling")?;
+            self.downsampled.store(Arc::new(downsampled));
+            self.graph_cache.clear();
+        }
+        Ok(())
+    }
+
     pub fn summary_patches(&self) -> Vec<crate::db::Cache> {
         vec![
             crate::db::Cache::Empty,
@@ -98,12 +574,35 @@ impl InputData {
         crate::selector::data_for(&self.index.load(), is_left, query)
     }
 
-    pub fn data_range(&self, range: RangeInclusive<Bound>) -> Vec<Commit> {
-        crate::selector::range_subset(self.index.load().commits(), range)
+    pub fn data_range(&self, range: impl RangeBounds<Bound>) -> Vec<Commit> {
+        crate::selector::range_subset(&self.index.load(), range)
+    }
+
+    /// Like `data_range`, but only the commits standing for each period at a
+    /// coarser resolution.
+    pub fn data_range_at(
+        &self,
+        resolution: Resolution,
+        range: impl RangeBounds<Bound>,
+    ) -> Vec<Commit> {
+        let commits = self.data_range(range);
+        if resolution == Resolution::Commit {
+            return commits;
+        }
+        let downsampled = self.downsampled.load();
+        let representatives = downsampled[&resolution]
+            .commits
+            .iter()
+            .map(|c| c.sha.as_str())
+            .collect::<HashSet<_>>();
+        commits
+            .into_iter()
+            .filter(|c| representatives.contains(c.sha.as_str()))
+            .collect()
     }
 
     /// Initialize `InputData from the file system.
-    pub async fn from_fs(db: &str) -> anyhow::Result<InputData> {
+    pub async fn from_fs(db: &str, mode: LoadMode) -> anyhow::Result<InputData> {
         if Path::new(db).join("times").exists() {
             eprintln!("It looks like you're running the site off of the old data format");
             eprintln!(
@@ -116,30 +615,110 @@ impl InputData {
             std::process::exit(1);
         }
 
+        let mut config_problems = Vec::new();
+        let config = Config::load(mode, &mut config_problems).context("loading the config")?;
+        let mut problems = Vec::new();
+        util::round::set_precision(config.precision());
+        let cutoff = reduced_precision_cutoff_with(mode, &mut problems)?;
+        let benchmarks = mode.handle(
+            collector::manifest::load_all(Path::new("collector/benchmarks"))
+                .context("failed to load benchmark manifests"),
+            Vec::new(),
+            &mut problems,
+        )?;
+
         let pool = Pool::open(db);
 
         let mut conn = pool.connection().await;
-        let index = db::Index::load(&mut *conn).await;
-
-        let config = if let Ok(s) = fs::read_to_string("site-config.toml") {
-            toml::from_str(&s)?
-        } else {
-            Config {
-                keys: Keys {
-                    github: std::env::var("GITHUB_API_TOKEN").ok(),
-                    secret: std::env::var("GITHUB_WEBHOOK_SECRET").ok(),
-                },
-            }
-        };
+        let index = load_index(&mut *conn).await;
+        let pstats = database::PstatCache::load(&*conn, &index, cutoff).await;
+        let downsampled = downsample(&index, &pstats, &config.calendar());
+        let deltas = Deltas::new(&index, &pstats);
+        let releases = crate::releases::Releases::new(&index, &pstats);
 
         Ok(InputData {
-            config,
+            config: ArcSwap::new(Arc::new(config)),
             index: ArcSwap::new(Arc::new(index)),
+            pstats: ArcSwap::new(Arc::new(pstats)),
+            downsampled: ArcSwap::new(Arc::new(downsampled)),
+            deltas: ArcSwap::new(Arc::new(deltas)),
+            releases: ArcSwap::new(Arc::new(releases)),
             pool,
             landing_page: ArcSwap::new(Arc::new(None)),
+            landing_page_size: AtomicUsize::new(0),
+            graph_cache: LruCache::new(GRAPH_CACHE_BYTES),
+            compare_cache: LruCache::new(COMPARE_CACHE_BYTES),
+            metrics_cache: LruCache::new(METRICS_CACHE_BYTES),
+            benchmarks,
+            load_problems: problems,
+            config_problems: ArcSwap::new(Arc::new(config_problems)),
+            reload_lock: tokio::sync::Mutex::new(()),
         })
     }
 
+    pub fn set_landing_page(&self, resp: Arc<crate::api::graph::Response>) {
+        let size = serde_json::to_vec(&resp).map_or(0, |v| v.len());
+        self.landing_page.store(Arc::new(Some(resp)));
+        self.landing_page_size.store(size, Ordering::Relaxed);
+    }
+
+    /// Approximate bytes used by each of the structures holding the data, and
+    /// the bytes allocated and resident in all.
+    pub fn memory_usage(&self) -> BTreeMap<String, usize> {
+        let mut usage = BTreeMap::new();
+        usage.insert("index".into(), self.index.load().heap_size());
+        usage.insert("pstats".into(), self.pstats.load().heap_size());
+        usage.insert(
+            "downsampled".into(),
+            self.downsampled
+                .load()
+                .values()
+                .map(|d| d.heap_size())
+                .sum(),
+        );
+        usage.insert("deltas".into(), self.deltas.load().heap_size());
+        usage.insert("releases".into(), self.releases.load().heap_size());
+        usage.insert(
+            "landing_page".into(),
+            self.landing_page_size.load(Ordering::Relaxed),
+        );
+        usage.insert("graph_cache".into(), self.graph_cache.serialized_size());
+        usage.insert("compare_cache".into(), self.compare_cache.serialized_size());
+        usage.insert("metrics_cache".into(), self.metrics_cache.serialized_size());
+
+        // The statistics are only updated when the epoch is advanced.
+        if jemalloc_ctl::epoch::advance().is_ok() {
+            if let Ok(allocated) = jemalloc_ctl::stats::allocated::read() {
+                usage.insert("allocated".into(), allocated);
+            }
+            if let Ok(resident) = jemalloc_ctl::stats::resident::read() {
+                usage.insert("resident".into(), resident);
+            }
+        }
+        usage
+    }
+
+    /// The merges into master since `since` which have no results, oldest
+    /// first, from the checkout of rust-lang/rust named by `RUST_CHECKOUT`.
+    pub fn unbenchmarked_commits(&self, since: Date) -> anyhow::Result<Vec<Commit>> {
+        let checkout = std::env::var_os("RUST_CHECKOUT").context("RUST_CHECKOUT is not set")?;
+        let history = master_history(Path::new(&checkout), Some(since))?;
+        let index = self.index.load();
+        Ok(history
+            .into_iter()
+            .filter(|c| index.commit_position(&c.sha).is_none())
+            .collect())
+    }
+
+    /// The master commit a try commit was built on, if it has been
+    /// benchmarked.
+    pub async fn merge_base(&self, try_commit: &Commit) -> Option<Commit> {
+        let parent = self.conn().await.parent_of(&try_commit.sha).await?;
+        let index = self.index.load();
+        let position = index.commit_position(&parent)?;
+        Some(index.sorted_commits()[position].clone())
+    }
+
     pub async fn conn(&self) -> Box<dyn database::pool::Connection> {
         self.pool.connection().await
     }
@@ -158,7 +737,7 @@ impl InputData {
 
         let index = self.index.load();
         let mut have = index
-            .commits()
+            .sorted_commits()
             .iter()
             .map(|commit| commit.sha.clone())
             .collect::<HashSet<_>>();
@@ -248,6 +827,7 @@ impl InputData {
     }
 }
 
-/// One decimal place rounded percent
+/// A percentage, serialized rounded like the other percentages in responses
+/// (see `util::round`).
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Percent(#[serde(with = "util::round_float")] pub f64);
+pub struct Percent(#[serde(serialize_with = "util::round::percent")] pub f64);
@@ -7,8 +7,6 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use env_logger;
-
 use futures::future::FutureExt;
 use parking_lot::RwLock;
 use site::load;
@@ -20,23 +18,46 @@ static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    database::logging::init();
     let _ = jemalloc_ctl::background_thread::write(true);
 
     let data: Arc<RwLock<Option<Arc<load::InputData>>>> = Arc::new(RwLock::new(None));
     let data_ = data.clone();
+    if env::args().any(|a| a == "--trace") {
+        database::trace::enable();
+        tokio::spawn(async {
+            loop {
+                tokio::time::delay_for(std::time::Duration::from_secs(60)).await;
+                eprint!("{}", database::trace::report());
+            }
+        });
+    }
+
     let db_url = env::var("DATABASE_URL")
         .ok()
-        .or_else(|| env::args().nth(1))
+        .or_else(|| env::args().skip(1).find(|a| !a.starts_with("--")))
         .unwrap_or_else(|| {
             eprintln!("Defaulting to loading from `results.db`");
             String::from("results.db")
         });
+    // Production keeps serving despite problems in the config or manifests;
+    // `--strict` (for CI) makes them fatal.
+    let mode = if env::args().any(|a| a == "--strict") {
+        load::LoadMode::Strict
+    } else {
+        load::LoadMode::Lenient
+    };
     let fut = tokio::task::spawn_blocking(move || {
         tokio::task::spawn(async move {
-            let res = Arc::new(load::InputData::from_fs(&db_url).await.unwrap());
+            let res = match load::InputData::from_fs(&db_url, mode).await {
+                Ok(res) => Arc::new(res),
+                Err(e) => {
+                    eprintln!("Loading failed: {:?}", e);
+                    std::process::exit(1);
+                }
+            };
             *data_.write() = Some(res.clone());
-            let commits = res.index.load().commits().len();
+            let commits = res.index.load().sorted_commits().len();
             let artifacts = res.index.load().artifacts().count();
             if commits + artifacts == 0 {
                 eprintln!("Loading complete but no data identified; exiting.");
@@ -47,6 +68,30 @@ async fn main() {
                 commits, artifacts,
             );
             eprintln!("View the results in a web browser at 'localhost:2346/compare.html'");
+            let data = res.clone();
+            tokio::spawn(async move {
+                site::server::refresh_landing_page(&data).await;
+            });
+            // Reloading the data takes minutes, so the config can be
+            // reloaded on its own with SIGHUP.
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+                let data = res.clone();
+                match signal(SignalKind::hangup()) {
+                    Ok(mut hangups) => {
+                        tokio::spawn(async move {
+                            while hangups.recv().await.is_some() {
+                                match data.reload_config().await {
+                                    Ok(()) => eprintln!("Reloaded the config"),
+                                    Err(e) => eprintln!("Reloading the config failed: {:?}", e),
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => eprintln!("Not reloading the config on SIGHUP: {:?}", e),
+                }
+            }
             // Spawn off a task to post the results of any commit results that we
             // are now aware of.
             site::github::post_finished(&res).await;
@@ -0,0 +1,102 @@
+//! The self time of a benchmark's self-profile queries summed into the broad
+//! phases of compilation, over a range of commits, for stacked area charts.
+//!
+//! Every phase gets a point at every commit, so that the stacks line up: a
+//! phase which a commit's profile doesn't have is zero there, and a commit
+//! without a profile at all takes the points of the nearest commit before it
+//! (or, at the start of the range, after it) which has one, marked as
+//! interpolated.
+
+use crate::api::phases::{Phase, Response};
+use crate::selector::SelfProfileData;
+
+/// The phases, bottom of the stack first.
+const PHASES: &[&str] = &["parsing", "typeck", "trans", "llvm", "other"];
+
+/// The phase a query belongs to, by its name.
+fn phase_of(label: &str) -> &'static str {
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|p| label.starts_with(p));
+    if starts(&["LLVM", "llvm"]) || label.contains("lto") {
+        "llvm"
+    } else if starts(&[
+        "codegen",
+        "trans",
+        "monomorphization",
+        "collect_and_partition_mono_items",
+        "symbol_name",
+        "link",
+    ]) {
+        "trans"
+    } else if starts(&[
+        "typeck",
+        "type_of",
+        "type_check",
+        "check_",
+        "coherent",
+        "wf_checking",
+        "item_types_checking",
+    ]) {
+        "typeck"
+    } else if starts(&[
+        "parse",
+        "expand",
+        "macro_expand",
+        "configure_and_expand",
+        "pre_AST_expansion",
+        "early_lint",
+        "resolve",
+        "hir_lowering",
+    ]) {
+        "parsing"
+    } else {
+        "other"
+    }
+}
+
+/// A profile's self time by phase, in seconds, in the order of `PHASES`.
+fn stack(profile: &SelfProfileData) -> Vec<f64> {
+    let mut stack = vec![0.0; PHASES.len()];
+    for qd in &profile.query_data {
+        let phase = phase_of(qd.label.as_str());
+        let idx = PHASES.iter().position(|p| *p == phase).unwrap();
+        stack[idx] += qd.self_time().as_secs_f64();
+    }
+    stack
+}
+
+/// Aligns the profiles of the commits (oldest first) into phases, or `None` if
+/// none of them has a profile.
+pub fn align(points: Vec<(String, Option<SelfProfileData>)>) -> Option<Response> {
+    let stacks = points
+        .iter()
+        .map(|(_, profile)| profile.as_ref().map(stack))
+        .collect::<Vec<_>>();
+    let commits = points.into_iter().map(|(sha, _)| sha).collect::<Vec<_>>();
+    let first = stacks.iter().position(|s| s.is_some())?;
+
+    let mut filled = Vec::with_capacity(stacks.len());
+    let mut is_interpolated = Vec::with_capacity(stacks.len());
+    let mut last = stacks[first].clone().unwrap();
+    for stack in stacks {
+        is_interpolated.push(stack.is_none());
+        if let Some(stack) = stack {
+            last = stack;
+        }
+        filled.push(last.clone());
+    }
+
+    let phases = PHASES
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| filled.iter().any(|stack| stack[*idx] > 0.0))
+        .map(|(idx, name)| Phase {
+            name: name.to_string(),
+            self_time: filled.iter().map(|stack| stack[idx] as f32).collect(),
+        })
+        .collect();
+    Some(Response {
+        commits,
+        is_interpolated,
+        phases,
+    })
+}
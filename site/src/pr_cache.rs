@@ -0,0 +1,142 @@
+//! The pull request of each commit, looked up on GitHub the first time it is
+//! needed and cached in the database after that.
+//!
+//! When GitHub's rate limit is hit, lookups of uncached commits fail without
+//! asking GitHub until the limit resets. Only benchmarked commits and full
+//! shas are looked up, and commits without a pull request are remembered (in
+//! memory), so that made-up shas can't use up the limit.
+
+use crate::load::InputData;
+use anyhow::Context as _;
+use database::CommitPr;
+use parking_lot::Mutex;
+use reqwest::header::{ACCEPT, USER_AGENT};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const REPOSITORY_URL: &str = "https://api.github.com/repos/rust-lang/rust";
+
+lazy_static::lazy_static! {
+    /// When GitHub may be asked again, after the rate limit was hit.
+    static ref RATE_LIMITED_UNTIL: Mutex<Option<SystemTime>> = Mutex::new(None);
+    /// The commits GitHub knows no pull request of.
+    static ref MISSES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// How many misses are remembered; past this, they're forgotten and start
+/// over.
+const MAX_MISSES: usize = 10_000;
+
+/// Whether `sha` is worth asking GitHub about: a benchmarked commit, or at
+/// least a well-formed full sha.
+fn is_lookup_candidate(data: &InputData, sha: &str) -> bool {
+    let is_full_sha = sha.len() == 40
+        && sha
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    is_full_sha || data.index.load().commit_position(sha).is_some()
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequest {
+    number: u32,
+    title: String,
+    user: User,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    login: String,
+}
+
+/// The pull request of `sha`, if it's already cached, without asking GitHub.
+pub async fn cached_pr_of(data: &InputData, sha: &str) -> Option<CommitPr> {
+    data.conn().await.commit_pr(sha).await
+}
+
+/// The pull request of `sha`, if it has one and it can be found.
+pub async fn pr_of(data: &InputData, sha: &str) -> Option<CommitPr> {
+    if !is_lookup_candidate(data, sha) || MISSES.lock().contains(sha) {
+        return None;
+    }
+    let conn = data.conn().await;
+    if let Some(pr) = conn.commit_pr(sha).await {
+        return Some(pr);
+    }
+    match fetch(data, sha).await {
+        Ok(Some(pr)) => {
+            conn.record_commit_pr(&pr).await;
+            Some(pr)
+        }
+        Ok(None) => {
+            let mut misses = MISSES.lock();
+            if misses.len() >= MAX_MISSES {
+                misses.clear();
+            }
+            misses.insert(sha.to_owned());
+            None
+        }
+        Err(e) => {
+            log::warn!("failed to look up the PR of {}: {:?}", sha, e);
+            None
+        }
+    }
+}
+
+async fn fetch(data: &InputData, sha: &str) -> anyhow::Result<Option<CommitPr>> {
+    if let Some(until) = *RATE_LIMITED_UNTIL.lock() {
+        if SystemTime::now() < until {
+            anyhow::bail!("rate limited until {:?}", until);
+        }
+    }
+
+    let mut request = reqwest::Client::new()
+        .get(&format!("{}/commits/{}/pulls", REPOSITORY_URL, sha))
+        .header(USER_AGENT, "perf-rust-lang-org-server")
+        // Listing the pull requests of a commit is a preview API.
+        .header(ACCEPT, "application/vnd.github.groot-preview+json");
+    if let Some(token) = &data.config().keys.github {
+        request = request.basic_auth("rust-timer", Some(token));
+    }
+    let response = request.send().await.context("GET commits/pulls failed")?;
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    let rate_limited = response.status() == StatusCode::TOO_MANY_REQUESTS
+        || (response.status() == StatusCode::FORBIDDEN
+            && header("x-ratelimit-remaining") == Some(0));
+    if rate_limited {
+        let until = match header("x-ratelimit-reset") {
+            Some(reset) => UNIX_EPOCH + Duration::from_secs(reset),
+            None => SystemTime::now() + Duration::from_secs(60),
+        };
+        *RATE_LIMITED_UNTIL.lock() = Some(until);
+        anyhow::bail!("rate limited until {:?}", until);
+    }
+
+    // Commits GitHub doesn't have at all.
+    if response.status() == StatusCode::NOT_FOUND
+        || response.status() == StatusCode::UNPROCESSABLE_ENTITY
+    {
+        return Ok(None);
+    }
+    let prs = response
+        .error_for_status()?
+        .json::<Vec<PullRequest>>()
+        .await
+        .context("deserializing failed")?;
+    // A merge commit is only in the pull request it merged.
+    Ok(prs.into_iter().next().map(|pr| CommitPr {
+        sha: sha.to_owned(),
+        pr: pr.number,
+        title: pr.title,
+        author: pr.user.login,
+    }))
+}
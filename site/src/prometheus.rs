@@ -0,0 +1,135 @@
+//! The latest master commit's results in the Prometheus text exposition
+//! format, served at `/perf/metrics` for Prometheus (and so Grafana) to scrape.
+//!
+//! Samples aren't timestamped, so Prometheus records them at each scrape; a
+//! series steps when a new merge is benchmarked. Which commit that is, is
+//! exposed by `rustc_perf_commit_info` rather than as a label of every series,
+//! which would start new series at every merge.
+//!
+//! The output only changes when new data is loaded, so it's rendered once and
+//! kept in `InputData::metrics_cache` until then.
+
+use crate::load::InputData;
+use database::{ArtifactId, DbLabel, Lookup};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Escapes a label value: backslashes, double quotes and newlines.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the statistics of every benchmark of the latest master commit and,
+/// with `phases`, the self time of every self-profile query (which is many
+/// more series, each needing a database query per benchmark), or returns them
+/// as last rendered since the data was loaded.
+pub async fn render(data: &InputData, phases: bool) -> Arc<String> {
+    let key = if phases { "phases" } else { "statistics" };
+    if let Some(out) = data.metrics_cache.get(key) {
+        return out;
+    }
+    let out = Arc::new(render_uncached(data, phases).await);
+    data.metrics_cache.insert(key.to_string(), out.clone());
+    out
+}
+
+async fn render_uncached(data: &InputData, phases: bool) -> String {
+    let index = data.index.load();
+    let commit = match index.sorted_commits().iter().rev().find(|c| !c.is_try()) {
+        Some(commit) => commit.clone(),
+        None => return String::new(),
+    };
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# HELP rustc_perf_commit_info The latest benchmarked master commit, which the other metrics are of."
+    )
+    .unwrap();
+    writeln!(out, "# TYPE rustc_perf_commit_info gauge").unwrap();
+    writeln!(
+        out,
+        "rustc_perf_commit_info{{sha=\"{}\",date=\"{}\"}} 1",
+        escape(&commit.sha),
+        commit.date
+    )
+    .unwrap();
+    let aid = match ArtifactId::Commit(commit).lookup(&index) {
+        Some(aid) => aid,
+        None => return out,
+    };
+
+    writeln!(
+        out,
+        "# HELP rustc_perf_statistic A statistic of a benchmark, at the latest benchmarked master commit."
+    )
+    .unwrap();
+    writeln!(out, "# TYPE rustc_perf_statistic gauge").unwrap();
+    let mut series = index.all_pstat_series().copied().collect::<Vec<_>>();
+    series.sort_by_key(|&(krate, profile, cache, stat)| (krate, profile, cache.to_string(), stat));
+    let pstats = data.pstats.load();
+    for (krate, profile, cache, stat) in series {
+        let label = DbLabel::ProcessStat {
+            krate,
+            profile,
+            cache,
+            stat,
+        };
+        let value = match label
+            .lookup(&index)
+            .and_then(|sid| pstats.get(sid, &[Some(aid)])[0])
+        {
+            Some(value) => value,
+            None => continue,
+        };
+        writeln!(
+            out,
+            "rustc_perf_statistic{{crate=\"{}\",profile=\"{}\",cache=\"{}\",statistic=\"{}\"}} {}",
+            escape(krate.as_str()),
+            profile,
+            escape(&cache.to_string()),
+            escape(stat.as_str()),
+            value
+        )
+        .unwrap();
+    }
+
+    if !phases {
+        return out;
+    }
+    writeln!(
+        out,
+        "# HELP rustc_perf_query_self_seconds The self time of a query (compiler phase) in a benchmark, at the latest benchmarked master commit."
+    )
+    .unwrap();
+    writeln!(out, "# TYPE rustc_perf_query_self_seconds gauge").unwrap();
+    let benchmarks = index
+        .all_query_series()
+        .map(|&(krate, profile, cache, _)| (krate, profile, cache.to_string()))
+        .collect::<BTreeSet<_>>();
+    let conn = data.conn().await;
+    for (krate, profile, cache) in benchmarks {
+        let queries = conn
+            .get_self_profile(aid, krate.as_str(), &profile.to_string(), &cache)
+            .await;
+        let mut queries = queries.into_iter().collect::<Vec<_>>();
+        queries.sort_by_key(|(label, _)| *label);
+        for (label, qd) in queries {
+            writeln!(
+                out,
+                "rustc_perf_query_self_seconds{{crate=\"{}\",profile=\"{}\",cache=\"{}\",query=\"{}\"}} {}",
+                escape(krate.as_str()),
+                profile,
+                escape(&cache),
+                escape(label.as_str()),
+                qd.self_time.as_secs_f64()
+            )
+            .unwrap();
+        }
+    }
+    out
+}
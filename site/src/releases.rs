@@ -0,0 +1,110 @@
+//! Benchmark totals at each stable release and at the latest beta and nightly,
+//! for a dashboard of long-term progress release over release. Computed
+//! whenever the data is (re)loaded, for every statistic, and served at
+//! `/perf/releases`.
+//!
+//! Releases are the artifacts named like `1.45.0`; the latest beta is the
+//! newest `beta-<date>` artifact, and the nightly is the latest benchmarked
+//! master commit. So that totals are comparable from one to the next, each
+//! profile's total is only over the benchmarks (and caches) measured at all of
+//! them.
+
+use crate::api::releases::Response;
+use database::{ArtifactId, ArtifactIdNumber, DbLabel, Index, Lookup, PstatCache};
+use std::collections::{BTreeMap, HashMap};
+
+/// The releases series of each statistic.
+#[derive(Default)]
+pub struct Releases {
+    by_stat: HashMap<String, Response>,
+}
+
+impl Releases {
+    pub fn new(index: &Index, pstats: &PstatCache) -> Releases {
+        let _span = database::trace::span("releases");
+        let mut stable = index
+            .artifacts()
+            .filter_map(|a| Some((a.parse::<semver::Version>().ok()?, a)))
+            .collect::<Vec<_>>();
+        stable.sort();
+        let mut artifacts = stable
+            .into_iter()
+            .map(|(_, a)| ArtifactId::Artifact(a.to_string()))
+            .collect::<Vec<_>>();
+        // The dates sort as strings.
+        if let Some(beta) = index.artifacts().filter(|a| a.starts_with("beta-")).max() {
+            artifacts.push(ArtifactId::Artifact(beta.to_string()));
+        }
+        if let Some(nightly) = index.sorted_commits().iter().rev().find(|c| !c.is_try()) {
+            artifacts.push(ArtifactId::Commit(nightly.clone()));
+        }
+        let aids = artifacts
+            .iter()
+            .filter_map(|a| Some((a.clone(), a.lookup(index)?)))
+            .collect::<Vec<_>>();
+        if aids.is_empty() {
+            return Releases::default();
+        }
+        let names = aids
+            .iter()
+            .map(|(a, _)| match a {
+                ArtifactId::Commit(c) => format!("nightly: {}", &c.sha[..c.sha.len().min(8)]),
+                ArtifactId::Artifact(a) => a.clone(),
+            })
+            .collect::<Vec<_>>();
+        let aids = aids.into_iter().map(|(_, aid)| aid).collect::<Vec<_>>();
+
+        let mut by_stat = HashMap::new();
+        for &(krate, profile, cache, stat) in index.all_pstat_series() {
+            let label = DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            };
+            let sid = match label.lookup(index) {
+                Some(sid) => sid,
+                None => continue,
+            };
+            let values = match values(pstats, sid, &aids) {
+                Some(values) => values,
+                None => continue,
+            };
+            let response = by_stat.entry(stat.to_string()).or_insert_with(|| Response {
+                artifacts: names.clone(),
+                totals: BTreeMap::new(),
+                benchmarks: BTreeMap::new(),
+            });
+            let totals = response
+                .totals
+                .entry(profile.to_string())
+                .or_insert_with(|| vec![0.0; aids.len()]);
+            for (total, value) in totals.iter_mut().zip(values) {
+                *total += value;
+            }
+            *response.benchmarks.entry(profile.to_string()).or_insert(0) += 1;
+        }
+        Releases { by_stat }
+    }
+
+    pub fn get(&self, stat: &str) -> Option<&Response> {
+        self.by_stat.get(stat)
+    }
+
+    /// Approximately, the bytes on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.by_stat
+            .iter()
+            .map(|(stat, r)| {
+                stat.len()
+                    + r.artifacts.iter().map(|a| a.len()).sum::<usize>()
+                    + r.totals.values().map(|t| t.len() * 8).sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+/// A series' values at every one of `aids`, or `None` if it's missing any.
+fn values(pstats: &PstatCache, sid: u32, aids: &[ArtifactIdNumber]) -> Option<Vec<f64>> {
+    aids.iter().map(|&aid| pstats.value(sid, aid)).collect()
+}
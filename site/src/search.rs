@@ -0,0 +1,63 @@
+//! Prefix search over the benchmarked crates and the self-profile queries
+//! ("phases"), so that selection UIs needn't list them all as the suite grows.
+//!
+//! Matching ignores case. With a category, only the crates of that category in
+//! the benchmark manifests match, and only the queries profiled in those
+//! crates.
+
+use crate::api::search::Response;
+use collector::manifest::{BenchmarkConfig, Category};
+use database::Index;
+use std::collections::{BTreeSet, HashSet};
+
+/// How many results there are at most, unless the request says otherwise.
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// The most results a request can ask for.
+const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Crate,
+    Phase,
+}
+
+pub fn search(
+    index: &Index,
+    benchmarks: &[BenchmarkConfig],
+    kind: Kind,
+    prefix: &str,
+    category: Option<Category>,
+    limit: usize,
+) -> Response {
+    let prefix = prefix.to_lowercase();
+    let in_category = category.map(|category| {
+        benchmarks
+            .iter()
+            .filter(|b| b.category == category)
+            .map(|b| b.name.as_str())
+            .collect::<HashSet<_>>()
+    });
+    let included = |krate: &str| in_category.as_ref().map_or(true, |c| c.contains(krate));
+    let matches = match kind {
+        Kind::Crate => index
+            .all_pstat_series()
+            .map(|s| s.0.as_str())
+            .filter(|k| included(k))
+            .filter(|k| k.to_lowercase().starts_with(&prefix))
+            .map(String::from)
+            .collect::<BTreeSet<_>>(),
+        Kind::Phase => index
+            .all_query_series()
+            .filter(|s| included(s.0.as_str()))
+            .map(|s| s.3.as_str())
+            .filter(|q| q.to_lowercase().starts_with(&prefix))
+            .map(String::from)
+            .collect::<BTreeSet<_>>(),
+    };
+    let limit = limit.min(MAX_LIMIT);
+    Response {
+        truncated: matches.len() > limit,
+        results: matches.into_iter().take(limit).collect(),
+    }
+}
@@ -27,58 +27,144 @@ use crate::interpolate::Interpolate;
 use crate::load::InputData as Db;
 use async_trait::async_trait;
 use collector::Bound;
-use database::{Commit, Crate, Index, Lookup, ProcessStatistic, QueryLabel};
+use database::{Commit, Crate, Index, Lookup, ProcessStatistic, QueryLabel, Resolution};
 use std::convert::TryInto;
 use std::fmt;
-use std::ops::RangeInclusive;
+use std::ops::{self, RangeBounds, RangeInclusive};
 use std::sync::Arc;
 
 pub fn data_for(data: &Index, is_left: bool, query: Bound) -> Option<ArtifactId> {
-    let commits = data.commits();
-    let commit = if is_left {
-        commits
-            .iter()
-            .find(|commit| query.left_match(commit))
-            .cloned()
+    let idx = if is_left {
+        resolve_range(data, &query..).map(|r| *r.start())
     } else {
-        commits
-            .iter()
-            .rfind(|commit| query.left_match(commit))
-            .cloned()
+        resolve_range(data, ..=&query).map(|r| *r.end())
     };
+    let commit = idx.map(|idx| data.sorted_commits()[idx].clone());
     commit.map(|c| ArtifactId::Commit(c)).or_else(|| {
         data.artifacts()
             .find(|aid| match &query {
                 Bound::Commit(c) => *c == **aid,
                 Bound::Date(_) => false,
+                Bound::Count(_) => false,
                 Bound::None => false,
             })
             .map(|aid| ArtifactId::Artifact(aid.to_string()))
     })
 }
 
-pub fn range_subset(data: Vec<Commit>, range: RangeInclusive<Bound>) -> Vec<Commit> {
-    let (a, b) = range.into_inner();
+/// The position of the first commit which `bound.left_match`es.
+///
+/// The commits are (very nearly) sorted by date, and a date bound (or none)
+/// matches every commit from some date on, so that one is found by binary
+/// search. A commit bound is looked up by its sha or nightly alias.
+fn first_match(index: &Index, bound: &Bound) -> Option<usize> {
+    let commits = index.sorted_commits();
+    match bound {
+        Bound::Commit(sha) => index.resolve_commit(sha),
+        Bound::Count(_) => None,
+        Bound::Date(_) | Bound::None => {
+            let idx = commits
+                .binary_search_by(|c| {
+                    if bound.left_match(c) {
+                        std::cmp::Ordering::Greater
+                    } else {
+                        std::cmp::Ordering::Less
+                    }
+                })
+                .unwrap_err();
+            if idx < commits.len() {
+                Some(idx)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// The position of the last commit which `bound.left_match`es.
+fn last_match(index: &Index, bound: &Bound) -> Option<usize> {
+    let commits = index.sorted_commits();
+    match bound {
+        Bound::Commit(sha) => index.resolve_commit(sha),
+        Bound::Count(_) => None,
+        // As above, if any commit matches, the last one does.
+        Bound::Date(_) | Bound::None => commits
+            .last()
+            .filter(|c| bound.left_match(c))
+            .map(|_| commits.len() - 1),
+    }
+}
 
-    let left_idx = data.iter().position(|commit| a.left_match(commit));
-    let right_idx = data.iter().rposition(|commit| b.left_match(commit));
+/// One end of a range, before any count in it is resolved.
+enum End {
+    At(usize),
+    Count(usize),
+}
 
-    if let (Some(left), Some(right)) = (left_idx, right_idx) {
-        data.get(left..=right)
-            .map(|s| s.to_vec())
-            .unwrap_or_else(|| {
-                log::error!(
-                    "Failed to compute left/right indices from {:?}..={:?}",
-                    a,
-                    b
-                );
-                vec![]
-            })
+fn resolve_end(index: &Index, bound: ops::Bound<&Bound>, is_left: bool) -> Option<End> {
+    let len = index.sorted_commits().len();
+    let (bound, excluded) = match bound {
+        ops::Bound::Unbounded if is_left => return Some(End::At(0)),
+        ops::Bound::Unbounded => return len.checked_sub(1).map(End::At),
+        ops::Bound::Included(bound) => (bound, false),
+        ops::Bound::Excluded(bound) => (bound, true),
+    };
+    if let Bound::Count(n) = *bound {
+        // Excluding the far end of a count leaves one fewer commit.
+        return Some(End::Count(if excluded { n.saturating_sub(1) } else { n }));
+    }
+    if is_left {
+        let idx = first_match(index, bound)?;
+        Some(End::At(if excluded { idx + 1 } else { idx }))
     } else {
-        vec![]
+        let idx = last_match(index, bound)?;
+        if excluded {
+            idx.checked_sub(1).map(End::At)
+        } else {
+            Some(End::At(idx))
+        }
     }
 }
 
+/// The positions in `sorted_commits` of the commits in `range`, or `None` if
+/// there are none. Every endpoint resolves its range with this.
+///
+/// Either end may be a date, a sha (or nightly alias) or a number of commits
+/// counted from the other end. On the left, `Bound::None` is the last month;
+/// on the right, it's the newest commit. An unbounded end is the oldest or
+/// newest commit. If both ends are counts, the end is counted from the oldest
+/// commit.
+pub fn resolve_range(
+    index: &Index,
+    range: impl RangeBounds<Bound>,
+) -> Option<RangeInclusive<usize>> {
+    let len = index.sorted_commits().len();
+    if len == 0 {
+        return None;
+    }
+    let start = resolve_end(index, range.start_bound(), true)?;
+    let end = resolve_end(index, range.end_bound(), false)?;
+    let (start, end) = match (start, end) {
+        (End::At(start), End::At(end)) => (start, end),
+        (End::Count(n), End::At(end)) => ((end + 1).saturating_sub(n), end),
+        (End::At(start), End::Count(n)) => (start, (start + n).checked_sub(1)?.min(len - 1)),
+        (End::Count(n), End::Count(m)) => {
+            let end = m.checked_sub(1)?.min(len - 1);
+            ((end + 1).saturating_sub(n), end)
+        }
+    };
+    if start <= end && end < len {
+        Some(start..=end)
+    } else {
+        None
+    }
+}
+
+pub fn range_subset(index: &Index, range: impl RangeBounds<Bound>) -> Vec<Commit> {
+    resolve_range(index, range)
+        .map_or_else(Vec::new, |range| index.sorted_commits()[range].to_vec())
+}
+
 struct ArtifactIdIter {
     s: Arc<Vec<ArtifactId>>,
     idx: usize,
@@ -300,6 +386,8 @@ impl Path {
 #[derive(Clone, Hash, Eq, PartialEq)]
 pub struct Query {
     path: Vec<QueryComponent>,
+    /// Only honored by process statistics.
+    resolution: Resolution,
 }
 
 impl fmt::Debug for Query {
@@ -311,6 +399,9 @@ impl fmt::Debug for Query {
             }
             write!(f, "{:?}={:?}", qc.tag, qc.raw)?;
         }
+        if self.resolution != Resolution::Commit {
+            write!(f, " by {:?}", self.resolution)?;
+        }
         write!(f, " }}")?;
         Ok(())
     }
@@ -318,7 +409,17 @@ impl fmt::Debug for Query {
 
 impl Query {
     pub fn new() -> Self {
-        Self { path: vec![] }
+        Self {
+            path: vec![],
+            resolution: Resolution::Commit,
+        }
+    }
+
+    /// Reads process statistics at the given resolution, in which case the
+    /// queried artifacts should be those standing for each period.
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
     }
 
     pub fn set<T>(mut self, tag: Tag, selector: Selector<T>) -> Self
@@ -555,6 +656,7 @@ impl ProcessStatisticSeries {
             .extract(Tag::ProcessStatistic)?
             .raw
             .try_map(|p| p.parse::<ProcessStatistic>())?;
+        let resolution = query.resolution;
         query.assert_empty()?;
 
         let index = db.index.load();
@@ -587,15 +689,17 @@ impl ProcessStatisticSeries {
             .map(|cid| cid.lookup(&index))
             .collect::<Vec<_>>();
 
-        let mut conn = db.conn().await;
-        let mut tx = conn.transaction().await;
+        let pstats = db.pstats.load();
+        let downsampled = db.downsampled.load();
+        let pstats = match resolution {
+            Resolution::Commit => &**pstats,
+            resolution => &downsampled[&resolution].pstats,
+        };
 
         let start = std::time::Instant::now();
-        let res = tx
-            .conn()
-            .get_pstats(&sids, &cids)
-            .await
-            .into_iter()
+        let res = sids
+            .iter()
+            .map(|&sid| pstats.get(sid, &cids))
             .enumerate()
             .map(|(idx, points)| {
                 let path = &series[idx];
@@ -13,13 +13,19 @@ type Response = http::Response<hyper::Body>;
 
 pub mod crox;
 pub mod flamegraph;
+pub mod speedscope;
 
 pub struct Output {
     pub data: Vec<u8>,
     pub filename: &'static str,
     pub is_download: bool,
+    /// The viewer allowed to fetch the output from the browser.
+    pub allow_origin: &'static str,
 }
 
+const FIREFOX_PROFILER: &str = "https://profiler.firefox.com";
+const SPEEDSCOPE: &str = "https://www.speedscope.app";
+
 pub fn generate(
     title: &str,
     pieces: Pieces,
@@ -34,6 +40,29 @@ pub fn generate(
                 filename: "chrome_profiler.json",
                 data: crox::generate(pieces, opt).context("crox")?,
                 is_download: true,
+                allow_origin: FIREFOX_PROFILER,
+            })
+        }
+        // The Firefox Profiler imports Chrome's format, but fetches it from
+        // here rather than having it downloaded.
+        Some("firefox") => {
+            let opt = serde_json::from_str(&serde_json::to_string(&params).unwrap())
+                .context("crox opts")?;
+            Ok(Output {
+                filename: "chrome_profiler.json",
+                data: crox::generate(pieces, opt).context("crox")?,
+                is_download: false,
+                allow_origin: FIREFOX_PROFILER,
+            })
+        }
+        Some("speedscope") => {
+            let opt = serde_json::from_str(&serde_json::to_string(&params).unwrap())
+                .context("speedscope opts")?;
+            Ok(Output {
+                filename: "speedscope.json",
+                data: speedscope::generate(title, pieces, opt).context("speedscope")?,
+                is_download: false,
+                allow_origin: SPEEDSCOPE,
             })
         }
         Some("flamegraph") => {
@@ -43,9 +72,10 @@ pub fn generate(
                 filename: "flamegraph.svg",
                 data: flamegraph::generate(title, pieces, opt).context("flame")?,
                 is_download: false,
+                allow_origin: FIREFOX_PROFILER,
             })
         }
-        _ => anyhow::bail!("Unknown type, specify type={crox,flamegraph}"),
+        _ => anyhow::bail!("Unknown type, specify type={crox,firefox,flamegraph,speedscope}"),
     }
 }
 
@@ -0,0 +1,150 @@
+//! Converts a self-profile into speedscope's own format
+//! (https://www.speedscope.app/file-format-schema.json): an "evented" profile
+//! of opening and closing frames per thread, with a frame per event label.
+
+use analyzeme::ProfilingData;
+use hashbrown::HashMap;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::time::Duration;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Opt {}
+
+#[derive(Serialize)]
+struct Frame {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    frame: usize,
+    at: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Profile {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    unit: &'static str,
+    start_value: u64,
+    end_value: u64,
+    events: Vec<Event>,
+}
+
+#[derive(Serialize)]
+struct Shared {
+    frames: Vec<Frame>,
+}
+
+#[derive(Serialize)]
+struct File {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    name: String,
+    exporter: &'static str,
+    shared: Shared,
+    profiles: Vec<Profile>,
+}
+
+fn nanos(d: Duration) -> u64 {
+    d.as_nanos() as u64
+}
+
+/// Returns speedscope JSON, with times in nanoseconds since the start of the
+/// profile.
+pub fn generate(title: &str, pieces: super::Pieces, _: Opt) -> anyhow::Result<Vec<u8>> {
+    let data = ProfilingData::from_buffers(pieces.string_data, pieces.string_index, pieces.events)
+        .map_err(|e| anyhow::format_err!("{:?}", e))?;
+    let start_time = data.metadata.start_time;
+
+    let mut frames = Vec::new();
+    let mut frame_ids = HashMap::new();
+    // thread -> (start, end, frame)
+    let mut threads: HashMap<u32, Vec<(u64, u64, usize)>> = HashMap::new();
+    for event in data.iter().filter(|e| !e.timestamp.is_instant()) {
+        let label = event.to_event().label.into_owned();
+        let frame = *frame_ids.entry(label.clone()).or_insert_with(|| {
+            frames.push(Frame { name: label });
+            frames.len() - 1
+        });
+        let start = nanos(
+            event
+                .timestamp
+                .start()
+                .duration_since(start_time)
+                .unwrap_or_default(),
+        );
+        let end = start + nanos(event.duration().unwrap_or_default());
+        threads
+            .entry(event.thread_id)
+            .or_default()
+            .push((start, end, frame));
+    }
+
+    let mut thread_ids = threads.keys().copied().collect::<Vec<_>>();
+    thread_ids.sort_unstable();
+    let profiles = thread_ids
+        .into_iter()
+        .map(|thread_id| {
+            let mut intervals = threads.remove(&thread_id).unwrap();
+            // Parents before their children, which start at the same time at
+            // the earliest and end sooner.
+            intervals.sort_unstable_by_key(|&(start, end, _)| (start, Reverse(end)));
+            let mut events = Vec::with_capacity(intervals.len() * 2);
+            let mut stack: Vec<(u64, usize)> = Vec::new();
+            let mut end_value = 0;
+            for (start, end, frame) in intervals {
+                while let Some(&(top_end, top_frame)) = stack.last() {
+                    if top_end > start {
+                        break;
+                    }
+                    stack.pop();
+                    events.push(Event {
+                        kind: "C",
+                        frame: top_frame,
+                        at: top_end,
+                    });
+                }
+                // speedscope rejects frames which aren't properly nested, so
+                // clip any which outlive their parent.
+                let end = stack.last().map_or(end, |&(top_end, _)| end.min(top_end));
+                events.push(Event {
+                    kind: "O",
+                    frame,
+                    at: start,
+                });
+                stack.push((end, frame));
+                end_value = end_value.max(end);
+            }
+            while let Some((end, frame)) = stack.pop() {
+                events.push(Event {
+                    kind: "C",
+                    frame,
+                    at: end,
+                });
+            }
+            Profile {
+                kind: "evented",
+                name: format!("thread {}", thread_id),
+                unit: "nanoseconds",
+                start_value: 0,
+                end_value,
+                events,
+            }
+        })
+        .collect();
+
+    let file = File {
+        schema: "https://www.speedscope.app/file-format-schema.json",
+        name: title.to_owned(),
+        exporter: "rustc-perf",
+        shared: Shared { frames },
+        profiles,
+    };
+    Ok(serde_json::to_vec(&file)?)
+}
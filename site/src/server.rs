@@ -10,7 +10,7 @@
 use bytes::buf::BufExt;
 use parking_lot::Mutex;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::fs;
@@ -39,15 +39,17 @@ type Request = http::Request<hyper::Body>;
 type Response = http::Response<hyper::Body>;
 
 pub use crate::api::{
-    self, bootstrap, dashboard, data, days, github, graph, info, self_profile, self_profile_raw,
-    status, CommitResponse, DateData, ServerResult, StyledBenchmarkName,
+    self, artifacts, benchmark_detail, benchmarks, bootstrap, commit_info, dashboard, data, days,
+    github, grafana, graph, info, nearest, phases, releases, search, self_profile,
+    self_profile_diff, self_profile_raw, status, unbenchmarked, zulip, CommitResponse, DateData,
+    ServerResult, StyledBenchmarkName,
 };
 use crate::db::{self, Cache, Crate, Profile};
 use crate::interpolate::Interpolated;
 use crate::load::{Config, InputData};
 use crate::selector::{self, PathComponent, Tag};
 use collector::Bound;
-use db::{ArtifactId, Lookup};
+use db::{ArtifactId, JobKind, Lookup};
 use parking_lot::RwLock;
 
 static INTERPOLATED_COLOR: &str = "#fcb0f1";
@@ -57,7 +59,43 @@ pub fn handle_info(data: &InputData) -> info::Response {
     stats.sort();
     info::Response {
         stats,
-        as_of: data.index.load().commits().last().map(|d| d.date),
+        as_of: data.index.load().sorted_commits().last().map(|d| d.date),
+        benchmarks: data
+            .benchmarks
+            .iter()
+            .map(|b| info::Benchmark {
+                name: b.name.clone(),
+                category: b.category,
+                weight: b.weight,
+                patches: b.patches.iter().flatten().map(|p| p.name.clone()).collect(),
+            })
+            .collect(),
+    }
+}
+
+pub fn handle_benchmarks(data: &InputData) -> benchmarks::Response {
+    benchmarks::Response(data.benchmarks.iter().map(benchmark_info).collect())
+}
+
+pub fn benchmark_info(b: &collector::manifest::BenchmarkConfig) -> benchmarks::Benchmark {
+    benchmarks::Benchmark {
+        name: b.name.clone(),
+        category: b.category,
+        weight: b.weight,
+        description: b.description.clone(),
+        rationale: b.rationale.clone(),
+        source: b.source.clone(),
+        noise: b.noise.clone(),
+        supports_stable: b.supports_stable,
+        patches: b
+            .patches
+            .iter()
+            .flatten()
+            .map(|p| benchmarks::Patch {
+                name: p.name.clone(),
+                description: p.description.clone(),
+            })
+            .collect(),
     }
 }
 
@@ -65,6 +103,7 @@ pub struct ByProfile<T> {
     pub check: T,
     pub debug: T,
     pub doc: T,
+    pub doc_json: T,
     pub opt: T,
 }
 
@@ -78,6 +117,7 @@ impl<T> ByProfile<T> {
             check: f(Profile::Check).await?,
             debug: f(Profile::Debug).await?,
             doc: f(Profile::Doc).await?,
+            doc_json: f(Profile::DocJson).await?,
             opt: f(Profile::Opt).await?,
         })
     }
@@ -90,6 +130,7 @@ impl<T> std::ops::Index<Profile> for ByProfile<T> {
             Profile::Check => &self.check,
             Profile::Debug => &self.debug,
             Profile::Doc => &self.doc,
+            Profile::DocJson => &self.doc_json,
             Profile::Opt => &self.opt,
         }
     }
@@ -161,9 +202,7 @@ pub async fn handle_dashboard(data: Arc<InputData>) -> ServerResult<dashboard::R
         versions
             .into_iter()
             .map(|v| db::ArtifactId::Artifact(v.to_string()))
-            .chain(std::iter::once(
-                data.index.load().commits().last().unwrap().clone().into(),
-            ))
+            .chain(index.sorted_commits().last().cloned().map(Into::into))
             .collect::<Vec<_>>(),
     );
 
@@ -228,8 +267,7 @@ pub async fn handle_dashboard(data: Arc<InputData>) -> ServerResult<dashboard::R
             Ok(cases)
         }
     })
-    .await
-    .unwrap();
+    .await?;
 
     Ok(dashboard::Response {
         versions: cids
@@ -245,6 +283,12 @@ pub async fn handle_dashboard(data: Arc<InputData>) -> ServerResult<dashboard::R
     })
 }
 
+/// The benchmark totals at each release in a statistic; see `releases`.
+pub fn handle_releases(data: &InputData, stat: &str) -> Option<releases::Response> {
+    log::info!("handle_releases(stat: {})", stat);
+    data.releases.load().get(stat).cloned()
+}
+
 fn prettify_log(log: &str) -> Option<String> {
     let mut lines = log.lines();
     let first = lines.next()?;
@@ -255,7 +299,7 @@ fn prettify_log(log: &str) -> Option<String> {
 
 pub async fn handle_status_page(data: Arc<InputData>) -> status::Response {
     let idx = data.index.load();
-    let last_commit = idx.commits().last().cloned();
+    let last_commit = idx.sorted_commits().last().cloned();
 
     let missing = data.missing_commits().await;
     // FIXME: no current builds
@@ -305,8 +349,13 @@ pub async fn handle_status_page(data: Arc<InputData>) -> status::Response {
         })
         .collect::<Vec<_>>();
 
-    benchmark_state.sort_by_key(|s| s.error.is_some());
-    benchmark_state.reverse();
+    // Failures first, then by name.
+    benchmark_state.sort_by(|a, b| {
+        b.error
+            .is_some()
+            .cmp(&a.error.is_some())
+            .then_with(|| a.name.cmp(&b.name))
+    });
 
     status::Response {
         last_commit,
@@ -314,40 +363,76 @@ pub async fn handle_status_page(data: Arc<InputData>) -> status::Response {
         missing,
         current,
         most_recent_end: conn.last_end_time().await.map(|d| d.timestamp()),
+        memory: data.memory_usage(),
+        load_problems: data
+            .config_problems
+            .load()
+            .iter()
+            .chain(&data.load_problems)
+            .cloned()
+            .collect(),
+        duplicates: conn.duplicate_uploads().await,
     }
 }
 
-pub async fn handle_next_commit(data: Arc<InputData>) -> collector::api::next_commit::Response {
-    let commit = data.missing_commits().await.into_iter().next().map(|c| {
-        let (include, exclude, runs) = match c.1 {
-            crate::load::MissingReason::Try {
+pub async fn handle_next_commit(
+    data: Arc<InputData>,
+    collector: String,
+) -> collector::api::next_commit::Response {
+    use crate::load::MissingReason;
+
+    let conn = data.conn().await;
+
+    // Make sure every missing commit is in the job queue; jobs which are
+    // already queued are left untouched.
+    let queued = conn
+        .queued_jobs()
+        .await
+        .into_iter()
+        .map(|job| job.artifact)
+        .collect::<HashSet<_>>();
+    for (commit, reason) in data.missing_commits().await {
+        if queued.contains(&commit.sha) {
+            continue;
+        }
+        let reason = match reason {
+            MissingReason::InProgress(Some(previous)) => *previous,
+            reason => reason,
+        };
+        let (kind, include, exclude, runs) = match reason {
+            MissingReason::Try {
                 include,
                 exclude,
                 runs,
                 ..
-            } => (include, exclude, runs),
-            crate::load::MissingReason::InProgress(Some(previous)) => {
-                if let crate::load::MissingReason::Try {
-                    include,
-                    exclude,
-                    runs,
-                    ..
-                } = *previous
-                {
-                    (include, exclude, runs)
-                } else {
-                    (None, None, None)
-                }
+            } => (JobKind::Try, include, exclude, runs),
+            // The parent must be benchmarked for the try results to be
+            // useful, so it gets the same priority as the try commit itself.
+            MissingReason::TryParent => (JobKind::Try, None, None, None),
+            MissingReason::Master { .. } | MissingReason::InProgress(_) => {
+                (JobKind::Master, None, None, None)
             }
-            _ => (None, None, None),
         };
-        collector::api::next_commit::Commit {
-            sha: c.0.sha,
-            include,
-            exclude,
+        conn.enqueue_job(
+            &commit.sha,
+            kind,
+            include.as_deref(),
+            exclude.as_deref(),
             runs,
-        }
-    });
+        )
+        .await;
+    }
+
+    let commit = conn
+        .claim_job(&collector)
+        .await
+        .map(|job| collector::api::next_commit::Commit {
+            sha: job.artifact,
+            include: job.include,
+            exclude: job.exclude,
+            runs: job.runs,
+            kind: Some(job.kind),
+        });
 
     collector::api::next_commit::Response { commit }
 }
@@ -384,34 +469,58 @@ impl CommitIdxCache {
     }
 }
 
+/// The geometric mean of every crate's series at each commit, served as the
+/// `Geomean` benchmark: one headline line per profile and cache, in the
+/// statistic's own units. Series with a value of zero (or less) anywhere have
+/// no logarithm, so are left out.
+fn geomean(
+    points: &[Vec<(db::ArtifactId, Option<f64>)>],
+) -> Vec<((db::ArtifactId, Option<f64>), Interpolated)> {
+    let logs = points
+        .iter()
+        .filter(|points| points.iter().all(|(_, v)| v.map_or(true, |v| v > 0.0)))
+        .filter(|points| points.iter().any(|(_, v)| v.is_some()))
+        .map(|points| {
+            crate::interpolate::Interpolate::new(
+                points.iter().map(|(c, v)| (c.clone(), v.map(f64::ln))),
+            )
+        })
+        .collect::<Vec<_>>();
+    if logs.is_empty() {
+        return Vec::new();
+    }
+    db::average(logs)
+        .map(|((c, d), i)| ((c, d.map(f64::exp)), i))
+        .collect()
+}
+
 fn to_graph_data<'a>(
     cc: &'a CommitIdxCache,
     is_absolute: bool,
     points: impl Iterator<Item = ((db::ArtifactId, Option<f64>), Interpolated)> + 'a,
 ) -> impl Iterator<Item = graph::GraphData> + 'a {
     let mut first = None;
-    points.map(move |((cid, point), interpolated)| {
-        let commit = if let db::ArtifactId::Commit(commit) = cid {
-            commit
-        } else {
-            unimplemented!()
+    // Points without a value (or commit) are left out rather than drawn.
+    points.filter_map(move |((cid, point), interpolated)| {
+        let commit = match cid {
+            db::ArtifactId::Commit(commit) => commit,
+            db::ArtifactId::Artifact(_) => return None,
         };
-        let point = point.expect("interpolated");
-        first = Some(first.unwrap_or(point));
-        let first = first.unwrap();
+        let point = point?;
+        let first = *first.get_or_insert(point);
         let percent = (point - first) / first * 100.0;
-        graph::GraphData {
+        Some(graph::GraphData {
             commit: cc.lookup(commit.sha),
-            absolute: point as f32,
-            percent: percent as f32,
+            absolute: Some(point as f32),
+            percent: Some(percent as f32),
             y: if is_absolute {
-                point as f32
+                graph::Y::Absolute(point as f32)
             } else {
-                percent as f32
+                graph::Y::Percent(percent as f32)
             },
             x: commit.date.0.timestamp() as u64 * 1000, // all dates are since 1970
             is_interpolated: interpolated.is_interpolated(),
-        }
+        })
     })
 }
 
@@ -420,18 +529,18 @@ pub async fn handle_graph_new(
     data: &InputData,
 ) -> ServerResult<Arc<graph::NewResponse>> {
     log::info!("handle_graph_new({:?})", body);
-    let range = data.data_range(body.start.clone()..=body.end.clone());
+    let range = data.data_range_at(body.resolution, body.start.clone()..=body.end.clone());
     let commits: Arc<Vec<ArtifactId>> = Arc::new(range.iter().map(|c| c.clone().into()).collect());
 
-    let mut benchmarks = HashMap::new();
+    let mut benchmarks = BTreeMap::new();
 
     let raw = handle_graph(body, data).await?;
 
     for (crate_, crate_data) in raw.benchmarks.iter() {
-        let mut by_profile = HashMap::with_capacity(3);
+        let mut by_profile = BTreeMap::new();
 
         for (profile, series) in crate_data.iter() {
-            let mut by_run = HashMap::with_capacity(3);
+            let mut by_run = BTreeMap::new();
 
             for (name, points) in series.iter() {
                 let mut series = graph::Series {
@@ -467,18 +576,36 @@ pub async fn handle_graph_new(
     }))
 }
 
+/// The graphs shown on the landing page, which are kept precomputed.
+fn landing_page_request() -> graph::Request {
+    graph::Request {
+        start: Bound::None,
+        end: Bound::None,
+        stat: String::from("instructions:u"),
+        absolute: true,
+        resolution: database::Resolution::Commit,
+    }
+}
+
+/// Recomputes the landing page's graphs. Until this completes, requests for
+/// them are answered with the previous version, if any, rather than waiting.
+pub async fn refresh_landing_page(data: &InputData) {
+    let start = Instant::now();
+    match compute_graph(landing_page_request(), data).await {
+        Ok(resp) => {
+            data.set_landing_page(resp);
+            info!("recomputed landing page in {:?}", start.elapsed());
+        }
+        Err(e) => error!("failed to recompute landing page: {}", e),
+    }
+}
+
 pub async fn handle_graph(
     body: graph::Request,
     data: &InputData,
 ) -> ServerResult<Arc<graph::Response>> {
     log::info!("handle_graph({:?})", body);
-    let is_default_query = body
-        == graph::Request {
-            start: Bound::None,
-            end: Bound::None,
-            stat: String::from("instructions:u"),
-            absolute: true,
-        };
+    let is_default_query = body == landing_page_request();
 
     if is_default_query {
         match &**data.landing_page.load() {
@@ -487,8 +614,29 @@ pub async fn handle_graph(
         }
     }
 
+    let key = crate::cache::key(&body);
+    if let Some(resp) = key.as_ref().and_then(|k| data.graph_cache.get(k)) {
+        return Ok(resp);
+    }
+
+    let resp = compute_graph(body, data).await?;
+
+    if is_default_query {
+        data.set_landing_page(resp.clone());
+    } else if let Some(key) = key {
+        data.graph_cache.insert(key, resp.clone());
+    }
+
+    Ok(resp)
+}
+
+async fn compute_graph(
+    body: graph::Request,
+    data: &InputData,
+) -> ServerResult<Arc<graph::Response>> {
+    let _span = database::trace::span("graph");
     let cc = CommitIdxCache::new();
-    let range = data.data_range(body.start.clone()..=body.end.clone());
+    let range = data.data_range_at(body.resolution, body.start.clone()..=body.end.clone());
     let commits: Arc<Vec<_>> = Arc::new(range.iter().map(|c| c.clone().into()).collect());
 
     let stat_selector = selector::Selector::One(body.stat.clone());
@@ -499,7 +647,8 @@ pub async fn handle_graph(
                 .set::<String>(selector::Tag::Crate, selector::Selector::All)
                 .set::<String>(selector::Tag::Profile, selector::Selector::All)
                 .set::<String>(selector::Tag::Cache, selector::Selector::All)
-                .set::<String>(selector::Tag::ProcessStatistic, stat_selector.clone()),
+                .set::<String>(selector::Tag::ProcessStatistic, stat_selector.clone())
+                .resolution(body.resolution),
             commits.clone(),
         )
         .await?;
@@ -530,8 +679,11 @@ pub async fn handle_graph(
                 selector::Tag::ProcessStatistic,
                 selector::Selector::One(pstat),
             )
+            .resolution(body.resolution)
     });
 
+    let summary_span = database::trace::span("graph summaries");
+    let mut summaries = Vec::new();
     for query in summary_queries {
         let profile = query
             .get(Tag::Profile)
@@ -554,7 +706,10 @@ pub async fn handle_graph(
             .set(
                 selector::Tag::ProcessStatistic,
                 query.get(Tag::ProcessStatistic).unwrap().raw.clone(),
-            );
+            )
+            .resolution(body.resolution);
+        // Without a (non-zero) baseline, e.g. before anything has been
+        // benchmarked in this profile, the summary is left empty.
         let against = match baselines.entry(q.clone()) {
             std::collections::hash_map::Entry::Occupied(o) => *o.get(),
             std::collections::hash_map::Entry::Vacant(v) => {
@@ -566,79 +721,164 @@ pub async fn handle_graph(
                         .collect::<Vec<_>>(),
                 )
                 .next()
-                .map_or(0.0, |((_c, d), _interpolated)| d.expect("interpolated"));
+                .and_then(|((_c, d), _interpolated)| d)
+                .filter(|&d| d != 0.0);
                 *v.insert(value)
             }
         };
-        let averaged = db::average(
-            data.query::<Option<f64>>(query.clone(), commits.clone())
-                .await?
-                .into_iter()
-                .map(|sr| sr.interpolate().series)
-                .collect(),
-        )
-        .map(|((c, d), i)| ((c, Some(d.expect("interpolated") / against)), i));
-        let graph_data = to_graph_data(&cc, body.absolute, averaged).collect::<Vec<_>>();
-        series.push(selector::SeriesResponse {
-            path: selector::Path::new()
-                .set(PathComponent::Crate("Summary".into()))
-                .set(PathComponent::Profile(profile))
-                .set(PathComponent::Cache(cache))
-                .set(PathComponent::ProcessStatistic(
-                    query
-                        .get(Tag::ProcessStatistic)
-                        .unwrap()
-                        .raw
-                        .assert_one()
-                        .parse()
-                        .unwrap(),
-                )),
-            series: graph_data,
-        })
+        let points = data
+            .query::<Option<f64>>(query.clone(), commits.clone())
+            .await?
+            .into_iter()
+            .map(|sr| sr.series.collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let stat = query
+            .get(Tag::ProcessStatistic)
+            .unwrap()
+            .raw
+            .assert_one()
+            .parse()
+            .unwrap();
+        // Each summary averages every crate's series, which is by far the
+        // slowest part of a graph; the summaries are independent of each
+        // other, so average them on the blocking pool in parallel.
+        let averaged = tokio::task::spawn_blocking(move || {
+            let geomean = geomean(&points);
+            let against = match against {
+                Some(against) => against,
+                None => return (Vec::new(), geomean),
+            };
+            let averaged = db::average(
+                points
+                    .into_iter()
+                    .map(|points| crate::interpolate::Interpolate::new(points.into_iter()))
+                    .collect(),
+            )
+            .filter_map(|((c, d), i)| Some(((c, Some(d? / against)), i)))
+            .collect::<Vec<_>>();
+            (averaged, geomean)
+        });
+        summaries.push((profile, cache, stat, averaged));
     }
+    for (profile, cache, stat, averaged) in summaries {
+        let (averaged, geomean) = averaged
+            .await
+            .map_err(|e| format!("failed to average summary: {:?}", e))?;
+        for (krate, points) in vec![("Summary", averaged), ("Geomean", geomean)] {
+            let graph_data =
+                to_graph_data(&cc, body.absolute, points.into_iter()).collect::<Vec<_>>();
+            series.push(selector::SeriesResponse {
+                path: selector::Path::new()
+                    .set(PathComponent::Crate(krate.into()))
+                    .set(PathComponent::Profile(profile))
+                    .set(PathComponent::Cache(cache))
+                    .set(PathComponent::ProcessStatistic(stat.clone())),
+                series: graph_data,
+            })
+        }
+    }
+    drop(summary_span);
 
-    let mut by_krate = HashMap::new();
-    let mut by_krate_max = HashMap::new();
+    let mut by_krate = BTreeMap::new();
+    let mut by_krate_max = BTreeMap::new();
     for sr in series {
         let krate = sr.path.get::<Crate>()?.to_string();
         let max = by_krate_max.entry(krate.clone()).or_insert(f32::MIN);
         *max = sr
             .series
             .iter()
-            .map(|p| p.y)
+            .map(|p| p.y.get())
             .fold(*max, |max, p| max.max(p));
         by_krate
             .entry(krate)
-            .or_insert_with(HashMap::new)
+            .or_insert_with(BTreeMap::new)
             .entry(sr.path.get::<Profile>()?.to_string())
             .or_insert_with(Vec::new)
             .push((sr.path.get::<Cache>()?.to_string(), sr.series));
     }
+    for caches in by_krate
+        .values_mut()
+        .flat_map(|profiles| profiles.values_mut())
+    {
+        caches.sort_by(|a, b| a.0.cmp(&b.0));
+    }
 
-    let resp = Arc::new(graph::Response {
+    Ok(Arc::new(graph::Response {
         max: by_krate_max,
         benchmarks: by_krate,
         colors: vec![String::new(), String::from(INTERPOLATED_COLOR)],
         commits: cc.into_commits(),
-    });
+    }))
+}
 
-    if is_default_query {
-        data.landing_page.store(Arc::new(Some(resp.clone())));
+pub async fn handle_compare(body: days::Request, data: &InputData) -> ServerResult<days::Response> {
+    log::info!("handle_compare({:?})", body);
+    let key = crate::cache::key(&body);
+    if let Some(resp) = key.as_ref().and_then(|k| data.compare_cache.get(k)) {
+        return Ok(resp);
     }
 
+    let resp = compute_compare(body, data).await?;
+    if let Some(key) = key {
+        data.compare_cache.insert(key, resp.clone());
+    }
     Ok(resp)
 }
 
-pub async fn handle_compare(body: days::Request, data: &InputData) -> ServerResult<days::Response> {
-    log::info!("handle_compare({:?})", body);
-    let a = data.data_for(true, body.start.clone()).ok_or(format!(
-        "could not find start commit for bound {:?}",
-        body.start
-    ))?;
+async fn compute_compare(body: days::Request, data: &InputData) -> ServerResult<days::Response> {
+    let _span = database::trace::span("compare");
     let b = data.data_for(false, body.end.clone()).ok_or(format!(
         "could not find end commit for bound {:?}",
         body.end
     ))?;
+    // Try commits never land, so unless told otherwise, compare one with the
+    // master commit it was built on.
+    let a = match &b {
+        ArtifactId::Commit(b)
+            if b.is_try() && (body.start == Bound::None || body.start == body.end) =>
+        {
+            ArtifactId::Commit(data.merge_base(b).await.ok_or_else(|| {
+                format!(
+                    "the merge-base of try commit {} is unknown or hasn't been benchmarked",
+                    b.sha
+                )
+            })?)
+        }
+        _ => data.data_for(true, body.start.clone()).ok_or(format!(
+            "could not find start commit for bound {:?}",
+            body.start
+        ))?,
+    };
+
+    // Numbers from different machines aren't comparable. Artifacts collected
+    // before machines were recorded have no machines and are let through.
+    // Graphs and summaries don't check this, and mix the results of every
+    // machine (see the collector's README).
+    let conn = data.conn().await;
+    let (aid_a, aid_b) = {
+        let idx = data.index.load();
+        (
+            a.lookup(&idx)
+                .ok_or_else(|| format!("{} is not indexed", a))?,
+            b.lookup(&idx)
+                .ok_or_else(|| format!("{} is not indexed", b))?,
+        )
+    };
+    let (machines_a, machines_b) = futures::join!(conn.machines_of(aid_a), conn.machines_of(aid_b));
+    if !machines_a.is_empty() && !machines_b.is_empty() && machines_a != machines_b {
+        return Err(format!(
+            "{} and {} were benchmarked on different machines ({:?} vs. {:?})",
+            a, b, machines_a, machines_b
+        ));
+    }
+
+    let cachegrind_diffs = list_artifacts(&format!(
+        "cgann-diff-{}-{}-",
+        artifact_name(&a),
+        artifact_name(&b)
+    ));
+    let chrome_traces = list_artifacts(&format!("crox-{}-", artifact_name(&b)));
+
     let cids = Arc::new(vec![a.clone().into(), b.clone().into()]);
 
     let query = selector::Query::new()
@@ -657,7 +897,6 @@ pub async fn handle_compare(body: days::Request, data: &InputData) -> ServerResu
     let commits = commits.map_err(|e| e.to_string())?;
     let mut responses = responses?;
 
-    let conn = data.conn().await;
     let prev = match &a {
         ArtifactId::Commit(a) => commits
             .iter()
@@ -683,17 +922,362 @@ pub async fn handle_compare(body: days::Request, data: &InputData) -> ServerResu
         ArtifactId::Artifact(_) => None,
     };
 
+    let until = match &a {
+        ArtifactId::Commit(c) => Some(c.clone()),
+        ArtifactId::Artifact(_) => None,
+    };
     let a = DateData::consume_one(&*conn, a, &mut responses).await;
     let b = DateData::consume_one(&*conn, b, &mut responses).await;
+    let missing_in_a = missing_measurements(&b, &a);
+    let missing_in_b = missing_measurements(&a, &b);
+    let rows = crate::significance::rows(data, &body.stat, until.as_ref(), &a, &b);
     Ok(days::Response {
         prev,
         a,
         b,
         next,
         is_contiguous,
+        cachegrind_diffs,
+        chrome_traces,
+        missing_in_a,
+        missing_in_b,
+        rows,
+    })
+}
+
+/// The measurements of `from` which `to` lacks, as `crate-profile cache`.
+fn missing_measurements(from: &DateData, to: &DateData) -> Vec<String> {
+    // `from` is sorted by name and then by cache, and so is the result.
+    from.data
+        .iter()
+        .flat_map(|(name, cases)| {
+            let other = to.data.get(name).map_or(&[][..], |o| &o[..]);
+            cases
+                .iter()
+                .filter(move |(cache, _)| !other.iter().any(|(c, _)| c == cache))
+                .map(move |(cache, _)| format!("{} {}", name, cache))
+        })
+        .collect()
+}
+
+fn artifact_name(aid: &ArtifactId) -> &str {
+    match aid {
+        ArtifactId::Commit(c) => &c.sha,
+        ArtifactId::Artifact(a) => a,
+    }
+}
+
+/// The directory holding profiler output (e.g., Cachegrind diffs) which is
+/// served under `/perf/artifacts/`.
+fn artifacts_dir() -> std::path::PathBuf {
+    std::env::var_os("ARTIFACTS_DIR")
+        .unwrap_or_else(|| "artifacts".into())
+        .into()
+}
+
+/// The names of all files in the artifacts directory starting with `prefix`,
+/// sorted.
+fn list_artifacts(prefix: &str) -> Vec<String> {
+    let entries = match fs::read_dir(artifacts_dir()) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut names = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|n| n.starts_with(prefix))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// The merges into master since `since` (30 days ago by default) which have no
+/// results.
+pub async fn handle_unbenchmarked(
+    data: Arc<InputData>,
+    since: Option<String>,
+) -> ServerResult<unbenchmarked::Response> {
+    let since = match since {
+        Some(since) => since
+            .parse::<database::Date>()
+            .map_err(|e| format!("invalid date {:?}: {:?}", since, e))?,
+        None => database::Date(chrono::Utc::now() - chrono::Duration::days(30)),
+    };
+    // Reading the history runs git.
+    let commits = tokio::task::spawn_blocking(move || data.unbenchmarked_commits(since))
+        .await
+        .map_err(|e| format!("{:?}", e))?
+        .map_err(|e| format!("failed to find unbenchmarked commits: {:?}", e))?;
+    Ok(unbenchmarked::Response { commits })
+}
+
+pub async fn handle_commit_info(data: Arc<InputData>, sha: String) -> commit_info::Response {
+    let pr = crate::pr_cache::pr_of(&data, &sha).await;
+    commit_info::Response { sha, pr }
+}
+
+/// The crates or self-profile queries starting with `prefix`, for
+/// autocompletion; see `search::search`.
+pub fn handle_search(
+    data: &InputData,
+    kind: Option<String>,
+    prefix: String,
+    category: Option<String>,
+    limit: Option<String>,
+) -> ServerResult<search::Response> {
+    log::info!(
+        "handle_search(kind: {:?}, prefix: {:?}, category: {:?})",
+        kind,
+        prefix,
+        category
+    );
+    let kind = match kind.as_deref() {
+        None | Some("crate") => crate::search::Kind::Crate,
+        Some("phase") => crate::search::Kind::Phase,
+        Some(other) => return Err(format!("unknown kind {:?}, expected crate or phase", other)),
+    };
+    let category = match category {
+        Some(category) => {
+            use serde::de::IntoDeserializer;
+            let parsed: Result<collector::manifest::Category, serde::de::value::Error> =
+                serde::Deserialize::deserialize(category.as_str().into_deserializer());
+            Some(parsed.map_err(|_| format!("unknown category {:?}", category))?)
+        }
+        None => None,
+    };
+    let limit = match limit {
+        Some(limit) => limit
+            .parse::<usize>()
+            .map_err(|e| format!("invalid limit {:?}: {}", limit, e))?,
+        None => crate::search::DEFAULT_LIMIT,
+    };
+    Ok(crate::search::search(
+        &data.index.load(),
+        &data.benchmarks,
+        kind,
+        &prefix,
+        category,
+        limit,
+    ))
+}
+
+/// The benchmarked commit with a sha starting with `sha`, or else the master
+/// commit merged closest to `date` (the earlier one of a tie).
+pub fn handle_nearest(
+    data: &InputData,
+    date: Option<String>,
+    sha: Option<String>,
+) -> ServerResult<nearest::Response> {
+    log::info!("handle_nearest(date: {:?}, sha: {:?})", date, sha);
+    let index = data.index.load();
+    let commits = index.sorted_commits();
+    if let Some(sha) = sha.filter(|s| !s.is_empty()) {
+        let mut matches = commits.iter().filter(|c| c.sha.starts_with(sha.as_str()));
+        return match (matches.next(), matches.next()) {
+            (Some(commit), None) => Ok(nearest::Response {
+                exact: commit.sha == sha,
+                commit: commit.clone(),
+            }),
+            (Some(_), Some(_)) => Err(format!("sha prefix {:?} is ambiguous", sha)),
+            (None, _) => Err(format!("no benchmarked commit has sha {:?}", sha)),
+        };
+    }
+    let date = match date {
+        Some(date) => date
+            .parse::<database::Date>()
+            .map_err(|e| format!("invalid date {:?}: {:?}", date, e))?,
+        None => return Err(String::from("either date or sha is required")),
+    };
+    let commit = commits
+        .iter()
+        .filter(|c| !c.is_try())
+        .min_by_key(|c| ((c.date.0 - date.0).num_seconds().abs(), c.date))
+        .ok_or_else(|| String::from("no master commits have been benchmarked"))?;
+    Ok(nearest::Response {
+        exact: commit.date == date,
+        commit: commit.clone(),
     })
 }
 
+pub fn handle_artifacts(commit: String) -> artifacts::Response {
+    log::info!("handle_artifacts({:?})", commit);
+    // Names have the form $PREFIX-$ID-$BENCHMARK-..., and the prefix may
+    // itself contain dashes.
+    let needle = format!("-{}-", commit);
+    let artifacts = if commit.is_empty() {
+        Vec::new()
+    } else {
+        list_artifacts("")
+            .into_iter()
+            .filter(|n| n.contains(&needle))
+            .collect()
+    };
+    artifacts::Response { artifacts }
+}
+
+/// The latest master commit's results for Prometheus to scrape; see
+/// `prometheus::render`.
+pub async fn handle_metrics(data: &InputData, phases: bool) -> Response {
+    log::info!("handle_metrics(phases: {})", phases);
+    http::Response::builder()
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .status(StatusCode::OK)
+        .body(hyper::Body::from(
+            (*crate::prometheus::render(data, phases).await).clone(),
+        ))
+        .unwrap()
+}
+
+/// The filtered values as JSON Lines, streamed with backpressure; see
+/// `export::stream`.
+pub fn handle_export(data: Arc<InputData>, filter: crate::export::Filter) -> Response {
+    log::info!(
+        "handle_export(stat: {:?}, crate: {:?})",
+        filter.stat,
+        filter.krate
+    );
+    let (sender, body) = hyper::Body::channel();
+    tokio::spawn(crate::export::stream(data, filter, sender));
+    http::Response::builder()
+        .header("Content-Type", "application/x-ndjson")
+        .header_typed(CacheControl::new().with_no_cache().with_no_store())
+        .status(StatusCode::OK)
+        .body(body)
+        .unwrap()
+}
+
+/// A status badge; see `badge::metric`.
+pub fn handle_badge(data: &InputData, name: &str, stat: &str) -> Response {
+    log::info!("handle_badge({:?}, stat: {})", name, stat);
+    if !data.index.load().stats().iter().any(|s| s == stat) {
+        return http::Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header_typed(ContentType::text_utf8())
+            .body(hyper::Body::from("unknown statistic"))
+            .unwrap();
+    }
+    let (label, value, color) = match crate::badge::metric(data, name, stat) {
+        Some(metric) => metric,
+        None => {
+            return http::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+                .unwrap()
+        }
+    };
+    http::Response::builder()
+        .header("Content-Type", "image/svg+xml")
+        .header_typed(CacheControl::new().with_no_cache())
+        .status(StatusCode::OK)
+        .body(hyper::Body::from(crate::badge::render(
+            &label, &value, color,
+        )))
+        .unwrap()
+}
+
+/// The significant changes in a statistic over the latest master commits, as
+/// an Atom feed; see `feed::render`.
+pub fn handle_feed(data: &InputData, stat: &str, threshold: f64) -> Response {
+    log::info!("handle_feed(stat: {}, threshold: {})", stat, threshold);
+    http::Response::builder()
+        .header("Content-Type", "application/atom+xml; charset=utf-8")
+        .status(StatusCode::OK)
+        .body(hyper::Body::from(crate::feed::render(
+            data, stat, threshold,
+        )))
+        .unwrap()
+}
+
+/// The significant changes to a crate's benchmarks over a range of commits,
+/// as a Mermaid or Graphviz diagram; see `timeline::render`.
+pub async fn handle_timeline(
+    data: &InputData,
+    range: (Bound, Bound),
+    krate: &str,
+    stat: &str,
+    threshold: f64,
+    format: crate::timeline::Format,
+) -> Response {
+    log::info!(
+        "handle_timeline({}, stat: {}, threshold: {}, {:?})",
+        krate,
+        stat,
+        threshold,
+        format
+    );
+    let commits = data
+        .data_range(range.0..=range.1)
+        .into_iter()
+        .filter(|c| !c.is_try())
+        .collect::<Vec<_>>();
+    let body = crate::timeline::render(data, &commits, krate, stat, threshold, format).await;
+    http::Response::builder()
+        .header("Content-Type", format.content_type())
+        .status(StatusCode::OK)
+        .body(hyper::Body::from(body))
+        .unwrap()
+}
+
+pub async fn handle_zulip(body: zulip::Request, data: &InputData) -> ServerResult<zulip::Response> {
+    log::info!("handle_zulip({:?})", body.data);
+    let content = crate::zulip::command(data, &body.message.sender_full_name, &body.data).await;
+    Ok(zulip::Response { content })
+}
+
+pub async fn handle_grafana_search(
+    body: grafana::SearchRequest,
+    data: &InputData,
+) -> ServerResult<Vec<String>> {
+    log::info!("handle_grafana_search({:?})", body.target);
+    Ok(crate::grafana::search(data, &body.target))
+}
+
+pub async fn handle_grafana_query(
+    body: grafana::QueryRequest,
+    data: &InputData,
+) -> ServerResult<Vec<grafana::TimeSeries>> {
+    log::info!("handle_grafana_query({} targets)", body.targets.len());
+    Ok(crate::grafana::query(data, &body))
+}
+
+pub async fn handle_grafana_annotations(
+    body: grafana::AnnotationsRequest,
+    data: &InputData,
+) -> ServerResult<Vec<grafana::Annotation>> {
+    log::info!("handle_grafana_annotations");
+    Ok(crate::grafana::annotations(data, &body))
+}
+
+pub fn handle_artifact(name: &str) -> Response {
+    log::info!("handle_artifact({:?})", name);
+    let path = artifacts_dir().join(name);
+    if name.is_empty() || name.contains('/') || name.contains("..") || !path.is_file() {
+        return http::Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(hyper::Body::empty())
+            .unwrap();
+    }
+    let mut response = http::Response::builder();
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("svg") => response = response.header("Content-Type", "image/svg+xml"),
+        Some("json") => response = response.header_typed(ContentType::json()),
+        _ => response = response.header_typed(ContentType::text_utf8()),
+    }
+    match fs::read(&path) {
+        Ok(contents) => response
+            .status(StatusCode::OK)
+            .body(hyper::Body::from(contents))
+            .unwrap(),
+        Err(e) => http::Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(hyper::Body::from(format!(
+                "failed to read {}: {:?}",
+                name, e
+            )))
+            .unwrap(),
+    }
+}
+
 impl DateData {
     async fn consume_one<'a, T>(
         conn: &dyn database::Connection,
@@ -703,7 +1287,7 @@ impl DateData {
     where
         T: Iterator<Item = (db::ArtifactId, Option<f64>)>,
     {
-        let mut data = HashMap::new();
+        let mut data = BTreeMap::new();
 
         for response in series {
             let (id, point) = response.series.next().expect("must have element");
@@ -722,6 +1306,9 @@ impl DateData {
             .or_insert_with(Vec::new)
             .push((response.path.get::<Cache>().unwrap().to_string(), point));
         }
+        for cases in data.values_mut() {
+            cases.sort_by(|a, b| a.0.cmp(&b.0));
+        }
 
         let bootstrap = conn.get_bootstrap(&[conn.artifact_id(&commit).await]).await;
         let bootstrap = bootstrap
@@ -740,7 +1327,7 @@ impl DateData {
                     .filter(|v| v.as_secs() >= 1)
                     .map(|v| (k, v.as_nanos() as u64))
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<BTreeMap<_, _>>();
 
         DateData {
             date: if let ArtifactId::Commit(c) = &commit {
@@ -779,6 +1366,57 @@ pub async fn handle_collected() -> ServerResult<()> {
     Ok(())
 }
 
+pub async fn handle_queue(
+    body: collector::api::queue::Request,
+    data: &InputData,
+) -> ServerResult<collector::api::queue::Response> {
+    use collector::api::queue::{Job, Request};
+    log::info!("handle_queue({:?})", body);
+
+    let conn = data.conn().await;
+    let (found, artifact) = match &body {
+        Request::List => (true, ""),
+        Request::Prioritize { artifact, priority } => (
+            conn.set_job_priority(artifact, *priority).await,
+            &artifact[..],
+        ),
+        Request::Cancel { artifact } => (conn.cancel_job(artifact).await, &artifact[..]),
+        Request::Retry { artifact } => (conn.retry_job(artifact).await, &artifact[..]),
+        Request::Backfill { since, limit } => {
+            let since = since
+                .parse::<database::Date>()
+                .map_err(|e| format!("invalid date {:?}: {:?}", since, e))?;
+            let commits = data
+                .unbenchmarked_commits(since)
+                .map_err(|e| format!("failed to find unbenchmarked commits: {:?}", e))?;
+            for commit in commits.into_iter().take(*limit) {
+                conn.enqueue_job(&commit.sha, JobKind::Backfill, None, None, None)
+                    .await;
+            }
+            (true, "")
+        }
+    };
+    if !found {
+        return Err(format!("no matching job for {}", artifact));
+    }
+
+    let jobs = conn
+        .queued_jobs()
+        .await
+        .into_iter()
+        .map(|job| Job {
+            artifact: job.artifact,
+            kind: job.kind,
+            priority: job.priority,
+            include: job.include,
+            exclude: job.exclude,
+            runs: job.runs,
+            claimed_by: job.claimed_by,
+        })
+        .collect();
+    Ok(collector::api::queue::Response { jobs })
+}
+
 fn get_self_profile_data(
     cpu_clock: Option<f64>,
     self_profile: Option<crate::selector::SelfProfileData>,
@@ -838,6 +1476,9 @@ fn get_self_profile_data(
             .collect(),
         totals,
     };
+    // By label unless asked otherwise, and by label among equals otherwise,
+    // since the sorts below are stable.
+    profile.query_data.sort_by_key(|qd| qd.label);
 
     if let Some(sort_idx) = sort_idx {
         loop {
@@ -925,7 +1566,7 @@ pub async fn handle_self_profile_processed_download(
 
     builder.headers_mut().unwrap().insert(
         hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN,
-        hyper::header::HeaderValue::from_static("https://profiler.firefox.com"),
+        hyper::header::HeaderValue::from_static(output.allow_origin),
     );
 
     builder.body(hyper::Body::from(output.data)).unwrap()
@@ -986,6 +1627,44 @@ pub async fn handle_self_profile_raw_download(
     server_resp
 }
 
+fn query_param(req: &Request, key: &str) -> Option<String> {
+    uri_param(req.uri(), key)
+}
+
+/// Whether `?values=` asks for absolute (rather than normalized) graph values,
+/// if given; it takes precedence over the request's `absolute`, so that the
+/// relative view is a matter of the URL alone.
+fn values_param(uri: &http::Uri) -> Result<Option<bool>, Response> {
+    match uri_param(uri, "values").as_deref() {
+        None => Ok(None),
+        Some("absolute") => Ok(Some(true)),
+        Some("normalized") => Ok(Some(false)),
+        Some(other) => Err(http::Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header_typed(ContentType::text_utf8())
+            .body(hyper::Body::from(format!(
+                "unknown values {:?}, expected absolute or normalized",
+                other
+            )))
+            .unwrap()),
+    }
+}
+
+fn uri_param(uri: &http::Uri, key: &str) -> Option<String> {
+    let url = url::Url::parse(&format!("http://example.com{}", uri)).ok()?;
+    url.query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+}
+
+/// A range bound given as a query parameter, read as in graph requests.
+fn parse_bound(value: String) -> Bound {
+    use serde::de::IntoDeserializer;
+    let bound: Result<Bound, serde::de::value::Error> =
+        serde::Deserialize::deserialize(value.into_deserializer());
+    bound.unwrap_or(Bound::None)
+}
+
 fn get_self_profile_raw(
     req: &Request,
 ) -> Result<(HashMap<String, String>, self_profile_raw::Request), Response> {
@@ -1083,7 +1762,9 @@ pub async fn handle_self_profile_raw(
     log::info!("handle_self_profile_raw({:?})", body);
     let mut it = body.benchmark.rsplitn(2, '-');
     let bench_ty = it.next().ok_or(format!("no benchmark type"))?;
-    let bench_name = it.next().ok_or(format!("no benchmark name"))?;
+    let requested_name = it.next().ok_or(format!("no benchmark name"))?;
+    let index = data.index.load();
+    let bench_name = index.resolve_benchmark(requested_name);
 
     let cache = body
         .run_name
@@ -1119,35 +1800,38 @@ pub async fn handle_self_profile_raw(
         _ => first_cid,
     };
 
-    let url_prefix = format!(
-        "https://perf-data.rust-lang.org/self-profile/{}/{}/{}/{}/self-profile-{}",
-        aid.0,
-        bench_name,
-        bench_ty,
-        cache.to_id(),
-        cid,
-    );
-
     let cids = aids_and_cids
         .into_iter()
         .map(|(_, cid)| cid)
         .collect::<Vec<_>>();
 
-    return match fetch(&cids, cid, format!("{}.mm_profdata.sz", url_prefix), false).await {
-        Ok(fetched) => Ok(fetched),
-        Err(new_error) => {
-            match fetch(&cids, cid, format!("{}.tar.sz", url_prefix), true).await {
-                Ok(fetched) => Ok(fetched),
-                Err(old_error) => {
-                    // Both files failed to fetch; return the errors for both:
-                    Err(format!(
-                        "mm_profdata download failed: {:?}, tarball download failed: {:?}",
-                        new_error, old_error
-                    ))
-                }
-            }
+    // Files uploaded before a benchmark was renamed are under its old name, so
+    // the name as requested is tried too.
+    let mut names = vec![bench_name];
+    if requested_name != bench_name {
+        names.push(requested_name);
+    }
+    let mut errors = Vec::new();
+    for name in names {
+        let url_prefix = format!(
+            "https://perf-data.rust-lang.org/self-profile/{}/{}/{}/{}/self-profile-{}",
+            aid.0,
+            name,
+            bench_ty,
+            cache.to_id(),
+            cid,
+        );
+        match fetch(&cids, cid, format!("{}.mm_profdata.sz", url_prefix), false).await {
+            Ok(fetched) => return Ok(fetched),
+            Err(e) => errors.push(format!("{} mm_profdata download failed: {:?}", name, e)),
         }
-    };
+        match fetch(&cids, cid, format!("{}.tar.sz", url_prefix), true).await {
+            Ok(fetched) => return Ok(fetched),
+            Err(e) => errors.push(format!("{} tarball download failed: {:?}", name, e)),
+        }
+    }
+    // All the files failed to fetch; return the errors for each.
+    return Err(errors.join(", "));
 
     async fn fetch(
         cids: &[i32],
@@ -1176,6 +1860,51 @@ pub async fn handle_self_profile_raw(
     }
 }
 
+pub async fn handle_benchmark_detail(
+    body: benchmark_detail::Request,
+    data: &InputData,
+) -> ServerResult<benchmark_detail::Response> {
+    log::info!("handle_benchmark_detail({:?})", body);
+    crate::detail::detail(body, data).await
+}
+
+pub async fn handle_phases(
+    body: phases::Request,
+    data: &InputData,
+) -> ServerResult<phases::Response> {
+    log::info!("handle_phases({:?})", body);
+    let mut it = body.benchmark.rsplitn(2, '-');
+    let bench_ty = it.next().ok_or(format!("no benchmark type"))?;
+    let bench_name = it.next().ok_or(format!("no benchmark name"))?;
+    let bench_name = data.index.load().resolve_benchmark(bench_name);
+
+    let query = selector::Query::new()
+        .set(Tag::Crate, selector::Selector::One(bench_name))
+        .set(Tag::Profile, selector::Selector::One(bench_ty))
+        .set(Tag::Cache, selector::Selector::One(body.run_name.clone()));
+    let commits = data
+        .data_range(body.start.clone()..=body.end.clone())
+        .into_iter()
+        .map(database::ArtifactId::Commit)
+        .collect::<Vec<_>>();
+    let mut responses = data
+        .query::<Option<selector::SelfProfileData>>(query.clone(), Arc::new(commits))
+        .await?;
+    if responses.len() != 1 {
+        return Err(format!("no results found for {:?}", query));
+    }
+    let points = responses
+        .remove(0)
+        .series
+        .map(|(aid, profile)| match aid {
+            database::ArtifactId::Commit(c) => (c.sha, profile),
+            database::ArtifactId::Artifact(a) => (a, profile),
+        })
+        .collect();
+    crate::phases::align(points)
+        .ok_or_else(|| format!("no self-profiles of {:?} in the range", query))
+}
+
 pub async fn handle_self_profile(
     body: self_profile::Request,
     data: &InputData,
@@ -1185,6 +1914,7 @@ pub async fn handle_self_profile(
     let bench_ty = it.next().ok_or(format!("no benchmark type"))?;
     let bench_name = it.next().ok_or(format!("no benchmark name"))?;
     let index = data.index.load();
+    let bench_name = index.resolve_benchmark(bench_name);
 
     let sort_idx = body
         .sort_idx
@@ -1198,10 +1928,10 @@ pub async fn handle_self_profile(
         .set(Tag::Cache, selector::Selector::One(body.run_name.clone()));
 
     let mut commits = vec![index
-        .commits()
-        .into_iter()
+        .sorted_commits()
+        .iter()
         .find(|c| c.sha == *body.commit.as_str())
-        .map(|c| database::ArtifactId::Commit(c))
+        .map(|c| database::ArtifactId::Commit(c.clone()))
         .or_else(|| {
             index
                 .artifacts()
@@ -1213,10 +1943,10 @@ pub async fn handle_self_profile(
     if let Some(bc) = &body.base_commit {
         commits.push(
             index
-                .commits()
-                .into_iter()
+                .sorted_commits()
+                .iter()
                 .find(|c| c.sha == *bc.as_str())
-                .map(|c| database::ArtifactId::Commit(c))
+                .map(|c| database::ArtifactId::Commit(c.clone()))
                 .or_else(|| {
                     index
                         .artifacts()
@@ -1278,9 +2008,134 @@ pub async fn handle_self_profile(
         None
     };
 
+    // A query missing from one profile didn't take zero time there; it just
+    // wasn't run (or was renamed), so it's listed rather than compared.
+    let (added_queries, removed_queries) = match &base_profile {
+        Some(base) => {
+            let labels = |p: &self_profile::SelfProfile| {
+                p.query_data
+                    .iter()
+                    .map(|qd| qd.label)
+                    .collect::<HashSet<_>>()
+            };
+            let (ours, theirs) = (labels(&profile), labels(base));
+            let mut added = ours.difference(&theirs).copied().collect::<Vec<_>>();
+            let mut removed = theirs.difference(&ours).copied().collect::<Vec<_>>();
+            added.sort();
+            removed.sort();
+            (added, removed)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+
     Ok(self_profile::Response {
         base_profile,
         profile,
+        added_queries,
+        removed_queries,
+    })
+}
+
+pub async fn handle_self_profile_diff(
+    body: self_profile_diff::Request,
+    data: &InputData,
+) -> ServerResult<self_profile_diff::Response> {
+    log::info!("handle_self_profile_diff({:?})", body);
+    let mut it = body.benchmark.rsplitn(2, '-');
+    let bench_ty = it.next().ok_or(format!("no benchmark type"))?;
+    let bench_name = it.next().ok_or(format!("no benchmark name"))?;
+    let index = data.index.load();
+    let bench_name = index.resolve_benchmark(bench_name);
+
+    let query = selector::Query::new()
+        .set(Tag::Crate, selector::Selector::One(bench_name))
+        .set(Tag::Profile, selector::Selector::One(bench_ty))
+        .set(Tag::Cache, selector::Selector::One(body.run_name.clone()));
+    let find = |name: &str| {
+        index
+            .sorted_commits()
+            .iter()
+            .find(|c| c.sha == name)
+            .map(|c| database::ArtifactId::Commit(c.clone()))
+            .or_else(|| {
+                index
+                    .artifacts()
+                    .find(|a| *a == name)
+                    .map(|a| database::ArtifactId::Artifact(a.to_owned()))
+            })
+            .ok_or(format!("could not find artifact {}", name))
+    };
+    let commits = Arc::new(vec![find(&body.base_commit)?, find(&body.commit)?]);
+    let mut responses = data
+        .query::<Option<selector::SelfProfileData>>(query.clone(), commits)
+        .await?;
+    if responses.len() != 1 {
+        return Err(format!("no results found for {:?}", query));
+    }
+    let mut series = responses.remove(0).series;
+    let base = series
+        .next()
+        .and_then(|(_, p)| p)
+        .ok_or(format!("no self profile results for {}", body.base_commit))?;
+    let profile = series
+        .next()
+        .and_then(|(_, p)| p)
+        .ok_or(format!("no self profile results for {}", body.commit))?;
+
+    let mut queries = HashMap::new();
+    for (side, p) in [&base, &profile].iter().enumerate() {
+        for qd in &p.query_data {
+            queries.entry(qd.label).or_insert_with(|| [None, None])[side] = Some(qd);
+        }
+    }
+    let total_self_time_delta = profile
+        .query_data
+        .iter()
+        .map(|qd| qd.self_time().as_secs_f64())
+        .sum::<f64>()
+        - base
+            .query_data
+            .iter()
+            .map(|qd| qd.self_time().as_secs_f64())
+            .sum::<f64>();
+    let mut queries = queries
+        .into_iter()
+        .map(|(label, [a, b])| {
+            let self_time = |qd: Option<&selector::QueryData>| {
+                qd.map_or(0.0, |qd| qd.self_time().as_secs_f64())
+            };
+            let count = |qd: Option<&selector::QueryData>, f: fn(&selector::QueryData) -> u32| {
+                qd.map_or(0, |qd| i64::from(f(qd)))
+            };
+            let self_time_delta = self_time(b) - self_time(a);
+            self_profile_diff::QueryDelta {
+                label,
+                base_self_time: self_time(a),
+                self_time: self_time(b),
+                self_time_delta,
+                contribution: if total_self_time_delta == 0.0 {
+                    0.0
+                } else {
+                    self_time_delta / total_self_time_delta * 100.0
+                },
+                invocation_count_delta: count(b, |qd| qd.invocation_count)
+                    - count(a, |qd| qd.invocation_count),
+                number_of_cache_hits_delta: count(b, |qd| qd.number_of_cache_hits)
+                    - count(a, |qd| qd.number_of_cache_hits),
+            }
+        })
+        .collect::<Vec<_>>();
+    queries.sort_by(|a, b| {
+        b.self_time_delta
+            .abs()
+            .partial_cmp(&a.self_time_delta.abs())
+            .unwrap()
+            .then_with(|| a.label.cmp(&b.label))
+    });
+
+    Ok(self_profile_diff::Response {
+        total_self_time_delta,
+        queries,
     })
 }
 
@@ -1316,7 +2171,7 @@ pub async fn handle_bootstrap(
                 None
             }
         })
-        .collect::<hashbrown::HashMap<String, Vec<Option<u64>>>>();
+        .collect::<BTreeMap<String, Vec<Option<u64>>>>();
 
     // Don't return commits/nulls for completely null commits at the beginning
     let start: usize = by_crate
@@ -1451,7 +2306,7 @@ impl Server {
                 &mut Some(auth).into_iter(),
             )
             .unwrap();
-            if auth.0.token() == *data.config.keys.secret.as_ref().unwrap() {
+            if auth.0.token() == *data.config().keys.secret.as_ref().unwrap() {
                 return true;
             }
         }
@@ -1498,16 +2353,45 @@ impl Server {
 
         let data: Arc<InputData> = self.data.read().as_ref().unwrap().clone();
         let _updating = self.updating.release_on_drop(channel);
+        let reloading = data.reload_lock.lock().await;
+        let _span = database::trace::span("reload");
         let mut conn = data.conn().await;
-        let index = db::Index::load(&mut *conn).await;
-        eprintln!("index has {} commits", index.commits().len());
+        let index = crate::load::load_index(&mut *conn).await;
+        let pstats =
+            database::PstatCache::load(&*conn, &index, crate::load::reduced_precision_cutoff())
+                .await;
+        let downsampled = crate::load::downsample(&index, &pstats, &data.config().calendar());
+        let deltas = database::Deltas::new(&index, &pstats);
+        let releases = crate::releases::Releases::new(&index, &pstats);
+        eprintln!(
+            "index has {} commits, {} values",
+            index.sorted_commits().len(),
+            pstats.len()
+        );
         data.index.store(Arc::new(index));
-
-        // Refresh the landing page
-        data.landing_page.store(Arc::new(None));
+        data.pstats.store(Arc::new(pstats));
+        data.downsampled.store(Arc::new(downsampled));
+        data.deltas.store(Arc::new(deltas));
+        data.releases.store(Arc::new(releases));
+        data.graph_cache.clear();
+        data.compare_cache.clear();
+        data.metrics_cache.clear();
+        drop(reloading);
+
+        // Refresh the landing page in the background; the old one is served
+        // until then.
+        let data_ = data.clone();
+        tokio::spawn(async move {
+            refresh_landing_page(&data_).await;
+        });
 
         // Spawn off a task to post the results of any commit results that we
         // are now aware of.
+        let data_ = data.clone();
+        tokio::spawn(async move {
+            crate::zulip::post_regressions(&data_).await;
+        });
+
         tokio::spawn(async move {
             crate::github::post_finished(&data).await;
         });
@@ -1575,6 +2459,11 @@ async fn serve_req(ctx: Arc<Server>, req: Request) -> Result<Response, ServerErr
 
     match req.uri().path() {
         "/perf/info" => return ctx.handle_get(&req, handle_info),
+        "/perf/benchmarks" => return ctx.handle_get(&req, handle_benchmarks),
+        "/perf/releases" => {
+            let stat = query_param(&req, "stat").unwrap_or_else(|| String::from("instructions:u"));
+            return ctx.handle_get(&req, |data| handle_releases(data, &stat));
+        }
         "/perf/dashboard" => {
             let ret = ctx.handle_get_async(&req, |c| handle_dashboard(c));
             return ret.await;
@@ -1584,12 +2473,125 @@ async fn serve_req(ctx: Arc<Server>, req: Request) -> Result<Response, ServerErr
             return ret.await;
         }
         "/perf/next_commit" => {
-            let ret = ctx.handle_get_async(&req, |c| handle_next_commit(c));
+            // Collectors which predate multi-machine support don't identify
+            // themselves; they all share the empty name.
+            let collector = query_param(&req, "collector").unwrap_or_default();
+            let ret = ctx.handle_get_async(&req, |c| handle_next_commit(c, collector));
+            return ret.await;
+        }
+        "/perf/artifacts" => {
+            let commit = query_param(&req, "commit").unwrap_or_default();
+            return ctx.handle_get(&req, |_| handle_artifacts(commit));
+        }
+        "/perf/unbenchmarked" => {
+            let since = query_param(&req, "since");
+            let ret = ctx.handle_get_async(&req, |c| handle_unbenchmarked(c, since));
+            return ret.await;
+        }
+        "/perf/search" => {
+            let kind = query_param(&req, "kind");
+            let prefix = query_param(&req, "q").unwrap_or_default();
+            let category = query_param(&req, "category");
+            let limit = query_param(&req, "limit");
+            return ctx.handle_get(&req, |data| {
+                handle_search(data, kind, prefix, category, limit)
+            });
+        }
+        "/perf/nearest" => {
+            let date = query_param(&req, "date");
+            let sha = query_param(&req, "sha");
+            return ctx.handle_get(&req, |data| handle_nearest(data, date, sha));
+        }
+        "/perf/commit-info" => {
+            let sha = query_param(&req, "sha").unwrap_or_default();
+            let ret = ctx.handle_get_async(&req, |c| handle_commit_info(c, sha));
             return ret.await;
         }
         _ => {}
     }
 
+    if let Some(name) = req.uri().path().strip_prefix("/perf/artifacts/") {
+        return Ok(handle_artifact(name));
+    }
+    if req.uri().path() == "/perf/metrics" {
+        let phases = query_param(&req, "phases").map_or(false, |p| p == "1" || p == "true");
+        let data: Arc<InputData> = ctx.data.read().as_ref().unwrap().clone();
+        return Ok(handle_metrics(&data, phases).await);
+    }
+    if let Some(name) = req
+        .uri()
+        .path()
+        .strip_prefix("/perf/badge/")
+        .and_then(|n| n.strip_suffix(".svg"))
+    {
+        let stat = query_param(&req, "stat").unwrap_or_else(|| String::from("instructions:u"));
+        let data: Arc<InputData> = ctx.data.read().as_ref().unwrap().clone();
+        return Ok(handle_badge(&data, name, &stat));
+    }
+    if req.uri().path() == "/perf/export.jsonl" {
+        let bound = |key| query_param(&req, key).map(parse_bound);
+        let range = match (bound("start"), bound("end")) {
+            (None, None) => None,
+            (start, end) => Some((start.unwrap_or(Bound::None), end.unwrap_or(Bound::None))),
+        };
+        let filter = crate::export::Filter {
+            range,
+            stat: query_param(&req, "stat"),
+            krate: query_param(&req, "crate"),
+        };
+        let data: Arc<InputData> = ctx.data.read().as_ref().unwrap().clone();
+        return Ok(handle_export(data, filter));
+    }
+    if req.uri().path() == "/perf/feed.xml" {
+        let stat = query_param(&req, "stat").unwrap_or_else(|| String::from("instructions:u"));
+        // In percent, like the rest of the site.
+        let threshold = query_param(&req, "threshold")
+            .and_then(|t| t.parse::<f64>().ok())
+            .filter(|t| t.is_finite() && *t >= 0.0)
+            .unwrap_or(1.0);
+        let data: Arc<InputData> = ctx.data.read().as_ref().unwrap().clone();
+        return Ok(handle_feed(&data, &stat, threshold / 100.0));
+    }
+    if req.uri().path() == "/perf/timeline" {
+        let krate = match query_param(&req, "crate") {
+            Some(krate) => krate,
+            None => {
+                return Ok(http::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(hyper::Body::from("a crate is required"))
+                    .unwrap())
+            }
+        };
+        let format = match query_param(&req, "format")
+            .unwrap_or_else(|| String::from("mermaid"))
+            .parse()
+        {
+            Ok(format) => format,
+            Err(e) => {
+                return Ok(http::Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(hyper::Body::from(e))
+                    .unwrap())
+            }
+        };
+        let bound = |key| query_param(&req, key).map_or(Bound::None, parse_bound);
+        let range = (bound("start"), bound("end"));
+        let stat = query_param(&req, "stat").unwrap_or_else(|| String::from("instructions:u"));
+        // In percent, like the rest of the site.
+        let threshold = query_param(&req, "threshold")
+            .and_then(|t| t.parse::<f64>().ok())
+            .filter(|t| t.is_finite() && *t >= 0.0)
+            .unwrap_or(1.0);
+        let data: Arc<InputData> = ctx.data.read().as_ref().unwrap().clone();
+        return Ok(handle_timeline(&data, range, &krate, &stat, threshold / 100.0, format).await);
+    }
+    if req.uri().path() == "/perf/grafana" || req.uri().path() == "/perf/grafana/" {
+        // Grafana's "test connection" only needs a 200.
+        return Ok(http::Response::builder()
+            .status(StatusCode::OK)
+            .body(hyper::Body::from("OK"))
+            .unwrap());
+    }
     if req.uri().path() == "/perf/onpush" {
         return Ok(ctx.handle_push(req).await);
     }
@@ -1642,9 +2644,33 @@ async fn serve_req(ctx: Arc<Server>, req: Request) -> Result<Response, ServerErr
 
     // Can't use match because of https://github.com/rust-lang/rust/issues/57017
     if p == "/perf/graph" {
-        Ok(to_response(
-            handle_graph(body!(parse_body(&body)), &data).await,
-        ))
+        let mut body: graph::Request = body!(parse_body(&body));
+        let values = match values_param(&req.uri) {
+            Ok(values) => values,
+            Err(response) => return Ok(response),
+        };
+        if let Some(absolute) = values {
+            body.absolute = absolute;
+        }
+        Ok(to_response(handle_graph(body, &data).await.map(
+            |response| {
+                if values.is_none() {
+                    return response;
+                }
+                let mut response = (*response).clone();
+                for point in response
+                    .benchmarks
+                    .values_mut()
+                    .flat_map(|profiles| profiles.values_mut())
+                    .flat_map(|caches| caches.iter_mut())
+                    .flat_map(|(_, points)| points.iter_mut())
+                {
+                    point.absolute = None;
+                    point.percent = None;
+                }
+                Arc::new(response)
+            },
+        )))
     } else if p == "/perf/get" {
         Ok(to_response(
             handle_compare(body!(parse_body(&body)), &data).await,
@@ -1657,8 +2683,50 @@ async fn serve_req(ctx: Arc<Server>, req: Request) -> Result<Response, ServerErr
                 .unwrap());
         }
         Ok(to_response(handle_collected().await))
+    } else if p == "/perf/queue" {
+        if !ctx.check_auth(&req) {
+            return Ok(http::Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(hyper::Body::empty())
+                .unwrap());
+        }
+        // Answered with JSON rather than MessagePack, for the collector.
+        Ok(match handle_queue(body!(parse_body(&body)), &data).await {
+            Ok(result) => http::Response::builder()
+                .header_typed(ContentType::json())
+                .header_typed(CacheControl::new().with_no_cache().with_no_store())
+                .body(hyper::Body::from(serde_json::to_vec(&result).unwrap()))
+                .unwrap(),
+            Err(err) => http::Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header_typed(ContentType::text_utf8())
+                .header_typed(CacheControl::new().with_no_cache().with_no_store())
+                .body(hyper::Body::from(err))
+                .unwrap(),
+        })
+    } else if p == "/perf/grafana/search" {
+        Ok(to_json_response(
+            handle_grafana_search(body!(parse_body(&body)), &data).await,
+        ))
+    } else if p == "/perf/grafana/query" {
+        Ok(to_json_response(
+            handle_grafana_query(body!(parse_body(&body)), &data).await,
+        ))
+    } else if p == "/perf/grafana/annotations" {
+        Ok(to_json_response(
+            handle_grafana_annotations(body!(parse_body(&body)), &data).await,
+        ))
+    } else if p == "/perf/zulip" {
+        let body: zulip::Request = body!(parse_body(&body));
+        if data.config().zulip.webhook_token.as_ref() != Some(&body.token) {
+            return Ok(http::Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(hyper::Body::empty())
+                .unwrap());
+        }
+        Ok(to_json_response(handle_zulip(body, &data).await))
     } else if p == "/perf/github-hook" {
-        if !verify_gh(&data.config, &req, &body) {
+        if !verify_gh(&data.config(), &req, &body) {
             return Ok(http::Response::builder()
                 .status(StatusCode::UNAUTHORIZED)
                 .body(hyper::Body::empty())
@@ -1684,36 +2752,52 @@ async fn serve_req(ctx: Arc<Server>, req: Request) -> Result<Response, ServerErr
                 .body(hyper::Body::from(format!("unknown event: {}", event)))
                 .unwrap()),
         }
+    } else if p == "/perf/benchmark-detail" {
+        Ok(to_response(
+            handle_benchmark_detail(body!(parse_body(&body)), &data).await,
+        ))
+    } else if p == "/perf/phases" {
+        Ok(to_response(
+            handle_phases(body!(parse_body(&body)), &data).await,
+        ))
     } else if p == "/perf/self-profile" {
         Ok(to_response(
             handle_self_profile(body!(parse_body(&body)), &data).await,
         ))
+    } else if p == "/perf/self-profile-diff" {
+        Ok(to_response(
+            handle_self_profile_diff(body!(parse_body(&body)), &data).await,
+        ))
     } else if p == "/perf/self-profile-raw" {
         Ok(to_response(
             handle_self_profile_raw(body!(parse_body(&body)), &data).await,
         ))
     } else if p == "/perf/graph-new" {
-        Ok(
-            match handle_graph_new(body!(parse_body(&body)), &data).await {
-                Ok(result) => {
-                    let mut response = http::Response::builder()
-                        .header_typed(ContentType::json())
-                        .header_typed(CacheControl::new().with_no_cache().with_no_store());
-                    response.headers_mut().unwrap().insert(
-                        hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN,
-                        hyper::header::HeaderValue::from_static("*"),
-                    );
-                    let body = serde_json::to_vec(&result).unwrap();
-                    response.body(hyper::Body::from(body)).unwrap()
-                }
-                Err(err) => http::Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .header_typed(ContentType::text_utf8())
-                    .header_typed(CacheControl::new().with_no_cache().with_no_store())
-                    .body(hyper::Body::from(err))
-                    .unwrap(),
-            },
-        )
+        let mut body: graph::Request = body!(parse_body(&body));
+        match values_param(&req.uri) {
+            Ok(Some(absolute)) => body.absolute = absolute,
+            Ok(None) => {}
+            Err(response) => return Ok(response),
+        }
+        Ok(match handle_graph_new(body, &data).await {
+            Ok(result) => {
+                let mut response = http::Response::builder()
+                    .header_typed(ContentType::json())
+                    .header_typed(CacheControl::new().with_no_cache().with_no_store());
+                response.headers_mut().unwrap().insert(
+                    hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                    hyper::header::HeaderValue::from_static("*"),
+                );
+                let body = serde_json::to_vec(&result).unwrap();
+                response.body(hyper::Body::from(body)).unwrap()
+            }
+            Err(err) => http::Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header_typed(ContentType::text_utf8())
+                .header_typed(CacheControl::new().with_no_cache().with_no_store())
+                .body(hyper::Body::from(err))
+                .unwrap(),
+        })
     } else if p == "/perf/bootstrap" {
         Ok(
             match handle_bootstrap(body!(parse_body(&body)), &data).await {
@@ -1814,6 +2898,82 @@ where
     }
 }
 
+/// Like `to_response`, but JSON, for clients other than the site's pages.
+fn to_json_response<S>(result: ServerResult<S>) -> Response
+where
+    S: Serialize,
+{
+    match result {
+        Ok(result) => http::Response::builder()
+            .header_typed(ContentType::json())
+            .header_typed(CacheControl::new().with_no_cache().with_no_store())
+            .body(hyper::Body::from(serde_json::to_vec(&result).unwrap()))
+            .unwrap(),
+        Err(err) => http::Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .header_typed(ContentType::text_utf8())
+            .header_typed(CacheControl::new().with_no_cache().with_no_store())
+            .body(hyper::Body::from(err))
+            .unwrap(),
+    }
+}
+
+/// The routes served, other than static files, by which requests are traced.
+const ROUTES: &[&str] = &[
+    "/perf/artifacts",
+    "/perf/benchmark-detail",
+    "/perf/benchmarks",
+    "/perf/bootstrap",
+    "/perf/collected",
+    "/perf/commit-info",
+    "/perf/dashboard",
+    "/perf/download-raw-self-profile",
+    "/perf/export.jsonl",
+    "/perf/feed.xml",
+    "/perf/get",
+    "/perf/github-hook",
+    "/perf/grafana",
+    "/perf/grafana/annotations",
+    "/perf/grafana/query",
+    "/perf/grafana/search",
+    "/perf/graph",
+    "/perf/graph-new",
+    "/perf/info",
+    "/perf/metrics",
+    "/perf/nearest",
+    "/perf/next_commit",
+    "/perf/onpush",
+    "/perf/phases",
+    "/perf/processed-self-profile",
+    "/perf/queue",
+    "/perf/releases",
+    "/perf/search",
+    "/perf/self-profile",
+    "/perf/self-profile-diff",
+    "/perf/self-profile-raw",
+    "/perf/status_page",
+    "/perf/timeline",
+    "/perf/unbenchmarked",
+    "/perf/zulip",
+];
+
+/// The route `path` is served by, with any names in it left out, so that the
+/// totals kept for each span name stay few.
+fn route_name(path: &str) -> &'static str {
+    if let Some(route) = ROUTES.iter().find(|r| **r == path) {
+        return route;
+    }
+    if path.starts_with("/perf/artifacts/") {
+        "/perf/artifacts/*"
+    } else if path.starts_with("/perf/badge/") {
+        "/perf/badge/*"
+    } else if path.starts_with("/perf/") {
+        "/perf/* (unknown)"
+    } else {
+        "static"
+    }
+}
+
 async fn run_server(data: Arc<RwLock<Option<Arc<InputData>>>>, addr: SocketAddr) {
     let ctx = Arc::new(Server {
         data,
@@ -1825,8 +2985,14 @@ async fn run_server(data: Arc<RwLock<Option<Arc<InputData>>>>, addr: SocketAddr)
             Ok::<_, hyper::Error>(hyper::service::service_fn(move |req| {
                 let start = std::time::Instant::now();
                 let desc = format!("{} {}", req.method(), req.uri());
+                let span = database::trace::span(format!(
+                    "{} {}",
+                    req.method(),
+                    route_name(req.uri().path())
+                ));
                 serve_req(ctx.clone(), req)
                     .inspect(move |r| {
+                        drop(span);
                         let dur = start.elapsed();
                         info!("{}: {:?} {:?}", desc, r.as_ref().map(|r| r.status()), dur)
                     })
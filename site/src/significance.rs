@@ -0,0 +1,127 @@
+//! The rows of a comparison, each enriched with what the comparison page sorts
+//! and filters by, so that it needn't ask again: the changes, the benchmark's
+//! category, and whether the change stands out from the measurement's usual
+//! noise.
+//!
+//! The noise is the standard deviation of the measurement's relative change
+//! between consecutive master commits (see `database::Deltas`), over the
+//! latest `HISTORY` of them up to the start of the comparison. A change is
+//! significant if it's more than `NOISE_FACTOR` times that.
+
+use crate::api::{days::Row, DateData};
+use crate::load::InputData;
+use database::{ArtifactId, ArtifactIdNumber, Commit, DbLabel, Deltas, Index, Lookup};
+use std::collections::HashMap;
+
+/// How many master commits the noise is measured over.
+const HISTORY: usize = 100;
+
+/// The fewest deltas the noise is measured from.
+const MIN_HISTORY: usize = 10;
+
+/// How many times the noise a change must be to be significant.
+const NOISE_FACTOR: f64 = 3.0;
+
+/// The standard deviation of a series' deltas at `aids`, as a fraction.
+pub fn noise(deltas: &Deltas, sid: u32, aids: &[ArtifactIdNumber]) -> Option<f64> {
+    let values = aids
+        .iter()
+        .filter_map(|&aid| deltas.get(sid, aid))
+        .map(f64::from)
+        .collect::<Vec<_>>();
+    if values.len() < MIN_HISTORY {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    Some(variance.sqrt())
+}
+
+/// The latest `HISTORY` master commits up to `until` (or up to the latest),
+/// which the noise is measured over.
+pub fn history(index: &Index, until: Option<&Commit>) -> Vec<ArtifactIdNumber> {
+    let mut aids = index
+        .sorted_commits()
+        .iter()
+        .filter(|c| !c.is_try())
+        .filter(|c| until.map_or(true, |u| c.date <= u.date))
+        .filter_map(|c| ArtifactId::Commit(c.clone()).lookup(index))
+        .collect::<Vec<_>>();
+    aids.drain(..aids.len().saturating_sub(HISTORY));
+    aids
+}
+
+/// The rows of the comparison of `a` with `b` in `stat`, for the measurements
+/// both have. `until` is the commit the noise is measured up to, if `a` is a
+/// commit.
+pub fn rows(
+    data: &InputData,
+    stat: &str,
+    until: Option<&Commit>,
+    a: &DateData,
+    b: &DateData,
+) -> Vec<Row> {
+    let index = data.index.load();
+    // By `(crate-profile, cache)`, as in `DateData::data`.
+    let series = index
+        .all_pstat_series()
+        .filter(|s| s.3.as_str() == stat)
+        .filter_map(|&(krate, profile, cache, stat)| {
+            let sid = DbLabel::ProcessStat {
+                krate,
+                profile,
+                cache,
+                stat,
+            }
+            .lookup(&index)?;
+            let key = (format!("{}-{}", krate, profile), cache.to_string());
+            Some((key, (krate, sid)))
+        })
+        .collect::<HashMap<_, _>>();
+    let history = &history(&index, until);
+    let deltas = data.deltas.load();
+    let categories = data
+        .benchmarks
+        .iter()
+        .map(|b| (b.name.as_str(), b.category))
+        .collect::<HashMap<_, _>>();
+
+    let mut rows = Vec::new();
+    for (benchmark, cases) in &a.data {
+        let other = match b.data.get(benchmark) {
+            Some(other) => other,
+            None => continue,
+        };
+        for (cache, va) in cases {
+            let vb = match other.iter().find(|(c, _)| c == cache) {
+                Some((_, vb)) => *vb,
+                None => continue,
+            };
+            let percent_change = if *va == 0.0 {
+                0.0
+            } else {
+                (vb - va) / va * 100.0
+            };
+            let (krate, sid) = match series.get(&(benchmark.clone(), cache.clone())) {
+                Some(&(krate, sid)) => (Some(krate), Some(sid)),
+                None => (None, None),
+            };
+            let noise = sid
+                .and_then(|sid| noise(&deltas, sid, history))
+                .map(|n| n * 100.0);
+            rows.push(Row {
+                benchmark: benchmark.clone(),
+                cache: cache.clone(),
+                category: krate.and_then(|k| categories.get(k.as_str()).copied()),
+                a: *va,
+                b: vb,
+                absolute_change: vb - va,
+                percent_change,
+                noise,
+                significant: noise.map(|n| percent_change.abs() > NOISE_FACTOR * n),
+            });
+        }
+    }
+    rows
+}
@@ -0,0 +1,174 @@
+//! A timeline of the significant changes to one crate's benchmarks over a
+//! range of commits, as a Mermaid or Graphviz diagram to embed in triage
+//! write-ups and blog posts. Served at `/perf/timeline`.
+//!
+//! Each event is a master commit which moved a statistic of the crate by at
+//! least the threshold (see `feed::changes_in`), labelled with its date, its
+//! pull request and its largest changes. Only `PR_LOOKUPS` pull requests not
+//! yet cached are looked up per timeline; commits past that are labelled by
+//! sha until a later request finds their pull request.
+
+use crate::feed::Changes;
+use crate::load::InputData;
+use std::fmt::Write;
+
+/// How many changes are listed per commit; the rest are counted.
+const CHANGES_SHOWN: usize = 5;
+
+/// How many pull requests are looked up on GitHub per timeline.
+const PR_LOOKUPS: usize = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Mermaid,
+    Dot,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "mermaid" => Format::Mermaid,
+            "dot" | "graphviz" => Format::Dot,
+            _ => return Err(format!("{:?} is not a timeline format", s)),
+        })
+    }
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Mermaid => "text/plain; charset=utf-8",
+            Format::Dot => "text/vnd.graphviz; charset=utf-8",
+        }
+    }
+}
+
+/// One commit on the timeline.
+struct Event {
+    date: String,
+    pr: Option<String>,
+    changes: Vec<String>,
+    regression: bool,
+}
+
+impl Event {
+    fn new(changes: &Changes, pr: Option<database::CommitPr>) -> Event {
+        let pr = pr.map(|pr| format!("PR {}: {}", pr.pr, pr.title));
+        let mut lines = changes
+            .changes
+            .iter()
+            .take(CHANGES_SHOWN)
+            .map(|(name, delta)| format!("{:+.2}% {}", delta * 100.0, name))
+            .collect::<Vec<_>>();
+        if changes.changes.len() > CHANGES_SHOWN {
+            lines.push(format!(
+                "and {} more",
+                changes.changes.len() - CHANGES_SHOWN
+            ));
+        }
+        Event {
+            date: changes.commit.date.0.format("%Y-%m-%d").to_string(),
+            pr,
+            changes: lines,
+            regression: changes.regressions() * 2 > changes.changes.len(),
+        }
+    }
+
+    /// The PR (or the commit, if it has none) and then the changes.
+    fn lines(&self, sha: &str) -> Vec<String> {
+        let mut lines = vec![self
+            .pr
+            .clone()
+            .unwrap_or_else(|| sha[..std::cmp::min(10, sha.len())].to_string())];
+        lines.extend(self.changes.iter().cloned());
+        lines
+    }
+}
+
+/// Mermaid gives `:` and `#` meaning, so they're written as entity codes.
+fn mermaid_escape(text: &str) -> String {
+    text.replace('#', "#35;").replace(':', "#58;")
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders the timeline of `krate`'s changes in `stat` among the given master
+/// commits (oldest first), with `threshold` as a fraction (`0.01` is 1%).
+pub async fn render(
+    data: &InputData,
+    commits: &[database::Commit],
+    krate: &str,
+    stat: &str,
+    threshold: f64,
+    format: Format,
+) -> String {
+    let mut changes = crate::feed::changes_in(data, commits, stat, threshold, Some(krate));
+    changes.reverse();
+    let mut events = Vec::new();
+    let mut lookups = 0;
+    for changes in &changes {
+        let sha = changes.commit.sha.as_str();
+        let pr = match crate::pr_cache::cached_pr_of(data, sha).await {
+            Some(pr) => Some(pr),
+            None if lookups < PR_LOOKUPS => {
+                lookups += 1;
+                crate::pr_cache::pr_of(data, sha).await
+            }
+            None => None,
+        };
+        events.push((sha, Event::new(changes, pr)));
+    }
+    let title = format!(
+        "{}: changes in {} of at least {}%",
+        krate,
+        stat,
+        threshold * 100.0
+    );
+
+    let mut out = String::new();
+    match format {
+        Format::Mermaid => {
+            writeln!(out, "timeline").unwrap();
+            writeln!(out, "    title {}", mermaid_escape(&title)).unwrap();
+            for (sha, event) in &events {
+                write!(out, "    {}", event.date).unwrap();
+                for line in event.lines(sha) {
+                    write!(out, " : {}", mermaid_escape(&line)).unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+        }
+        Format::Dot => {
+            writeln!(out, "digraph timeline {{").unwrap();
+            writeln!(out, "    label=\"{}\";", dot_escape(&title)).unwrap();
+            writeln!(out, "    rankdir=LR;").unwrap();
+            writeln!(out, "    node [shape=box, style=filled];").unwrap();
+            for (idx, (sha, event)) in events.iter().enumerate() {
+                let mut label = dot_escape(&event.date);
+                for line in event.lines(sha) {
+                    label.push_str("\\n");
+                    label.push_str(&dot_escape(&line));
+                }
+                let color = if event.regression {
+                    "#f8d7da"
+                } else {
+                    "#d4edda"
+                };
+                writeln!(
+                    out,
+                    "    c{} [label=\"{}\", fillcolor=\"{}\"];",
+                    idx, label, color
+                )
+                .unwrap();
+                if idx > 0 {
+                    writeln!(out, "    c{} -> c{};", idx - 1, idx).unwrap();
+                }
+            }
+            writeln!(out, "}}").unwrap();
+        }
+    }
+    out
+}
@@ -8,3 +8,115 @@
 // except according to those terms.
 
 pub use collector::{null_means_nan, round_float};
+
+/// The rounding of the metrics in API responses, so that they're consistent
+/// and no bigger than they need be. Percentages are rounded to a number of
+/// decimal places, and everything else (times, counts, sizes) to a number of
+/// significant digits, since those range over many orders of magnitude.
+///
+/// Fields opt in with e.g. `#[serde(serialize_with = "util::round::value")]`.
+/// The precision is set once, from the config, when the data is loaded.
+pub mod round {
+    use serde::{Serialize, Serializer};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct Precision {
+        pub percent_decimals: u32,
+        pub significant_digits: u32,
+    }
+
+    impl Default for Precision {
+        fn default() -> Precision {
+            Precision {
+                percent_decimals: 2,
+                significant_digits: 6,
+            }
+        }
+    }
+
+    static PERCENT_DECIMALS: AtomicU32 = AtomicU32::new(2);
+    static SIGNIFICANT_DIGITS: AtomicU32 = AtomicU32::new(6);
+
+    pub fn set_precision(precision: Precision) {
+        PERCENT_DECIMALS.store(precision.percent_decimals, Ordering::Relaxed);
+        SIGNIFICANT_DIGITS.store(precision.significant_digits, Ordering::Relaxed);
+    }
+
+    fn to_decimals(n: f64) -> f64 {
+        let scale = 10f64.powi(PERCENT_DECIMALS.load(Ordering::Relaxed) as i32);
+        (n * scale).round() / scale
+    }
+
+    fn to_significant_digits(n: f64) -> f64 {
+        if n == 0.0 || !n.is_finite() {
+            return n;
+        }
+        let digits = SIGNIFICANT_DIGITS.load(Ordering::Relaxed) as i32;
+        let magnitude = n.abs().log10().floor() as i32;
+        let scale = 10f64.powi(digits - 1 - magnitude);
+        (n * scale).round() / scale
+    }
+
+    /// Something holding metrics, which can be rounded as a whole.
+    pub trait Round {
+        fn round_with(&self, round: fn(f64) -> f64) -> Self;
+    }
+
+    impl Round for f64 {
+        fn round_with(&self, round: fn(f64) -> f64) -> f64 {
+            round(*self)
+        }
+    }
+
+    impl Round for f32 {
+        fn round_with(&self, round: fn(f64) -> f64) -> f32 {
+            round(f64::from(*self)) as f32
+        }
+    }
+
+    impl<T: Round> Round for Vec<T> {
+        fn round_with(&self, round: fn(f64) -> f64) -> Vec<T> {
+            self.iter().map(|v| v.round_with(round)).collect()
+        }
+    }
+
+    impl<T: Round> Round for Option<T> {
+        fn round_with(&self, round: fn(f64) -> f64) -> Option<T> {
+            self.as_ref().map(|v| v.round_with(round))
+        }
+    }
+
+    impl<K: Clone, T: Round> Round for (K, T) {
+        fn round_with(&self, round: fn(f64) -> f64) -> (K, T) {
+            (self.0.clone(), self.1.round_with(round))
+        }
+    }
+
+    impl<K: Clone + Ord, T: Round> Round for BTreeMap<K, T> {
+        fn round_with(&self, round: fn(f64) -> f64) -> BTreeMap<K, T> {
+            self.iter()
+                .map(|(k, v)| (k.clone(), v.round_with(round)))
+                .collect()
+        }
+    }
+
+    /// Serializes percentages, rounded to the configured decimal places.
+    pub fn percent<T, S>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Round + Serialize,
+        S: Serializer,
+    {
+        v.round_with(to_decimals).serialize(serializer)
+    }
+
+    /// Serializes other metrics, rounded to the configured significant digits.
+    pub fn value<T, S>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Round + Serialize,
+        S: Serializer,
+    {
+        v.round_with(to_significant_digits).serialize(serializer)
+    }
+}
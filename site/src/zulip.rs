@@ -0,0 +1,200 @@
+//! A Zulip bot for triaging regressions. After each reload it posts the
+//! master commits which regressed any benchmark's instructions by more than
+//! the threshold to a stream, once each. As the target of an outgoing webhook
+//! (`/perf/zulip`) it takes commands which update a commit's triage state:
+//!
+//! - `ack <sha>` acknowledges the regressions, as the sender;
+//! - `assign <sha> <who>` records who is looking into them;
+//! - `issue <sha> <link>` records the issue tracking them;
+//! - `status <sha>` shows what has been recorded.
+//!
+//! Shas can be abbreviated to any prefix which only one master commit has.
+
+use crate::feed::{self, Changes};
+use crate::load::InputData;
+use chrono::{Duration, Utc};
+use database::Triage;
+use std::fmt::Write;
+
+/// The statistic regressions are posted for.
+const STAT: &str = "instructions:u";
+
+/// How recent a commit has to be to be posted, so that setting the bot up
+/// doesn't post every regression on record.
+const POST_WITHIN_DAYS: i64 = 3;
+
+/// How many of a commit's regressions are listed in its message.
+const LISTED: usize = 5;
+
+const USAGE: &str = "Commands: `ack <sha>`, `assign <sha> <who>`, `issue <sha> <link>` and \
+                     `status <sha>`.";
+
+/// Where and when to post regressions; see `Config::zulip`.
+#[derive(Debug, Clone)]
+pub struct Zulip {
+    pub site: String,
+    pub bot_email: String,
+    pub api_key: String,
+    pub stream: String,
+    pub topic: String,
+    /// The smallest regression posted, as a fraction (`0.01` is 1%).
+    pub threshold: f64,
+}
+
+impl Zulip {
+    async fn post(&self, content: &str) -> anyhow::Result<()> {
+        let response = reqwest::Client::new()
+            .post(&format!("{}/api/v1/messages", self.site))
+            .basic_auth(&self.bot_email, Some(&self.api_key))
+            .form(&[
+                ("type", "stream"),
+                ("to", &self.stream),
+                ("topic", &self.topic),
+                ("content", content),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Zulip responded {}: {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn short(sha: &str) -> &str {
+    &sha[..std::cmp::min(10, sha.len())]
+}
+
+fn message(changes: &Changes, threshold: f64) -> String {
+    let sha = short(&changes.commit.sha);
+    let regressions = changes
+        .changes
+        .iter()
+        .filter(|(_, delta)| *delta > 0.0)
+        .collect::<Vec<_>>();
+    let mut message = format!(
+        "**{}** regressed {} of {} benchmarks by {}% or more ([comparison]({})):\n",
+        sha,
+        STAT,
+        regressions.len(),
+        threshold * 100.0,
+        changes.comparison_url(STAT)
+    );
+    for (name, delta) in regressions.iter().take(LISTED) {
+        writeln!(message, "- {:+.2}% {}", delta * 100.0, name).unwrap();
+    }
+    if regressions.len() > LISTED {
+        writeln!(message, "- and {} more", regressions.len() - LISTED).unwrap();
+    }
+    write!(
+        message,
+        "\nTriage by mentioning me with `ack {}`, `assign {} <who>` or `issue {} <link>`.",
+        sha, sha, sha
+    )
+    .unwrap();
+    message
+}
+
+/// Posts each recent master commit with regressions which hasn't been posted
+/// yet, oldest first, if posting is set up.
+pub async fn post_regressions(data: &InputData) {
+    let zulip = match data.config().zulip() {
+        Some(zulip) => zulip,
+        None => return,
+    };
+    let cutoff = Utc::now() - Duration::days(POST_WITHIN_DAYS);
+    let conn = data.conn().await;
+    let changes = feed::significant_changes(data, STAT, zulip.threshold);
+    for changes in changes.iter().rev() {
+        if changes.regressions() == 0 || changes.commit.date.0 < cutoff {
+            continue;
+        }
+        let sha = &changes.commit.sha;
+        let mut triage = conn.triage(sha).await.unwrap_or_else(|| Triage {
+            sha: sha.clone(),
+            ..Triage::default()
+        });
+        if triage.posted {
+            continue;
+        }
+        if let Err(e) = zulip.post(&message(changes, zulip.threshold)).await {
+            // Try again after the next reload.
+            log::error!("failed to post {} to Zulip: {:?}", sha, e);
+            return;
+        }
+        triage.posted = true;
+        conn.record_triage(&triage).await;
+    }
+}
+
+/// The master commit whose sha starts with `prefix`.
+fn resolve(data: &InputData, prefix: &str) -> Result<String, String> {
+    let index = data.index.load();
+    let matches = index
+        .sorted_commits()
+        .iter()
+        .filter(|c| !c.is_try() && c.sha.starts_with(prefix))
+        .map(|c| c.sha.clone())
+        .collect::<Vec<_>>();
+    match &matches[..] {
+        [sha] => Ok(sha.clone()),
+        [] => Err(format!("No master commit starts with `{}`.", prefix)),
+        _ => Err(format!(
+            "More than one master commit starts with `{}`.",
+            prefix
+        )),
+    }
+}
+
+fn describe(triage: &Triage) -> String {
+    let mut parts = Vec::new();
+    if let Some(who) = &triage.acknowledged_by {
+        parts.push(format!("acknowledged by {}", who));
+    }
+    if let Some(who) = &triage.assignee {
+        parts.push(format!("assigned to {}", who));
+    }
+    if let Some(issue) = &triage.issue {
+        parts.push(format!("tracked in {}", issue));
+    }
+    if parts.is_empty() {
+        parts.push(String::from("not triaged yet"));
+    }
+    format!("`{}`: {}.", short(&triage.sha), parts.join(", "))
+}
+
+/// Carries out a command sent by `sender`, returning the reply.
+pub async fn command(data: &InputData, sender: &str, text: &str) -> String {
+    // Messages to the bot start by mentioning it, e.g. `@**perf**`.
+    let mut words = text
+        .split_whitespace()
+        .skip_while(|w| w.starts_with("@**") || w.starts_with("@_**"));
+    let (command, prefix) = match (words.next(), words.next()) {
+        (Some(command), Some(prefix)) => (command, prefix),
+        _ => return USAGE.to_string(),
+    };
+    let rest = words.collect::<Vec<_>>().join(" ");
+    let sha = match resolve(data, prefix) {
+        Ok(sha) => sha,
+        Err(e) => return e,
+    };
+
+    let conn = data.conn().await;
+    let mut triage = conn.triage(&sha).await.unwrap_or_else(|| Triage {
+        sha: sha.clone(),
+        ..Triage::default()
+    });
+    match command {
+        "ack" => triage.acknowledged_by = Some(sender.to_string()),
+        "assign" if !rest.is_empty() => triage.assignee = Some(rest),
+        "issue" if !rest.is_empty() => triage.issue = Some(rest),
+        "status" => return describe(&triage),
+        _ => return USAGE.to_string(),
+    }
+    conn.record_triage(&triage).await;
+    describe(&triage)
+}
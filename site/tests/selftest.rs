@@ -0,0 +1,212 @@
+//! Checks that the numbers the site computes haven't changed, by computing the
+//! graphs (including the summary graphs) and the comparison of the first and
+//! last commits for a generated fixture database, and comparing them with the
+//! recorded ("golden") output in `tests/golden`. Also checks that those
+//! responses list their caches in order, as the golden output (being keyed by
+//! name) wouldn't notice if they didn't.
+//!
+//! When a change in the numbers is intended, record the new output with
+//! `BLESS=1 cargo test -p site --test selftest` and commit it.
+
+use collector::Bound;
+use database::testdata;
+use serde::{de::DeserializeOwned, Serialize};
+use site::api::{days, graph};
+use site::load::{InputData, LoadMode};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const STATS: &[&str] = &["instructions:u", "wall-time", "max-rss"];
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Point {
+    commit: String,
+    y: f32,
+    absolute: Option<f32>,
+    percent: Option<f32>,
+    is_interpolated: bool,
+}
+
+/// The points of each series, keyed by `crate/profile/cache`, with commits
+/// identified by sha rather than by their (arbitrary) index in the response.
+fn flatten(resp: &graph::Response) -> BTreeMap<String, Vec<Point>> {
+    let mut series = BTreeMap::new();
+    for (krate, profiles) in &resp.benchmarks {
+        for (profile, caches) in profiles {
+            for (cache, points) in caches {
+                let points = points
+                    .iter()
+                    .map(|p| Point {
+                        commit: resp.commits[p.commit as usize].clone(),
+                        y: p.y.get(),
+                        absolute: p.absolute,
+                        percent: p.percent,
+                        is_interpolated: p.is_interpolated,
+                    })
+                    .collect();
+                series.insert(format!("{}/{}/{}", krate, profile, cache), points);
+            }
+        }
+    }
+    series
+}
+
+/// The values of each benchmark at each of the compared commits, keyed by
+/// `crate/profile-cache` and then by commit.
+fn flatten_compare(resp: &days::Response) -> BTreeMap<String, BTreeMap<String, f64>> {
+    let mut values = BTreeMap::new();
+    for side in &[&resp.a, &resp.b] {
+        for (krate, points) in &side.data {
+            for (case, value) in points {
+                values
+                    .entry(format!("{}/{}", krate, case))
+                    .or_insert_with(BTreeMap::new)
+                    .insert(side.commit.clone(), *value);
+            }
+        }
+    }
+    values
+}
+
+/// The lists of `(name, _)` in a response which aren't sorted by name, which
+/// the golden output (being keyed by name) wouldn't notice. Responses should
+/// be the same from one run to the next, regardless of hash order.
+fn unsorted<'a, T: 'a>(lists: impl Iterator<Item = (String, &'a [(String, T)])>) -> Vec<String> {
+    lists
+        .filter(|(_, list)| list.windows(2).any(|w| w[0].0 > w[1].0))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Compares `actual` with the golden output called `name`, or records it as
+/// the golden output if blessing. Returns a description of the differences,
+/// if any.
+fn check<T>(name: &str, actual: &BTreeMap<String, T>) -> Option<String>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let path = golden.join(format!("{}.json", name));
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::create_dir_all(&golden).unwrap();
+        std::fs::write(&path, serde_json::to_string_pretty(actual).unwrap()).unwrap();
+        return None;
+    }
+    let expected: BTreeMap<String, T> = match std::fs::read_to_string(&path) {
+        Ok(s) => serde_json::from_str(&s).unwrap(),
+        Err(_) => return Some(format!("{}: no golden output; run with BLESS=1", name)),
+    };
+    if &expected == actual {
+        return None;
+    }
+    let keys = expected
+        .keys()
+        .chain(actual.keys())
+        .collect::<std::collections::BTreeSet<_>>();
+    let differing = keys
+        .into_iter()
+        .filter(|key| expected.get(*key) != actual.get(*key))
+        .cloned()
+        .collect::<Vec<_>>();
+    Some(format!("{}: {:?} differ", name, differing))
+}
+
+/// Loads a freshly generated fixture, named `name` so that tests running at
+/// the same time don't share one.
+async fn fixture(name: &str) -> InputData {
+    // The benchmark manifests are found relative to the repository's root.
+    std::env::set_current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")).unwrap();
+    let db: PathBuf =
+        std::env::temp_dir().join(format!("rustc-perf-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&db);
+    let db = db.to_str().unwrap().to_string();
+    testdata::generate(&db, testdata::Params::default()).await;
+    InputData::from_fs(&db, LoadMode::Strict).await.unwrap()
+}
+
+#[tokio::test]
+async fn golden_output() {
+    let data = fixture("golden").await;
+    let (first, last) = {
+        let index = data.index.load();
+        let commits = index.sorted_commits();
+        (commits[0].clone(), commits[commits.len() - 1].clone())
+    };
+
+    let mut failures = Vec::new();
+    for stat in STATS {
+        for &absolute in &[true, false] {
+            let name = format!(
+                "graph-{}-{}",
+                stat.replace(':', "_"),
+                if absolute { "absolute" } else { "relative" }
+            );
+            let request = graph::Request {
+                start: Bound::Commit(first.sha.clone()),
+                end: Bound::Commit(last.sha.clone()),
+                stat: stat.to_string(),
+                absolute,
+                resolution: Default::default(),
+            };
+            let resp = site::server::handle_graph(request, &data).await.unwrap();
+            failures.extend(check(&name, &flatten(&resp)));
+        }
+
+        let name = format!("compare-{}", stat.replace(':', "_"));
+        let request = days::Request {
+            start: Bound::Commit(first.sha.clone()),
+            end: Bound::Commit(last.sha.clone()),
+            stat: stat.to_string(),
+        };
+        let resp = site::server::handle_compare(request, &data).await.unwrap();
+        failures.extend(check(&name, &flatten_compare(&resp)));
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[tokio::test]
+async fn responses_are_sorted() {
+    let data = fixture("sorted").await;
+    let (first, last) = {
+        let index = data.index.load();
+        let commits = index.sorted_commits();
+        (commits[0].clone(), commits[commits.len() - 1].clone())
+    };
+
+    for stat in STATS {
+        let request = graph::Request {
+            start: Bound::Commit(first.sha.clone()),
+            end: Bound::Commit(last.sha.clone()),
+            stat: stat.to_string(),
+            absolute: true,
+            resolution: Default::default(),
+        };
+        let resp = site::server::handle_graph(request, &data).await.unwrap();
+        let lists = resp.benchmarks.iter().flat_map(|(krate, profiles)| {
+            profiles
+                .iter()
+                .map(move |(profile, caches)| (format!("{}/{}", krate, profile), &caches[..]))
+        });
+        assert_eq!(
+            unsorted(lists),
+            Vec::<String>::new(),
+            "graph of {}: caches out of order",
+            stat
+        );
+
+        let request = days::Request {
+            start: Bound::Commit(first.sha.clone()),
+            end: Bound::Commit(last.sha.clone()),
+            stat: stat.to_string(),
+        };
+        let resp = site::server::handle_compare(request, &data).await.unwrap();
+        let lists = resp.a.data.iter().chain(&resp.b.data);
+        assert_eq!(
+            unsorted(lists.map(|(krate, cases)| (krate.clone(), &cases[..]))),
+            Vec::<String>::new(),
+            "comparison of {}: cases out of order",
+            stat
+        );
+    }
+}